@@ -0,0 +1,156 @@
+use clap::{value_t, App, Arg};
+use std::fs;
+use std::io::{self, Read, Write};
+
+const SUFFIX: &str = ".zst";
+
+// This program is a small companion to the `zstd` command-line tool,
+// built entirely on this crate's public API.
+//
+// Without a file, it reads from stdin and writes to stdout:
+//
+//     cargo run --example zstd-rs -- -l 19 < data > data.zst
+//     cargo run --example zstd-rs -- -d < data.zst > data
+//
+// Multi-threaded compression (`-T`) requires the `zstdmt` feature.
+fn main() {
+    let matches = App::new("zstd-rs")
+        .author("Alexandre Bury <alexandre.bury@gmail.com>")
+        .about("Compress or decompress files with zstd")
+        .arg(
+            Arg::with_name("decompress")
+                .help("Decompress instead of compressing")
+                .short("d")
+                .long("decompress"),
+        )
+        .arg(
+            Arg::with_name("level")
+                .help("Compression level (defaults to 3)")
+                .short("l")
+                .long("level")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("dictionary")
+                .help("Dictionary file to compress or decompress with")
+                .short("D")
+                .long("dictionary")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("threads")
+                .help("Number of compression worker threads")
+                .short("T")
+                .long("threads")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("stdout")
+                .help("Write to stdout, even when given files")
+                .short("c")
+                .long("stdout"),
+        )
+        .arg(
+            Arg::with_name("FILE")
+                .help("Files to process (stdin if none is given)")
+                .multiple(true),
+        )
+        .get_matches();
+
+    let options = Options {
+        decompress: matches.is_present("decompress"),
+        level: value_t!(matches, "level", i32)
+            .unwrap_or(zstd::DEFAULT_COMPRESSION_LEVEL),
+        threads: value_t!(matches, "threads", u32).unwrap_or(0),
+        dictionary: match matches.value_of("dictionary") {
+            Some(path) => fs::read(path).unwrap_or_else(|e| {
+                eprintln!("Error reading dictionary {}: {}", path, e);
+                std::process::exit(1);
+            }),
+            None => Vec::new(),
+        },
+    };
+
+    let files: Vec<_> =
+        matches.values_of("FILE").into_iter().flatten().collect();
+    if files.is_empty() {
+        if let Err(e) = options.run(io::stdin().lock(), io::stdout().lock()) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let mut failed = false;
+    for file in files {
+        let result = if matches.is_present("stdout") {
+            fs::File::open(file)
+                .and_then(|input| options.run(input, io::stdout().lock()))
+        } else {
+            options.run_file(file)
+        };
+        if let Err(e) = result {
+            eprintln!("Error processing {}: {}", file, e);
+            failed = true;
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+struct Options {
+    decompress: bool,
+    level: i32,
+    threads: u32,
+    dictionary: Vec<u8>,
+}
+
+impl Options {
+    fn run<R: Read, W: Write>(
+        &self,
+        input: R,
+        mut output: W,
+    ) -> io::Result<()> {
+        if self.decompress {
+            let mut decoder = zstd::Decoder::with_dictionary(
+                io::BufReader::new(input),
+                &self.dictionary,
+            )?;
+            io::copy(&mut decoder, &mut output)?;
+        } else {
+            let mut input = input;
+            let mut encoder = zstd::Encoder::with_dictionary(
+                output,
+                self.level,
+                &self.dictionary,
+            )?;
+            if self.threads > 0 {
+                encoder.multithread(self.threads)?;
+            }
+            io::copy(&mut input, &mut encoder)?;
+            output = encoder.finish()?;
+        }
+        output.flush()
+    }
+
+    fn run_file(&self, source: &str) -> io::Result<()> {
+        let target = if self.decompress {
+            match source.strip_suffix(SUFFIX) {
+                Some(target) => target.to_string(),
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("unknown suffix, expected {}", SUFFIX),
+                    ))
+                }
+            }
+        } else {
+            source.to_string() + SUFFIX
+        };
+
+        let input = fs::File::open(source)?;
+        let output = io::BufWriter::new(fs::File::create(target)?);
+        self.run(input, output)
+    }
+}
@@ -3,14 +3,29 @@ use crate::map_error_code;
 use std::io;
 use zstd_safe;
 
+#[cfg(feature = "experimental")]
+use zstd_safe::Sequence;
+
 /// Allows to compress independently multiple blocks of data.
 ///
 /// This reduces memory usage compared to calling `compress` multiple times.
 /// The compressed blocks are still completely independent.
+///
+/// The compression level, dictionary and any other parameter can be
+/// configured once, and then apply to every block compressed with the
+/// `compress2` methods. The other methods take the level as argument.
 #[derive(Default)]
 pub struct Compressor {
     context: zstd_safe::CCtx<'static>,
     dict: Vec<u8>,
+    // Whether `dict` is loaded in the context, with the current parameters.
+    dict_loaded: bool,
+    // Last level set with `set_parameter`, if known.
+    level: Option<i32>,
+    // Set by `fallback_to_stored`.
+    stored_margin: Option<usize>,
+    // Whether checksums were enabled, for stored frames.
+    checksum: bool,
 }
 
 impl Compressor {
@@ -24,7 +39,125 @@ impl Compressor {
         Compressor {
             context: zstd_safe::create_cctx(),
             dict,
+            dict_loaded: false,
+            level: None,
+            stored_margin: None,
+            checksum: false,
+        }
+    }
+
+    /// Creates a new zstd compressor with the given compression level.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`).
+    pub fn with_level(level: i32) -> io::Result<Self> {
+        let mut compressor = Compressor::new();
+        compressor.set_compression_level(level)?;
+        Ok(compressor)
+    }
+
+    /// Changes the dictionary used for the next blocks.
+    ///
+    /// An empty dictionary disables the use of a dictionary.
+    pub fn set_dictionary(&mut self, dict: Vec<u8>) {
+        self.dict = dict;
+        self.dict_loaded = false;
+    }
+
+    /// Sets a compression parameter for the next blocks.
+    pub fn set_parameter(
+        &mut self,
+        parameter: zstd_safe::CParameter,
+    ) -> io::Result<()> {
+        self.context
+            .set_parameter(parameter)
+            .map_err(map_error_code)?;
+        // zstd prepares the dictionary with the parameters in use when it
+        // is first needed: it must be loaded again to apply new ones.
+        self.dict_loaded = false;
+        match parameter {
+            zstd_safe::CParameter::ChecksumFlag(checksum) => {
+                self.checksum = checksum;
+            }
+            zstd_safe::CParameter::CompressionLevel(level) => {
+                self.level = Some(level);
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Sets the compression level for the next blocks.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`).
+    pub fn set_compression_level(&mut self, level: i32) -> io::Result<()> {
+        self.set_parameter(zstd_safe::CParameter::CompressionLevel(level))
+    }
+
+    /// Controls whether zstd should include a content checksum at the end
+    /// of each block.
+    pub fn include_checksum(
+        &mut self,
+        include_checksum: bool,
+    ) -> io::Result<()> {
+        self.set_parameter(zstd_safe::CParameter::ChecksumFlag(
+            include_checksum,
+        ))
+    }
+
+    /// Sets the maximum back-reference distance, as a power of two.
+    ///
+    /// This is only useful for blocks larger than the default window.
+    pub fn window_log(&mut self, log_distance: u32) -> io::Result<()> {
+        self.set_parameter(zstd_safe::CParameter::WindowLog(log_distance))
+    }
+
+    /// Applies all the parameters of the given profile.
+    ///
+    /// Individual parameters can still be changed afterwards.
+    pub fn apply_profile(
+        &mut self,
+        profile: crate::Profile,
+    ) -> io::Result<()> {
+        for parameter in profile.parameters() {
+            self.set_parameter(parameter)?;
+        }
+        Ok(())
+    }
+
+    /// Applies all the given parameters.
+    ///
+    /// Parameters not included keep their current value.
+    pub fn apply_parameters(
+        &mut self,
+        parameters: &crate::CompressionParameters,
+    ) -> io::Result<()> {
+        for parameter in parameters.parameters() {
+            self.set_parameter(parameter)?;
         }
+        Ok(())
+    }
+
+    /// Stores blocks uncompressed when compression saves `margin` bytes or
+    /// less.
+    ///
+    /// Data which doesn't compress (for example already compressed payloads)
+    /// is then stored as-is in a standard frame (see `frame::store`), which
+    /// any zstd decoder reads transparently, and faster than compressed data.
+    ///
+    /// `None` (the default) always keeps the compressed result.
+    pub fn fallback_to_stored(&mut self, margin: Option<usize>) {
+        self.stored_margin = margin;
+    }
+
+    /// Returns a mutable reference to the underlying zstd context.
+    ///
+    /// This gives access to zstd functions not covered by this crate.
+    /// Parameters set this way are kept for the next blocks, but the
+    /// dictionary is replaced with this compressor's own on the next call.
+    pub fn context_mut(&mut self) -> &mut zstd_safe::CCtx<'static> {
+        self.dict_loaded = false;
+        self.level = None;
+        &mut self.context
     }
 
     /// Compress a single block of data to the given destination buffer.
@@ -32,45 +165,372 @@ impl Compressor {
     /// Returns the number of bytes written, or an error if something happened
     /// (for instance if the destination buffer was too small).
     ///
-    /// A level of `0` uses zstd's default (currently `3`).
+    /// A level of `0` uses zstd's default (currently `3`). The level is kept
+    /// for the next blocks.
     pub fn compress_to_buffer(
         &mut self,
         source: &[u8],
         destination: &mut [u8],
         level: i32,
     ) -> io::Result<usize> {
-        zstd_safe::compress_using_dict(
-            &mut self.context,
-            destination,
-            source,
-            &self.dict[..],
-            level,
-        )
-        .map_err(map_error_code)
+        self.use_level(level)?;
+        self.compress2_to_buffer(source, destination)
     }
 
     /// Compresses a block of data and returns the compressed result.
     ///
-    /// A level of `0` uses zstd's default (currently `3`).
+    /// A level of `0` uses zstd's default (currently `3`). The level is kept
+    /// for the next blocks.
     pub fn compress(
         &mut self,
         data: &[u8],
         level: i32,
     ) -> io::Result<Vec<u8>> {
+        self.use_level(level)?;
+        self.compress2(data)
+    }
+
+    /// Compresses a block of data into `output`, replacing its content.
+    ///
+    /// The capacity of `output` is kept and grown as needed: reusing the
+    /// same `Vec` (and compressor) across calls avoids any allocation once
+    /// it is large enough.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`). The level is kept
+    /// for the next blocks.
+    pub fn compress_to_vec(
+        &mut self,
+        data: &[u8],
+        output: &mut Vec<u8>,
+        level: i32,
+    ) -> io::Result<()> {
+        self.use_level(level)?;
+        self.compress2_to_vec(data, output)
+    }
+
+    /// Compress a single block of data against the given prefix.
+    ///
+    /// See `compress2_to_buffer_with_prefix`.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`). The level is kept
+    /// for the next blocks.
+    pub fn compress_to_buffer_with_prefix(
+        &mut self,
+        source: &[u8],
+        destination: &mut [u8],
+        level: i32,
+        prefix: &[u8],
+    ) -> io::Result<usize> {
+        self.use_level(level)?;
+        self.compress2_to_buffer_with_prefix(source, destination, prefix)
+    }
+
+    /// Compresses a block of data against the given prefix and returns the
+    /// compressed result.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`). The level is kept
+    /// for the next blocks.
+    pub fn compress_with_prefix(
+        &mut self,
+        data: &[u8],
+        level: i32,
+        prefix: &[u8],
+    ) -> io::Result<Vec<u8>> {
+        self.use_level(level)?;
+        self.compress2_with_prefix(data, prefix)
+    }
+
+    /// Returns the literals/match sequences zstd would produce when
+    /// compressing `data`.
+    ///
+    /// See `generate_sequences2`.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`). The level is kept
+    /// for the next blocks.
+    #[cfg(feature = "experimental")]
+    pub fn generate_sequences(
+        &mut self,
+        data: &[u8],
+        level: i32,
+    ) -> io::Result<Vec<Sequence>> {
+        self.use_level(level)?;
+        self.generate_sequences2(data)
+    }
+
+    /// Compresses `data` using the given literals/match sequences.
+    ///
+    /// See `compress_sequences2`.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`). The level is kept
+    /// for the next blocks.
+    #[cfg(feature = "experimental")]
+    pub fn compress_sequences(
+        &mut self,
+        sequences: &[Sequence],
+        data: &[u8],
+        level: i32,
+    ) -> io::Result<Vec<u8>> {
+        self.use_level(level)?;
+        self.compress_sequences2(sequences, data)
+    }
+
+    /// Compress a single block of data to the given destination buffer,
+    /// with the parameters set on this compressor.
+    ///
+    /// Returns the number of bytes written, or an error if something happened
+    /// (for instance if the destination buffer was too small).
+    pub fn compress2_to_buffer(
+        &mut self,
+        source: &[u8],
+        destination: &mut [u8],
+    ) -> io::Result<usize> {
+        self.load_dictionary()?;
+        let len = self
+            .context
+            .compress2(destination, source)
+            .map_err(map_error_code)?;
+        Ok(self.fallback(source, destination, len))
+    }
+
+    /// Compresses a block of data with the parameters set on this
+    /// compressor, and returns the compressed result.
+    pub fn compress2(&mut self, data: &[u8]) -> io::Result<Vec<u8>> {
         // We allocate a big buffer, slightly larger than the input data.
-        let buffer_len = zstd_safe::compress_bound(data.len());
+        let buffer_len = compress_bound(data.len())?;
         let mut buffer = Vec::with_capacity(buffer_len);
         unsafe {
             // Use all capacity.
             // Memory may not be initialized, but we won't read it.
             buffer.set_len(buffer_len);
-            let len = self.compress_to_buffer(data, &mut buffer[..], level)?;
+            let len = self.compress2_to_buffer(data, &mut buffer[..])?;
             buffer.set_len(len);
         }
 
         // Should we shrink the vec? Meh, let the user do it if he wants.
         Ok(buffer)
     }
+
+    /// Compresses a block of data into `output`, replacing its content, with
+    /// the parameters set on this compressor.
+    ///
+    /// The capacity of `output` is kept and grown as needed: reusing the
+    /// same `Vec` (and compressor) across calls avoids any allocation once
+    /// it is large enough.
+    pub fn compress2_to_vec(
+        &mut self,
+        data: &[u8],
+        output: &mut Vec<u8>,
+    ) -> io::Result<()> {
+        output.clear();
+        output.resize(compress_bound(data.len())?, 0);
+        let result = self.compress2_to_buffer(data, &mut output[..]);
+        output.truncate(*result.as_ref().unwrap_or(&0));
+        result.map(|_| ())
+    }
+
+    /// Compress a single block of data against the given prefix, with the
+    /// parameters set on this compressor.
+    ///
+    /// The prefix is used instead of this compressor's dictionary, as
+    /// raw content: this is typically a previous version of the same data,
+    /// and the result is a small delta.
+    ///
+    /// The same prefix must be provided again during decompression.
+    pub fn compress2_to_buffer_with_prefix(
+        &mut self,
+        source: &[u8],
+        destination: &mut [u8],
+        prefix: &[u8],
+    ) -> io::Result<usize> {
+        // A prefix only applies to the next frame: `compress2` consumes it,
+        // and it is cleared right after in any case, so `prefix` is never
+        // used after this call.
+        let prefix: &'static [u8] = unsafe { &*(prefix as *const [u8]) };
+        // This also unloads our dictionary, loaded back by the next call.
+        self.dict_loaded = false;
+        self.context.ref_prefix(prefix).map_err(map_error_code)?;
+        let result = self.context.compress2(destination, source);
+        self.context
+            .reset(zstd_safe::ResetDirective::ZSTD_reset_session_only)
+            .and_then(|_| self.context.ref_prefix(&[]))
+            .map_err(map_error_code)?;
+        let len = result.map_err(map_error_code)?;
+        Ok(self.fallback(source, destination, len))
+    }
+
+    /// Compresses a block of data against the given prefix, with the
+    /// parameters set on this compressor, and returns the compressed result.
+    pub fn compress2_with_prefix(
+        &mut self,
+        data: &[u8],
+        prefix: &[u8],
+    ) -> io::Result<Vec<u8>> {
+        let mut buffer = vec![0u8; compress_bound(data.len())?];
+        let len = self.compress2_to_buffer_with_prefix(
+            data,
+            &mut buffer[..],
+            prefix,
+        )?;
+        buffer.truncate(len);
+        Ok(buffer)
+    }
+
+    /// Returns the literals/match sequences zstd would produce when
+    /// compressing `data` with the parameters set on this compressor.
+    ///
+    /// Each block ends with a delimiter: a sequence with a zero `offset`
+    /// and `matchLength`, where `litLength` covers the block's last literals.
+    #[cfg(feature = "experimental")]
+    pub fn generate_sequences2(
+        &mut self,
+        data: &[u8],
+    ) -> io::Result<Vec<Sequence>> {
+        let bound = zstd_safe::sequence_bound(data.len());
+        let empty = Sequence {
+            offset: 0,
+            litLength: 0,
+            matchLength: 0,
+            rep: 0,
+        };
+        let mut sequences = vec![empty; bound];
+
+        let len = self.generate_sequences_to(&mut sequences, data)?;
+        sequences.truncate(len);
+        Ok(sequences)
+    }
+
+    /// Compresses `data` using the given literals/match sequences, instead of
+    /// running zstd's own match finder, with the parameters set on this
+    /// compressor.
+    ///
+    /// The sequences must cover `data`, except for the last literals. They
+    /// can include block delimiters, as returned by `generate_sequences`.
+    ///
+    /// Invalid sequences are rejected with an error.
+    #[cfg(feature = "experimental")]
+    pub fn compress_sequences2(
+        &mut self,
+        sequences: &[Sequence],
+        data: &[u8],
+    ) -> io::Result<Vec<u8>> {
+        let covered: u64 = sequences
+            .iter()
+            .map(|seq| u64::from(seq.litLength) + u64::from(seq.matchLength))
+            .sum();
+        if covered > data.len() as u64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "sequences are longer than the data",
+            ));
+        }
+
+        // Let zstd place the block boundaries itself.
+        let mut sequences = sequences.to_vec();
+        let len = zstd_safe::merge_block_delimiters(&mut sequences);
+        sequences.truncate(len);
+
+        let mut buffer = vec![0u8; compress_bound(data.len())?];
+        let len = self.compress_sequences_to(&sequences, data, &mut buffer)?;
+        buffer.truncate(len);
+        Ok(buffer)
+    }
+
+    #[cfg(feature = "experimental")]
+    fn compress_sequences_to(
+        &mut self,
+        sequences: &[Sequence],
+        data: &[u8],
+        destination: &mut [u8],
+    ) -> io::Result<usize> {
+        use zstd_safe::{CParameter, SequenceFormat};
+
+        self.load_dictionary()?;
+        for parameter in &[
+            CParameter::BlockDelimiters(
+                SequenceFormat::ZSTD_sf_noBlockDelimiters,
+            ),
+            // Unchecked sequences could make zstd read out of bounds.
+            CParameter::ValidateSequences(true),
+        ] {
+            self.context
+                .set_parameter(*parameter)
+                .map_err(map_error_code)?;
+        }
+        // Safe: `compress_sequences` checked that the sequences fit in
+        // `data`, and zstd validates the offsets.
+        unsafe {
+            self.context
+                .compress_sequences(destination, sequences, data)
+                .map_err(map_error_code)
+        }
+    }
+
+    #[cfg(feature = "experimental")]
+    fn generate_sequences_to(
+        &mut self,
+        sequences: &mut [Sequence],
+        data: &[u8],
+    ) -> io::Result<usize> {
+        self.load_dictionary()?;
+        self.context
+            .generate_sequences(sequences, data)
+            .map_err(map_error_code)
+    }
+
+    // Replaces the `len` bytes compressed frame in `destination` with a
+    // stored frame if it doesn't save enough, and returns the final size.
+    fn fallback(
+        &self,
+        source: &[u8],
+        destination: &mut [u8],
+        len: usize,
+    ) -> usize {
+        match self.stored_margin {
+            Some(margin) if len.saturating_add(margin) >= source.len() => {
+                crate::frame::store_to_buffer(
+                    source,
+                    self.checksum,
+                    destination,
+                )
+                .unwrap_or(len)
+            }
+            _ => len,
+        }
+    }
+
+    // Sets the level, unless it is already in use.
+    fn use_level(&mut self, level: i32) -> io::Result<()> {
+        if self.level == Some(level) {
+            return Ok(());
+        }
+        self.set_compression_level(level)
+    }
+
+    // Loads our dictionary in the context, unless it is already there.
+    fn load_dictionary(&mut self) -> io::Result<()> {
+        if !self.dict_loaded {
+            self.context
+                .load_dictionary(&self.dict)
+                .map_err(map_error_code)?;
+            self.dict_loaded = true;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the maximum compressed size of a `len`-bytes block.
+///
+/// Fails instead of overflowing when the input is too large to be compressed
+/// in a single buffer (for instance, close to 4GB on 32-bit targets).
+pub(super) fn compress_bound(len: usize) -> io::Result<usize> {
+    let bound = zstd_safe::compress_bound(len);
+    if bound < len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "input too large for a single block, use the stream API instead",
+        ));
+    }
+    Ok(bound)
 }
 
 fn _assert_traits() {
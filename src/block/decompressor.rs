@@ -26,6 +26,15 @@ impl Decompressor {
         }
     }
 
+    /// Returns a mutable reference to the underlying zstd context.
+    ///
+    /// This gives access to zstd functions not covered by this crate.
+    /// Parameters set this way may be overridden by the `decompress` methods,
+    /// which configure the context from their arguments.
+    pub fn context_mut(&mut self) -> &mut zstd_safe::DCtx<'static> {
+        &mut self.context
+    }
+
     /// Deompress a single block of data to the given destination buffer.
     ///
     /// Returns the number of bytes written, or an error if something happened
@@ -61,6 +70,103 @@ impl Decompressor {
         }
         Ok(buffer)
     }
+
+    /// Decompresses a block of data into `output`, replacing its content.
+    ///
+    /// The decompressed data should be less than `capacity` bytes,
+    /// or an error will be returned.
+    ///
+    /// The capacity of `output` is kept and grown as needed: reusing the
+    /// same `Vec` (and decompressor) across calls avoids any allocation once
+    /// it is large enough.
+    pub fn decompress_to_vec(
+        &mut self,
+        data: &[u8],
+        output: &mut Vec<u8>,
+        capacity: usize,
+    ) -> io::Result<()> {
+        output.clear();
+        output.resize(capacity, 0);
+        let result = self.decompress_to_buffer(data, &mut output[..]);
+        output.truncate(*result.as_ref().unwrap_or(&0));
+        result.map(|_| ())
+    }
+
+    /// Decompress a single block of data against the given prefix.
+    ///
+    /// The prefix is used instead of this decompressor's dictionary, and must
+    /// be the same as the one used during compression.
+    pub fn decompress_to_buffer_with_prefix(
+        &mut self,
+        source: &[u8],
+        destination: &mut [u8],
+        prefix: &[u8],
+    ) -> io::Result<usize> {
+        // A prefix only applies to the next frame, and it is cleared right
+        // after in any case, so `prefix` is never used after this call.
+        // Unlike `decompress_using_dict`, it is always raw content.
+        let prefix: &'static [u8] = unsafe { &*(prefix as *const [u8]) };
+        self.context.ref_prefix(prefix).map_err(map_error_code)?;
+        let result = self.context.decompress(destination, source);
+        self.context.ref_prefix(&[]).map_err(map_error_code)?;
+        result.map_err(map_error_code)
+    }
+
+    /// Decompress a block of data against the given prefix, and return the
+    /// result in a `Vec<u8>`.
+    ///
+    /// The decompressed data should be less than `capacity` bytes,
+    /// or an error will be returned.
+    pub fn decompress_with_prefix(
+        &mut self,
+        data: &[u8],
+        capacity: usize,
+        prefix: &[u8],
+    ) -> io::Result<Vec<u8>> {
+        let mut buffer = vec![0u8; capacity];
+        let len =
+            self.decompress_to_buffer_with_prefix(data, &mut buffer, prefix)?;
+        buffer.truncate(len);
+        Ok(buffer)
+    }
+
+    /// Decompress a single block of data using the given dictionary.
+    ///
+    /// The dictionary is only used for this call, instead of this
+    /// decompressor's own: this allows reusing the same context when each
+    /// message uses a different dictionary.
+    pub fn decompress_to_buffer_with_dict(
+        &mut self,
+        source: &[u8],
+        destination: &mut [u8],
+        dict: &[u8],
+    ) -> io::Result<usize> {
+        zstd_safe::decompress_using_dict(
+            &mut self.context,
+            destination,
+            source,
+            dict,
+        )
+        .map_err(map_error_code)
+    }
+
+    /// Decompress a block of data using the given dictionary, and return the
+    /// result in a `Vec<u8>`.
+    ///
+    /// The decompressed data should be less than `capacity` bytes,
+    /// or an error will be returned.
+    pub fn decompress_with_dict(
+        &mut self,
+        data: &[u8],
+        dict: &[u8],
+        capacity: usize,
+    ) -> io::Result<Vec<u8>> {
+        let mut buffer = vec![0u8; capacity];
+        let len =
+            self.decompress_to_buffer_with_dict(data, &mut buffer, dict)?;
+        buffer.truncate(len);
+        Ok(buffer)
+    }
 }
 
 fn _assert_traits() {
@@ -0,0 +1,194 @@
+use crate::dict::{DecoderDictionary, EncoderDictionary};
+use crate::map_error_code;
+
+use std::io;
+use std::sync::Arc;
+
+use super::compressor::compress_bound;
+
+/// Compresses many small independent messages.
+///
+/// This keeps a single context, and an optional prepared dictionary which
+/// can be shared (through an `Arc`) by the compressors of many threads or
+/// connections. Each message is compressed to a complete frame including
+/// its content size, which `MessageDecompressor` relies on.
+pub struct MessageCompressor {
+    context: zstd_safe::CCtx<'static>,
+    dictionary: Option<Arc<EncoderDictionary<'static>>>,
+}
+
+impl MessageCompressor {
+    /// Creates a new compressor, without a dictionary.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`).
+    pub fn new(level: i32) -> io::Result<Self> {
+        let mut context = zstd_safe::CCtx::create();
+        context
+            .set_parameter(zstd_safe::CParameter::CompressionLevel(level))
+            .map_err(map_error_code)?;
+        Ok(MessageCompressor {
+            context,
+            dictionary: None,
+        })
+    }
+
+    /// Creates a new compressor using the given prepared dictionary.
+    ///
+    /// The compression level is the one the dictionary was prepared with.
+    pub fn with_dictionary(
+        dictionary: Arc<EncoderDictionary<'static>>,
+    ) -> Self {
+        MessageCompressor {
+            context: zstd_safe::CCtx::create(),
+            dictionary: Some(dictionary),
+        }
+    }
+
+    /// Compresses a message.
+    pub fn compress(&mut self, message: &[u8]) -> io::Result<Vec<u8>> {
+        let mut output = Vec::new();
+        self.compress_to_vec(message, &mut output)?;
+        Ok(output)
+    }
+
+    /// Compresses a message into `output`, replacing its content.
+    ///
+    /// Reusing the same `output` avoids allocating for each message.
+    pub fn compress_to_vec(
+        &mut self,
+        message: &[u8],
+        output: &mut Vec<u8>,
+    ) -> io::Result<()> {
+        output.clear();
+        output.resize(compress_bound(message.len())?, 0);
+        let result = match &self.dictionary {
+            Some(dictionary) => self.context.compress_using_cdict(
+                output,
+                message,
+                dictionary.as_cdict(),
+            ),
+            None => self.context.compress2(output, message),
+        };
+        let len = result.map_err(|code| {
+            output.clear();
+            map_error_code(code)
+        })?;
+        output.truncate(len);
+        Ok(())
+    }
+}
+
+/// Decompresses messages produced by a `MessageCompressor`.
+///
+/// This keeps a single context, and an optional prepared dictionary which
+/// can be shared by the decompressors of many threads or connections.
+///
+/// Each message is decompressed into a buffer of exactly the size declared
+/// in its header, up to a maximum size to protect against malicious input.
+pub struct MessageDecompressor {
+    context: zstd_safe::DCtx<'static>,
+    dictionary: Option<Arc<DecoderDictionary<'static>>>,
+    max_size: usize,
+}
+
+impl MessageDecompressor {
+    /// Default value for `max_size`: 16MB.
+    pub const DEFAULT_MAX_SIZE: usize = 1 << 24;
+
+    /// Creates a new decompressor, without a dictionary.
+    pub fn new() -> Self {
+        MessageDecompressor {
+            context: zstd_safe::DCtx::create(),
+            dictionary: None,
+            max_size: Self::DEFAULT_MAX_SIZE,
+        }
+    }
+
+    /// Creates a new decompressor using the given prepared dictionary.
+    pub fn with_dictionary(
+        dictionary: Arc<DecoderDictionary<'static>>,
+    ) -> Self {
+        MessageDecompressor {
+            dictionary: Some(dictionary),
+            ..MessageDecompressor::new()
+        }
+    }
+
+    /// Sets the maximum decompressed size of a message.
+    ///
+    /// Messages declaring a larger size are rejected before any allocation.
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Decompresses a message.
+    pub fn decompress(&mut self, message: &[u8]) -> io::Result<Vec<u8>> {
+        let mut output = Vec::new();
+        self.decompress_to_vec(message, &mut output)?;
+        Ok(output)
+    }
+
+    /// Decompresses a message into `output`, replacing its content.
+    ///
+    /// Reusing the same `output` avoids allocating for each message.
+    pub fn decompress_to_vec(
+        &mut self,
+        message: &[u8],
+        output: &mut Vec<u8>,
+    ) -> io::Result<()> {
+        output.clear();
+        let size = match zstd_safe::get_frame_content_size(message) {
+            zstd_safe::CONTENTSIZE_ERROR => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "invalid message header",
+                ))
+            }
+            zstd_safe::CONTENTSIZE_UNKNOWN => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "message does not declare its size",
+                ))
+            }
+            size if size > self.max_size as u64 => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "message is larger than the maximum size",
+                ))
+            }
+            size => size as usize,
+        };
+
+        output.resize(size, 0);
+        let result = match &self.dictionary {
+            Some(dictionary) => self.context.decompress_using_ddict(
+                output,
+                message,
+                dictionary.as_ddict(),
+            ),
+            None => self.context.decompress(output, message),
+        };
+        let len = result.map_err(|code| {
+            output.clear();
+            map_error_code(code)
+        })?;
+        output.truncate(len);
+        Ok(())
+    }
+}
+
+impl Default for MessageDecompressor {
+    fn default() -> Self {
+        MessageDecompressor::new()
+    }
+}
+
+fn _assert_traits() {
+    fn _assert_send<T: Send>(_: T) {}
+
+    _assert_send(MessageCompressor::with_dictionary(Arc::new(
+        EncoderDictionary::copy(&[], 1),
+    )));
+    _assert_send(MessageDecompressor::new());
+}
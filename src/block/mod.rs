@@ -3,12 +3,22 @@
 //! These methods process all the input data at once.
 //! It is therefore best used with relatively small blocks
 //! (like small network packets).
+//!
+//! Inputs too large to fit in memory, or in a single buffer (more than 4GB
+//! on 32-bit targets), should use the `stream` module instead, for example
+//! with `stream::copy_encode_with_size`.
 
 mod compressor;
 mod decompressor;
+mod message;
+#[cfg(feature = "experimental")]
+mod prepared;
 
 pub use self::compressor::Compressor;
 pub use self::decompressor::Decompressor;
+pub use self::message::{MessageCompressor, MessageDecompressor};
+#[cfg(feature = "experimental")]
+pub use self::prepared::PreparedCompressor;
 
 use std::io;
 
@@ -33,6 +43,22 @@ pub fn compress(data: &[u8], level: i32) -> io::Result<Vec<u8>> {
     Compressor::new().compress(data, level)
 }
 
+/// Compresses a block of data into `output`, replacing its content.
+///
+/// The capacity of `output` is reused. To also reuse the compression
+/// context, use [`Compressor::compress_to_vec`].
+///
+/// A level of `0` uses zstd's default (currently `3`).
+///
+/// [`Compressor::compress_to_vec`]: struct.Compressor.html#method.compress_to_vec
+pub fn compress_to_vec(
+    data: &[u8],
+    output: &mut Vec<u8>,
+    level: i32,
+) -> io::Result<()> {
+    Compressor::new().compress_to_vec(data, output, level)
+}
+
 /// Deompress a single block of data to the given destination buffer.
 ///
 /// Returns the number of bytes written, or an error if something happened
@@ -52,6 +78,23 @@ pub fn decompress(data: &[u8], capacity: usize) -> io::Result<Vec<u8>> {
     Decompressor::new().decompress(data, capacity)
 }
 
+/// Decompresses a block of data into `output`, replacing its content.
+///
+/// The decompressed data should be less than `capacity` bytes,
+/// or an error will be returned.
+///
+/// The capacity of `output` is reused. To also reuse the decompression
+/// context, use [`Decompressor::decompress_to_vec`].
+///
+/// [`Decompressor::decompress_to_vec`]: struct.Decompressor.html#method.decompress_to_vec
+pub fn decompress_to_vec(
+    data: &[u8],
+    output: &mut Vec<u8>,
+    capacity: usize,
+) -> io::Result<()> {
+    Decompressor::new().decompress_to_vec(data, output, capacity)
+}
+
 #[cfg(test)]
 mod tests {
     use super::{compress, decompress};
@@ -73,10 +116,323 @@ mod tests {
                     ramps pabst farm-to-table art party kombucha artisan \
                     fanny pack. Flannel salvia ennui viral leggings selfies.";
 
-        crate::test_cycle_unwrap(
+        crate::test_util::cycle_unwrap(
             text.as_bytes(),
             |data| compress(data, 1),
             |data| decompress(data, text.len()),
         );
     }
+
+    #[cfg(feature = "experimental")]
+    #[test]
+    fn test_generate_sequences() {
+        let text = "abcdefgh abcdefgh abcdefgh abcdefgh 12345678".repeat(10);
+
+        let sequences = super::Compressor::new()
+            .generate_sequences(text.as_bytes(), 3)
+            .unwrap();
+
+        assert!(sequences.iter().any(|seq| seq.matchLength > 0));
+        let covered: u32 = sequences
+            .iter()
+            .map(|seq| seq.litLength + seq.matchLength)
+            .sum();
+        assert_eq!(covered as usize, text.len());
+    }
+
+    #[cfg(feature = "experimental")]
+    #[test]
+    fn test_compress_sequences() {
+        let text = "abcdefgh abcdefgh abcdefgh abcdefgh 12345678".repeat(10);
+
+        let mut compressor = super::Compressor::new();
+        let sequences =
+            compressor.generate_sequences(text.as_bytes(), 3).unwrap();
+        let compressed = compressor
+            .compress_sequences(&sequences, text.as_bytes(), 3)
+            .unwrap();
+
+        assert!(compressed.len() < text.len());
+        assert_eq!(
+            decompress(&compressed, text.len()).unwrap(),
+            text.as_bytes()
+        );
+
+        // Sequences going past the end of the data are rejected.
+        assert!(compressor
+            .compress_sequences(&sequences, &text.as_bytes()[1..], 3)
+            .is_err());
+    }
+
+    #[test]
+    fn test_prefix() {
+        use super::{Compressor, Decompressor};
+
+        let old = "The quick brown fox jumps over the lazy dog. ".repeat(20);
+        let new = old.replace("lazy", "sleepy");
+
+        let mut compressor = Compressor::new();
+        let plain = compressor.compress(new.as_bytes(), 3).unwrap();
+        let delta = compressor
+            .compress_with_prefix(new.as_bytes(), 3, old.as_bytes())
+            .unwrap();
+        assert!(delta.len() < plain.len());
+
+        let decompressed = Decompressor::new()
+            .decompress_with_prefix(&delta, new.len(), old.as_bytes())
+            .unwrap();
+        assert_eq!(decompressed, new.as_bytes());
+
+        // A prefix is raw content, even if it looks like a dictionary.
+        let mut header = zstd_safe::MAGIC_DICTIONARY.to_le_bytes().to_vec();
+        header.extend_from_slice(&[0xFF; 4]);
+        let prefix = [&header[..], old.as_bytes()].concat();
+        let delta = compressor
+            .compress_with_prefix(new.as_bytes(), 3, &prefix)
+            .unwrap();
+        let mut decompressor = Decompressor::new();
+        let decompressed = decompressor
+            .decompress_with_prefix(&delta, new.len(), &prefix)
+            .unwrap();
+        assert_eq!(decompressed, new.as_bytes());
+
+        // The prefix is not kept for the next calls.
+        assert_eq!(
+            decompressor.decompress(&plain, new.len()).unwrap(),
+            new.as_bytes()
+        );
+        assert_eq!(compressor.compress(new.as_bytes(), 3).unwrap(), plain);
+
+        // Data that doesn't compress, even with the prefix, is stored.
+        let noise = crate::test_util::random_bytes(1000, 1);
+        compressor.fallback_to_stored(Some(16));
+        let compressed = compressor
+            .compress2_with_prefix(&noise, old.as_bytes())
+            .unwrap();
+        assert_eq!(compressed, crate::frame::store(&noise, false));
+        let decompressed = decompressor
+            .decompress_with_prefix(&compressed, noise.len(), old.as_bytes())
+            .unwrap();
+        assert_eq!(decompressed, noise);
+
+        // But a good prefix still gives a small delta.
+        let delta = compressor
+            .compress2_with_prefix(new.as_bytes(), old.as_bytes())
+            .unwrap();
+        assert!(delta.len() < plain.len());
+    }
+
+    #[test]
+    fn test_decompress_with_dict() {
+        use super::{Compressor, Decompressor};
+
+        let dicts = [
+            b"first dictionary content, ".repeat(20),
+            b"second dictionary content, ".repeat(20),
+        ];
+
+        let mut decompressor = Decompressor::new();
+        for (i, dict) in dicts.iter().enumerate() {
+            let message = format!("{} message", i).repeat(4);
+            let compressed = Compressor::with_dict(dict.clone())
+                .compress(message.as_bytes(), 3)
+                .unwrap();
+            let decompressed = decompressor
+                .decompress_with_dict(&compressed, dict, message.len())
+                .unwrap();
+            assert_eq!(decompressed, message.as_bytes());
+        }
+
+        // The dictionary is not kept for the next calls.
+        let compressed = super::compress(b"no dictionary", 1).unwrap();
+        assert_eq!(
+            decompressor.decompress(&compressed, 100).unwrap(),
+            b"no dictionary"
+        );
+    }
+
+    #[test]
+    fn test_reuse_buffers() {
+        use super::{
+            compress_to_vec, decompress_to_vec, Compressor, Decompressor,
+        };
+
+        let mut compressor = Compressor::new();
+        let mut decompressor = Decompressor::new();
+        let mut compressed = Vec::new();
+        let mut decompressed = Vec::new();
+
+        for i in 0..10 {
+            let message = format!("message number {}", i).repeat(i + 1);
+            compressor
+                .compress_to_vec(message.as_bytes(), &mut compressed, 3)
+                .unwrap();
+            decompressor
+                .decompress_to_vec(
+                    &compressed,
+                    &mut decompressed,
+                    message.len(),
+                )
+                .unwrap();
+            assert_eq!(decompressed, message.as_bytes());
+        }
+
+        compress_to_vec(b"free function", &mut compressed, 1).unwrap();
+        decompress_to_vec(&compressed, &mut decompressed, 13).unwrap();
+        assert_eq!(decompressed, b"free function");
+
+        // Errors leave an empty output.
+        assert!(decompress_to_vec(&compressed, &mut decompressed, 5).is_err());
+        assert!(decompressed.is_empty());
+    }
+
+    #[test]
+    fn test_compress_bound_overflow() {
+        let len = usize::MAX - 10;
+        assert!(super::compressor::compress_bound(len).is_err());
+        assert!(super::compressor::compress_bound(1000).unwrap() > 1000);
+    }
+
+    #[test]
+    fn test_stateful_parameters() {
+        use super::{Compressor, Decompressor};
+
+        let dict = b"a dictionary with some shared content".to_vec();
+        let mut compressor = Compressor::with_dict(dict.clone());
+        compressor.set_compression_level(19).unwrap();
+        compressor.include_checksum(true).unwrap();
+        compressor.window_log(20).unwrap();
+
+        let mut decompressor = Decompressor::with_dict(dict);
+        for i in 0..3 {
+            let message = format!("{} with some shared content", i);
+            let compressed = compressor.compress2(message.as_bytes()).unwrap();
+
+            // The frame header descriptor has the checksum flag set.
+            assert_ne!(compressed[4] & 0x04, 0);
+            assert_eq!(
+                decompressor.decompress(&compressed, message.len()).unwrap(),
+                message.as_bytes()
+            );
+
+            // A prefix only applies to a single block.
+            compressor
+                .compress2_with_prefix(message.as_bytes(), b"prefix")
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_dictionary_levels() {
+        use super::{Compressor, Decompressor};
+
+        let dict = b"some dictionary content, with words. ".repeat(20);
+        let text = crate::test_util::text(100_000, 2);
+        let mut compressor = Compressor::with_dict(dict.clone());
+        let mut decompressor = Decompressor::with_dict(dict);
+
+        // The dictionary is only loaded once, but still follows the level.
+        let fast = compressor.compress(&text, 1).unwrap();
+        assert_eq!(compressor.compress(&text, 1).unwrap(), fast);
+        let strong = compressor.compress(&text, 19).unwrap();
+        assert!(strong.len() < fast.len());
+        compressor.set_compression_level(1).unwrap();
+        assert_eq!(compressor.compress2(&text).unwrap(), fast);
+
+        for compressed in &[fast, strong] {
+            assert_eq!(
+                decompressor.decompress(compressed, text.len()).unwrap(),
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_messages() {
+        use super::{MessageCompressor, MessageDecompressor};
+        use crate::dict::{DecoderDictionary, EncoderDictionary};
+        use std::sync::Arc;
+
+        let messages: Vec<String> = (0..100)
+            .map(|i| format!("{{\"id\": {}, \"method\": \"ping\"}}", i))
+            .collect();
+        let dict = b"{\"id\": , \"method\": \"ping\"}".repeat(4);
+
+        let mut compressor = MessageCompressor::new(3).unwrap();
+        let mut decompressor = MessageDecompressor::new();
+        let encoder_dict = Arc::new(EncoderDictionary::copy(&dict, 3));
+        let decoder_dict = Arc::new(DecoderDictionary::copy(&dict));
+        let mut dict_compressor =
+            MessageCompressor::with_dictionary(encoder_dict);
+        let mut dict_decompressor =
+            MessageDecompressor::with_dictionary(decoder_dict);
+
+        for message in &messages {
+            let compressed = compressor.compress(message.as_bytes()).unwrap();
+            assert_eq!(
+                decompressor.decompress(&compressed).unwrap(),
+                message.as_bytes()
+            );
+
+            let compressed =
+                dict_compressor.compress(message.as_bytes()).unwrap();
+            assert_eq!(
+                dict_decompressor.decompress(&compressed).unwrap(),
+                message.as_bytes()
+            );
+        }
+
+        // Messages over the limit are rejected.
+        let compressed = compressor.compress(&[0u8; 1000]).unwrap();
+        let mut decompressor = MessageDecompressor::new().max_size(999);
+        assert!(decompressor.decompress(&compressed).is_err());
+    }
+
+    #[test]
+    fn test_fallback_to_stored() {
+        use super::Compressor;
+
+        let noise = crate::test_util::random_bytes(200_000, 1);
+
+        let mut compressor = Compressor::new();
+        compressor.include_checksum(true).unwrap();
+        compressor.fallback_to_stored(Some(16));
+
+        for &len in &[0, 10, 300, 70_000, 200_000] {
+            let data = &noise[..len];
+            let compressed = compressor.compress2(data).unwrap();
+            assert_eq!(compressed, crate::frame::store(data, true));
+            assert_eq!(decompress(&compressed, len).unwrap(), data);
+            assert_eq!(crate::decode_all(&compressed[..]).unwrap(), data);
+            crate::verify(&compressed[..]).unwrap();
+        }
+
+        // Compressible data is still compressed.
+        let text = b"compressible ".repeat(100);
+        assert!(compressor.compress2(&text).unwrap().len() < 100);
+    }
+
+    #[cfg(feature = "experimental")]
+    #[test]
+    fn test_prepared_compressor() {
+        use super::{Decompressor, PreparedCompressor};
+
+        let dict = b"header: value; other-header: other value; ".repeat(50);
+        let template = PreparedCompressor::new(&dict, 3).unwrap();
+
+        let mut decompressor = Decompressor::with_dict(dict.clone());
+        for i in 0..4 {
+            let mut compressor = template.try_clone().unwrap();
+            for j in 0..10 {
+                let message = format!("header: {}; other-header: {}; ", i, j);
+                let compressed =
+                    compressor.compress(message.as_bytes()).unwrap();
+                assert!(compressed.len() < message.len());
+                let decompressed = decompressor
+                    .decompress(&compressed, message.len())
+                    .unwrap();
+                assert_eq!(decompressed, message.as_bytes());
+            }
+        }
+    }
 }
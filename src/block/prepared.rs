@@ -0,0 +1,78 @@
+use crate::map_error_code;
+
+use std::io;
+
+use super::compressor::compress_bound;
+
+/// Compresses blocks with a dictionary loaded only once.
+///
+/// Loading a dictionary in a context is expensive for large dictionaries.
+/// Here it is loaded once in a template context, which is then duplicated
+/// (a plain memory copy) for each block. `try_clone` duplicates it as well,
+/// to get a compressor per thread or task without loading the dictionary
+/// again.
+pub struct PreparedCompressor<'a> {
+    template: zstd_safe::CCtx<'a>,
+    context: zstd_safe::CCtx<'a>,
+}
+
+impl<'a> PreparedCompressor<'a> {
+    /// Creates a compressor for the given dictionary and level.
+    ///
+    /// The dictionary is referenced, not copied.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`).
+    pub fn new(dictionary: &'a [u8], level: i32) -> io::Result<Self> {
+        let mut template = zstd_safe::CCtx::create();
+        template
+            .compress_begin_using_dict(dictionary, level)
+            .map_err(map_error_code)?;
+        Ok(PreparedCompressor {
+            template,
+            context: zstd_safe::CCtx::create(),
+        })
+    }
+
+    /// Duplicates this compressor, without loading the dictionary again.
+    pub fn try_clone(&self) -> io::Result<Self> {
+        let mut template = zstd_safe::CCtx::create();
+        template
+            .copy_cctx(&self.template, 0)
+            .map_err(map_error_code)?;
+        Ok(PreparedCompressor {
+            template,
+            context: zstd_safe::CCtx::create(),
+        })
+    }
+
+    /// Compress a single block of data to the given destination buffer.
+    ///
+    /// Returns the number of bytes written, or an error if something happened
+    /// (for instance if the destination buffer was too small).
+    pub fn compress_to_buffer(
+        &mut self,
+        source: &[u8],
+        destination: &mut [u8],
+    ) -> io::Result<usize> {
+        self.context
+            .copy_cctx(&self.template, source.len() as u64)
+            .map_err(map_error_code)?;
+        self.context
+            .compress_end(destination, source)
+            .map_err(map_error_code)
+    }
+
+    /// Compresses a block of data and returns the compressed result.
+    pub fn compress(&mut self, data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut buffer = vec![0u8; compress_bound(data.len())?];
+        let len = self.compress_to_buffer(data, &mut buffer)?;
+        buffer.truncate(len);
+        Ok(buffer)
+    }
+}
+
+fn _assert_traits() {
+    fn _assert_send<T: Send>(_: T) {}
+
+    _assert_send(PreparedCompressor::new(&[], 1));
+}
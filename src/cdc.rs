@@ -0,0 +1,375 @@
+//! Content-defined chunking, as a building block for deduplication.
+//!
+//! The input is split where its content matches a rolling hash (the gear
+//! hash used by FastCDC), rather than at fixed offsets. Inserting or
+//! removing data only changes the chunks around the edit: the following
+//! chunks keep the same boundaries, and so the same hashes.
+//!
+//! [`ChunkEncoder`] compresses each chunk into its own zstd frame, and
+//! reports its position and hash. A backup tool can then store each frame
+//! once, indexed by hash, and rebuild the input by decompressing the frames
+//! in order.
+//!
+//! This module is only available with the `cdc` feature.
+//!
+//! ```
+//! use std::collections::HashMap;
+//!
+//! let data = vec![42u8; 1_000_000];
+//!
+//! let mut store = HashMap::new();
+//! let mut hashes = Vec::new();
+//! for chunk in zstd::cdc::ChunkEncoder::new(&data[..], 3)? {
+//!     let chunk = chunk?;
+//!     hashes.push(chunk.hash);
+//!     store.entry(chunk.hash).or_insert(chunk.frame);
+//! }
+//!
+//! let mut restored = Vec::new();
+//! for hash in hashes {
+//!     restored.extend(zstd::decode_all(&store[&hash][..])?);
+//! }
+//! assert_eq!(restored, data);
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! [`ChunkEncoder`]: struct.ChunkEncoder.html
+
+use std::io::{self, Read};
+use std::sync::Arc;
+
+use crate::block::MessageCompressor;
+use crate::dict::EncoderDictionary;
+
+/// Finds content-defined chunk boundaries.
+///
+/// Chunks are between `min_size` and `max_size` bytes long, and about
+/// `avg_size` on average. Boundaries are more likely around `avg_size`
+/// (the "normalized chunking" of FastCDC), which keeps the sizes close to
+/// the average.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chunker {
+    min_size: usize,
+    avg_size: usize,
+    max_size: usize,
+
+    // Hash bits checked before `avg_size`: more bits, fewer boundaries.
+    mask_small: u64,
+    // Hash bits checked after `avg_size`.
+    mask_large: u64,
+}
+
+impl Chunker {
+    /// Creates a new chunker with the given sizes.
+    ///
+    /// `avg_size` must be a power of two, at least 256, and the sizes must
+    /// satisfy `min_size <= avg_size <= max_size`.
+    pub fn new(
+        min_size: usize,
+        avg_size: usize,
+        max_size: usize,
+    ) -> io::Result<Self> {
+        if !avg_size.is_power_of_two()
+            || avg_size < 256
+            || min_size > avg_size
+            || avg_size > max_size
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid chunk sizes",
+            ));
+        }
+
+        let bits = avg_size.trailing_zeros();
+        Ok(Chunker {
+            min_size,
+            avg_size,
+            max_size,
+            mask_small: high_bits(bits + 2),
+            mask_large: high_bits(bits - 2),
+        })
+    }
+
+    /// Returns the minimum chunk size.
+    pub fn min_size(&self) -> usize {
+        self.min_size
+    }
+
+    /// Returns the average chunk size.
+    pub fn avg_size(&self) -> usize {
+        self.avg_size
+    }
+
+    /// Returns the maximum chunk size.
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// Returns the size of the first chunk in `data`.
+    ///
+    /// When no boundary is found, this is `data.len()` (or `max_size`, if
+    /// smaller). When reading a stream, only call this with at least
+    /// `max_size` bytes, or at the end of the input: otherwise, the chunk
+    /// could end earlier than it would with more data.
+    pub fn cut_point(&self, data: &[u8]) -> usize {
+        if data.len() <= self.min_size {
+            return data.len();
+        }
+        let end = data.len().min(self.max_size);
+        let normal = self.avg_size.min(end);
+
+        let mut hash = 0u64;
+        for (i, &byte) in data.iter().enumerate().take(end).skip(self.min_size)
+        {
+            hash = (hash << 1).wrapping_add(GEAR[byte as usize]);
+            let mask = if i < normal {
+                self.mask_small
+            } else {
+                self.mask_large
+            };
+            if hash & mask == 0 {
+                return i + 1;
+            }
+        }
+        end
+    }
+}
+
+impl Default for Chunker {
+    /// Chunks of 64KB on average, from 16KB to 256KB.
+    fn default() -> Self {
+        Chunker::new(16 * 1024, 64 * 1024, 256 * 1024).unwrap()
+    }
+}
+
+// With the shifts of the gear hash, the high bits depend on the most bytes.
+fn high_bits(count: u32) -> u64 {
+    !0u64 << (64 - count)
+}
+
+/// A chunk of the input, compressed in its own frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    /// Position of this chunk in the uncompressed input.
+    pub offset: u64,
+
+    /// Size of the uncompressed chunk.
+    pub len: usize,
+
+    /// XXH64 hash (with a seed of `0`) of the uncompressed chunk.
+    ///
+    /// Identical chunks have the same hash, and can be stored only once.
+    pub hash: u64,
+
+    /// The compressed chunk, as a complete zstd frame.
+    ///
+    /// The frame includes the content size. With a dictionary, the same
+    /// dictionary is needed to decompress it.
+    pub frame: Vec<u8>,
+}
+
+/// Splits a stream into chunks, and compresses each of them.
+///
+/// This is an iterator over the chunks of the input, in order. It stops
+/// after the first error.
+pub struct ChunkEncoder<R> {
+    reader: R,
+    chunker: Chunker,
+    compressor: MessageCompressor,
+
+    // Input read but not chunked yet.
+    buffer: Vec<u8>,
+    offset: u64,
+    eof: bool,
+    done: bool,
+}
+
+impl<R: Read> ChunkEncoder<R> {
+    /// Creates a new chunk encoder, with the default `Chunker`.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`).
+    pub fn new(reader: R, level: i32) -> io::Result<Self> {
+        Ok(Self::with_compressor(
+            reader,
+            MessageCompressor::new(level)?,
+        ))
+    }
+
+    /// Creates a new chunk encoder, compressing with a prepared dictionary.
+    ///
+    /// Small chunks benefit a lot from a dictionary, which can be shared by
+    /// many encoders.
+    pub fn with_dictionary(
+        reader: R,
+        dictionary: Arc<EncoderDictionary<'static>>,
+    ) -> Self {
+        Self::with_compressor(
+            reader,
+            MessageCompressor::with_dictionary(dictionary),
+        )
+    }
+
+    fn with_compressor(reader: R, compressor: MessageCompressor) -> Self {
+        ChunkEncoder {
+            reader,
+            chunker: Chunker::default(),
+            compressor,
+            buffer: Vec::new(),
+            offset: 0,
+            eof: false,
+            done: false,
+        }
+    }
+
+    /// Sets the chunk sizes.
+    pub fn chunker(mut self, chunker: Chunker) -> Self {
+        self.chunker = chunker;
+        self
+    }
+
+    /// Returns the inner reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    // Reads until `buffer` holds a maximum-size chunk, or the end of input.
+    fn fill_buffer(&mut self) -> io::Result<()> {
+        let max_size = self.chunker.max_size();
+        while !self.eof && self.buffer.len() < max_size {
+            let start = self.buffer.len();
+            self.buffer.resize(max_size, 0);
+            match self.reader.read(&mut self.buffer[start..]) {
+                Ok(0) => {
+                    self.buffer.truncate(start);
+                    self.eof = true;
+                }
+                Ok(n) => self.buffer.truncate(start + n),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {
+                    self.buffer.truncate(start);
+                }
+                Err(e) => {
+                    self.buffer.truncate(start);
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn next_chunk(&mut self) -> io::Result<Option<Chunk>> {
+        self.fill_buffer()?;
+        if self.buffer.is_empty() {
+            return Ok(None);
+        }
+
+        let len = self.chunker.cut_point(&self.buffer);
+        let data = &self.buffer[..len];
+        let chunk = Chunk {
+            offset: self.offset,
+            len,
+            hash: crate::xxhash64(data, 0),
+            frame: self.compressor.compress(data)?,
+        };
+        self.buffer.drain(..len);
+        self.offset += len as u64;
+        Ok(Some(chunk))
+    }
+}
+
+impl<R: Read> Iterator for ChunkEncoder<R> {
+    type Item = io::Result<Chunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self.next_chunk().transpose();
+        if !matches!(result, Some(Ok(_))) {
+            self.done = true;
+        }
+        result
+    }
+}
+
+// Random values for the gear hash, one per byte value.
+const GEAR: [u64; 256] = gear_table();
+
+// Fills the table with a splitmix64 sequence, so it is fixed across builds.
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state = 0u64;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+fn _assert_traits() {
+    fn _assert_send<T: Send>(_: T) {}
+
+    _assert_send(ChunkEncoder::new(&b""[..], 1));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChunkEncoder, Chunker};
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_chunks() {
+        let chunker = Chunker::new(1024, 4096, 16 * 1024).unwrap();
+        let data = crate::test_util::random_bytes(500_000, 1);
+
+        let chunks: Vec<_> = ChunkEncoder::new(&data[..], 1)
+            .unwrap()
+            .chunker(chunker)
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        let mut restored = Vec::new();
+        for (i, chunk) in chunks.iter().enumerate() {
+            assert_eq!(chunk.offset, restored.len() as u64);
+            assert!(chunk.len <= chunker.max_size());
+            if i + 1 < chunks.len() {
+                assert!(chunk.len > chunker.min_size());
+            }
+            let content = crate::decode_all(&chunk.frame[..]).unwrap();
+            assert_eq!(content.len(), chunk.len);
+            assert_eq!(crate::xxhash64(&content, 0), chunk.hash);
+            restored.extend(content);
+        }
+        assert_eq!(restored, data);
+
+        // Boundaries depend on the content, not on the offsets: after an
+        // insertion, most chunks are the same.
+        let mut edited = b"inserted".to_vec();
+        edited.extend_from_slice(&data);
+        let hashes: HashSet<u64> = chunks.iter().map(|c| c.hash).collect();
+        let shared = ChunkEncoder::new(&edited[..], 1)
+            .unwrap()
+            .chunker(chunker)
+            .filter(|chunk| hashes.contains(&chunk.as_ref().unwrap().hash))
+            .count();
+        assert!(shared + 3 >= chunks.len());
+    }
+
+    #[test]
+    fn test_sizes() {
+        assert!(Chunker::new(1024, 3000, 16 * 1024).is_err());
+        assert!(Chunker::new(8192, 4096, 16 * 1024).is_err());
+        assert!(Chunker::new(0, 4096, 2048).is_err());
+
+        let chunker = Chunker::new(0, 256, 256).unwrap();
+        assert_eq!(chunker.cut_point(&[]), 0);
+        assert!(chunker.cut_point(&[0; 1000]) <= 256);
+
+        let empty: Vec<_> = ChunkEncoder::new(&b""[..], 1).unwrap().collect();
+        assert!(empty.is_empty());
+    }
+}
@@ -0,0 +1,267 @@
+//! An append-only byte buffer, kept compressed in memory.
+//!
+//! See [`CompressedVec`](struct.CompressedVec.html).
+use std::io::{self, Write};
+use std::{slice, vec};
+
+use crate::block::{Compressor, Decompressor};
+
+/// An append-only byte buffer, kept compressed in memory.
+///
+/// Appended data is gathered in an uncompressed tail, which is compressed
+/// into an independent frame (a segment) every time it reaches the segment
+/// size. This works well for in-memory logs or event buffers, which are
+/// mostly appended to and only read back as a whole.
+///
+/// The content can be read back with [`iter`] or [`drain`], one segment at
+/// a time.
+///
+/// [`iter`]: #method.iter
+/// [`drain`]: #method.drain
+///
+/// # Examples
+///
+/// ```rust
+/// # fn main() -> std::io::Result<()> {
+/// let mut buffer = zstd::CompressedVec::new(3)?;
+/// for i in 0..1000 {
+///     buffer.extend_from_slice(format!("event {}\n", i).as_bytes())?;
+/// }
+///
+/// let mut content = Vec::new();
+/// for segment in buffer.iter() {
+///     content.extend_from_slice(&segment?);
+/// }
+/// assert_eq!(content.len() as u64, buffer.len());
+/// # Ok(())
+/// # }
+/// ```
+pub struct CompressedVec {
+    compressor: Compressor,
+    segment_size: usize,
+
+    // Compressed segments, each holding `segment_size` bytes.
+    segments: Vec<Vec<u8>>,
+    // Data not compressed yet.
+    tail: Vec<u8>,
+}
+
+/// Iterator over the decompressed segments of a `CompressedVec`.
+///
+/// This is returned by [`CompressedVec::iter`].
+///
+/// [`CompressedVec::iter`]: struct.CompressedVec.html#method.iter
+pub struct Iter<'a> {
+    segments: slice::Iter<'a, Vec<u8>>,
+    tail: Option<&'a [u8]>,
+    decompressor: Decompressor,
+    segment_size: usize,
+}
+
+/// Draining iterator over the decompressed segments of a `CompressedVec`.
+///
+/// This is returned by [`CompressedVec::drain`].
+///
+/// [`CompressedVec::drain`]: struct.CompressedVec.html#method.drain
+pub struct Drain {
+    segments: vec::IntoIter<Vec<u8>>,
+    tail: Option<Vec<u8>>,
+    decompressor: Decompressor,
+    segment_size: usize,
+}
+
+impl CompressedVec {
+    /// Default size of a segment (1MB).
+    pub const DEFAULT_SEGMENT_SIZE: usize = 1 << 20;
+
+    /// Creates an empty buffer, compressing with the given level.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`).
+    pub fn new(level: i32) -> io::Result<Self> {
+        Self::with_segment_size(level, Self::DEFAULT_SEGMENT_SIZE)
+    }
+
+    /// Creates an empty buffer with the given segment size.
+    ///
+    /// Larger segments compress better, but are less granular to read, and
+    /// use more uncompressed memory for the tail.
+    ///
+    /// # Panics
+    ///
+    /// If `segment_size` is 0.
+    pub fn with_segment_size(
+        level: i32,
+        segment_size: usize,
+    ) -> io::Result<Self> {
+        assert!(segment_size != 0, "segment size must be non-zero");
+        Ok(CompressedVec {
+            compressor: Compressor::with_level(level)?,
+            segment_size,
+            segments: Vec::new(),
+            tail: Vec::new(),
+        })
+    }
+
+    /// Appends data to the buffer.
+    ///
+    /// Full segments are compressed right away.
+    pub fn extend_from_slice(&mut self, mut data: &[u8]) -> io::Result<()> {
+        while !data.is_empty() {
+            let n =
+                usize::min(data.len(), self.segment_size - self.tail.len());
+            self.tail.extend_from_slice(&data[..n]);
+            data = &data[n..];
+            if self.tail.len() == self.segment_size {
+                let segment = self.compressor.compress2(&self.tail)?;
+                self.segments.push(segment);
+                self.tail.clear();
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the size of the decompressed content.
+    pub fn len(&self) -> u64 {
+        self.segments.len() as u64 * self.segment_size as u64
+            + self.tail.len() as u64
+    }
+
+    /// Returns `true` if nothing was appended.
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty() && self.tail.is_empty()
+    }
+
+    /// Returns the memory used by the content, compressed segments and
+    /// uncompressed tail included.
+    pub fn memory_size(&self) -> usize {
+        self.segments.iter().map(Vec::len).sum::<usize>() + self.tail.len()
+    }
+
+    /// Returns the number of compressed segments.
+    pub fn segment_count(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// Removes all the content.
+    pub fn clear(&mut self) {
+        self.segments.clear();
+        self.tail.clear();
+    }
+
+    /// Returns an iterator over the decompressed content.
+    ///
+    /// Each item is a full segment, except for the last one.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            segments: self.segments.iter(),
+            tail: Some(&self.tail[..]).filter(|tail| !tail.is_empty()),
+            decompressor: Decompressor::new(),
+            segment_size: self.segment_size,
+        }
+    }
+
+    /// Removes all the content, and returns an iterator over it.
+    ///
+    /// Segments are only decompressed, and freed, as the iterator goes.
+    pub fn drain(&mut self) -> Drain {
+        let tail = std::mem::take(&mut self.tail);
+        Drain {
+            segments: std::mem::take(&mut self.segments).into_iter(),
+            tail: Some(tail).filter(|tail| !tail.is_empty()),
+            decompressor: Decompressor::new(),
+            segment_size: self.segment_size,
+        }
+    }
+
+    /// Decompresses the entire content.
+    pub fn to_vec(&self) -> io::Result<Vec<u8>> {
+        let mut content = Vec::new();
+        for segment in self.iter() {
+            content.extend_from_slice(&segment?);
+        }
+        Ok(content)
+    }
+}
+
+impl Write for CompressedVec {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.extend_from_slice(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Iterator for Iter<'_> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.segments.next() {
+            Some(segment) => {
+                Some(self.decompressor.decompress(segment, self.segment_size))
+            }
+            None => self.tail.take().map(|tail| Ok(tail.to_vec())),
+        }
+    }
+}
+
+impl Iterator for Drain {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.segments.next() {
+            Some(segment) => {
+                Some(self.decompressor.decompress(&segment, self.segment_size))
+            }
+            None => self.tail.take().map(Ok),
+        }
+    }
+}
+
+fn _assert_traits() {
+    fn _assert_send<T: Send>(_: T) {}
+
+    _assert_send(CompressedVec::new(1));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CompressedVec;
+    use std::io::Write;
+
+    #[test]
+    fn test_compressed_vec() {
+        let mut buffer = CompressedVec::with_segment_size(1, 1000).unwrap();
+        assert!(buffer.is_empty());
+
+        let mut content = Vec::new();
+        for i in 0..500 {
+            let event = format!("event {} happened\n", i);
+            buffer.write_all(event.as_bytes()).unwrap();
+            content.extend_from_slice(event.as_bytes());
+        }
+        assert_eq!(buffer.len(), content.len() as u64);
+        assert_eq!(buffer.segment_count(), content.len() / 1000);
+        assert!(buffer.memory_size() < content.len() / 2);
+
+        let segments: Vec<Vec<u8>> =
+            buffer.iter().map(Result::unwrap).collect();
+        assert!(segments[..segments.len() - 1]
+            .iter()
+            .all(|segment| segment.len() == 1000));
+        assert_eq!(segments.concat(), content);
+        assert_eq!(buffer.to_vec().unwrap(), content);
+
+        let drained: Vec<Vec<u8>> =
+            buffer.drain().map(Result::unwrap).collect();
+        assert_eq!(drained.concat(), content);
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.iter().count(), 0);
+
+        // A large append spans several segments.
+        buffer.extend_from_slice(&content).unwrap();
+        assert_eq!(buffer.to_vec().unwrap(), content);
+    }
+}
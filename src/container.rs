@@ -0,0 +1,457 @@
+//! Store several named entries in a single file.
+//!
+//! Each entry is compressed on its own, into a separate zstd frame. An index
+//! at the end of the file lists the entries with their position, so a
+//! single entry can be extracted without reading the others.
+//!
+//! The index is stored in a skippable frame: the whole file is still a
+//! valid zstd stream, which decompresses to all the entries concatenated.
+//!
+//! ```
+//! use std::io::Cursor;
+//! use zstd::container::{ContainerReader, ContainerWriter};
+//!
+//! let mut writer = ContainerWriter::new(Vec::new(), 3);
+//! writer.add("hello.txt", b"Hello")?;
+//! writer.add("world.txt", b"world!")?;
+//! let file = writer.finish()?;
+//!
+//! let mut reader = ContainerReader::new(Cursor::new(file))?;
+//! assert_eq!(reader.read("world.txt")?, b"world!");
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! # Format
+//!
+//! All integers are little-endian. The entries come first, each as a
+//! single zstd frame. The index is a skippable frame (magic number
+//! `0x184D2A5D`), whose content is:
+//!
+//! * The number of entries, as a `u32`.
+//! * For each entry: the length of its name as a `u16`, the UTF-8 name,
+//!   then its offset in the file, its compressed size and its decompressed
+//!   size, as `u64`s.
+//! * The size of the whole index frame, as a `u32`.
+//! * The magic number `0x52544E43` (`b"CNTR"`).
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+
+use crate::stream::read::Decoder;
+use crate::stream::write::Encoder;
+
+const INDEX_MAGIC: u32 = 0x184D_2A5D;
+const FOOTER_MAGIC: u32 = 0x5254_4E43;
+const FOOTER_SIZE: u64 = 8;
+
+/// An entry of a container.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    name: String,
+    offset: u64,
+    compressed_size: u64,
+    size: u64,
+}
+
+impl Entry {
+    /// Returns the name of this entry.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the decompressed size of this entry.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Returns the compressed size of this entry.
+    pub fn compressed_size(&self) -> u64 {
+        self.compressed_size
+    }
+}
+
+/// Writes a container.
+///
+/// Entries are written as they are added. Call [`finish`] to write the
+/// index: without it, the container cannot be read.
+///
+/// If adding an entry fails after writing part of it, the container is
+/// left incomplete: any further call returns an error.
+///
+/// [`finish`]: #method.finish
+pub struct ContainerWriter<W: Write> {
+    writer: W,
+    level: i32,
+    offset: u64,
+    entries: Vec<Entry>,
+    // Set when an entry was only partially written.
+    failed: bool,
+}
+
+impl<W: Write> ContainerWriter<W> {
+    /// Creates a new container, written to `writer`.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`).
+    pub fn new(writer: W, level: i32) -> Self {
+        ContainerWriter {
+            writer,
+            level,
+            offset: 0,
+            entries: Vec::new(),
+            failed: false,
+        }
+    }
+
+    /// Compresses and adds an entry.
+    ///
+    /// Names must be unique, and at most 65535 bytes long.
+    pub fn add(&mut self, name: &str, data: &[u8]) -> io::Result<()> {
+        self.add_reader(name, data)
+    }
+
+    /// Compresses and adds an entry, read from `reader`.
+    pub fn add_reader<R: Read>(
+        &mut self,
+        name: &str,
+        mut reader: R,
+    ) -> io::Result<()> {
+        self.check_failed()?;
+        if name.len() > usize::from(u16::MAX) {
+            return Err(invalid_input("entry name too long"));
+        }
+        if self.entries.iter().any(|entry| entry.name == name) {
+            return Err(invalid_input("duplicate entry name"));
+        }
+
+        let counter = CountingWriter {
+            writer: &mut self.writer,
+            count: 0,
+        };
+        let mut encoder = Encoder::new(counter, self.level)?;
+        let result = io::copy(&mut reader, &mut encoder)
+            .and_then(|size| encoder.do_finish().map(|()| size));
+
+        // Keep the offset in sync with what actually reached the writer.
+        let compressed_size = encoder.get_ref().count;
+        self.offset += compressed_size;
+        let size = match result {
+            Ok(size) => size,
+            Err(e) => {
+                self.failed = compressed_size > 0;
+                return Err(e);
+            }
+        };
+
+        self.entries.push(Entry {
+            name: name.to_owned(),
+            offset: self.offset - compressed_size,
+            compressed_size,
+            size,
+        });
+        Ok(())
+    }
+
+    /// Returns the entries added so far.
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Writes the index, and returns the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.check_failed()?;
+        let mut index = Vec::new();
+        index.extend_from_slice(&INDEX_MAGIC.to_le_bytes());
+        // The frame size goes here.
+        index.extend_from_slice(&[0; 4]);
+        index.extend_from_slice(&count(self.entries.len())?.to_le_bytes());
+        for entry in &self.entries {
+            index.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+            index.extend_from_slice(entry.name.as_bytes());
+            index.extend_from_slice(&entry.offset.to_le_bytes());
+            index.extend_from_slice(&entry.compressed_size.to_le_bytes());
+            index.extend_from_slice(&entry.size.to_le_bytes());
+        }
+        let index_size = count(index.len() + FOOTER_SIZE as usize)?;
+        index.extend_from_slice(&index_size.to_le_bytes());
+        index.extend_from_slice(&FOOTER_MAGIC.to_le_bytes());
+        index[4..8].copy_from_slice(&(index_size - 8).to_le_bytes());
+
+        self.writer.write_all(&index)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn check_failed(&self) -> io::Result<()> {
+        if self.failed {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "a previous entry was only partially written",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Reads entries from a container.
+pub struct ContainerReader<R: Read + Seek> {
+    reader: R,
+    entries: Vec<Entry>,
+    by_name: HashMap<String, usize>,
+}
+
+impl<R: Read + Seek> ContainerReader<R> {
+    /// Opens a container, reading its index.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let end = reader.seek(SeekFrom::End(0))?;
+        if end < FOOTER_SIZE {
+            return Err(invalid_data());
+        }
+        reader.seek(SeekFrom::Start(end - FOOTER_SIZE))?;
+        let mut footer = [0u8; FOOTER_SIZE as usize];
+        reader.read_exact(&mut footer)?;
+        let index_size = u64::from(read_u32(&footer[..4]));
+        if read_u32(&footer[4..]) != FOOTER_MAGIC
+            || index_size < 12 + FOOTER_SIZE
+            || index_size > end
+        {
+            return Err(invalid_data());
+        }
+
+        let index_start = end - index_size;
+        reader.seek(SeekFrom::Start(index_start))?;
+        let mut index = vec![0u8; (index_size - FOOTER_SIZE) as usize];
+        reader.read_exact(&mut index)?;
+        if read_u32(&index[..4]) != INDEX_MAGIC
+            || u64::from(read_u32(&index[4..8])) != index_size - 8
+        {
+            return Err(invalid_data());
+        }
+
+        let entries = parse_index(&index[8..], index_start)?;
+        let mut by_name = HashMap::with_capacity(entries.len());
+        for (i, entry) in entries.iter().enumerate() {
+            by_name.insert(entry.name.clone(), i);
+        }
+        Ok(ContainerReader {
+            reader,
+            entries,
+            by_name,
+        })
+    }
+
+    /// Returns all the entries, in the order they were added.
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Returns the entry with the given name.
+    pub fn entry(&self, name: &str) -> Option<&Entry> {
+        self.by_name.get(name).map(|&i| &self.entries[i])
+    }
+
+    /// Returns a reader over the decompressed content of an entry.
+    ///
+    /// Fails with `NotFound` if there is no entry with this name.
+    pub fn open(
+        &mut self,
+        name: &str,
+    ) -> io::Result<Decoder<'static, BufReader<io::Take<&mut R>>>> {
+        let entry = self.entry(name).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "no such entry")
+        })?;
+        let (offset, compressed_size) = (entry.offset, entry.compressed_size);
+
+        self.reader.seek(SeekFrom::Start(offset))?;
+        let input = (&mut self.reader).take(compressed_size);
+        Ok(Decoder::new(input)?.single_frame())
+    }
+
+    /// Reads the decompressed content of an entry.
+    ///
+    /// Fails with `NotFound` if there is no entry with this name.
+    pub fn read(&mut self, name: &str) -> io::Result<Vec<u8>> {
+        let size = self.entry(name).map_or(0, |entry| entry.size);
+        let mut decoder = self.open(name)?;
+        // The index may be damaged: don't trust it for large allocations.
+        let capacity = usize::try_from(size).unwrap_or(0).min(1 << 20);
+        let mut output = Vec::with_capacity(capacity);
+        decoder.read_to_end(&mut output)?;
+        if output.len() as u64 != size {
+            return Err(invalid_data());
+        }
+        Ok(output)
+    }
+
+    /// Returns the inner reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+fn parse_index(mut index: &[u8], index_start: u64) -> io::Result<Vec<Entry>> {
+    let n_entries = read_u32(take(&mut index, 4)?);
+    let mut entries = Vec::new();
+    for _ in 0..n_entries {
+        let name_len = take(&mut index, 2)?;
+        let name_len = u16::from_le_bytes([name_len[0], name_len[1]]);
+        let name = take(&mut index, usize::from(name_len))?;
+        let name =
+            String::from_utf8(name.to_vec()).map_err(|_| invalid_data())?;
+        let entry = Entry {
+            name,
+            offset: read_u64(take(&mut index, 8)?),
+            compressed_size: read_u64(take(&mut index, 8)?),
+            size: read_u64(take(&mut index, 8)?),
+        };
+        match entry.offset.checked_add(entry.compressed_size) {
+            Some(end) if end <= index_start => entries.push(entry),
+            _ => return Err(invalid_data()),
+        }
+    }
+    if !index.is_empty() {
+        return Err(invalid_data());
+    }
+    Ok(entries)
+}
+
+// Splits the first `len` bytes from `data`.
+fn take<'a>(data: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if data.len() < len {
+        return Err(invalid_data());
+    }
+    let (head, tail) = data.split_at(len);
+    *data = tail;
+    Ok(head)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    let mut buffer = [0u8; 4];
+    buffer.copy_from_slice(bytes);
+    u32::from_le_bytes(buffer)
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut buffer = [0u8; 8];
+    buffer.copy_from_slice(bytes);
+    u64::from_le_bytes(buffer)
+}
+
+fn count(len: usize) -> io::Result<u32> {
+    u32::try_from(len).map_err(|_| invalid_input("index too large"))
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+fn invalid_data() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "invalid container index")
+}
+
+// Counts the bytes written to the container.
+struct CountingWriter<W> {
+    writer: W,
+    count: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.writer.write(buf)?;
+        self.count += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ContainerReader, ContainerWriter};
+    use std::io::{Cursor, ErrorKind, Read};
+
+    #[test]
+    fn test_container() {
+        let text = crate::test_util::text(100_000, 1);
+
+        let mut writer = ContainerWriter::new(Vec::new(), 3);
+        writer.add("text", &text).unwrap();
+        writer.add("empty", b"").unwrap();
+        writer.add_reader("reader", &b"from a reader"[..]).unwrap();
+        assert!(writer.add("text", b"again").is_err());
+        let file = writer.finish().unwrap();
+
+        let mut reader = ContainerReader::new(Cursor::new(&file)).unwrap();
+        let names: Vec<_> =
+            reader.entries().iter().map(|e| e.name()).collect();
+        assert_eq!(names, ["text", "empty", "reader"]);
+        assert_eq!(reader.entry("text").unwrap().size(), text.len() as u64);
+
+        // Entries can be read in any order.
+        assert_eq!(reader.read("reader").unwrap(), b"from a reader");
+        assert_eq!(reader.read("empty").unwrap(), b"");
+        let mut output = Vec::new();
+        reader
+            .open("text")
+            .unwrap()
+            .read_to_end(&mut output)
+            .unwrap();
+        assert_eq!(output, text);
+        let error = reader.read("missing").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::NotFound);
+
+        // The whole file is still a zstd stream.
+        let mut all = text.clone();
+        all.extend_from_slice(b"from a reader");
+        assert_eq!(crate::decode_all(&file[..]).unwrap(), all);
+    }
+
+    #[test]
+    fn test_failed_entry() {
+        struct Failing;
+        impl Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(ErrorKind::BrokenPipe.into())
+            }
+        }
+
+        // Nothing was written yet: the container is still usable.
+        let mut writer = ContainerWriter::new(Vec::new(), 3);
+        assert!(writer.add_reader("failing", Failing).is_err());
+        writer.add("ok", b"fine").unwrap();
+        let file = writer.finish().unwrap();
+        let mut reader = ContainerReader::new(Cursor::new(&file)).unwrap();
+        assert_eq!(reader.read("ok").unwrap(), b"fine");
+
+        // A partial entry breaks it.
+        let text = crate::test_util::text(1_000_000, 2);
+        let mut writer = ContainerWriter::new(Vec::new(), 1);
+        let input = (&text[..]).chain(Failing);
+        assert!(writer.add_reader("partial", input).is_err());
+        assert!(writer.add("ok", b"fine").is_err());
+        assert!(writer.finish().is_err());
+    }
+
+    #[test]
+    fn test_invalid() {
+        let mut writer = ContainerWriter::new(Vec::new(), 1);
+        writer.add("entry", b"content").unwrap();
+        let file = writer.finish().unwrap();
+
+        assert!(ContainerReader::new(Cursor::new(&file[..4])).is_err());
+        for i in 0..file.len() {
+            // Damaged files may be rejected, but never cause a panic.
+            let mut damaged = file.clone();
+            damaged[i] ^= 0x40;
+            if let Ok(mut reader) = ContainerReader::new(Cursor::new(damaged))
+            {
+                let _ = reader.read("entry");
+            }
+        }
+        let empty = ContainerWriter::new(Vec::new(), 1).finish().unwrap();
+        let reader = ContainerReader::new(Cursor::new(empty)).unwrap();
+        assert!(reader.entries().is_empty());
+    }
+}
@@ -25,6 +25,7 @@ pub use zstd_safe::{CDict, DDict};
 /// Prepared dictionary for compression
 pub struct EncoderDictionary<'a> {
     cdict: CDict<'a>,
+    size: usize,
 }
 
 impl EncoderDictionary<'static> {
@@ -34,6 +35,23 @@ impl EncoderDictionary<'static> {
     pub fn copy(dictionary: &[u8], level: i32) -> Self {
         Self {
             cdict: zstd_safe::create_cdict(dictionary, level),
+            size: dictionary.len(),
+        }
+    }
+
+    /// Creates a prepared dictionary for compression, tuned for many small
+    /// inputs.
+    ///
+    /// This will copy the dictionary internally.
+    ///
+    /// See [`EncoderDictionary::new_with_dedicated_search`].
+    ///
+    /// [`EncoderDictionary::new_with_dedicated_search`]: #method.new_with_dedicated_search
+    #[cfg(feature = "experimental")]
+    pub fn copy_with_dedicated_search(dictionary: &[u8], level: i32) -> Self {
+        Self {
+            cdict: CDict::create_with_dedicated_search(dictionary, level),
+            size: dictionary.len(),
         }
     }
 }
@@ -45,6 +63,26 @@ impl<'a> EncoderDictionary<'a> {
     pub fn new(dictionary: &'a [u8], level: i32) -> Self {
         Self {
             cdict: zstd_safe::create_cdict_by_reference(dictionary, level),
+            size: dictionary.len(),
+        }
+    }
+
+    /// Create prepared dictionary for compression, tuned for many small
+    /// inputs.
+    ///
+    /// This uses zstd's dedicated dictionary search, which spends more time
+    /// preparing the dictionary to make each compression faster, and often
+    /// smaller. It currently only has an effect on levels 5 to 12.
+    #[cfg(feature = "experimental")]
+    pub fn new_with_dedicated_search(
+        dictionary: &'a [u8],
+        level: i32,
+    ) -> Self {
+        Self {
+            cdict: CDict::create_by_reference_with_dedicated_search(
+                dictionary, level,
+            ),
+            size: dictionary.len(),
         }
     }
 
@@ -52,6 +90,12 @@ impl<'a> EncoderDictionary<'a> {
     pub fn as_cdict(&self) -> &CDict<'a> {
         &self.cdict
     }
+
+    // Size of the dictionary content, which zstd takes into account when
+    // choosing compression parameters.
+    pub(crate) fn size(&self) -> usize {
+        self.size
+    }
 }
 
 /// Prepared dictionary for decompression
@@ -84,6 +128,112 @@ impl<'a> DecoderDictionary<'a> {
     }
 }
 
+/// A validated zstd dictionary, as produced by training.
+///
+/// Unlike raw dictionary bytes, this is guaranteed to start with the zstd
+/// dictionary magic number and to have valid entropy tables, so mistakes
+/// (like loading the wrong file) are caught early instead of silently
+/// degrading compression or failing during decompression.
+///
+/// The content can be given to any constructor taking a dictionary, with
+/// [`as_bytes`], or prepared once with [`encoder_dictionary`] and
+/// [`decoder_dictionary`].
+///
+/// [`as_bytes`]: #method.as_bytes
+/// [`encoder_dictionary`]: #method.encoder_dictionary
+/// [`decoder_dictionary`]: #method.decoder_dictionary
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Dictionary {
+    content: Vec<u8>,
+    id: u32,
+}
+
+impl Dictionary {
+    /// Largest dictionary accepted, matching the `zstd` command-line tool.
+    pub const MAX_SIZE: usize = 32 << 20;
+
+    /// Validates the given dictionary content.
+    ///
+    /// Fails with `InvalidData` if it is not a zstd dictionary (for example
+    /// raw content), if it is corrupted, or if it is larger than
+    /// [`MAX_SIZE`](#associatedconstant.MAX_SIZE).
+    pub fn new(content: Vec<u8>) -> io::Result<Self> {
+        let invalid =
+            |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+        if content.len() > Self::MAX_SIZE {
+            return Err(invalid("dictionary is too large"));
+        }
+        if content.len() < 8
+            || content[..4] != zstd_safe::MAGIC_DICTIONARY.to_le_bytes()
+        {
+            return Err(invalid("not a zstd dictionary"));
+        }
+        let id = zstd_safe::get_dict_id_from_dict(&content);
+        if id == 0 {
+            return Err(invalid("dictionary has no ID"));
+        }
+
+        // Loading parses the entropy tables.
+        zstd_safe::create_dctx()
+            .load_dictionary(&content)
+            .map_err(|code| invalid(zstd_safe::get_error_name(code)))?;
+
+        Ok(Dictionary { content, id })
+    }
+
+    /// Reads and validates a dictionary file.
+    pub fn load<P: AsRef<path::Path>>(path: P) -> io::Result<Self> {
+        let file = fs::File::open(path)?;
+        // Don't read a huge file before rejecting it.
+        let mut content = Vec::new();
+        file.take(Self::MAX_SIZE as u64 + 1)
+            .read_to_end(&mut content)?;
+        Self::new(content)
+    }
+
+    /// Writes this dictionary to a file, replacing any previous content.
+    ///
+    /// The file can be loaded back with [`load`](#method.load), or used by
+    /// the `zstd` command-line tool.
+    pub fn save<P: AsRef<path::Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, &self.content)
+    }
+
+    /// Returns the ID of this dictionary.
+    ///
+    /// Frames compressed with it record this ID, unless disabled.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Returns the content of this dictionary.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.content
+    }
+
+    /// Returns the content of this dictionary.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.content
+    }
+
+    /// Prepares this dictionary for compression at the given level.
+    pub fn encoder_dictionary(&self, level: i32) -> EncoderDictionary<'_> {
+        EncoderDictionary::new(&self.content, level)
+    }
+
+    /// Prepares this dictionary for decompression.
+    pub fn decoder_dictionary(&self) -> DecoderDictionary<'_> {
+        DecoderDictionary::new(&self.content)
+    }
+}
+
+impl AsRef<[u8]> for Dictionary {
+    fn as_ref(&self) -> &[u8] {
+        &self.content
+    }
+}
+
 /// Train a dictionary from a big continuous chunk of data.
 ///
 /// This is the most efficient way to train a dictionary,
@@ -115,6 +265,105 @@ pub fn from_continuous(
     Ok(result)
 }
 
+/// Parameters picked by [`from_continuous_optimized`].
+///
+/// These can be used to train similar dictionaries later, for example with
+/// the `zstd` command-line tool
+/// (`--train-fastcover=k=...,d=...,f=...,accel=...`).
+///
+/// [`from_continuous_optimized`]: ./fn.from_continuous_optimized.html
+#[cfg(feature = "experimental")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TrainingParameters {
+    /// Segment size.
+    pub k: u32,
+    /// Dmer size.
+    pub d: u32,
+    /// Log of the size of the frequency array.
+    pub f: u32,
+    /// Acceleration level.
+    pub accel: u32,
+}
+
+/// Train a dictionary from a big continuous chunk of data, searching for the
+/// best training parameters.
+///
+/// This tries many combinations of the fastCover `k` and `d` parameters,
+/// and returns the best dictionary with the parameters used to build it.
+/// This is slower than [`from_continuous`], but usually gives better
+/// dictionaries.
+///
+/// [`from_continuous`]: ./fn.from_continuous.html
+#[cfg(feature = "experimental")]
+pub fn from_continuous_optimized(
+    sample_data: &[u8],
+    sample_sizes: &[usize],
+    max_size: usize,
+) -> io::Result<(Vec<u8>, TrainingParameters)> {
+    // Complain if the lengths don't add up to the entire data.
+    if sample_sizes.iter().sum::<usize>() != sample_data.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            "sample sizes don't add up".to_string(),
+        ));
+    }
+
+    // Zero values are searched for, or use zstd's defaults.
+    let mut parameters = zstd_safe::FastCoverParams {
+        k: 0,
+        d: 0,
+        f: 0,
+        steps: 0,
+        nbThreads: 0,
+        splitPoint: 0.0,
+        accel: 0,
+        shrinkDict: 0,
+        shrinkDictMaxRegression: 0,
+        zParams: zstd_safe::DictParams {
+            compressionLevel: 0,
+            notificationLevel: 0,
+            dictID: 0,
+        },
+    };
+
+    let mut result = vec![0u8; max_size];
+    let written = zstd_safe::optimize_train_from_buffer_fast_cover(
+        &mut result,
+        sample_data,
+        sample_sizes,
+        &mut parameters,
+    )
+    .map_err(map_error_code)?;
+    result.truncate(written);
+
+    let parameters = TrainingParameters {
+        k: parameters.k,
+        d: parameters.d,
+        f: parameters.f,
+        accel: parameters.accel,
+    };
+    Ok((result, parameters))
+}
+
+/// Train a dictionary from multiple samples, searching for the best training
+/// parameters.
+///
+/// See [`from_continuous_optimized`] and [`from_samples`].
+///
+/// [`from_continuous_optimized`]: ./fn.from_continuous_optimized.html
+/// [`from_samples`]: ./fn.from_samples.html
+#[cfg(feature = "experimental")]
+pub fn from_samples_optimized<S: AsRef<[u8]>>(
+    samples: &[S],
+    max_size: usize,
+) -> io::Result<(Vec<u8>, TrainingParameters)> {
+    let data: Vec<_> =
+        samples.iter().flat_map(|s| s.as_ref()).cloned().collect();
+    let sizes: Vec<_> = samples.iter().map(|s| s.as_ref().len()).collect();
+
+    from_continuous_optimized(&data, &sizes, max_size)
+}
+
 /// Train a dictionary from multiple samples.
 ///
 /// The samples will internaly be copied to a single continuous buffer,
@@ -154,6 +403,16 @@ where
     from_continuous(&buffer, &sizes, max_size)
 }
 
+fn _assert_traits() {
+    fn _assert_send_sync<T: Send + Sync>(_: T) {}
+
+    _assert_send_sync(EncoderDictionary::copy(&[], 1));
+    _assert_send_sync(DecoderDictionary::copy(&[]));
+    _assert_send_sync(EncoderDictionary::new(&[], 1));
+    _assert_send_sync(DecoderDictionary::new(&[]));
+    _assert_send_sync(Dictionary::new(Vec::new()));
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs;
@@ -205,4 +464,171 @@ mod tests {
             assert_eq!(&content, &result);
         }
     }
+
+    #[test]
+    fn test_save_load() {
+        use super::Dictionary;
+
+        let samples: Vec<Vec<u8>> = (0..1000u32)
+            .map(|i| format!("{{\"id\": {}, \"name\": \"user{}\"}}", i, i))
+            .map(String::into_bytes)
+            .collect();
+        let dict = super::from_samples(&samples, 1000).unwrap();
+        let dict = Dictionary::new(dict).unwrap();
+        assert_ne!(dict.id(), 0);
+
+        let path = std::env::temp_dir()
+            .join(format!("zstd-dict-{}", std::process::id()));
+        dict.save(&path).unwrap();
+        let loaded = Dictionary::load(&path);
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded, dict);
+
+        let compressed =
+            crate::block::Compressor::with_dict(loaded.as_bytes().to_vec())
+                .compress(&samples[0], 3)
+                .unwrap();
+        assert_eq!(zstd_safe::get_dict_id_from_frame(&compressed), dict.id());
+        let decoder_dict = dict.decoder_dictionary();
+        let mut decoder = crate::stream::Decoder::with_prepared_dictionary(
+            &compressed[..],
+            &decoder_dict,
+        )
+        .unwrap();
+        let mut result = Vec::new();
+        decoder.read_to_end(&mut result).unwrap();
+        assert_eq!(result, samples[0]);
+
+        // Raw content and corrupted dictionaries are rejected.
+        assert!(Dictionary::new(b"raw content".to_vec()).is_err());
+        let mut corrupted = loaded.into_bytes();
+        for byte in &mut corrupted[8..40] {
+            *byte = 0xff;
+        }
+        let error = Dictionary::new(corrupted).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    #[cfg(feature = "experimental")]
+    fn test_optimized_training() {
+        let samples: Vec<Vec<u8>> = (0..1000u32)
+            .map(|i| format!("{{\"id\": {}, \"name\": \"user{}\"}}", i, i))
+            .map(String::into_bytes)
+            .collect();
+
+        let (dict, parameters) =
+            super::from_samples_optimized(&samples, 1000).unwrap();
+        assert!(!dict.is_empty() && dict.len() <= 1000);
+        // The searched parameters are reported.
+        assert_ne!(parameters.k, 0);
+        assert_ne!(parameters.d, 0);
+
+        let compressed = crate::block::Compressor::with_dict(dict.clone())
+            .compress(&samples[0], 3)
+            .unwrap();
+        let plain = crate::block::compress(&samples[0], 3).unwrap();
+        assert!(compressed.len() < plain.len());
+        let decompressed = crate::block::Decompressor::with_dict(dict)
+            .decompress(&compressed, samples[0].len())
+            .unwrap();
+        assert_eq!(decompressed, samples[0]);
+    }
+
+    #[test]
+    #[cfg(feature = "experimental")]
+    fn test_dedicated_search() {
+        use super::{DecoderDictionary, EncoderDictionary};
+
+        let samples: Vec<Vec<u8>> = (0..1000u32)
+            .map(|i| format!("{{\"id\": {}, \"name\": \"user{}\"}}", i, i))
+            .map(String::into_bytes)
+            .collect();
+        let sizes: Vec<usize> = samples.iter().map(Vec::len).collect();
+        let dict =
+            super::from_continuous(&samples.concat(), &sizes, 1000).unwrap();
+
+        let encoder_dict =
+            EncoderDictionary::new_with_dedicated_search(&dict, 6);
+        let decoder_dict = DecoderDictionary::new(&dict);
+
+        for sample in &samples[..10] {
+            let mut buffer = Vec::new();
+            let mut encoder =
+                crate::stream::Encoder::with_prepared_dictionary(
+                    &mut buffer,
+                    &encoder_dict,
+                )
+                .unwrap();
+            io::copy(&mut &sample[..], &mut encoder).unwrap();
+            encoder.finish().unwrap();
+
+            let mut result = Vec::new();
+            crate::stream::Decoder::with_prepared_dictionary(
+                &buffer[..],
+                &decoder_dict,
+            )
+            .unwrap()
+            .read_to_end(&mut result)
+            .unwrap();
+            assert_eq!(sample, &result);
+        }
+    }
+
+    #[test]
+    fn test_shared_between_threads() {
+        use std::io::Write;
+        use std::sync::Arc;
+        use std::thread;
+
+        let dict_data = b"shared dictionary content ".repeat(20);
+        let encoder_dict =
+            Arc::new(super::EncoderDictionary::copy(&dict_data, 3));
+        let decoder_dict =
+            Arc::new(super::DecoderDictionary::copy(&dict_data));
+
+        let threads: Vec<_> = (0..4)
+            .map(|i| {
+                let encoder_dict = Arc::clone(&encoder_dict);
+                let decoder_dict = Arc::clone(&decoder_dict);
+                thread::spawn(move || {
+                    let message = format!("shared dictionary content {}", i);
+                    let mut encoder =
+                        crate::stream::Encoder::with_prepared_dictionary(
+                            Vec::new(),
+                            &encoder_dict,
+                        )
+                        .unwrap();
+                    encoder.write_all(message.as_bytes()).unwrap();
+                    let compressed = encoder.finish().unwrap();
+
+                    let mut decoder =
+                        crate::stream::Decoder::with_prepared_dictionary(
+                            &compressed[..],
+                            &decoder_dict,
+                        )
+                        .unwrap();
+                    let mut result = String::new();
+                    decoder.read_to_string(&mut result).unwrap();
+                    assert_eq!(result, message);
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        // Contexts can be handed to another thread as well.
+        let mut compressor = crate::block::Compressor::new();
+        let compressed =
+            thread::spawn(move || compressor.compress(b"moved", 1))
+                .join()
+                .unwrap()
+                .unwrap();
+        assert_eq!(
+            crate::block::decompress(&compressed, 5).unwrap(),
+            b"moved"
+        );
+    }
 }
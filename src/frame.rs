@@ -0,0 +1,739 @@
+//! Inspect compressed frames without decompressing them, or create frames
+//! without compressing.
+
+use crate::map_error_code;
+
+use std::fmt;
+use std::io::{self, BufRead, Read};
+use std::str::FromStr;
+
+/// Returns the checksum stored at the end of the first frame in `data`.
+///
+/// `data` must start with a complete frame. Returns `None` if the frame was
+/// compressed without a checksum, or is a skippable frame.
+///
+/// zstd stores the lowest 32 bits of the XXH64 hash (with a seed of `0`)
+/// of the decompressed content: two frames with the same checksum most
+/// likely have the same content, even if compressed differently.
+pub fn checksum(data: &[u8]) -> io::Result<Option<u32>> {
+    let size =
+        zstd_safe::find_frame_compressed_size(data).map_err(map_error_code)?;
+    let frame = &data[..size];
+
+    if !has_checksum(frame) {
+        return Ok(None);
+    }
+    let mut checksum = [0u8; 4];
+    checksum.copy_from_slice(&frame[size - 4..]);
+    Ok(Some(u32::from_le_bytes(checksum)))
+}
+
+/// Returns the checksum of each frame in `data`, in order.
+///
+/// Skippable frames are ignored. See [`checksum`] for details.
+///
+/// [`checksum`]: fn.checksum.html
+pub fn checksums(mut data: &[u8]) -> io::Result<Vec<Option<u32>>> {
+    let mut checksums = Vec::new();
+    while !data.is_empty() {
+        let size = zstd_safe::find_frame_compressed_size(data)
+            .map_err(map_error_code)?;
+        if !is_skippable(data) {
+            checksums.push(checksum(data)?);
+        }
+        data = &data[size..];
+    }
+    Ok(checksums)
+}
+
+/// Describes a frame of a compressed stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameInfo {
+    /// Position of the frame in the compressed stream.
+    pub offset: u64,
+
+    /// Size of the entire frame.
+    pub compressed_size: u64,
+
+    /// Decompressed size declared in the frame header, if any.
+    pub content_size: Option<u64>,
+
+    /// Checksum stored at the end of the frame, if any.
+    ///
+    /// For skippable frames, this is the checksum of the payload, computed
+    /// like zstd does, so changes to the payload are detected too.
+    pub checksum: Option<u32>,
+
+    /// `true` for a skippable frame.
+    pub skippable: bool,
+}
+
+/// Lists the frames of a compressed stream, to audit it later.
+///
+/// A manifest records the layout of a stream (offsets and sizes of its
+/// frames) and the checksums stored in it. Kept next to archived data, it
+/// lets [`verify`] check that the data was not truncated, reordered or
+/// damaged since, down to the decompressed content.
+///
+/// The manifest is only as good as the stream it was created from: enable
+/// checksums when compressing, or only the structure can be checked.
+///
+/// Manifests can be saved as text, with one line per frame, using the
+/// `Display` and `FromStr` implementations.
+///
+/// Streams too large to be loaded in memory can be read with
+/// [`from_reader`] and [`verify_reader`] instead, which only hold one frame
+/// at a time. Legacy frames are not supported there.
+///
+/// ```
+/// use zstd::frame::Manifest;
+///
+/// let mut encoder = zstd::stream::write::Encoder::new(Vec::new(), 3)?;
+/// encoder.include_checksum(true)?;
+/// std::io::Write::write_all(&mut encoder, b"archived data")?;
+/// let data = encoder.finish()?;
+///
+/// let saved = Manifest::new(&data)?.to_string();
+///
+/// // Later on...
+/// let manifest: Manifest = saved.parse()?;
+/// manifest.verify(&data)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// [`verify`]: #method.verify
+/// [`from_reader`]: #method.from_reader
+/// [`verify_reader`]: #method.verify_reader
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    frames: Vec<FrameInfo>,
+}
+
+impl Manifest {
+    /// Creates the manifest of the given compressed stream.
+    ///
+    /// This only reads the frame headers and checksums: nothing is
+    /// decompressed, so this doesn't detect damaged content.
+    pub fn new(mut data: &[u8]) -> io::Result<Self> {
+        let mut frames = Vec::new();
+        let mut offset = 0;
+        while !data.is_empty() {
+            let frame = frame_info(data, offset)?;
+            data = &data[frame.compressed_size as usize..];
+            offset += frame.compressed_size;
+            frames.push(frame);
+        }
+        Ok(Manifest { frames })
+    }
+
+    /// Creates the manifest of the compressed stream read from `reader`.
+    ///
+    /// This is the same as `new`, without loading the whole stream in
+    /// memory.
+    pub fn from_reader<R: Read>(reader: R) -> io::Result<Self> {
+        let mut reader = io::BufReader::new(reader);
+        let mut frames = Vec::new();
+        let mut offset = 0;
+        let mut data = Vec::new();
+        while read_frame(&mut reader, &mut data)? {
+            let frame = frame_info(&data, offset)?;
+            offset += frame.compressed_size;
+            frames.push(frame);
+        }
+        Ok(Manifest { frames })
+    }
+
+    /// Returns all the frames, in order.
+    pub fn frames(&self) -> &[FrameInfo] {
+        &self.frames
+    }
+
+    /// Checks `data` against this manifest.
+    ///
+    /// The frames must be the same as when the manifest was created, with
+    /// the same stored checksums. Every frame is then decompressed, which
+    /// checks its content against its checksum and content size.
+    ///
+    /// Fails with `InvalidData` at the first difference.
+    pub fn verify(&self, data: &[u8]) -> io::Result<()> {
+        let actual = Manifest::new(data)?;
+        if actual.frames.len() != self.frames.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "expected {} frames, found {}",
+                    self.frames.len(),
+                    actual.frames.len()
+                ),
+            ));
+        }
+        for (i, frame) in actual.frames.iter().enumerate() {
+            let start = frame.offset as usize;
+            let end = start + frame.compressed_size as usize;
+            self.verify_frame(i, frame, &data[start..end])?;
+        }
+        Ok(())
+    }
+
+    /// Checks the compressed stream read from `reader` against this
+    /// manifest.
+    ///
+    /// This is the same as `verify`, without loading the whole stream in
+    /// memory. Frames are checked as they are read, so a stream with
+    /// missing frames is only detected at the end.
+    pub fn verify_reader<R: Read>(&self, reader: R) -> io::Result<()> {
+        let mut reader = io::BufReader::new(reader);
+        let mut count = 0;
+        let mut offset = 0;
+        let mut data = Vec::new();
+        while read_frame(&mut reader, &mut data)? {
+            let frame = frame_info(&data, offset)?;
+            if count == self.frames.len() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "expected {} frames, found more",
+                        self.frames.len()
+                    ),
+                ));
+            }
+            self.verify_frame(count, &frame, &data)?;
+            offset += frame.compressed_size;
+            count += 1;
+        }
+        if count != self.frames.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "expected {} frames, found {}",
+                    self.frames.len(),
+                    count
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    // Checks the `index`-th frame, described by `frame`, with its `data`.
+    fn verify_frame(
+        &self,
+        index: usize,
+        frame: &FrameInfo,
+        data: &[u8],
+    ) -> io::Result<()> {
+        if self.frames[index] != *frame {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("frame {} does not match the manifest", index),
+            ));
+        }
+        if !frame.skippable {
+            crate::stream::verify(data)?;
+        }
+        Ok(())
+    }
+}
+
+// Describes the first frame of `data`, found at `offset` in the stream.
+fn frame_info(data: &[u8], offset: u64) -> io::Result<FrameInfo> {
+    let size =
+        zstd_safe::find_frame_compressed_size(data).map_err(map_error_code)?;
+    let frame = &data[..size];
+    let skippable = is_skippable(frame);
+    let content_size = match zstd_safe::get_frame_content_size(frame) {
+        _ if skippable => None,
+        zstd_safe::CONTENTSIZE_UNKNOWN | zstd_safe::CONTENTSIZE_ERROR => None,
+        size => Some(size),
+    };
+    let checksum = if skippable {
+        Some(zstd_safe::xxh64(&frame[8..], 0) as u32)
+    } else {
+        checksum(frame)?
+    };
+    Ok(FrameInfo {
+        offset,
+        compressed_size: size as u64,
+        content_size,
+        checksum,
+        skippable,
+    })
+}
+
+impl fmt::Display for Manifest {
+    /// Writes one line per frame, with its kind (`frame` or `skippable`),
+    /// offset, compressed size, content size and checksum in hexadecimal.
+    /// Missing values are written as `-`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for frame in &self.frames {
+            let kind = if frame.skippable {
+                "skippable"
+            } else {
+                "frame"
+            };
+            write!(f, "{} {} {} ", kind, frame.offset, frame.compressed_size)?;
+            match frame.content_size {
+                Some(size) => write!(f, "{} ", size)?,
+                None => f.write_str("- ")?,
+            }
+            match frame.checksum {
+                Some(checksum) => writeln!(f, "{:08x}", checksum)?,
+                None => writeln!(f, "-")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Manifest {
+    type Err = io::Error;
+
+    /// Parses a manifest written by the `Display` implementation.
+    fn from_str(s: &str) -> io::Result<Self> {
+        let invalid = |line: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid manifest line: {:?}", line),
+            )
+        };
+
+        let mut frames = Vec::new();
+        for line in s.lines().filter(|line| !line.trim().is_empty()) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (kind, offset, compressed_size, content_size, checksum) =
+                match fields[..] {
+                    [a, b, c, d, e] => (a, b, c, d, e),
+                    _ => return Err(invalid(line)),
+                };
+            let skippable = match kind {
+                "frame" => false,
+                "skippable" => true,
+                _ => return Err(invalid(line)),
+            };
+            let optional = |field: &str, radix| match field {
+                "-" => Ok(None),
+                _ => u64::from_str_radix(field, radix)
+                    .map(Some)
+                    .map_err(|_| invalid(line)),
+            };
+            let checksum = match optional(checksum, 16)? {
+                Some(checksum) if checksum > u64::from(u32::MAX) => {
+                    return Err(invalid(line))
+                }
+                checksum => checksum.map(|checksum| checksum as u32),
+            };
+            frames.push(FrameInfo {
+                offset: offset.parse().map_err(|_| invalid(line))?,
+                compressed_size: compressed_size
+                    .parse()
+                    .map_err(|_| invalid(line))?,
+                content_size: optional(content_size, 10)?,
+                checksum,
+                skippable,
+            });
+        }
+        Ok(Manifest { frames })
+    }
+}
+
+/// Wraps `data` in a zstd frame, without compressing it.
+///
+/// The data is stored in raw blocks, in a standard frame which any zstd
+/// decoder reads, at almost no cost. The frame declares its content size,
+/// and includes a checksum if `include_checksum` is `true`.
+///
+/// This is useful for data which doesn't compress, like already compressed
+/// payloads.
+pub fn store(data: &[u8], include_checksum: bool) -> Vec<u8> {
+    let mut frame = vec![0u8; stored_size(data.len())];
+    let len = store_to_buffer(data, include_checksum, &mut frame).unwrap();
+    frame.truncate(len);
+    frame
+}
+
+/// Writes a skippable frame containing `data`.
+///
+/// Decoders ignore skippable frames, so they can carry application data in
+/// a compressed stream. `magic_variant` (from `0` to `15`) is added to the
+/// skippable magic number, which helps telling different uses apart.
+pub fn write_skippable_frame<W: io::Write>(
+    mut writer: W,
+    magic_variant: u8,
+    data: &[u8],
+) -> io::Result<()> {
+    if magic_variant > 0xF {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "invalid magic variant",
+        ));
+    }
+    if data.len() > u32::MAX as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "skippable frame too large",
+        ));
+    }
+    let magic = zstd_safe::MAGIC_SKIPPABLE_START | u32::from(magic_variant);
+    writer.write_all(&magic.to_le_bytes())?;
+    writer.write_all(&(data.len() as u32).to_le_bytes())?;
+    writer.write_all(data)
+}
+
+/// Returns the content of each skippable frame in `data`, in order.
+///
+/// Each frame comes with its magic variant (see [`write_skippable_frame`]).
+/// Other frames are skipped without being decompressed.
+///
+/// [`write_skippable_frame`]: fn.write_skippable_frame.html
+pub fn skippable_frames(mut data: &[u8]) -> io::Result<Vec<(u8, &[u8])>> {
+    let mut frames = Vec::new();
+    while !data.is_empty() {
+        let size = zstd_safe::find_frame_compressed_size(data)
+            .map_err(map_error_code)?;
+        if is_skippable(data) {
+            frames.push((data[0] & 0xF, &data[8..size]));
+        }
+        data = &data[size..];
+    }
+    Ok(frames)
+}
+
+// Upper bound of the size of a frame made by `store`.
+fn stored_size(len: usize) -> usize {
+    let blocks = usize::max(1, len.div_ceil(BLOCK_SIZE));
+    // Magic number, header descriptor, window descriptor, content size,
+    // block headers and checksum.
+    len + 4 + 1 + 1 + 8 + blocks * 3 + 4
+}
+
+const BLOCK_SIZE: usize = zstd_safe::BLOCKSIZE_MAX as usize;
+
+// Writes the frame made by `store` to `destination`, if it fits.
+pub(crate) fn store_to_buffer(
+    data: &[u8],
+    include_checksum: bool,
+    destination: &mut [u8],
+) -> Option<usize> {
+    let mut frame = Vec::with_capacity(18);
+    frame.extend_from_slice(&zstd_safe::MAGICNUMBER.to_le_bytes());
+
+    let content_size = data.len() as u64;
+    let checksum_flag = if include_checksum { 0x04 } else { 0 };
+    if data.len() <= BLOCK_SIZE {
+        // Single segment: the window is the content itself.
+        let (flag, size) = match content_size {
+            0..=255 => (0, 1),
+            256..=65791 => (1, 2),
+            _ => (2, 4),
+        };
+        let field = if flag == 1 {
+            content_size - 256
+        } else {
+            content_size
+        };
+        frame.push(flag << 6 | 0x20 | checksum_flag);
+        frame.extend_from_slice(&field.to_le_bytes()[..size]);
+    } else {
+        // A window of one block is enough for raw blocks.
+        let (flag, size) = if content_size <= u64::from(u32::MAX) {
+            (2, 4)
+        } else {
+            (3, 8)
+        };
+        frame.push(flag << 6 | checksum_flag);
+        frame.push(((BLOCK_SIZE.trailing_zeros() - 10) << 3) as u8);
+        frame.extend_from_slice(&content_size.to_le_bytes()[..size]);
+    }
+
+    let blocks = usize::max(1, data.len().div_ceil(BLOCK_SIZE));
+    let checksum_len = if include_checksum { 4 } else { 0 };
+    let len = frame.len() + data.len() + blocks * 3 + checksum_len;
+    if destination.len() < len {
+        return None;
+    }
+
+    let (header, mut rest) = destination.split_at_mut(frame.len());
+    header.copy_from_slice(&frame);
+    let mut chunks = data.chunks(BLOCK_SIZE).peekable();
+    if data.is_empty() {
+        // A single empty last block.
+        rest[..3].copy_from_slice(&[1, 0, 0]);
+        rest = &mut rest[3..];
+    }
+    while let Some(chunk) = chunks.next() {
+        // Raw block: last block flag, block type 0, and size.
+        let last = chunks.peek().is_none() as u32;
+        let block_header = (chunk.len() as u32) << 3 | last;
+        rest[..3].copy_from_slice(&block_header.to_le_bytes()[..3]);
+        rest[3..3 + chunk.len()].copy_from_slice(chunk);
+        rest = &mut rest[3 + chunk.len()..];
+    }
+    if include_checksum {
+        let checksum = zstd_safe::xxh64(data, 0) as u32;
+        rest[..4].copy_from_slice(&checksum.to_le_bytes());
+    }
+    Some(len)
+}
+
+// `frame` is a complete frame, as validated by `find_frame_compressed_size`.
+fn has_checksum(frame: &[u8]) -> bool {
+    // Legacy frames use a different header.
+    frame.len() >= 5
+        && frame[..4] == zstd_safe::MAGICNUMBER.to_le_bytes()
+        // Content_Checksum_flag, in the frame header descriptor.
+        && frame[4] & 0x04 != 0
+}
+
+// Reads the next frame of `reader` into `frame`, replacing its content.
+//
+// Returns `false` at the end of `reader`. Only the block headers are read,
+// to find the end of the frame: nothing is decompressed.
+fn read_frame<R: BufRead>(
+    reader: &mut R,
+    frame: &mut Vec<u8>,
+) -> io::Result<bool> {
+    frame.clear();
+    let header = match read_frame_header(reader)? {
+        Some(header) => header,
+        None => return Ok(false),
+    };
+    frame.extend_from_slice(&header);
+
+    if is_skippable(&header) {
+        let size =
+            u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        read_frame_bytes(reader, frame, size.into())?;
+    } else if header.len() > 4 {
+        loop {
+            let start = frame.len();
+            read_frame_bytes(reader, frame, 3)?;
+            let block = u32::from_le_bytes([
+                frame[start],
+                frame[start + 1],
+                frame[start + 2],
+                0,
+            ]);
+            // Block type: RLE blocks only store one byte.
+            let size = match block >> 1 & 0x3 {
+                1 => 1,
+                3 => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "reserved block type",
+                    ))
+                }
+                _ => block >> 3,
+            };
+            read_frame_bytes(reader, frame, size.into())?;
+            // Last block flag.
+            if block & 0x1 != 0 {
+                break;
+            }
+        }
+        if has_checksum(frame) {
+            read_frame_bytes(reader, frame, 4)?;
+        }
+    } else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "unsupported frame format",
+        ));
+    }
+    Ok(true)
+}
+
+// Appends exactly `len` bytes of the current frame from `reader` to `frame`.
+fn read_frame_bytes<R: Read>(
+    reader: &mut R,
+    frame: &mut Vec<u8>,
+    len: u64,
+) -> io::Result<()> {
+    let read = Read::take(&mut *reader, len).read_to_end(frame)?;
+    if read as u64 != len {
+        return Err(incomplete_frame());
+    }
+    Ok(())
+}
+
+// Reads the header of the next frame, or returns `None` at the end of
+// `source`.
+//
+// For skippable frames, this is the magic number and the content size. For
+// legacy frames, this is only the magic number.
+pub(crate) fn read_frame_header<R: BufRead>(
+    source: &mut R,
+) -> io::Result<Option<Vec<u8>>> {
+    if source.fill_buf()?.is_empty() {
+        return Ok(None);
+    }
+
+    let mut header = vec![0; 4];
+    read_header_bytes(source, &mut header)?;
+    let mut filled = 4;
+    if is_skippable(&header) {
+        header.resize(8, 0);
+    } else if header[..] == zstd_safe::MAGICNUMBER.to_le_bytes() {
+        header.resize(5, 0);
+        read_header_bytes(source, &mut header[4..])?;
+        filled = 5;
+        // Frame header descriptor: content size, single segment and
+        // dictionary ID fields.
+        let descriptor = header[4];
+        let single_segment = descriptor & 0x20 != 0;
+        let content_size_len = match descriptor >> 6 {
+            0 => single_segment as usize,
+            flag => 1 << flag,
+        };
+        let dict_id_len = [0, 1, 2, 4][usize::from(descriptor & 0x3)];
+        let window_len = !single_segment as usize;
+        header.resize(5 + window_len + dict_id_len + content_size_len, 0);
+    }
+    read_header_bytes(source, &mut header[filled..])?;
+    Ok(Some(header))
+}
+
+fn read_header_bytes<R: Read>(
+    source: &mut R,
+    buf: &mut [u8],
+) -> io::Result<()> {
+    source.read_exact(buf).map_err(|e| {
+        if e.kind() == io::ErrorKind::UnexpectedEof {
+            incomplete_frame()
+        } else {
+            e
+        }
+    })
+}
+
+pub(crate) fn incomplete_frame() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "incomplete frame")
+}
+
+pub(crate) fn is_skippable(frame: &[u8]) -> bool {
+    frame.len() >= 4
+        && u32::from_le_bytes([frame[0], frame[1], frame[2], frame[3]]) & !0xF
+            == zstd_safe::MAGIC_SKIPPABLE_START
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{checksum, checksums, Manifest};
+    use std::io::Write;
+
+    fn compress(data: &[u8], include_checksum: bool) -> Vec<u8> {
+        let mut encoder =
+            crate::stream::write::Encoder::new(Vec::new(), 1).unwrap();
+        encoder.include_checksum(include_checksum).unwrap();
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_checksum() {
+        let with = compress(b"some content", true);
+        let without = compress(b"some content", false);
+
+        let stored = checksum(&with).unwrap().unwrap();
+        assert_eq!(stored.to_le_bytes(), with[with.len() - 4..]);
+        assert_eq!(checksum(&without).unwrap(), None);
+
+        // The same content gives the same checksum, whatever the level.
+        let mut encoder =
+            crate::stream::write::Encoder::new(Vec::new(), 19).unwrap();
+        encoder.include_checksum(true).unwrap();
+        encoder.write_all(b"some content").unwrap();
+        let other = encoder.finish().unwrap();
+        assert_eq!(checksum(&other).unwrap(), Some(stored));
+
+        // Incomplete frames are rejected.
+        assert!(checksum(&with[..with.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_checksums() {
+        let mut data = compress(b"first", true);
+        data.extend(compress(b"second", false));
+        // A skippable frame with a 3-bytes payload.
+        data.extend_from_slice(&[0x50, 0x2A, 0x4D, 0x18, 3, 0, 0, 0, 1, 2, 3]);
+        data.extend(compress(b"first", true));
+
+        let checksums = checksums(&data).unwrap();
+        assert_eq!(checksums.len(), 3);
+        assert!(checksums[0].is_some());
+        assert_eq!(checksums[1], None);
+        assert_eq!(checksums[0], checksums[2]);
+    }
+
+    #[test]
+    fn test_manifest() {
+        let mut data = compress(b"first", true);
+        data.extend(compress(b"second", false));
+        data.extend_from_slice(&[0x50, 0x2A, 0x4D, 0x18, 3, 0, 0, 0, 1, 2, 3]);
+        let third = b"third".repeat(1000);
+        let mut encoder =
+            crate::stream::write::Encoder::new(Vec::new(), 1).unwrap();
+        encoder.include_checksum(true).unwrap();
+        encoder.set_pledged_src_size(third.len() as u64).unwrap();
+        encoder.write_all(&third).unwrap();
+        data.extend(encoder.finish().unwrap());
+
+        let manifest = Manifest::new(&data).unwrap();
+        let frames = manifest.frames();
+        assert_eq!(frames.len(), 4);
+        assert_eq!(frames[1].offset, frames[0].compressed_size);
+        assert_eq!(frames[1].checksum, None);
+        assert!(frames[2].skippable);
+        assert_eq!(frames[3].content_size, Some(5000));
+        manifest.verify(&data).unwrap();
+
+        let text = manifest.to_string();
+        assert_eq!(text.lines().count(), 4);
+        assert_eq!(text.parse::<Manifest>().unwrap(), manifest);
+        assert!("frame 0 1".parse::<Manifest>().is_err());
+        assert!("frame 0 1 - 1ffffffff".parse::<Manifest>().is_err());
+
+        // Truncated, reordered and damaged streams are all detected.
+        assert!(manifest.verify(&data[..data.len() - 1]).is_err());
+        let mut reordered =
+            data[frames[0].compressed_size as usize..].to_vec();
+        reordered
+            .extend_from_slice(&data[..frames[0].compressed_size as usize]);
+        assert!(manifest.verify(&reordered).is_err());
+
+        // Flip a bit in the middle of the last frame's content.
+        let mut damaged = data.clone();
+        let last = frames[3];
+        damaged[(last.offset + last.compressed_size / 2) as usize] ^= 1;
+        assert!(manifest.verify(&damaged).is_err());
+        assert!(manifest.verify_reader(&damaged[..]).is_err());
+
+        // Skippable frames are checked too.
+        let mut damaged = data.clone();
+        damaged[(frames[2].offset + 9) as usize] ^= 1;
+        assert!(manifest.verify(&damaged).is_err());
+        assert!(manifest.verify_reader(&damaged[..]).is_err());
+    }
+
+    #[test]
+    fn test_manifest_reader() {
+        let mut data = compress(b"first", true);
+        crate::frame::write_skippable_frame(&mut data, 1, b"skipped").unwrap();
+        // Raw, RLE and compressed blocks.
+        data.extend(crate::frame::store(&b"stored".repeat(30_000), true));
+        data.extend(compress(&[7; 300_000], true));
+        data.extend(compress(&crate::test_util::text(300_000, 1), false));
+
+        let manifest = Manifest::from_reader(&data[..]).unwrap();
+        assert_eq!(manifest, Manifest::new(&data).unwrap());
+        assert_eq!(manifest.frames().len(), 5);
+        manifest.verify_reader(&data[..]).unwrap();
+
+        // Truncated streams, and missing or extra frames are detected.
+        let first = manifest.frames()[0].compressed_size as usize;
+        assert!(Manifest::from_reader(&data[..data.len() - 1]).is_err());
+        assert!(manifest.verify_reader(&data[..data.len() - 1]).is_err());
+        assert!(manifest.verify_reader(&data[first..]).is_err());
+        let mut extra = data.clone();
+        extra.extend_from_slice(&data[..first]);
+        assert!(manifest.verify_reader(&extra[..]).is_err());
+    }
+}
@@ -0,0 +1,588 @@
+//! Compress and decompress files.
+//!
+//! These functions take care of opening the files, streaming the data
+//! through an encoder or decoder, and optionally copying the permissions and
+//! modification time of the source to the destination.
+//!
+//! With the `mmap` feature, the source file is memory-mapped when possible,
+//! instead of being read into an intermediate buffer. Preserving the
+//! modification time requires the `filetime` feature.
+//!
+//! To compress many files at once, see [`Batch`].
+//!
+//! [`Batch`]: struct.Batch.html
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex, PoisonError};
+use std::thread;
+
+use crate::dict::EncoderDictionary;
+use crate::stream::{read, write};
+
+/// Options for [`compress_file`] and [`decompress_file`].
+///
+/// [`compress_file`]: fn.compress_file.html
+/// [`decompress_file`]: fn.decompress_file.html
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    level: i32,
+    checksum: bool,
+    preserve_permissions: bool,
+    #[cfg(feature = "filetime")]
+    preserve_mtime: bool,
+    dictionary: Option<Arc<[u8]>>,
+}
+
+impl Options {
+    /// Creates the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the compression level.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`).
+    pub fn level(mut self, level: i32) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Controls whether a content checksum is included when compressing.
+    pub fn checksum(mut self, checksum: bool) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Controls whether the destination gets the source's permissions.
+    pub fn preserve_permissions(mut self, preserve: bool) -> Self {
+        self.preserve_permissions = preserve;
+        self
+    }
+
+    /// Controls whether the destination gets the source's modification time.
+    ///
+    /// This is only available with the `filetime` feature.
+    #[cfg(feature = "filetime")]
+    pub fn preserve_mtime(mut self, preserve: bool) -> Self {
+        self.preserve_mtime = preserve;
+        self
+    }
+
+    /// Sets the dictionary to compress or decompress with.
+    pub fn dictionary(mut self, dictionary: Vec<u8>) -> Self {
+        self.dictionary = Some(dictionary.into());
+        self
+    }
+}
+
+/// Describes a completed file operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    /// Number of bytes read from the source file.
+    pub bytes_read: u64,
+
+    /// Number of bytes written to the destination file.
+    pub bytes_written: u64,
+
+    /// Whether the source file was memory-mapped.
+    pub mmap: bool,
+}
+
+/// Compresses the file at `src` into `dst`.
+///
+/// `dst` is created, or truncated if it already exists. It is an error
+/// (`InvalidInput`) for `dst` to be the same file as `src`.
+pub fn compress_file<P: AsRef<Path>, Q: AsRef<Path>>(
+    src: P,
+    dst: Q,
+    options: &Options,
+) -> io::Result<Stats> {
+    compress_file_with(src.as_ref(), dst.as_ref(), options, None)
+}
+
+// Compresses `src`, using a prepared dictionary if given.
+fn compress_file_with(
+    src: &Path,
+    dst: &Path,
+    options: &Options,
+    dictionary: Option<&EncoderDictionary<'_>>,
+) -> io::Result<Stats> {
+    let source = File::open(src)?;
+    let metadata = source.metadata()?;
+
+    check_distinct(src, &metadata, dst)?;
+    let destination = File::create(dst)?;
+    let mut encoder = match (dictionary, &options.dictionary) {
+        (Some(dictionary), _) => {
+            write::Encoder::with_prepared_dictionary(destination, dictionary)?
+        }
+        (None, Some(dictionary)) => write::Encoder::with_dictionary(
+            destination,
+            options.level,
+            dictionary,
+        )?,
+        (None, None) => write::Encoder::new(destination, options.level)?,
+    };
+    encoder.include_checksum(options.checksum)?;
+
+    let (bytes_read, mmap) = match map(&source, &metadata) {
+        Some(data) => {
+            io::Write::write_all(&mut encoder, &data)?;
+            (data.len() as u64, true)
+        }
+        None => (io::copy(&mut &source, &mut encoder)?, false),
+    };
+    let destination = encoder.finish()?;
+
+    finish(destination, &metadata, dst, options, bytes_read, mmap)
+}
+
+/// Decompresses the file at `src` into `dst`.
+///
+/// `dst` is created, or truncated if it already exists. It is an error
+/// (`InvalidInput`) for `dst` to be the same file as `src`.
+pub fn decompress_file<P: AsRef<Path>, Q: AsRef<Path>>(
+    src: P,
+    dst: Q,
+    options: &Options,
+) -> io::Result<Stats> {
+    let source = File::open(src.as_ref())?;
+    let metadata = source.metadata()?;
+
+    check_distinct(src.as_ref(), &metadata, dst.as_ref())?;
+    let mut destination = File::create(dst.as_ref())?;
+
+    let dictionary = options.dictionary.as_deref().unwrap_or(&[]);
+    let mmap = match map(&source, &metadata) {
+        Some(data) => {
+            let mut decoder =
+                read::Decoder::with_dictionary(&data[..], dictionary)?;
+            io::copy(&mut decoder, &mut destination)?;
+            true
+        }
+        None => {
+            let source =
+                BufReader::with_capacity(zstd_safe::DCtx::in_size(), &source);
+            let mut decoder =
+                read::Decoder::with_dictionary(source, dictionary)?;
+            io::copy(&mut decoder, &mut destination)?;
+            false
+        }
+    };
+
+    finish(
+        destination,
+        &metadata,
+        dst.as_ref(),
+        options,
+        metadata.len(),
+        mmap,
+    )
+}
+
+/// Compresses many files with the same options.
+///
+/// Each file is compressed next to the original, with a `.zst` extension
+/// appended. A dictionary given in the options is only prepared once for
+/// all files.
+///
+/// ```rust,no_run
+/// # fn main() -> std::io::Result<()> {
+/// use zstd::fs::{Batch, Options};
+///
+/// let results = Batch::new(Options::new().level(19))
+///     .threads(4)
+///     .compress_dir("backups")?;
+/// for result in results {
+///     if let Err(e) = result.stats {
+///         eprintln!("{}: {}", result.src.display(), e);
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Batch {
+    options: Options,
+    threads: usize,
+}
+
+/// The outcome of compressing one file in a [`Batch`].
+///
+/// [`Batch`]: struct.Batch.html
+#[derive(Debug)]
+pub struct FileResult {
+    /// The file that was compressed.
+    pub src: PathBuf,
+
+    /// The compressed file.
+    pub dst: PathBuf,
+
+    /// Statistics, or the error that happened with this file.
+    pub stats: io::Result<Stats>,
+}
+
+impl Batch {
+    /// Creates a new batch, using the given options for every file.
+    pub fn new(options: Options) -> Self {
+        Batch {
+            options,
+            threads: 1,
+        }
+    }
+
+    /// Sets the number of threads compressing files in parallel.
+    ///
+    /// Defaults to `1`, compressing files on the current thread. This is
+    /// ignored on wasm targets.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Compresses the given files.
+    ///
+    /// Results are returned in the same order as `paths`. An error with one
+    /// file does not prevent compressing the others. With several threads,
+    /// a panic while compressing a file is reported as an error for it.
+    pub fn compress_files<I>(&self, paths: I) -> Vec<FileResult>
+    where
+        I: IntoIterator,
+        I::Item: Into<PathBuf>,
+    {
+        let paths: Vec<PathBuf> = paths.into_iter().map(Into::into).collect();
+        let dictionary = self.options.dictionary.as_ref().map(|dictionary| {
+            Arc::new(EncoderDictionary::copy(dictionary, self.options.level))
+        });
+
+        // Threads cannot be spawned on wasm.
+        let threads = if cfg!(target_arch = "wasm32") {
+            1
+        } else {
+            usize::min(self.threads, paths.len())
+        };
+        if threads <= 1 {
+            return paths
+                .into_iter()
+                .map(|src| {
+                    compress_to_zst(src, &self.options, dictionary.as_deref())
+                })
+                .collect();
+        }
+
+        let mut slots: Vec<Option<FileResult>> =
+            paths.iter().map(|_| None).collect();
+        let jobs = Arc::new(Mutex::new(paths.clone().into_iter().enumerate()));
+        let (sender, receiver) = mpsc::channel();
+        let handles: Vec<_> = (0..threads)
+            .map(|_| {
+                let jobs = Arc::clone(&jobs);
+                let sender = sender.clone();
+                let options = self.options.clone();
+                let dictionary = dictionary.clone();
+                thread::spawn(move || loop {
+                    let job = jobs
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .next();
+                    let (i, src) = match job {
+                        Some(job) => job,
+                        None => break,
+                    };
+                    let dst = zst_path(&src);
+                    let stats = panic::catch_unwind(AssertUnwindSafe(|| {
+                        compress_file_with(
+                            &src,
+                            &dst,
+                            &options,
+                            dictionary.as_deref(),
+                        )
+                    }))
+                    .unwrap_or_else(|_| Err(panicked()));
+                    if sender
+                        .send((i, FileResult { src, dst, stats }))
+                        .is_err()
+                    {
+                        break;
+                    }
+                })
+            })
+            .collect();
+        drop(sender);
+
+        for (i, result) in receiver {
+            slots[i] = Some(result);
+        }
+        for handle in handles {
+            // Panics are caught for each file: this is only a safety net.
+            let _ = handle.join();
+        }
+
+        // A file without result was lost with its thread.
+        slots
+            .into_iter()
+            .zip(paths)
+            .map(|(slot, src)| {
+                slot.unwrap_or_else(|| FileResult {
+                    dst: zst_path(&src),
+                    src,
+                    stats: Err(panicked()),
+                })
+            })
+            .collect()
+    }
+
+    /// Compresses every file in the given directory and its
+    /// sub-directories.
+    ///
+    /// Files already ending with `.zst` and symbolic links are skipped.
+    ///
+    /// Returns an error if the directory cannot be listed.
+    pub fn compress_dir<P: AsRef<Path>>(
+        &self,
+        dir: P,
+    ) -> io::Result<Vec<FileResult>> {
+        let mut paths = Vec::new();
+        list_files(dir.as_ref(), &mut paths)?;
+        paths.sort();
+        Ok(self.compress_files(paths))
+    }
+}
+
+fn compress_to_zst(
+    src: PathBuf,
+    options: &Options,
+    dictionary: Option<&EncoderDictionary<'_>>,
+) -> FileResult {
+    let dst = zst_path(&src);
+    let stats = compress_file_with(&src, &dst, options, dictionary);
+    FileResult { src, dst, stats }
+}
+
+fn zst_path(src: &Path) -> PathBuf {
+    let mut dst = src.to_path_buf().into_os_string();
+    dst.push(".zst");
+    PathBuf::from(dst)
+}
+
+fn panicked() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "compression thread panicked")
+}
+
+// Recursively adds the regular files in `dir` to `paths`.
+fn list_files(dir: &Path, paths: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let path = entry.path();
+        if file_type.is_dir() {
+            list_files(&path, paths)?;
+        } else if file_type.is_file()
+            && path.extension() != Some("zst".as_ref())
+        {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+// Makes sure that creating `dst` won't truncate the source file.
+//
+// This also catches hard links and different paths to the same file.
+fn check_distinct(
+    src: &Path,
+    metadata: &fs::Metadata,
+    dst: &Path,
+) -> io::Result<()> {
+    let dst_metadata = match fs::metadata(dst) {
+        Ok(dst_metadata) => dst_metadata,
+        // `dst` doesn't exist yet (or isn't reachable): it will be created.
+        Err(_) => return Ok(()),
+    };
+
+    if same_file(metadata, &dst_metadata)
+        || fs::canonicalize(src)? == fs::canonicalize(dst)?
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "source and destination are the same file",
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn same_file(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    (a.dev(), a.ino()) == (b.dev(), b.ino())
+}
+
+#[cfg(not(unix))]
+fn same_file(_a: &fs::Metadata, _b: &fs::Metadata) -> bool {
+    false
+}
+
+// Applies the metadata options to `dst`, and collects the statistics.
+fn finish(
+    destination: File,
+    metadata: &fs::Metadata,
+    dst: &Path,
+    options: &Options,
+    bytes_read: u64,
+    mmap: bool,
+) -> io::Result<Stats> {
+    let bytes_written = destination.metadata()?.len();
+    drop(destination);
+
+    if options.preserve_permissions {
+        fs::set_permissions(dst, metadata.permissions())?;
+    }
+    #[cfg(feature = "filetime")]
+    if options.preserve_mtime {
+        filetime::set_file_mtime(
+            dst,
+            filetime::FileTime::from_last_modification_time(metadata),
+        )?;
+    }
+
+    Ok(Stats {
+        bytes_read,
+        bytes_written,
+        mmap,
+    })
+}
+
+// Memory-maps the given file, if it's a non-empty regular file.
+#[cfg(feature = "mmap")]
+fn map(file: &File, metadata: &fs::Metadata) -> Option<memmap2::Mmap> {
+    if !metadata.is_file() || metadata.len() == 0 {
+        return None;
+    }
+
+    // This is only unsafe if the file is modified while we read it.
+    unsafe { memmap2::Mmap::map(file) }.ok()
+}
+
+#[cfg(not(feature = "mmap"))]
+fn map(_file: &File, _metadata: &fs::Metadata) -> Option<Vec<u8>> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compress_file, decompress_file, Batch, Options};
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "zstd-fs-{}-{}",
+            std::process::id(),
+            name
+        ))
+    }
+
+    #[test]
+    fn test_cycle() {
+        let content: Vec<u8> =
+            (0..100_000u32).map(|i| (i % 13) as u8).collect();
+
+        let original = temp_path("original");
+        let compressed = temp_path("compressed.zst");
+        let decompressed = temp_path("decompressed");
+        fs::write(&original, &content).unwrap();
+
+        let options = Options::new().level(3);
+        #[cfg(feature = "filetime")]
+        let options = options.preserve_mtime(true);
+        let stats = compress_file(&original, &compressed, &options).unwrap();
+        assert_eq!(stats.bytes_read, content.len() as u64);
+        assert_eq!(
+            stats.bytes_written,
+            fs::metadata(&compressed).unwrap().len()
+        );
+        assert_eq!(stats.mmap, cfg!(feature = "mmap"));
+
+        let stats =
+            decompress_file(&compressed, &decompressed, &options).unwrap();
+        assert_eq!(stats.bytes_written, content.len() as u64);
+        assert_eq!(fs::read(&decompressed).unwrap(), content);
+        #[cfg(feature = "filetime")]
+        assert_eq!(
+            fs::metadata(&decompressed).unwrap().modified().unwrap(),
+            fs::metadata(&original).unwrap().modified().unwrap()
+        );
+
+        for path in &[original, compressed, decompressed] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_same_file() {
+        let original = temp_path("same");
+        let link = temp_path("same-link");
+        fs::write(&original, b"some content").unwrap();
+        let _ = fs::remove_file(&link);
+        fs::hard_link(&original, &link).unwrap();
+
+        let options = Options::new();
+        let dot = original
+            .parent()
+            .unwrap()
+            .join(".")
+            .join(original.file_name().unwrap());
+        for dst in &[&original, &dot, &link] {
+            let err = compress_file(&original, dst, &options).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+            let err = decompress_file(&original, dst, &options).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        }
+        assert_eq!(fs::read(&original).unwrap(), b"some content");
+
+        for path in &[original, link] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_batch() {
+        let dir = temp_path("batch");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        let files = ["a", "b", "sub/c", "sub/d", "sub/e"];
+        for name in &files {
+            fs::write(dir.join(name), name.repeat(1000)).unwrap();
+        }
+        fs::write(dir.join("old.zst"), b"not compressed").unwrap();
+
+        let dictionary = b"sub/sub/sub/sub/".repeat(10);
+        let options = Options::new().dictionary(dictionary);
+        let results = Batch::new(options.clone())
+            .threads(3)
+            .compress_dir(&dir)
+            .unwrap();
+        assert_eq!(results.len(), files.len());
+
+        for (result, name) in results.into_iter().zip(&files) {
+            assert_eq!(result.src, dir.join(name));
+            result.stats.unwrap();
+
+            let decompressed = dir.join(format!("{}.out", name));
+            decompress_file(&result.dst, &decompressed, &options).unwrap();
+            assert_eq!(
+                fs::read(&decompressed).unwrap(),
+                name.repeat(1000).as_bytes()
+            );
+        }
+
+        // Errors are reported per file.
+        let results = Batch::new(Options::new())
+            .compress_files(vec![dir.join("missing")]);
+        assert!(results[0].stats.is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,136 @@
+//! Convert gzip streams to zstd, and back.
+//!
+//! This helps migrating archives from gzip: data is decompressed and
+//! compressed again in a single pass, through a fixed-size buffer, so the
+//! plaintext is never entirely in memory.
+//!
+//! Concatenated gzip members (like the output of `cat a.gz b.gz`) are read
+//! as a single stream, as `gzip -d` does.
+//!
+//! *This module requires the `flate2` feature.*
+//!
+//! ```
+//! use std::io::Write;
+//!
+//! let mut gzip = flate2::write::GzEncoder::new(
+//!     Vec::new(),
+//!     flate2::Compression::default(),
+//! );
+//! gzip.write_all(b"legacy archive")?;
+//! let gzip = gzip.finish()?;
+//!
+//! let mut zstd = Vec::new();
+//! zstd::gzip::gzip_to_zstd(&gzip[..], &mut zstd, 3)?;
+//! assert_eq!(zstd::decode_all(&zstd[..])?, b"legacy archive");
+//!
+//! let mut back = Vec::new();
+//! zstd::gzip::zstd_to_gzip(&zstd[..], &mut back, 6)?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+use std::io::{self, Read, Write};
+
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::stream::{read, write};
+
+/// Largest gzip compression level.
+pub const MAX_GZIP_LEVEL: u32 = 9;
+
+/// Converts a gzip stream from `source` to a zstd stream in `destination`.
+///
+/// A level of `0` uses zstd's default (currently `3`).
+pub fn gzip_to_zstd<R, W>(
+    source: R,
+    destination: W,
+    level: i32,
+) -> io::Result<()>
+where
+    R: Read,
+    W: Write,
+{
+    let mut decoder = MultiGzDecoder::new(source);
+    let mut encoder = write::Encoder::new(destination, level)?;
+    io::copy(&mut decoder, &mut encoder)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+/// Converts a zstd stream from `source` to a gzip stream in `destination`.
+///
+/// `level` is the gzip compression level, from `0` (no compression) to
+/// [`MAX_GZIP_LEVEL`]. Higher levels are an `InvalidInput` error.
+///
+/// [`MAX_GZIP_LEVEL`]: constant.MAX_GZIP_LEVEL.html
+pub fn zstd_to_gzip<R, W>(
+    source: R,
+    destination: W,
+    level: u32,
+) -> io::Result<()>
+where
+    R: Read,
+    W: Write,
+{
+    if level > MAX_GZIP_LEVEL {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("gzip level must be at most {}", MAX_GZIP_LEVEL),
+        ));
+    }
+    let mut decoder = read::Decoder::new(source)?;
+    let mut encoder = GzEncoder::new(destination, Compression::new(level));
+    io::copy(&mut decoder, &mut encoder)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{gzip_to_zstd, zstd_to_gzip};
+    use std::io::{Read, Write};
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::fast(),
+        );
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let first = crate::test_util::text(300_000, 1);
+        let second = crate::test_util::text(100_000, 2);
+        // Concatenated members are a single stream.
+        let mut input = gzip(&first);
+        input.extend(gzip(&second));
+
+        let mut zstd = Vec::new();
+        gzip_to_zstd(&input[..], &mut zstd, 3).unwrap();
+        let data = [&first[..], &second[..]].concat();
+        assert_eq!(crate::decode_all(&zstd[..]).unwrap(), data);
+
+        let mut back = Vec::new();
+        zstd_to_gzip(&zstd[..], &mut back, 6).unwrap();
+        let mut output = Vec::new();
+        flate2::read::GzDecoder::new(&back[..])
+            .read_to_end(&mut output)
+            .unwrap();
+        assert_eq!(output, data);
+    }
+
+    #[test]
+    fn test_errors() {
+        let zstd = crate::encode_all(&b"data"[..], 1).unwrap();
+        let error = zstd_to_gzip(&zstd[..], Vec::new(), 10).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+
+        assert!(gzip_to_zstd(&zstd[..], Vec::new(), 1).is_err());
+        let input = gzip(b"some data");
+        let truncated = &input[..input.len() - 1];
+        assert!(gzip_to_zstd(truncated, Vec::new(), 1).is_err());
+        assert!(zstd_to_gzip(&input[..], Vec::new(), 1).is_err());
+    }
+}
@@ -0,0 +1,153 @@
+//! Helpers to use zstd as an HTTP content coding.
+//!
+//! This covers the `Content-Encoding: zstd` coding from [RFC 8878]: a body
+//! encoder for responses, a body decoder for requests, and header
+//! negotiation. These are independent of any HTTP library.
+//!
+//! The RFC asks encoders not to use windows larger than 8MB, and allows
+//! decoders to reject them. Both helpers here enforce this limit, which also
+//! bounds the memory a client can make a server allocate.
+//!
+//! *This module requires the `http` feature.*
+//!
+//! [RFC 8878]: https://www.rfc-editor.org/rfc/rfc8878.html
+use std::io::{self, BufReader, Read, Write};
+
+use crate::map_error_code;
+use crate::stream::{read, write};
+
+/// Name of the zstd content coding, for `Content-Encoding` headers.
+pub const ENCODING: &str = "zstd";
+
+/// Largest window size accepted for the `zstd` content coding, as a power
+/// of two (8MB).
+pub const MAX_WINDOW_LOG: u32 = 23;
+
+/// Creates an encoder for a response body.
+///
+/// The window size is capped at [`MAX_WINDOW_LOG`]: only levels above 19
+/// use larger windows by default.
+///
+/// [`MAX_WINDOW_LOG`]: constant.MAX_WINDOW_LOG.html
+pub fn response_encoder<W: Write>(
+    writer: W,
+    level: i32,
+) -> io::Result<write::Encoder<'static, W>> {
+    let mut encoder = write::Encoder::new(writer, level)?;
+    if level > 19 {
+        encoder
+            .context_mut()
+            .set_parameter(zstd_safe::CParameter::WindowLog(MAX_WINDOW_LOG))
+            .map_err(map_error_code)?;
+    }
+    Ok(encoder)
+}
+
+/// Creates a decoder for a request body.
+///
+/// Frames requiring a window larger than [`MAX_WINDOW_LOG`] are rejected
+/// with an error.
+///
+/// [`MAX_WINDOW_LOG`]: constant.MAX_WINDOW_LOG.html
+pub fn request_decoder<R: Read>(
+    reader: R,
+) -> io::Result<read::Decoder<'static, BufReader<R>>> {
+    let mut decoder = read::Decoder::new(reader)?;
+    decoder
+        .context_mut()
+        .set_parameter(zstd_safe::DParameter::WindowLogMax(MAX_WINDOW_LOG))
+        .map_err(map_error_code)?;
+    Ok(decoder)
+}
+
+/// Returns `true` if an `Accept-Encoding` header value allows the `zstd`
+/// coding.
+///
+/// An explicit `zstd` entry takes precedence over a `*` wildcard, and a
+/// weight of `q=0` means "not acceptable".
+pub fn accepts_zstd(accept_encoding: &str) -> bool {
+    let mut wildcard = None;
+    for item in accept_encoding.split(',') {
+        let mut parts = item.split(';');
+        let coding = parts.next().unwrap_or("").trim();
+        let acceptable = parts.all(|param| {
+            let param = param.trim();
+            match param.get(..2) {
+                Some(name) if name.eq_ignore_ascii_case("q=") => param[2..]
+                    .trim()
+                    .parse::<f32>()
+                    .map(|q| q > 0.0)
+                    .unwrap_or(false),
+                _ => true,
+            }
+        });
+
+        if coding.eq_ignore_ascii_case(ENCODING) {
+            return acceptable;
+        } else if coding == "*" {
+            wildcard = Some(acceptable);
+        }
+    }
+    wildcard.unwrap_or(false)
+}
+
+/// Returns `true` if a `Content-Encoding` header value is exactly the `zstd`
+/// coding.
+///
+/// Bodies with several codings (like `zstd, gzip`) need to be decoded in
+/// reverse order, and are not recognized here.
+pub fn is_zstd(content_encoding: &str) -> bool {
+    content_encoding.trim().eq_ignore_ascii_case(ENCODING)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{accepts_zstd, is_zstd, request_decoder, response_encoder};
+    use std::io::{Read, Write};
+
+    #[test]
+    fn test_negotiation() {
+        assert!(accepts_zstd("zstd"));
+        assert!(accepts_zstd("gzip, deflate, br, zstd"));
+        assert!(accepts_zstd("gzip;q=1.0, ZSTD;q=0.5"));
+        assert!(accepts_zstd("*"));
+        assert!(!accepts_zstd("gzip, br"));
+        assert!(!accepts_zstd("zstd;q=0"));
+        assert!(!accepts_zstd("*, zstd;q=0"));
+        assert!(accepts_zstd("*;q=0, zstd"));
+        assert!(!accepts_zstd(""));
+
+        assert!(is_zstd("zstd"));
+        assert!(is_zstd(" Zstd "));
+        assert!(!is_zstd("zstd, gzip"));
+    }
+
+    #[test]
+    fn test_bodies() {
+        let body = b"Hello, web! ".repeat(1000);
+
+        let mut encoder = response_encoder(Vec::new(), 22).unwrap();
+        encoder.write_all(&body).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut decoded = Vec::new();
+        request_decoder(&compressed[..])
+            .unwrap()
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, body);
+
+        // Frames with a window over 8MB are rejected.
+        let mut encoder =
+            crate::stream::write::Encoder::new(Vec::new(), 3).unwrap();
+        encoder
+            .context_mut()
+            .set_parameter(zstd_safe::CParameter::WindowLog(25))
+            .unwrap();
+        encoder.write_all(&body).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut decoder = request_decoder(&compressed[..]).unwrap();
+        assert!(decoder.read_to_end(&mut Vec::new()).is_err());
+    }
+}
@@ -21,12 +21,67 @@
 //! }
 //! ```
 //!
+//! # Thread safety
+//!
+//! Encoders, decoders and the `block` types own their zstd context: they
+//! are `Send`, so they can be moved to another thread (or handed to a thread
+//! pool task), but not `Sync`, since zstd contexts can only be used by one
+//! thread at a time. Use one of them per thread, or put them behind a
+//! `Mutex`.
+//!
+//! Prepared dictionaries (`EncoderDictionary` and `DecoderDictionary`) are
+//! read-only once created: they are both `Send` and `Sync`, and can be
+//! shared by many threads, for example in an `Arc`.
+//!
+//! # Legacy frames
+//!
+//! With the `legacy` feature (enabled by default), decoders also read frames
+//! produced by zstd versions before 0.8 (the first stable format), going
+//! back to v0.1.
+//!
+//! # Pure-Rust decompression
+//!
+//! With the `ruzstd` feature, the [`pure`] module also provides
+//! decompression implemented in Rust, with the same signatures as the
+//! corresponding [`stream`] and [`block`] functions. Legacy frames and
+//! dictionaries are not supported there.
+//!
 //! [zstd]: https://github.com/facebook/zstd
+//! [`pure`]: pure/index.html
+//! [`stream`]: stream/index.html
+//! [`block`]: block/index.html
 #![deny(missing_docs)]
 
 pub mod block;
+#[cfg(feature = "cdc")]
+pub mod cdc;
+pub mod compressed_vec;
+pub mod container;
 pub mod dict;
+pub mod frame;
+pub mod fs;
+#[cfg(feature = "flate2")]
+pub mod gzip;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod metadata;
+mod params;
+pub mod pipeline;
+mod profile;
+#[cfg(feature = "ruzstd")]
+pub mod pure;
+pub mod random_access;
+pub mod record;
 pub mod stream;
+#[cfg(feature = "tar")]
+pub mod tar;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+#[cfg(all(feature = "experimental", feature = "zstdmt"))]
+mod thread_pool;
+#[cfg(any(target_arch = "wasm32", feature = "wasm"))]
+pub mod wasm;
+mod xxhash;
 
 use std::io;
 
@@ -34,7 +89,73 @@ use std::io;
 pub use zstd_safe::CLEVEL_DEFAULT as DEFAULT_COMPRESSION_LEVEL;
 
 #[doc(no_inline)]
-pub use crate::stream::{decode_all, encode_all, Decoder, Encoder};
+pub use crate::stream::{decode_all, encode_all, verify, Decoder, Encoder};
+
+pub use crate::compressed_vec::CompressedVec;
+pub use crate::params::CompressionParameters;
+pub use crate::profile::Profile;
+#[cfg(all(feature = "experimental", feature = "zstdmt"))]
+pub use crate::thread_pool::ThreadPool;
+pub use crate::xxhash::{xxhash64, XxHash64};
+
+/// Returns the version of the zstd library in use, like `"1.4.9"`.
+///
+/// This can differ from the bundled version when linking a system library.
+pub fn version() -> &'static str {
+    zstd_safe::version_string()
+}
+
+/// Describes what the zstd library in use supports.
+///
+/// This is returned by [`capabilities`].
+///
+/// [`capabilities`]: fn.capabilities.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Capabilities {
+    /// Whether multithreaded compression is available.
+    ///
+    /// Without it, `multithread()` fails for any non-zero number of workers.
+    pub multithreading: bool,
+
+    /// Whether frames from zstd versions before 0.8 can be decoded.
+    pub legacy: bool,
+
+    /// Largest window log supported when compressing or decompressing.
+    pub max_window_log: u32,
+
+    /// Fastest compression level.
+    pub min_level: i32,
+
+    /// Slowest compression level.
+    pub max_level: i32,
+}
+
+/// Queries the capabilities of the zstd library in use.
+///
+/// This allows adapting the configuration at runtime, instead of failing
+/// with "Unsupported parameter" errors.
+pub fn capabilities() -> Capabilities {
+    use zstd_safe::{CParameter, DParameter};
+
+    let max_workers = zstd_safe::cparam_get_bounds(CParameter::NbWorkers(0))
+        .map_or(0, |bounds| *bounds.end());
+    let max_window_log =
+        zstd_safe::dparam_get_bounds(DParameter::WindowLogMax(0))
+            .map_or(zstd_safe::WINDOWLOG_MAX_32 as i32, |bounds| {
+                *bounds.end()
+            });
+    // Magic number of a zstd v0.7 frame.
+    let legacy = zstd_safe::is_frame(&0xFD2F_B527u32.to_le_bytes()) != 0;
+
+    Capabilities {
+        multithreading: max_workers > 0,
+        legacy,
+        max_window_log: max_window_log as u32,
+        min_level: zstd_safe::min_c_level(),
+        max_level: zstd_safe::max_c_level(),
+    }
+}
 
 /// Returns the error message as io::Error based on error_code.
 fn map_error_code(code: usize) -> io::Error {
@@ -42,24 +163,16 @@ fn map_error_code(code: usize) -> io::Error {
     io::Error::new(io::ErrorKind::Other, msg.to_string())
 }
 
-// Some helper functions to write full-cycle tests.
-
-#[cfg(test)]
-fn test_cycle<F, G>(data: &[u8], f: F, g: G)
-where
-    F: Fn(&[u8]) -> Vec<u8>,
-    G: Fn(&[u8]) -> Vec<u8>,
-{
-    let mid = f(data);
-    let end = g(&mid);
-    assert_eq!(data, &end[..]);
-}
+#[test]
+fn test_capabilities() {
+    if !cfg!(feature = "pkg-config") {
+        assert_eq!(version(), "1.4.9");
+    }
 
-#[cfg(test)]
-fn test_cycle_unwrap<F, G>(data: &[u8], f: F, g: G)
-where
-    F: Fn(&[u8]) -> io::Result<Vec<u8>>,
-    G: Fn(&[u8]) -> io::Result<Vec<u8>>,
-{
-    test_cycle(data, |data| f(data).unwrap(), |data| g(data).unwrap())
+    let capabilities = capabilities();
+    assert_eq!(capabilities.multithreading, cfg!(feature = "zstdmt"));
+    assert_eq!(capabilities.legacy, cfg!(feature = "legacy"));
+    assert!(capabilities.max_window_log >= 30);
+    assert!(capabilities.min_level < 0);
+    assert_eq!(capabilities.max_level, 22);
 }
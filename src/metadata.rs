@@ -0,0 +1,358 @@
+//! Embed metadata records in a compressed stream.
+//!
+//! A [`Metadata`] record is a set of typed key-value pairs (creation time,
+//! source hash, application tags...), stored in a skippable frame. zstd
+//! decoders ignore it, so the stream still decompresses as usual, and the
+//! records can be read back without decompressing anything.
+//!
+//! ```
+//! use std::io::Write;
+//! use zstd::metadata::Metadata;
+//!
+//! let mut stream = Vec::new();
+//! let mut metadata = Metadata::new();
+//! metadata.insert("created", 1_600_000_000u64);
+//! metadata.insert("source", "backup.sql");
+//! metadata.write_to(&mut stream)?;
+//! stream.write_all(&zstd::encode_all(&b"payload"[..], 3)?)?;
+//!
+//! assert_eq!(zstd::decode_all(&stream[..])?, b"payload");
+//!
+//! let records = Metadata::read_all(&stream)?;
+//! assert_eq!(records[0].get_u64("created"), Some(1_600_000_000));
+//! assert_eq!(records[0].get_str("source"), Some("backup.sql"));
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! # Format
+//!
+//! A record is a skippable frame (magic number `0x184D2A5E`), whose content
+//! is:
+//!
+//! * The signature `ZMET`, and the format version (`1`) as a byte.
+//! * For each entry, sorted by key:
+//!     * the key length (`u16`) and the UTF-8 key,
+//!     * the value type (a byte: `0` for bytes, `1` for a string, `2` for
+//!       a `u64`),
+//!     * the value length (`u32`) and the value.
+//!
+//! All integers are little-endian. Skippable frames with this magic number
+//! but another signature are ignored; a newer format version is an error.
+//!
+//! [`Metadata`]: struct.Metadata.html
+
+use std::collections::BTreeMap;
+use std::io;
+
+/// Magic variant of the skippable frames holding metadata.
+pub const MAGIC_VARIANT: u8 = 0xE;
+
+const SIGNATURE: &[u8; 4] = b"ZMET";
+const VERSION: u8 = 1;
+
+/// A metadata value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    /// Arbitrary bytes.
+    Bytes(Vec<u8>),
+    /// A UTF-8 string.
+    Str(String),
+    /// An unsigned integer.
+    U64(u64),
+}
+
+impl Value {
+    /// Returns the content of a `Bytes` value.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    /// Returns the content of a `Str` value.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Str(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    /// Returns the content of a `U64` value.
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Value::U64(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn kind(&self) -> u8 {
+        match self {
+            Value::Bytes(_) => 0,
+            Value::Str(_) => 1,
+            Value::U64(_) => 2,
+        }
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(bytes: Vec<u8>) -> Self {
+        Value::Bytes(bytes)
+    }
+}
+
+impl From<&[u8]> for Value {
+    fn from(bytes: &[u8]) -> Self {
+        Value::Bytes(bytes.to_vec())
+    }
+}
+
+impl From<String> for Value {
+    fn from(string: String) -> Self {
+        Value::Str(string)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(string: &str) -> Self {
+        Value::Str(string.to_string())
+    }
+}
+
+impl From<u64> for Value {
+    fn from(value: u64) -> Self {
+        Value::U64(value)
+    }
+}
+
+/// A metadata record: typed values, by key.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    entries: BTreeMap<String, Value>,
+}
+
+impl Metadata {
+    /// Creates an empty record.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the value of `key`, and returns the previous one.
+    pub fn insert<K: Into<String>, V: Into<Value>>(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> Option<Value> {
+        self.entries.insert(key.into(), value.into())
+    }
+
+    /// Removes `key`, and returns its value.
+    pub fn remove(&mut self, key: &str) -> Option<Value> {
+        self.entries.remove(key)
+    }
+
+    /// Returns the value of `key`.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.entries.get(key)
+    }
+
+    /// Returns the value of `key`, if it is a string.
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.get(key).and_then(Value::as_str)
+    }
+
+    /// Returns the value of `key`, if it is a `u64`.
+    pub fn get_u64(&self, key: &str) -> Option<u64> {
+        self.get(key).and_then(Value::as_u64)
+    }
+
+    /// Returns the value of `key`, if it is bytes.
+    pub fn get_bytes(&self, key: &str) -> Option<&[u8]> {
+        self.get(key).and_then(Value::as_bytes)
+    }
+
+    /// Returns all the entries, sorted by key.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.entries
+            .iter()
+            .map(|(key, value)| (key.as_str(), value))
+    }
+
+    /// Returns the number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if there is no entry.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Writes this record as a skippable frame.
+    ///
+    /// It can be written anywhere between the frames of a stream.
+    pub fn write_to<W: io::Write>(&self, writer: W) -> io::Result<()> {
+        crate::frame::write_skippable_frame(
+            writer,
+            MAGIC_VARIANT,
+            &self.to_bytes()?,
+        )
+    }
+
+    /// Returns all the records of a compressed stream, in order.
+    ///
+    /// Only the frame headers are read: nothing is decompressed.
+    pub fn read_all(data: &[u8]) -> io::Result<Vec<Metadata>> {
+        let mut records = Vec::new();
+        for (variant, content) in crate::frame::skippable_frames(data)? {
+            if variant != MAGIC_VARIANT {
+                continue;
+            }
+            if let Some(record) = Metadata::from_bytes(content)? {
+                records.push(record);
+            }
+        }
+        Ok(records)
+    }
+
+    fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        let mut data = SIGNATURE.to_vec();
+        data.push(VERSION);
+        for (key, value) in &self.entries {
+            let u64_bytes;
+            let bytes = match value {
+                Value::Bytes(bytes) => &bytes[..],
+                Value::Str(string) => string.as_bytes(),
+                Value::U64(value) => {
+                    u64_bytes = value.to_le_bytes();
+                    &u64_bytes[..]
+                }
+            };
+            if key.len() > u16::MAX as usize || bytes.len() > u32::MAX as usize
+            {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "metadata entry too large",
+                ));
+            }
+            data.extend_from_slice(&(key.len() as u16).to_le_bytes());
+            data.extend_from_slice(key.as_bytes());
+            data.push(value.kind());
+            data.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+            data.extend_from_slice(bytes);
+        }
+        Ok(data)
+    }
+
+    // Returns `None` if `data` isn't a metadata record.
+    fn from_bytes(data: &[u8]) -> io::Result<Option<Metadata>> {
+        if data.len() < 5 || &data[..4] != SIGNATURE {
+            return Ok(None);
+        }
+        if data[4] != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unsupported metadata version",
+            ));
+        }
+
+        let mut data = &data[5..];
+        let mut metadata = Metadata::new();
+        while !data.is_empty() {
+            let key_len = u16::from_le_bytes(read_array(&mut data)?);
+            let key = std::str::from_utf8(take(&mut data, key_len.into())?)
+                .map_err(|_| invalid())?
+                .to_string();
+            let [kind] = read_array(&mut data)?;
+            let len = u32::from_le_bytes(read_array(&mut data)?);
+            let bytes = take(&mut data, len as usize)?;
+            let value = match kind {
+                0 => Value::Bytes(bytes.to_vec()),
+                1 => Value::Str(
+                    String::from_utf8(bytes.to_vec())
+                        .map_err(|_| invalid())?,
+                ),
+                2 => {
+                    let mut bytes = bytes;
+                    let value = u64::from_le_bytes(read_array(&mut bytes)?);
+                    if !bytes.is_empty() {
+                        return Err(invalid());
+                    }
+                    Value::U64(value)
+                }
+                _ => return Err(invalid()),
+            };
+            metadata.insert(key, value);
+        }
+        Ok(Some(metadata))
+    }
+}
+
+fn invalid() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "invalid metadata record")
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if data.len() < len {
+        return Err(invalid());
+    }
+    let (head, tail) = data.split_at(len);
+    *data = tail;
+    Ok(head)
+}
+
+fn read_array<const N: usize>(data: &mut &[u8]) -> io::Result<[u8; N]> {
+    let mut array = [0u8; N];
+    array.copy_from_slice(take(data, N)?);
+    Ok(array)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Metadata, Value};
+
+    #[test]
+    fn test_metadata() {
+        let mut first = Metadata::new();
+        first.insert("created", 1_600_000_000u64);
+        first.insert("hash", &b"\x00\x01\x02"[..]);
+        first.insert("tag", "nightly");
+        let mut second = Metadata::new();
+        second.insert("tag", "weekly");
+
+        let mut stream = Vec::new();
+        first.write_to(&mut stream).unwrap();
+        stream.extend(crate::encode_all(&b"payload"[..], 1).unwrap());
+        // Other skippable frames are ignored.
+        crate::frame::write_skippable_frame(&mut stream, 0, b"other").unwrap();
+        second.write_to(&mut stream).unwrap();
+        Metadata::new().write_to(&mut stream).unwrap();
+
+        assert_eq!(crate::decode_all(&stream[..]).unwrap(), b"payload");
+
+        let records = Metadata::read_all(&stream).unwrap();
+        assert_eq!(records, vec![first, second, Metadata::new()]);
+        assert_eq!(records[0].get_u64("created"), Some(1_600_000_000));
+        assert_eq!(records[0].get_bytes("hash"), Some(&[0, 1, 2][..]));
+        assert_eq!(records[0].get_str("created"), None);
+        assert_eq!(records[1].get("tag"), Some(&Value::from("weekly")));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let mut record = Metadata::new();
+        record.insert("key", "value");
+        let mut stream = Vec::new();
+        record.write_to(&mut stream).unwrap();
+
+        // Truncated entry.
+        let mut damaged = stream[..stream.len() - 1].to_vec();
+        damaged[4] -= 1;
+        assert!(Metadata::read_all(&damaged).is_err());
+
+        // Newer version.
+        let mut newer = stream.clone();
+        newer[12] = 2;
+        assert!(Metadata::read_all(&newer).is_err());
+    }
+}
@@ -0,0 +1,346 @@
+use std::env;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+use zstd_safe::{CParameter, Strategy};
+
+/// A set of compression parameters, which can be parsed from a string.
+///
+/// This lets applications accept zstd tuning from configuration files or
+/// command-line flags. The format is a comma-separated list of `key=value`
+/// pairs, using the same short names as `zstd --zstd=`:
+///
+/// | Key        | Parameter                                    |
+/// |------------|----------------------------------------------|
+/// | `level`    | Compression level                            |
+/// | `wlog`     | Window log                                   |
+/// | `clog`     | Chain log                                    |
+/// | `hlog`     | Hash log                                     |
+/// | `slog`     | Search log                                   |
+/// | `mml`      | Minimum match length                         |
+/// | `tlen`     | Target length                                |
+/// | `strat`    | Strategy, from `1` (fast) to `9` (btultra2)  |
+/// | `ldm`      | Long distance matching (`0` or `1`)          |
+/// | `checksum` | Content checksum (`0` or `1`)                |
+/// | `threads`  | Number of worker threads                     |
+///
+/// Parameters not given keep zstd's defaults. Values are only checked by
+/// zstd, when applied to an encoder.
+///
+/// ```
+/// let params: zstd::CompressionParameters =
+///     "level=19,wlog=27,ldm=1,checksum=1".parse()?;
+/// assert_eq!(params.to_string(), "level=19,wlog=27,ldm=1,checksum=1");
+///
+/// let mut encoder = zstd::stream::write::Encoder::new(Vec::new(), 0)?;
+/// encoder.apply_parameters(&params)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompressionParameters {
+    level: Option<i32>,
+    window_log: Option<u32>,
+    chain_log: Option<u32>,
+    hash_log: Option<u32>,
+    search_log: Option<u32>,
+    min_match: Option<u32>,
+    target_length: Option<u32>,
+    strategy: Option<u32>,
+    long_distance_matching: Option<bool>,
+    checksum: Option<bool>,
+    threads: Option<u32>,
+}
+
+impl CompressionParameters {
+    /// Returns an empty set, keeping all defaults.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the parameters set by environment variables, like the zstd CLI.
+    ///
+    /// `ZSTD_CLEVEL` sets the compression level, and `ZSTD_NBTHREADS` the
+    /// number of worker threads. This lets operators tune a deployed
+    /// service without changing its configuration.
+    ///
+    /// Unset variables are ignored, and invalid values are an error rather
+    /// than silently falling back to the defaults. Threads are only
+    /// supported with the `zstdmt` feature: without it, a valid
+    /// `ZSTD_NBTHREADS` is ignored, and compression stays single-threaded.
+    pub fn from_env() -> io::Result<Self> {
+        Self::from_env_values(
+            env_var("ZSTD_CLEVEL")?.as_deref(),
+            env_var("ZSTD_NBTHREADS")?.as_deref(),
+        )
+    }
+
+    // Parses the values of `ZSTD_CLEVEL` and `ZSTD_NBTHREADS`.
+    fn from_env_values(
+        level: Option<&str>,
+        threads: Option<&str>,
+    ) -> io::Result<Self> {
+        let mut params = CompressionParameters::new();
+        if let Some(level) = level {
+            params.level = Some(parse("ZSTD_CLEVEL", level)?);
+        }
+        if let Some(threads) = threads {
+            let threads = parse("ZSTD_NBTHREADS", threads)?;
+            if cfg!(feature = "zstdmt") {
+                params.threads = Some(threads);
+            }
+        }
+        Ok(params)
+    }
+
+    /// Returns the compression level, if set.
+    pub fn level(&self) -> Option<i32> {
+        self.level
+    }
+
+    /// Returns all the parameters set, starting with the compression level.
+    pub fn parameters(&self) -> Vec<CParameter> {
+        let strategy = self.strategy.and_then(strategy);
+        let parameters = [
+            self.level.map(CParameter::CompressionLevel),
+            self.window_log.map(CParameter::WindowLog),
+            self.chain_log.map(CParameter::ChainLog),
+            self.hash_log.map(CParameter::HashLog),
+            self.search_log.map(CParameter::SearchLog),
+            self.min_match.map(CParameter::MinMatch),
+            self.target_length.map(CParameter::TargetLength),
+            strategy.map(CParameter::Strategy),
+            self.long_distance_matching
+                .map(CParameter::EnableLongDistanceMatching),
+            self.checksum.map(CParameter::ChecksumFlag),
+            self.threads.map(CParameter::NbWorkers),
+        ];
+        parameters.iter().flatten().copied().collect()
+    }
+}
+
+// Maps the strategy numbers used by the zstd CLI, which are also the values
+// of `ZSTD_c_strategy`.
+pub(crate) fn strategy(value: u32) -> Option<Strategy> {
+    Some(match value {
+        1 => Strategy::ZSTD_fast,
+        2 => Strategy::ZSTD_dfast,
+        3 => Strategy::ZSTD_greedy,
+        4 => Strategy::ZSTD_lazy,
+        5 => Strategy::ZSTD_lazy2,
+        6 => Strategy::ZSTD_btlazy2,
+        7 => Strategy::ZSTD_btopt,
+        8 => Strategy::ZSTD_btultra,
+        9 => Strategy::ZSTD_btultra2,
+        _ => return None,
+    })
+}
+
+fn env_var(key: &str) -> io::Result<Option<String>> {
+    match env::var(key) {
+        Ok(value) => Ok(Some(value)),
+        Err(env::VarError::NotPresent) => Ok(None),
+        Err(env::VarError::NotUnicode(_)) => {
+            Err(invalid(format!("invalid value for {}", key)))
+        }
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+fn parse<T: FromStr>(key: &str, value: &str) -> io::Result<T> {
+    value.parse().map_err(|_| {
+        invalid(format!("invalid value for {}: {:?}", key, value))
+    })
+}
+
+fn parse_flag(key: &str, value: &str) -> io::Result<bool> {
+    match value {
+        "0" => Ok(false),
+        "1" => Ok(true),
+        _ => Err(invalid(format!("{} must be 0 or 1, not {:?}", key, value))),
+    }
+}
+
+impl FromStr for CompressionParameters {
+    type Err = io::Error;
+
+    /// Parses a list like `"level=19,wlog=27,ldm=1"`.
+    ///
+    /// When a key is repeated, the last value wins.
+    fn from_str(s: &str) -> io::Result<Self> {
+        let mut params = CompressionParameters::new();
+        for pair in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = match pair.find('=') {
+                Some(i) => (pair[..i].trim(), pair[i + 1..].trim()),
+                None => {
+                    return Err(invalid(format!("missing value for {}", pair)))
+                }
+            };
+            match key {
+                "level" => params.level = Some(parse(key, value)?),
+                "wlog" => params.window_log = Some(parse(key, value)?),
+                "clog" => params.chain_log = Some(parse(key, value)?),
+                "hlog" => params.hash_log = Some(parse(key, value)?),
+                "slog" => params.search_log = Some(parse(key, value)?),
+                "mml" => params.min_match = Some(parse(key, value)?),
+                "tlen" => params.target_length = Some(parse(key, value)?),
+                "strat" => {
+                    let value = parse(key, value)?;
+                    if strategy(value).is_none() {
+                        return Err(invalid(format!(
+                            "strat must be between 1 and 9, not {}",
+                            value
+                        )));
+                    }
+                    params.strategy = Some(value);
+                }
+                "ldm" => {
+                    params.long_distance_matching =
+                        Some(parse_flag(key, value)?)
+                }
+                "checksum" => params.checksum = Some(parse_flag(key, value)?),
+                "threads" => params.threads = Some(parse(key, value)?),
+                _ => {
+                    return Err(invalid(format!(
+                        "unknown compression parameter: {}",
+                        key
+                    )))
+                }
+            }
+        }
+        Ok(params)
+    }
+}
+
+impl fmt::Display for CompressionParameters {
+    /// Writes the parameters set, in the format read by `from_str`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flag = |value: Option<bool>| value.map(u32::from);
+        let level = self.level.map(|level| level.to_string());
+        let values = [
+            ("level", level),
+            ("wlog", self.window_log.map(|v| v.to_string())),
+            ("clog", self.chain_log.map(|v| v.to_string())),
+            ("hlog", self.hash_log.map(|v| v.to_string())),
+            ("slog", self.search_log.map(|v| v.to_string())),
+            ("mml", self.min_match.map(|v| v.to_string())),
+            ("tlen", self.target_length.map(|v| v.to_string())),
+            ("strat", self.strategy.map(|v| v.to_string())),
+            (
+                "ldm",
+                flag(self.long_distance_matching).map(|v| v.to_string()),
+            ),
+            ("checksum", flag(self.checksum).map(|v| v.to_string())),
+            ("threads", self.threads.map(|v| v.to_string())),
+        ];
+
+        let mut first = true;
+        for (key, value) in values.iter() {
+            if let Some(value) = value {
+                if !first {
+                    f.write_str(",")?;
+                }
+                write!(f, "{}={}", key, value)?;
+                first = false;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CompressionParameters;
+    use std::io::Write;
+    use zstd_safe::CParameter;
+
+    #[test]
+    fn test_parse() {
+        let params: CompressionParameters =
+            " level=19, wlog=27,ldm=1 ,checksum=1,strat=9"
+                .parse()
+                .unwrap();
+        assert_eq!(params.level(), Some(19));
+        assert_eq!(params.parameters().len(), 5);
+        assert!(matches!(
+            params.parameters()[0],
+            CParameter::CompressionLevel(19)
+        ));
+        assert_eq!(
+            params.to_string(),
+            "level=19,wlog=27,strat=9,ldm=1,checksum=1"
+        );
+        assert_eq!(
+            params.to_string().parse::<CompressionParameters>().unwrap(),
+            params
+        );
+
+        assert_eq!(
+            "".parse::<CompressionParameters>().unwrap().to_string(),
+            ""
+        );
+        assert_eq!(
+            "level=-5".parse::<CompressionParameters>().unwrap().level(),
+            Some(-5)
+        );
+
+        for invalid in &["level", "wlog=big", "ldm=2", "strat=10", "foo=1"] {
+            let error = invalid.parse::<CompressionParameters>().unwrap_err();
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn test_from_env() {
+        let params =
+            CompressionParameters::from_env_values(Some("19"), Some("2"))
+                .unwrap();
+        // Threads are ignored without multithreading support.
+        let expected = if cfg!(feature = "zstdmt") {
+            "level=19,threads=2"
+        } else {
+            "level=19"
+        };
+        assert_eq!(params.to_string(), expected);
+        let mut encoder =
+            crate::stream::write::Encoder::new(Vec::new(), 1).unwrap();
+        encoder.apply_parameters(&params).unwrap();
+
+        for (level, threads) in &[(Some("high"), None), (None, Some("-1"))] {
+            let result =
+                CompressionParameters::from_env_values(*level, *threads);
+            assert!(result.is_err());
+        }
+
+        let params = CompressionParameters::from_env_values(None, None);
+        assert_eq!(params.unwrap(), CompressionParameters::new());
+    }
+
+    #[test]
+    fn test_apply() {
+        let data = include_bytes!("../assets/example.txt");
+        let params: CompressionParameters =
+            "level=19,wlog=20,checksum=1".parse().unwrap();
+
+        let mut encoder =
+            crate::stream::write::Encoder::new(Vec::new(), 0).unwrap();
+        encoder.apply_parameters(&params).unwrap();
+        encoder.write_all(data).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert_eq!(crate::decode_all(&compressed[..]).unwrap(), &data[..]);
+        assert!(crate::frame::checksum(&compressed).unwrap().is_some());
+
+        let mut compressor = crate::block::Compressor::new();
+        compressor.apply_parameters(&params).unwrap();
+        let compressed = compressor.compress2(data).unwrap();
+        assert!(crate::frame::checksum(&compressed).unwrap().is_some());
+
+        // zstd rejects out of range values.
+        let params: CompressionParameters = "wlog=99".parse().unwrap();
+        let mut compressor = crate::block::Compressor::new();
+        assert!(compressor.apply_parameters(&params).is_err());
+    }
+}
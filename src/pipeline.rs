@@ -0,0 +1,238 @@
+//! Compress streams with separate threads for reading, compressing and
+//! writing.
+//!
+//! With slow storage (spinning disks, network filesystems), `copy_encode`
+//! spends a lot of time waiting for IO while not compressing, and the other
+//! way around. Running each step on its own thread, connected by bounded
+//! queues, overlaps them.
+//!
+//! ```no_run
+//! # fn main() -> std::io::Result<()> {
+//! use std::fs::File;
+//! use std::io::BufWriter;
+//!
+//! let source = File::open("data.bin")?;
+//! let destination = BufWriter::new(File::create("data.bin.zst")?);
+//! zstd::pipeline::compress(
+//!     source,
+//!     destination,
+//!     &zstd::pipeline::Options::new().level(9),
+//! )?;
+//! # Ok(())
+//! # }
+//! ```
+use std::io::{self, Read, Write};
+use std::panic;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread;
+
+use crate::stream::write::Encoder;
+
+/// Options for [`compress`](fn.compress.html).
+#[derive(Debug, Clone)]
+pub struct Options {
+    level: i32,
+    checksum: bool,
+    chunk_size: usize,
+    queue_length: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            level: 0,
+            checksum: false,
+            chunk_size: 1 << 20,
+            queue_length: 4,
+        }
+    }
+}
+
+impl Options {
+    /// Returns the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the compression level.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`).
+    pub fn level(mut self, level: i32) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Sets whether a content checksum is written at the end of the frame.
+    pub fn checksum(mut self, checksum: bool) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Sets the size of each read from the source.
+    ///
+    /// Defaults to 1MB.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        assert!(chunk_size != 0, "chunk size must be non-zero");
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Sets how many chunks can wait between two threads.
+    ///
+    /// This bounds memory usage when one step is slower than the others.
+    /// Defaults to `4`.
+    pub fn queue_length(mut self, queue_length: usize) -> Self {
+        self.queue_length = queue_length;
+        self
+    }
+}
+
+/// Compresses all data from `source` to `destination`, as a single frame.
+///
+/// Reading and writing each happen on their own thread, while the calling
+/// thread compresses. The output is the same as with `copy_encode`.
+///
+/// The first error from any of the steps is returned.
+pub fn compress<R, W>(
+    mut source: R,
+    mut destination: W,
+    options: &Options,
+) -> io::Result<()>
+where
+    R: Read + Send,
+    W: Write + Send,
+{
+    let (input, input_receiver) = mpsc::sync_channel(options.queue_length);
+    let (output, output_receiver) = mpsc::sync_channel(options.queue_length);
+    let chunk_size = options.chunk_size;
+
+    thread::scope(|scope| {
+        let reader =
+            scope.spawn(move || read_chunks(&mut source, chunk_size, input));
+        let writer = scope
+            .spawn(move || write_chunks(&mut destination, output_receiver));
+
+        // This drops both channel ends, so the other threads stop even if
+        // compression fails.
+        let compressed =
+            compress_chunks(input_receiver, ChunkWriter(output), options);
+
+        // Errors from the source or destination explain compression errors.
+        join(reader).and(join(writer)).and(compressed)
+    })
+}
+
+fn join<T>(handle: thread::ScopedJoinHandle<'_, T>) -> T {
+    handle.join().unwrap_or_else(|e| panic::resume_unwind(e))
+}
+
+fn read_chunks<R: Read>(
+    source: &mut R,
+    chunk_size: usize,
+    input: SyncSender<Vec<u8>>,
+) -> io::Result<()> {
+    loop {
+        let mut chunk = Vec::with_capacity(chunk_size);
+        source
+            .by_ref()
+            .take(chunk_size as u64)
+            .read_to_end(&mut chunk)?;
+        if chunk.is_empty() {
+            return Ok(());
+        }
+        if input.send(chunk).is_err() {
+            // Compression stopped, and will report why.
+            return Ok(());
+        }
+    }
+}
+
+fn compress_chunks(
+    input: Receiver<Vec<u8>>,
+    output: ChunkWriter,
+    options: &Options,
+) -> io::Result<()> {
+    let mut encoder = Encoder::new(output, options.level)?;
+    encoder.include_checksum(options.checksum)?;
+    for chunk in input {
+        encoder.write_all(&chunk)?;
+    }
+    encoder.finish()?;
+    Ok(())
+}
+
+fn write_chunks<W: Write>(
+    destination: &mut W,
+    output: Receiver<Vec<u8>>,
+) -> io::Result<()> {
+    for chunk in output {
+        destination.write_all(&chunk)?;
+    }
+    destination.flush()
+}
+
+// Sends each write as a chunk to the writing thread.
+struct ChunkWriter(SyncSender<Vec<u8>>);
+
+impl Write for ChunkWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.send(buf.to_vec()).map_err(|_| {
+            io::Error::new(io::ErrorKind::BrokenPipe, "writing thread stopped")
+        })?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compress, Options};
+    use std::io::{self, Write};
+
+    #[test]
+    fn test_compress() {
+        let data = b"pipelined compression ".repeat(100_000);
+
+        let mut compressed = Vec::new();
+        let options = Options::new().level(1).chunk_size(64 << 10);
+        compress(&data[..], &mut compressed, &options).unwrap();
+        assert_eq!(crate::decode_all(&compressed[..]).unwrap(), data);
+
+        // Same output as the synchronous loop.
+        let mut expected = Vec::new();
+        crate::stream::copy_encode(&data[..], &mut expected, 1).unwrap();
+        assert_eq!(compressed, expected);
+    }
+
+    #[test]
+    fn test_errors() {
+        struct Failing;
+
+        impl io::Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::Other, "read failed"))
+            }
+        }
+
+        impl Write for Failing {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::Other, "write failed"))
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let data = b"pipelined compression ".repeat(100_000);
+        let error = compress(&data[..], Failing, &Options::new()).unwrap_err();
+        assert_eq!(error.to_string(), "write failed");
+
+        let error =
+            compress(Failing, io::sink(), &Options::new()).unwrap_err();
+        assert_eq!(error.to_string(), "read failed");
+    }
+}
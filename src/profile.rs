@@ -0,0 +1,108 @@
+use zstd_safe::CParameter;
+
+/// Predefined compression settings for common workloads.
+///
+/// A profile picks the compression level, window size, long distance
+/// matching and checksum for a workload. It can be applied to block
+/// compressors and stream encoders with their `apply_profile` method, and
+/// further adjusted with individual parameters afterwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Profile {
+    /// Many small independent messages, where latency matters most.
+    ///
+    /// Fast compression with a small window, and no checksum: the
+    /// transport usually has its own.
+    RealtimeSmallMessages,
+
+    /// Long-lived streams over a network connection.
+    ///
+    /// Balanced speed and ratio, with a moderate window to bound the
+    /// memory used by the receiving end, and a checksum.
+    NetworkStream,
+
+    /// Data compressed once and kept for a long time.
+    ///
+    /// Slow compression for the best ratio, with a large window and long
+    /// distance matching to find repetitions far apart, and a checksum.
+    /// Decompression speed is not affected.
+    Archival,
+}
+
+impl Profile {
+    /// Returns the compression level used by this profile.
+    pub fn level(self) -> i32 {
+        match self {
+            Profile::RealtimeSmallMessages => 1,
+            Profile::NetworkStream => 3,
+            Profile::Archival => 19,
+        }
+    }
+
+    /// Returns the size recommended for I/O buffers around the encoder.
+    ///
+    /// This can be used for a `BufReader` or `BufWriter`, or as the size of
+    /// the chunks given to the encoder.
+    pub fn buffer_size(self) -> usize {
+        match self {
+            Profile::RealtimeSmallMessages => 16 * 1024,
+            Profile::NetworkStream => 64 * 1024,
+            Profile::Archival => 1024 * 1024,
+        }
+    }
+
+    /// Returns all the parameters set by this profile, starting with the
+    /// compression level.
+    pub fn parameters(self) -> Vec<CParameter> {
+        let (window_log, long_distance, checksum) = match self {
+            Profile::RealtimeSmallMessages => (17, false, false),
+            Profile::NetworkStream => (20, false, true),
+            // Decoders accept windows up to 2^27 by default.
+            Profile::Archival => (27, true, true),
+        };
+        vec![
+            CParameter::CompressionLevel(self.level()),
+            CParameter::WindowLog(window_log),
+            CParameter::EnableLongDistanceMatching(long_distance),
+            CParameter::ChecksumFlag(checksum),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Profile;
+    use std::io::Write;
+
+    const PROFILES: [Profile; 3] = [
+        Profile::RealtimeSmallMessages,
+        Profile::NetworkStream,
+        Profile::Archival,
+    ];
+
+    #[test]
+    fn test_profiles() {
+        let data = include_bytes!("../assets/example.txt");
+
+        let mut sizes = Vec::new();
+        for &profile in &PROFILES {
+            let mut encoder =
+                crate::stream::write::Encoder::new(Vec::new(), 0).unwrap();
+            encoder.apply_profile(profile).unwrap();
+            encoder.write_all(data).unwrap();
+            let compressed = encoder.finish().unwrap();
+            assert_eq!(crate::decode_all(&compressed[..]).unwrap(), &data[..]);
+            sizes.push(compressed.len());
+
+            let mut compressor = crate::block::Compressor::new();
+            compressor.apply_profile(profile).unwrap();
+            let compressed = compressor.compress2(data).unwrap();
+            let checksum = crate::frame::checksum(&compressed).unwrap();
+            assert_eq!(
+                checksum.is_some(),
+                profile != Profile::RealtimeSmallMessages
+            );
+        }
+        assert!(sizes[2] < sizes[0]);
+    }
+}
@@ -0,0 +1,68 @@
+//! Decompress single blocks of data.
+use std::io::{self, Read};
+
+use crate::pure::stream::read::Decoder;
+
+/// Decompresses a block of data, and writes the result in a
+/// pre-allocated buffer.
+///
+/// Returns the number of bytes written, or an error if something happened
+/// (for instance if the destination buffer was too small).
+pub fn decompress_to_buffer(
+    source: &[u8],
+    destination: &mut [u8],
+) -> io::Result<usize> {
+    let mut decoder = Decoder::with_buffer(source)?;
+    let mut written = 0;
+    while written < destination.len() {
+        match decoder.read(&mut destination[written..])? {
+            0 => return Ok(written),
+            len => written += len,
+        }
+    }
+    if decoder.read(&mut [0u8])? != 0 {
+        return Err(too_small());
+    }
+    Ok(written)
+}
+
+/// Decompresses a block of data and returns the decompressed result.
+///
+/// The decompressed data should be less than `capacity` bytes,
+/// or an error will be returned.
+pub fn decompress(data: &[u8], capacity: usize) -> io::Result<Vec<u8>> {
+    let mut output = Vec::new();
+    let limit = (capacity as u64).saturating_add(1);
+    Decoder::with_buffer(data)?
+        .take(limit)
+        .read_to_end(&mut output)?;
+    if output.len() > capacity {
+        return Err(too_small());
+    }
+    Ok(output)
+}
+
+fn too_small() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "Destination buffer is too small")
+}
+
+#[cfg(test)]
+mod tests {
+    use ruzstd::encoding::{compress_to_vec, CompressionLevel};
+
+    #[test]
+    fn test_decompress() {
+        let data = b"block data ".repeat(100);
+        let compressed = compress_to_vec(&data[..], CompressionLevel::Fastest);
+
+        assert_eq!(super::decompress(&compressed, data.len()).unwrap(), data);
+        assert!(super::decompress(&compressed, data.len() - 1).is_err());
+
+        let mut buffer = vec![0u8; data.len()];
+        let len = super::decompress_to_buffer(&compressed, &mut buffer);
+        assert_eq!(len.unwrap(), data.len());
+        assert_eq!(buffer, data);
+        let mut buffer = vec![0u8; data.len() - 1];
+        assert!(super::decompress_to_buffer(&compressed, &mut buffer).is_err());
+    }
+}
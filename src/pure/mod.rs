@@ -0,0 +1,13 @@
+//! Pure-Rust decompression, based on `ruzstd`.
+//!
+//! The `block` and `stream` modules mirror the decompression functions of
+//! `zstd::block` and `zstd::stream`, with the same signatures.
+use std::io;
+
+pub mod block;
+pub mod stream;
+
+// Decoding errors are reported like the C library's.
+fn map_error(error: ruzstd::decoding::errors::FrameDecoderError) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, error)
+}
@@ -0,0 +1,28 @@
+//! Decompress zstd streams.
+use std::io;
+
+pub mod read;
+
+pub use self::read::Decoder;
+
+/// Decompress from the given source as if using a `Decoder`.
+///
+/// The input data must be in the zstd frame format.
+pub fn decode_all<R: io::Read>(source: R) -> io::Result<Vec<u8>> {
+    let mut result = Vec::new();
+    copy_decode(source, &mut result)?;
+    Ok(result)
+}
+
+/// Decompress from the given source as if using a `Decoder`.
+///
+/// Decompressed data will be appended to `destination`.
+pub fn copy_decode<R, W>(source: R, mut destination: W) -> io::Result<()>
+where
+    R: io::Read,
+    W: io::Write,
+{
+    let mut decoder = Decoder::new(source)?;
+    io::copy(&mut decoder, &mut destination)?;
+    Ok(())
+}
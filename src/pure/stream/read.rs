@@ -0,0 +1,261 @@
+//! Implement pull-based [`Read`] trait for decompressing.
+//!
+//! [`Read`]: https://doc.rust-lang.org/std/io/trait.Read.html
+use std::io::{self, BufRead, BufReader, Read};
+use std::marker::PhantomData;
+
+use ruzstd::decoding::errors::{FrameDecoderError, ReadFrameHeaderError};
+use ruzstd::decoding::{BlockDecodingStrategy, FrameDecoder};
+
+use crate::pure::map_error;
+
+/// A decoder that decompress input data from another `Read`.
+///
+/// This allows to read a stream of compressed data
+/// (good for files or heavy network stream).
+///
+/// Frames are decoded one after the other until the end of the input, and
+/// skippable frames are ignored. Frames with a checksum are checked against
+/// it.
+pub struct Decoder<'a, R: BufRead> {
+    reader: R,
+    decoder: FrameDecoder,
+    // Whether a frame was started and not entirely read yet.
+    in_frame: bool,
+    // Number of frames read so far, including skippable frames.
+    frames: u64,
+    single_frame: bool,
+    // Dictionaries are not supported, but keep the same type as the C
+    // decoder.
+    _dictionary: PhantomData<&'a [u8]>,
+}
+
+impl<R: Read> Decoder<'static, BufReader<R>> {
+    /// Creates a new decoder.
+    ///
+    /// The input is read through a `BufReader`.
+    pub fn new(reader: R) -> io::Result<Self> {
+        Self::with_buffer(BufReader::new(reader))
+    }
+}
+
+impl<R: BufRead> Decoder<'static, R> {
+    /// Creates a new decoder around a `BufRead`.
+    pub fn with_buffer(reader: R) -> io::Result<Self> {
+        Ok(Decoder {
+            reader,
+            decoder: FrameDecoder::new(),
+            in_frame: false,
+            frames: 0,
+            single_frame: false,
+            _dictionary: PhantomData,
+        })
+    }
+}
+
+impl<'a, R: BufRead> Decoder<'a, R> {
+    /// Sets this `Decoder` to stop after the first frame.
+    ///
+    /// By default, it keeps concatenating frames until EOF is reached.
+    /// Skippable frames count as frames.
+    pub fn single_frame(mut self) -> Self {
+        self.single_frame = true;
+        self
+    }
+
+    /// Acquire a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Acquire a mutable reference to the underlying reader.
+    ///
+    /// Note that mutation of the reader may result in surprising results if
+    /// this decoder is continued to be used.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Return the inner `Read`.
+    ///
+    /// Only the input actually decompressed is consumed: after a
+    /// [`single_frame`] decoder, the returned reader is positioned right
+    /// after the frame.
+    ///
+    /// [`single_frame`]: #method.single_frame
+    pub fn finish(self) -> R {
+        self.reader
+    }
+
+    // Reads the header of the next frame, skipping skippable frames.
+    //
+    // Returns `false` once there is no frame left to read.
+    fn start_frame(&mut self) -> io::Result<bool> {
+        loop {
+            if self.single_frame && self.frames > 0
+                || self.reader.fill_buf()?.is_empty()
+            {
+                return Ok(false);
+            }
+
+            match self.decoder.reset(&mut self.reader) {
+                Ok(()) => {
+                    self.in_frame = true;
+                    return Ok(true);
+                }
+                Err(FrameDecoderError::ReadFrameHeaderError(
+                    ReadFrameHeaderError::SkipFrame { length, .. },
+                )) => {
+                    let length = u64::from(length);
+                    let skipped = io::copy(
+                        &mut Read::take(&mut self.reader, length),
+                        &mut io::sink(),
+                    )?;
+                    if skipped != length {
+                        return Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "incomplete frame",
+                        ));
+                    }
+                    self.frames += 1;
+                }
+                Err(error) => return Err(map_error(error)),
+            }
+        }
+    }
+
+    fn end_frame(&mut self) -> io::Result<()> {
+        self.in_frame = false;
+        self.frames += 1;
+        match (
+            self.decoder.get_checksum_from_data(),
+            self.decoder.get_calculated_checksum(),
+        ) {
+            (Some(stored), Some(computed)) if stored != computed => {
+                Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "Restored data doesn't match checksum",
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<R: BufRead> Read for Decoder<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            if !self.in_frame && !self.start_frame()? {
+                return Ok(0);
+            }
+
+            // Blocks are decoded until enough data can be returned: the
+            // decoder keeps the last window of data for back-references.
+            while self.decoder.can_collect() < buf.len()
+                && !self.decoder.is_finished()
+            {
+                let missing = buf.len() - self.decoder.can_collect();
+                self.decoder
+                    .decode_blocks(
+                        &mut self.reader,
+                        BlockDecodingStrategy::UptoBytes(missing),
+                    )
+                    .map_err(map_error)?;
+            }
+
+            let len = self.decoder.read(buf)?;
+            if len > 0 {
+                return Ok(len);
+            }
+            self.end_frame()?;
+        }
+    }
+}
+
+fn _assert_traits() {
+    fn _assert_send<T: Send>(_: T) {}
+
+    _assert_send(Decoder::new(&[][..]));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Decoder;
+    use std::io::Read;
+
+    use ruzstd::encoding::{compress_to_vec, CompressionLevel};
+
+    fn compress(data: &[u8]) -> Vec<u8> {
+        compress_to_vec(data, CompressionLevel::Fastest)
+    }
+
+    #[test]
+    fn test_frames() {
+        let first = b"first frame ".repeat(10_000);
+        let second = b"second frame ".repeat(10_000);
+        let mut input = compress(&first);
+        // A skippable frame, with a 3-bytes payload.
+        input.extend_from_slice(&[0x50, 0x2A, 0x4D, 0x18, 3, 0, 0, 0, 1, 2]);
+        input.push(3);
+        input.extend(compress(&second));
+
+        let output = crate::pure::stream::decode_all(&input[..]).unwrap();
+        assert_eq!(output, [&first[..], &second[..]].concat());
+
+        // Small reads work too.
+        let mut decoder = Decoder::new(&input[..]).unwrap();
+        let mut output = Vec::new();
+        let mut buffer = [0u8; 7];
+        loop {
+            match decoder.read(&mut buffer).unwrap() {
+                0 => break,
+                len => output.extend_from_slice(&buffer[..len]),
+            }
+        }
+        assert_eq!(output, [&first[..], &second[..]].concat());
+
+        let mut decoder = Decoder::with_buffer(&input[..]).unwrap();
+        decoder = decoder.single_frame();
+        let mut output = Vec::new();
+        decoder.read_to_end(&mut output).unwrap();
+        assert_eq!(output, first);
+        assert_eq!(decoder.finish()[0], 0x50);
+
+        assert_eq!(crate::pure::stream::decode_all(&[][..]).unwrap(), b"");
+    }
+
+    #[test]
+    fn test_c_frames() {
+        // Compressed with the zstd CLI.
+        let input = include_bytes!("../../../assets/example.txt.zst");
+        let expected = include_bytes!("../../../assets/example.txt");
+        assert_eq!(
+            &crate::pure::stream::decode_all(&input[..]).unwrap()[..],
+            &expected[..]
+        );
+
+        // Compressed with the C library, with a checksum.
+        let data = crate::test_util::text(100_000, 1);
+        let mut encoder = crate::stream::Encoder::new(Vec::new(), 19).unwrap();
+        encoder.include_checksum(true).unwrap();
+        std::io::Write::write_all(&mut encoder, &data).unwrap();
+        let input = encoder.finish().unwrap();
+        assert_eq!(crate::pure::stream::decode_all(&input[..]).unwrap(), data);
+    }
+
+    #[test]
+    fn test_errors() {
+        let input = compress(&b"some data".repeat(1000));
+        assert!(crate::pure::stream::decode_all(&input[..input.len() - 1])
+            .is_err());
+        assert!(
+            crate::pure::stream::decode_all(&b"not zstd data"[..]).is_err()
+        );
+
+        assert!(crate::pure::stream::decode_all(&[0u8; 100][..]).is_err());
+    }
+}
@@ -0,0 +1,306 @@
+//! Read parts of a compressed buffer without decompressing everything.
+//!
+//! A zstd frame can only be decompressed from its start. A buffer made of
+//! many frames, however, can be read at any position by only decompressing
+//! the frames covering it. This is the case for data compressed in chunks,
+//! or in the [seekable format] (its seek table is a skippable frame, and is
+//! simply ignored here).
+//!
+//! [seekable format]: https://github.com/facebook/zstd/tree/dev/contrib/seekable_format
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::io::{self, Read};
+
+use crate::block::Decompressor;
+use crate::map_error_code;
+
+/// A read-only view over the decompressed content of a multi-frame buffer.
+///
+/// Creating it scans the frame headers, to know where each frame starts,
+/// both in the compressed and in the decompressed content. Frames without a
+/// declared content size are decompressed once during this scan, without
+/// keeping their content.
+///
+/// Each frame is decompressed in memory, so frames larger than a limit
+/// (256MB by default, see [`with_max_frame_size`]) are rejected.
+///
+/// Each read then only decompresses the frames it needs. The most recently
+/// used frames are kept decompressed, see [`cache_frames`].
+///
+/// [`cache_frames`]: #method.cache_frames
+/// [`with_max_frame_size`]: #method.with_max_frame_size
+///
+/// # Examples
+///
+/// ```rust
+/// # fn main() -> std::io::Result<()> {
+/// use zstd::random_access::RandomAccess;
+///
+/// let mut data = Vec::new();
+/// for chunk in b"Hello world!".chunks(4) {
+///     data.extend(zstd::encode_all(chunk, 3)?);
+/// }
+///
+/// let mut view = RandomAccess::new(data)?;
+/// assert_eq!(view.read_at(6, 5)?, b"world");
+/// # Ok(())
+/// # }
+/// ```
+pub struct RandomAccess<B> {
+    data: B,
+    frames: Vec<FrameEntry>,
+    decompressor: Decompressor,
+
+    // Most recently used frames are at the back.
+    cache: VecDeque<(usize, Vec<u8>)>,
+    cache_capacity: usize,
+}
+
+struct FrameEntry {
+    // Range in the compressed data.
+    start: usize,
+    end: usize,
+    // Position in the decompressed content.
+    offset: u64,
+    len: usize,
+}
+
+// Default limit on the decompressed size of a frame.
+const DEFAULT_MAX_FRAME_SIZE: usize = 256 * 1024 * 1024;
+
+impl<B: AsRef<[u8]>> RandomAccess<B> {
+    /// Indexes the frames in `data`.
+    ///
+    /// Skippable frames are ignored.
+    pub fn new(data: B) -> io::Result<Self> {
+        Self::with_max_frame_size(data, DEFAULT_MAX_FRAME_SIZE)
+    }
+
+    /// Indexes the frames in `data`, accepting frames of up to
+    /// `max_frame_size` decompressed bytes.
+    ///
+    /// A larger frame is an `InvalidData` error.
+    pub fn with_max_frame_size(
+        data: B,
+        max_frame_size: usize,
+    ) -> io::Result<Self> {
+        let too_large = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "frame too large to be decompressed in memory",
+            )
+        };
+
+        let mut frames = Vec::new();
+        let mut offset = 0u64;
+        let mut start = 0;
+
+        let bytes = data.as_ref();
+        while start < bytes.len() {
+            let frame = &bytes[start..];
+            let size = zstd_safe::find_frame_compressed_size(frame)
+                .map_err(map_error_code)?;
+            let frame = &frame[..size];
+
+            if !crate::frame::is_skippable(frame) {
+                let len = match zstd_safe::get_frame_content_size(frame) {
+                    zstd_safe::CONTENTSIZE_ERROR => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "invalid frame header",
+                        ))
+                    }
+                    zstd_safe::CONTENTSIZE_UNKNOWN => {
+                        // Only count the content, without storing it.
+                        let mut decoder =
+                            crate::stream::read::Decoder::with_buffer(frame)?
+                                .take(
+                                    (max_frame_size as u64).saturating_add(1),
+                                );
+                        io::copy(&mut decoder, &mut io::sink())? as usize
+                    }
+                    len => usize::try_from(len).map_err(|_| too_large())?,
+                };
+                if len > max_frame_size {
+                    return Err(too_large());
+                }
+                frames.push(FrameEntry {
+                    start,
+                    end: start + size,
+                    offset,
+                    len,
+                });
+                offset += len as u64;
+            }
+            start += size;
+        }
+
+        Ok(RandomAccess {
+            data,
+            frames,
+            decompressor: Decompressor::new(),
+            cache: VecDeque::new(),
+            cache_capacity: 1,
+        })
+    }
+
+    /// Keeps the `frames` most recently used frames decompressed in memory.
+    ///
+    /// This speeds up reads close to each other. Defaults to (and is at
+    /// least) `1`.
+    pub fn cache_frames(mut self, frames: usize) -> Self {
+        self.cache_capacity = frames.max(1);
+        while self.cache.len() > self.cache_capacity {
+            self.cache.pop_front();
+        }
+        self
+    }
+
+    /// Returns the size of the decompressed content.
+    pub fn len(&self) -> u64 {
+        self.frames
+            .last()
+            .map_or(0, |frame| frame.offset + frame.len as u64)
+    }
+
+    /// Returns `true` if the decompressed content is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of frames, skippable frames excluded.
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Reads `len` bytes of decompressed content, starting at `offset`.
+    ///
+    /// Returns fewer bytes if the end of the content is reached.
+    pub fn read_at(&mut self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let mut output = Vec::with_capacity(usize::min(
+            len,
+            self.len().saturating_sub(offset) as usize,
+        ));
+
+        let mut index = self.frames.partition_point(|frame| {
+            frame.offset + frame.len as u64 <= offset
+        });
+        let mut offset = offset;
+        while output.len() < len && index < self.frames.len() {
+            let skip = (offset - self.frames[index].offset) as usize;
+            let wanted = len - output.len();
+            let content = self.frame_content(index)?;
+            let available = &content[skip..];
+            let n = usize::min(wanted, available.len());
+            output.extend_from_slice(&available[..n]);
+
+            offset += n as u64;
+            index += 1;
+        }
+        Ok(output)
+    }
+
+    /// Returns the underlying data.
+    pub fn into_inner(self) -> B {
+        self.data
+    }
+
+    // Returns the decompressed content of a frame, from the cache if possible.
+    fn frame_content(&mut self, index: usize) -> io::Result<&[u8]> {
+        if let Some(position) =
+            self.cache.iter().position(|&(cached, _)| cached == index)
+        {
+            let entry = self.cache.remove(position).unwrap();
+            self.cache.push_back(entry);
+        } else {
+            let frame = &self.frames[index];
+            let content = self.decompressor.decompress(
+                &self.data.as_ref()[frame.start..frame.end],
+                frame.len,
+            )?;
+            if content.len() != frame.len {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "frame content size mismatch",
+                ));
+            }
+            if self.cache.len() >= self.cache_capacity {
+                self.cache.pop_front();
+            }
+            self.cache.push_back((index, content));
+        }
+        Ok(&self.cache.back().unwrap().1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RandomAccess;
+
+    #[test]
+    fn test_random_access() {
+        let content: Vec<u8> =
+            (0..100_000u32).map(|i| (i % 251) as u8).collect();
+
+        let mut data = Vec::new();
+        for (i, chunk) in content.chunks(7_000).enumerate() {
+            if i % 2 == 0 {
+                data.extend(crate::block::compress(chunk, 1).unwrap());
+            } else {
+                // Streamed frames don't declare their content size.
+                data.extend(crate::encode_all(chunk, 1).unwrap());
+            }
+        }
+        // A skippable frame, as in the seekable format.
+        data.extend_from_slice(&[0x5E, 0x2A, 0x4D, 0x18, 2, 0, 0, 0, 1, 2]);
+
+        for cache in &[1, 4] {
+            let mut view =
+                RandomAccess::new(&data[..]).unwrap().cache_frames(*cache);
+            assert_eq!(view.len(), content.len() as u64);
+            assert_eq!(view.frame_count(), 15);
+
+            for &(offset, len) in &[
+                (0, 10),
+                (6_990, 20),
+                (13_000, 30_000),
+                (99_990, 100),
+                (200_000, 10),
+            ] {
+                let start = usize::min(offset, content.len());
+                let end = usize::min(offset + len, content.len());
+                assert_eq!(
+                    view.read_at(offset as u64, len).unwrap(),
+                    &content[start..end]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_max_frame_size() {
+        let content = vec![0u8; 100_000];
+        let declared = crate::block::compress(&content, 1).unwrap();
+        let streamed = crate::encode_all(&content[..], 1).unwrap();
+
+        for data in &[declared, streamed] {
+            let err = RandomAccess::with_max_frame_size(&data[..], 99_999)
+                .err()
+                .unwrap();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+            let mut view =
+                RandomAccess::with_max_frame_size(&data[..], 100_000).unwrap();
+            assert_eq!(view.read_at(99_000, 10).unwrap(), &content[..10]);
+
+            // `usize::MAX` means no limit.
+            RandomAccess::with_max_frame_size(&data[..], usize::MAX).unwrap();
+        }
+
+        // A header can declare a huge size for a tiny frame.
+        let mut header = vec![0x28, 0xB5, 0x2F, 0xFD, 0xE0];
+        header.extend_from_slice(&u64::MAX.to_le_bytes());
+        header.extend_from_slice(&[1, 0, 0]);
+        assert!(RandomAccess::new(&header[..]).is_err());
+    }
+}
@@ -0,0 +1,323 @@
+//! Store independent records in a single stream.
+//!
+//! Each record is compressed on its own, and written as a little-endian
+//! `u32` length followed by a zstd frame. This makes it easy to append
+//! records to a log file, and to read them back one at a time.
+//!
+//! All the records of a writer share a compression context, and optionally
+//! a dictionary, which helps a lot with small records.
+
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+
+use crate::block::{MessageCompressor, MessageDecompressor};
+use crate::dict::{DecoderDictionary, EncoderDictionary};
+
+/// Writes length-prefixed compressed records.
+pub struct RecordWriter<W> {
+    writer: W,
+    compressor: MessageCompressor,
+    max_size: usize,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> RecordWriter<W> {
+    /// Creates a new record writer.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`).
+    pub fn new(writer: W, level: i32) -> io::Result<Self> {
+        Ok(Self::with_compressor(
+            writer,
+            MessageCompressor::new(level)?,
+        ))
+    }
+
+    /// Creates a new record writer, using a prepared dictionary.
+    pub fn with_dictionary(
+        writer: W,
+        dictionary: Arc<EncoderDictionary<'static>>,
+    ) -> Self {
+        Self::with_compressor(
+            writer,
+            MessageCompressor::with_dictionary(dictionary),
+        )
+    }
+
+    fn with_compressor(writer: W, compressor: MessageCompressor) -> Self {
+        RecordWriter {
+            writer,
+            compressor,
+            max_size: MessageDecompressor::DEFAULT_MAX_SIZE,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Sets the maximum size of a record.
+    ///
+    /// Larger records are rejected, so that a `RecordReader` with the same
+    /// limit can read everything this writer wrote. Defaults to 16MB.
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Compresses and writes a record.
+    ///
+    /// Records larger than the maximum size give an `InvalidInput` error.
+    pub fn write_record(&mut self, record: &[u8]) -> io::Result<()> {
+        if record.len() > self.max_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "record is larger than the maximum size",
+            ));
+        }
+        self.compressor.compress_to_vec(record, &mut self.buffer)?;
+        if self.buffer.len() > u32::MAX as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "record too large",
+            ));
+        }
+        self.writer
+            .write_all(&(self.buffer.len() as u32).to_le_bytes())?;
+        self.writer.write_all(&self.buffer)
+    }
+
+    /// Flushes the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Acquires a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Acquires a mutable reference to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Returns the underlying writer.
+    ///
+    /// Records are written as a whole, so there is nothing to finish.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Reads records written by a `RecordWriter`.
+///
+/// This is also an iterator over the records.
+pub struct RecordReader<R> {
+    reader: R,
+    decompressor: MessageDecompressor,
+    max_size: usize,
+    buffer: Vec<u8>,
+}
+
+impl<R: Read> RecordReader<R> {
+    /// Creates a new record reader.
+    pub fn new(reader: R) -> Self {
+        Self::with_decompressor(reader, MessageDecompressor::new())
+    }
+
+    /// Creates a new record reader, using a prepared dictionary.
+    pub fn with_dictionary(
+        reader: R,
+        dictionary: Arc<DecoderDictionary<'static>>,
+    ) -> Self {
+        Self::with_decompressor(
+            reader,
+            MessageDecompressor::with_dictionary(dictionary),
+        )
+    }
+
+    fn with_decompressor(
+        reader: R,
+        decompressor: MessageDecompressor,
+    ) -> Self {
+        RecordReader {
+            reader,
+            decompressor,
+            max_size: MessageDecompressor::DEFAULT_MAX_SIZE,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Sets the maximum size of a record.
+    ///
+    /// Larger records are rejected before any allocation. Defaults to 16MB.
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.decompressor = self.decompressor.max_size(max_size);
+        self.max_size = max_size;
+        self
+    }
+
+    /// Reads the next record.
+    ///
+    /// Returns `None` at the end of the stream. A stream ending in the
+    /// middle of a record (for example after a crash while appending to a
+    /// log) gives an `UnexpectedEof` error.
+    pub fn read_record(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut record = Vec::new();
+        if self.read_record_into(&mut record)? {
+            Ok(Some(record))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Reads the next record into `output`, replacing its content.
+    ///
+    /// Returns `false` at the end of the stream.
+    pub fn read_record_into(
+        &mut self,
+        output: &mut Vec<u8>,
+    ) -> io::Result<bool> {
+        let mut prefix = [0u8; 4];
+        let mut filled = 0;
+        while filled < prefix.len() {
+            match self.reader.read(&mut prefix[filled..]) {
+                Ok(0) if filled == 0 => {
+                    output.clear();
+                    return Ok(false);
+                }
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "incomplete record length",
+                    ))
+                }
+                Ok(n) => filled += n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        let len = u32::from_le_bytes(prefix) as usize;
+        // A corrupted length should not cause a huge allocation.
+        if len > max_compressed_size(self.max_size) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "record is larger than the maximum size",
+            ));
+        }
+        self.buffer.resize(len, 0);
+        self.reader.read_exact(&mut self.buffer)?;
+        self.decompressor.decompress_to_vec(&self.buffer, output)?;
+        Ok(true)
+    }
+
+    /// Acquires a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Acquires a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+// `compress_bound` overflows for sizes close to `usize::MAX`, but lengths
+// are stored as `u32` anyway.
+fn max_compressed_size(max_size: usize) -> usize {
+    if max_size > u32::MAX as usize {
+        usize::MAX
+    } else {
+        zstd_safe::compress_bound(max_size)
+    }
+}
+
+impl<R: Read> Iterator for RecordReader<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RecordReader, RecordWriter};
+    use crate::dict::{DecoderDictionary, EncoderDictionary};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_records() {
+        let records: Vec<String> = (0..50)
+            .map(|i| format!("event {} happened at {}", i, i * 1000))
+            .collect();
+
+        let mut writer = RecordWriter::new(Vec::new(), 3).unwrap();
+        for record in &records[..25] {
+            writer.write_record(record.as_bytes()).unwrap();
+        }
+        let log = writer.into_inner();
+
+        // Appending to an existing log.
+        let mut writer = RecordWriter::new(log, 3).unwrap();
+        for record in &records[25..] {
+            writer.write_record(record.as_bytes()).unwrap();
+        }
+        let log = writer.into_inner();
+
+        let read: Vec<Vec<u8>> =
+            RecordReader::new(&log[..]).map(Result::unwrap).collect();
+        assert_eq!(read.len(), records.len());
+        for (read, record) in read.iter().zip(&records) {
+            assert_eq!(read, record.as_bytes());
+        }
+
+        // A truncated last record is an error.
+        let mut reader = RecordReader::new(&log[..log.len() - 1]);
+        for _ in 0..records.len() - 1 {
+            reader.read_record().unwrap().unwrap();
+        }
+        let error = reader.read_record().unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+
+        // Records over the maximum size are rejected.
+        let mut reader = RecordReader::new(&log[..]).max_size(10);
+        assert!(reader.read_record().is_err());
+        let mut writer =
+            RecordWriter::new(Vec::new(), 3).unwrap().max_size(10);
+        let error = writer.write_record(records[0].as_bytes()).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        writer.write_record(b"short").unwrap();
+
+        // A huge maximum size doesn't overflow.
+        let mut reader = RecordReader::new(&log[..]).max_size(usize::MAX);
+        assert_eq!(
+            reader.read_record().unwrap().unwrap(),
+            records[0].as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_records_with_dictionary() {
+        let dict = b"event happened at ".repeat(10);
+        let encoder_dict = Arc::new(EncoderDictionary::copy(&dict, 3));
+        let decoder_dict = Arc::new(DecoderDictionary::copy(&dict));
+
+        let mut writer =
+            RecordWriter::with_dictionary(Vec::new(), encoder_dict);
+        writer.write_record(b"event 1 happened at 1").unwrap();
+        writer.write_record(b"").unwrap();
+        let log = writer.into_inner();
+
+        let mut reader = RecordReader::with_dictionary(&log[..], decoder_dict);
+        assert_eq!(
+            reader.read_record().unwrap().unwrap(),
+            b"event 1 happened at 1"
+        );
+        assert_eq!(reader.read_record().unwrap().unwrap(), b"");
+        assert!(reader.read_record().unwrap().is_none());
+    }
+}
@@ -0,0 +1,226 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Details about a decompression error.
+///
+/// Stream decoders report errors as `io::Error`, wrapping this type: use
+/// [`DecodeError::from_io_error`] to access it.
+///
+/// The position of the error is not known when using a `raw::Decoder`
+/// directly.
+///
+/// [`DecodeError::from_io_error`]: #method.from_io_error
+#[derive(Debug, Clone)]
+pub struct DecodeError {
+    message: &'static str,
+    stage: DecodeStage,
+    position: Option<Position>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Position {
+    frame_index: u64,
+    frame_offset: u64,
+    input_offset: u64,
+}
+
+/// Step of the decompression which failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DecodeStage {
+    /// Parsing a frame header (including unknown or unsupported frames).
+    Header,
+
+    /// Decoding a compressed block.
+    Block,
+
+    /// Verifying the content checksum at the end of a frame.
+    Checksum,
+
+    /// Anything else.
+    Other,
+}
+
+impl DecodeError {
+    pub(crate) fn from_code(code: usize) -> Self {
+        use zstd_safe::error_code as e;
+
+        let message = zstd_safe::get_error_name(code);
+        let stage = match zstd_safe::get_error_code(code) {
+            e::ZSTD_error_prefix_unknown
+            | e::ZSTD_error_version_unsupported
+            | e::ZSTD_error_frameParameter_unsupported
+            | e::ZSTD_error_frameParameter_windowTooLarge
+            | e::ZSTD_error_dictionary_wrong => DecodeStage::Header,
+            e::ZSTD_error_corruption_detected
+            | e::ZSTD_error_srcSize_wrong => DecodeStage::Block,
+            e::ZSTD_error_checksum_wrong => DecodeStage::Checksum,
+            _ => DecodeStage::Other,
+        };
+        DecodeError {
+            message,
+            stage,
+            position: None,
+        }
+    }
+
+    /// Returns the decompression error wrapped in `error`, if any.
+    pub fn from_io_error(error: &io::Error) -> Option<&Self> {
+        error.get_ref().and_then(|error| error.downcast_ref())
+    }
+
+    /// Returns zstd's description of the error.
+    pub fn message(&self) -> &str {
+        self.message
+    }
+
+    /// Returns the step of the decompression which failed.
+    pub fn stage(&self) -> DecodeStage {
+        self.stage
+    }
+
+    /// Returns the index of the frame which failed, starting at 0.
+    ///
+    /// Skippable frames are counted.
+    pub fn frame_index(&self) -> Option<u64> {
+        self.position.map(|position| position.frame_index)
+    }
+
+    /// Returns the offset in the compressed input where the failing frame
+    /// starts.
+    pub fn frame_offset(&self) -> Option<u64> {
+        self.position.map(|position| position.frame_offset)
+    }
+
+    /// Returns the offset in the compressed input of the chunk being
+    /// decoded when the error was detected.
+    ///
+    /// The corrupted data is in this chunk, or slightly before it.
+    pub fn input_offset(&self) -> Option<u64> {
+        self.position.map(|position| position.input_offset)
+    }
+}
+
+// Adds the position to a decompression error.
+pub(crate) fn locate(
+    mut error: io::Error,
+    frame_index: u64,
+    frame_offset: u64,
+    input_offset: u64,
+) -> io::Error {
+    if let Some(decode_error) = error
+        .get_mut()
+        .and_then(|error| error.downcast_mut::<DecodeError>())
+    {
+        decode_error.position = Some(Position {
+            frame_index,
+            frame_offset,
+            input_offset,
+        });
+    }
+    error
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message)?;
+        if let Some(position) = self.position {
+            let stage = match self.stage {
+                DecodeStage::Header => "header",
+                DecodeStage::Block => "block",
+                DecodeStage::Checksum => "checksum",
+                DecodeStage::Other => "data",
+            };
+            write!(
+                f,
+                " (in {} of frame #{} starting at byte {}, near byte {})",
+                stage,
+                position.frame_index,
+                position.frame_offset,
+                position.input_offset
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for DecodeError {}
+
+/// Error returned when decompressed data goes over a size limit.
+///
+/// This is wrapped in an `io::Error` of kind `InvalidData`: use
+/// [`LimitExceeded::from_io_error`] to tell it apart from corrupted data.
+///
+/// [`LimitExceeded::from_io_error`]: #method.from_io_error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LimitExceeded {
+    limit: u64,
+}
+
+impl LimitExceeded {
+    pub(crate) fn new(limit: u64) -> Self {
+        LimitExceeded { limit }
+    }
+
+    /// Returns the limit error wrapped in `error`, if any.
+    pub fn from_io_error(error: &io::Error) -> Option<&Self> {
+        error.get_ref().and_then(|error| error.downcast_ref())
+    }
+
+    /// Returns the limit which was exceeded, in bytes.
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "decompressed data exceeds the limit of {} bytes",
+            self.limit
+        )
+    }
+}
+
+impl Error for LimitExceeded {}
+
+impl From<LimitExceeded> for io::Error {
+    fn from(error: LimitExceeded) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    }
+}
+
+/// Error returned when writing to a decoder after its last frame.
+///
+/// Once the frames allowed by `max_frames` are decoded, a write decoder
+/// doesn't take any more input. This is wrapped in an `io::Error` of kind
+/// `Other`: use [`FrameLimitReached::from_io_error`] to tell it apart from
+/// other errors.
+///
+/// [`FrameLimitReached::from_io_error`]: #method.from_io_error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FrameLimitReached;
+
+impl FrameLimitReached {
+    /// Returns the error wrapped in `error`, if any.
+    pub fn from_io_error(error: &io::Error) -> Option<&Self> {
+        error.get_ref().and_then(|error| error.downcast_ref())
+    }
+}
+
+impl fmt::Display for FrameLimitReached {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the last frame was already decoded")
+    }
+}
+
+impl Error for FrameLimitReached {}
+
+impl From<FrameLimitReached> for io::Error {
+    fn from(error: FrameLimitReached) -> Self {
+        io::Error::new(io::ErrorKind::Other, error)
+    }
+}
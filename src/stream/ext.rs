@@ -0,0 +1,54 @@
+//! Extension traits to wrap readers and writers.
+//!
+//! ```
+//! use std::io::{Read, Write};
+//! use zstd::stream::{ZstdReadExt, ZstdWriteExt};
+//!
+//! let mut encoder = Vec::new().zstd_encode(3)?;
+//! encoder.write_all(b"some data")?;
+//! let compressed = encoder.finish()?;
+//!
+//! let mut content = String::new();
+//! compressed.as_slice().zstd_decode()?.read_to_string(&mut content)?;
+//! assert_eq!(content, "some data");
+//! # Ok::<(), std::io::Error>(())
+//! ```
+use std::io::{self, BufReader, Read, Write};
+
+use crate::stream::{read, write};
+
+/// Adds zstd methods to all readers.
+pub trait ZstdReadExt: Read + Sized {
+    /// Wraps this reader in a decoder, reading decompressed data.
+    ///
+    /// This is the same as [`read::Decoder::new`]: the reader is buffered.
+    ///
+    /// [`read::Decoder::new`]: read/struct.Decoder.html#method.new
+    fn zstd_decode(
+        self,
+    ) -> io::Result<read::Decoder<'static, BufReader<Self>>> {
+        read::Decoder::new(self)
+    }
+}
+
+impl<R: Read> ZstdReadExt for R {}
+
+/// Adds zstd methods to all writers.
+pub trait ZstdWriteExt: Write + Sized {
+    /// Wraps this writer in an encoder, compressing written data.
+    ///
+    /// This is the same as [`write::Encoder::new`]: remember to call
+    /// `finish()` (or use `auto_finish()`) to complete the stream.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`).
+    ///
+    /// [`write::Encoder::new`]: write/struct.Encoder.html#method.new
+    fn zstd_encode(
+        self,
+        level: i32,
+    ) -> io::Result<write::Encoder<'static, Self>> {
+        write::Encoder::new(self, level)
+    }
+}
+
+impl<W: Write> ZstdWriteExt for W {}
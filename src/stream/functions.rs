@@ -1,16 +1,38 @@
 use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use super::{Decoder, Encoder};
+use super::{raw, Decoder, Encoder, LimitExceeded};
 
 /// Decompress from the given source as if using a `Decoder`.
 ///
 /// The input data must be in the zstd frame format.
+///
+/// To reuse an output buffer across calls, use `decode_all_into` instead.
 pub fn decode_all<R: io::Read>(source: R) -> io::Result<Vec<u8>> {
     let mut result = Vec::new();
     copy_decode(source, &mut result)?;
     Ok(result)
 }
 
+/// Decompress from the given source as if using a `Decoder`, appending the
+/// result to `output`.
+///
+/// Reusing the same `output` (after clearing it) across calls avoids
+/// allocating a new buffer each time. If an error occurs, `output` is
+/// restored to its original length.
+pub fn decode_all_into<R: io::Read>(
+    source: R,
+    output: &mut Vec<u8>,
+) -> io::Result<()> {
+    let start = output.len();
+    let result = copy_decode(source, &mut *output);
+    if result.is_err() {
+        output.truncate(start);
+    }
+    result
+}
+
 /// Decompress from the given source as if using a `Decoder`.
 ///
 /// Decompressed data will be appended to `destination`.
@@ -24,22 +46,124 @@ where
     Ok(())
 }
 
+/// Decompress from the given source as if using a `Decoder`, stopping if
+/// the decompressed data goes over `max_bytes`.
+///
+/// Decompressed data will be appended to `destination`. Going over the
+/// limit fails with a [`LimitExceeded`] error, after exactly `max_bytes`
+/// were written: the caller is expected to discard the partial output.
+///
+/// This protects against decompression bombs when materializing untrusted
+/// data, without having to trust the content size declared by the frames.
+///
+/// [`LimitExceeded`]: struct.LimitExceeded.html
+pub fn copy_decode_with_limit<R, W>(
+    source: R,
+    mut destination: W,
+    max_bytes: u64,
+) -> io::Result<()>
+where
+    R: io::Read,
+    W: io::Write,
+{
+    let mut decoder = Decoder::new(source)?;
+    let mut limited = io::Read::take(&mut decoder, max_bytes);
+    let copied = io::copy(&mut limited, &mut destination)?;
+    if copied < max_bytes {
+        return Ok(());
+    }
+
+    // Exactly at the limit: only fail if there is more.
+    let mut probe = [0u8; 1];
+    loop {
+        match io::Read::read(&mut decoder, &mut probe) {
+            Ok(0) => return Ok(()),
+            Ok(_) => return Err(LimitExceeded::new(max_bytes).into()),
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Decompress from the given source as if using a `Decoder`, limiting the
+/// bandwidth used.
+///
+/// With `RateLimit::Input`, compressed data is read from `source` at most at
+/// the given rate. With `RateLimit::Output`, decompressed data is written to
+/// `destination` at most at the given rate.
+///
+/// A rate of `0` is an `InvalidInput` error.
+pub fn copy_decode_throttled<R, W>(
+    source: R,
+    destination: W,
+    limit: RateLimit,
+) -> io::Result<()>
+where
+    R: io::Read,
+    W: io::Write,
+{
+    match limit {
+        RateLimit::Input(rate) => {
+            copy_decode(Throttled::new(source, rate)?, destination)
+        }
+        RateLimit::Output(rate) => {
+            copy_decode(source, Throttled::new(destination, rate)?)
+        }
+    }
+}
+
+/// Checks that the given source only contains valid zstd frames.
+///
+/// Every frame is decompressed to validate its structure and, when present,
+/// its checksum, but the decompressed data is discarded as it goes: memory
+/// usage does not depend on the content size.
+///
+/// Works on a buffer (`verify(&data[..])`) as well as on any reader.
+pub fn verify<R: io::Read>(source: R) -> io::Result<()> {
+    copy_decode(source, io::sink())
+}
+
 /// Compress all data from the given source as if using an `Encoder`.
 ///
 /// Result will be in the zstd frame format.
 ///
 /// A level of `0` uses zstd's default (currently `3`).
+///
+/// To reuse an output buffer across calls, use `encode_all_into` instead.
 pub fn encode_all<R: io::Read>(source: R, level: i32) -> io::Result<Vec<u8>> {
     let mut result = Vec::<u8>::new();
     copy_encode(source, &mut result, level)?;
     Ok(result)
 }
 
+/// Compress all data from the given source as if using an `Encoder`,
+/// appending the result to `output`.
+///
+/// Reusing the same `output` (after clearing it) across calls avoids
+/// allocating a new buffer each time. If an error occurs, `output` is
+/// restored to its original length.
+///
+/// A level of `0` uses zstd's default (currently `3`).
+pub fn encode_all_into<R: io::Read>(
+    source: R,
+    level: i32,
+    output: &mut Vec<u8>,
+) -> io::Result<()> {
+    let start = output.len();
+    let result = copy_encode(source, &mut *output, level);
+    if result.is_err() {
+        output.truncate(start);
+    }
+    result
+}
+
 /// Compress all data from the given source as if using an `Encoder`.
 ///
 /// Compressed data will be appended to `destination`.
 ///
 /// A level of `0` uses zstd's default (currently `3`).
+///
+/// If `source` implements `BufRead`, `copy_encode_buffered` saves a copy.
 pub fn copy_encode<R, W>(
     mut source: R,
     destination: W,
@@ -55,5 +179,490 @@ where
     Ok(())
 }
 
+/// Compress all data from the given source as if using an `Encoder`,
+/// limiting the bandwidth used.
+///
+/// With `RateLimit::Input`, data is read from `source` at most at the given
+/// rate. With `RateLimit::Output`, compressed data is written to
+/// `destination` at most at the given rate.
+///
+/// This keeps background jobs from saturating a shared disk or network
+/// link. A level of `0` uses zstd's default (currently `3`), and a rate of
+/// `0` is an `InvalidInput` error.
+pub fn copy_encode_throttled<R, W>(
+    source: R,
+    destination: W,
+    level: i32,
+    limit: RateLimit,
+) -> io::Result<()>
+where
+    R: io::Read,
+    W: io::Write,
+{
+    match limit {
+        RateLimit::Input(rate) => {
+            copy_encode(Throttled::new(source, rate)?, destination, level)
+        }
+        RateLimit::Output(rate) => {
+            copy_encode(source, Throttled::new(destination, rate)?, level)
+        }
+    }
+}
+
+/// Compress all data from the given source as if using an `Encoder`,
+/// calling `pause` regularly.
+///
+/// `pause` is called with the number of bytes compressed so far, each time
+/// about `interval` more bytes were compressed. It can sleep, yield, or wait
+/// for a pacing budget, so that long background jobs leave room for more
+/// urgent work. Returning an error aborts the compression (except for
+/// `Interrupted` errors, which are retried like any read).
+///
+/// A level of `0` uses zstd's default (currently `3`), and an interval of
+/// `0` is an `InvalidInput` error.
+pub fn copy_encode_cooperative<R, W, F>(
+    source: R,
+    destination: W,
+    level: i32,
+    interval: u64,
+    pause: F,
+) -> io::Result<()>
+where
+    R: io::Read,
+    W: io::Write,
+    F: FnMut(u64) -> io::Result<()>,
+{
+    if interval == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "interval must be non-zero",
+        ));
+    }
+    let source = Cooperative {
+        inner: source,
+        interval,
+        pause,
+        total: 0,
+        next_pause: interval,
+    };
+    copy_encode(source, destination, level)
+}
+
+/// Compress all data from the given buffered source as if using an
+/// `Encoder`.
+///
+/// This is the same as `copy_encode`, but the data is given to the encoder
+/// straight from the source's own buffer, instead of being copied to an
+/// intermediate buffer first.
+///
+/// Compressed data will be appended to `destination`.
+///
+/// A level of `0` uses zstd's default (currently `3`).
+pub fn copy_encode_buffered<R, W>(
+    mut source: R,
+    destination: W,
+    level: i32,
+) -> io::Result<()>
+where
+    R: io::BufRead,
+    W: io::Write,
+{
+    use std::io::Write;
+
+    let mut encoder = Encoder::new(destination, level)?;
+    loop {
+        let written = {
+            let input = match source.fill_buf() {
+                Ok(input) => input,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {
+                    continue
+                }
+                Err(e) => return Err(e),
+            };
+            if input.is_empty() {
+                break;
+            }
+            match encoder.write(input) {
+                Ok(written) => written,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => 0,
+                Err(e) => return Err(e),
+            }
+        };
+        source.consume(written);
+    }
+    encoder.finish()?;
+    Ok(())
+}
+
+/// Compress exactly `size` bytes from the given source as if using an
+/// `Encoder`.
+///
+/// Compressed data will be appended to `destination`, as a single frame
+/// declaring its content size.
+///
+/// The data is processed in chunks, so `size` can be larger than what fits
+/// in memory, or in a `usize` on 32-bit targets. If `source` ends before
+/// `size` bytes, an `UnexpectedEof` error is returned.
+///
+/// A level of `0` uses zstd's default (currently `3`).
+pub fn copy_encode_with_size<R, W>(
+    source: R,
+    destination: W,
+    level: i32,
+    size: u64,
+) -> io::Result<()>
+where
+    R: io::Read,
+    W: io::Write,
+{
+    let mut encoder = Encoder::new(destination, level)?;
+    encoder.set_pledged_src_size(size)?;
+    let copied = io::copy(&mut source.take(size), &mut encoder)?;
+    if copied != size {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "source ended before the announced size",
+        ));
+    }
+    encoder.finish()?;
+    Ok(())
+}
+
+/// Decompresses a zstd stream and compresses it again with new parameters.
+///
+/// This re-packs existing data, for example from level 1 to level 19 when
+/// moving it to cold storage, in a single pass: the decompressed data only
+/// goes through one buffer, reused for the whole stream.
+///
+/// Parameters not given in `parameters` keep zstd's defaults, including the
+/// compression level.
+///
+/// With `preserve_frames`, each input frame gives one output frame, so
+/// readers relying on frame boundaries (like seekable archives) still work.
+/// Each output frame then declares the same content size as its input
+/// frame, if any, and skippable frames are copied unchanged. Otherwise, the
+/// whole stream is compressed as a single frame, and skippable frames are
+/// dropped.
+pub fn recompress<R, W>(
+    source: R,
+    mut destination: W,
+    parameters: &crate::CompressionParameters,
+    preserve_frames: bool,
+) -> io::Result<()>
+where
+    R: io::Read,
+    W: io::Write,
+{
+    let mut encoder = raw::Encoder::new(0)?;
+    for parameter in parameters.parameters() {
+        encoder.set_parameter(parameter)?;
+    }
+
+    let mut buffers = RecompressBuffers {
+        input: vec![0; zstd_safe::DCtx::out_size()],
+        output: vec![0; zstd_safe::CCtx::out_size()],
+    };
+    if !preserve_frames {
+        let mut decoder = Decoder::new(source)?;
+        return buffers.compress(&mut decoder, &mut encoder, &mut destination);
+    }
+
+    let mut source =
+        io::BufReader::with_capacity(zstd_safe::DCtx::in_size(), source);
+    let mut decoder = raw::Decoder::new()?;
+    while let Some(header) = crate::frame::read_frame_header(&mut source)? {
+        if crate::frame::is_skippable(&header) {
+            destination.write_all(&header)?;
+            let size = u32::from_le_bytes([
+                header[4], header[5], header[6], header[7],
+            ]);
+            let size = u64::from(size);
+            let copied = io::copy(
+                &mut io::Read::take(&mut source, size),
+                &mut destination,
+            )?;
+            if copied != size {
+                return Err(crate::frame::incomplete_frame());
+            }
+            continue;
+        }
+
+        match zstd_safe::get_frame_content_size(&header) {
+            zstd_safe::CONTENTSIZE_UNKNOWN | zstd_safe::CONTENTSIZE_ERROR => {}
+            size => encoder.set_pledged_src_size(size)?,
+        }
+        let mut frame = FrameReader {
+            header: &header,
+            source: &mut source,
+            decoder: &mut decoder,
+            done: false,
+        };
+        buffers.compress(&mut frame, &mut encoder, &mut destination)?;
+    }
+    Ok(())
+}
+
+// Decompresses a single frame, starting with its already read `header`.
+struct FrameReader<'a, R> {
+    header: &'a [u8],
+    source: &'a mut R,
+    decoder: &'a mut raw::Decoder<'static>,
+    done: bool,
+}
+
+impl<R: io::BufRead> io::Read for FrameReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        use self::raw::{InBuffer, Operation, OutBuffer};
+
+        while !self.done && !buf.is_empty() {
+            let from_header = !self.header.is_empty();
+            let input = if from_header {
+                self.header
+            } else {
+                self.source.fill_buf()?
+            };
+            let eof = input.is_empty();
+
+            let mut src = InBuffer::around(input);
+            let mut dst = OutBuffer::around(&mut *buf);
+            let hint = self.decoder.run(&mut src, &mut dst)?;
+            let (read, written) = (src.pos, dst.pos);
+            if from_header {
+                self.header = &self.header[read..];
+            } else {
+                self.source.consume(read);
+            }
+
+            self.done = hint == 0;
+            if written > 0 {
+                return Ok(written);
+            }
+            if eof && !self.done {
+                return Err(crate::frame::incomplete_frame());
+            }
+        }
+        Ok(0)
+    }
+}
+
+// Buffers reused by `recompress` for every frame.
+struct RecompressBuffers {
+    input: Vec<u8>,
+    output: Vec<u8>,
+}
+
+impl RecompressBuffers {
+    // Compresses all of `source` as a single frame.
+    fn compress<R, W>(
+        &mut self,
+        source: &mut R,
+        encoder: &mut raw::Encoder<'_>,
+        destination: &mut W,
+    ) -> io::Result<()>
+    where
+        R: io::Read,
+        W: io::Write,
+    {
+        use self::raw::{InBuffer, Operation, OutBuffer};
+
+        loop {
+            let len = match source.read(&mut self.input) {
+                Ok(0) => break,
+                Ok(len) => len,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {
+                    continue
+                }
+                Err(e) => return Err(e),
+            };
+            let mut input = InBuffer::around(&self.input[..len]);
+            while !input.remaining().is_empty() {
+                let mut output = OutBuffer::around(&mut self.output);
+                encoder.run(&mut input, &mut output)?;
+                destination.write_all(output.written())?;
+            }
+        }
+
+        loop {
+            let mut output = OutBuffer::around(&mut self.output);
+            let remaining = encoder.finish(&mut output, false)?;
+            destination.write_all(output.written())?;
+            if remaining == 0 {
+                break;
+            }
+        }
+        encoder.reinit()
+    }
+}
+
+/// A bandwidth limit, in bytes per second.
+///
+/// This is used by [`copy_encode_throttled`] and [`copy_decode_throttled`].
+///
+/// [`copy_encode_throttled`]: fn.copy_encode_throttled.html
+/// [`copy_decode_throttled`]: fn.copy_decode_throttled.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimit {
+    /// Limits how fast data is read from the source.
+    Input(u64),
+
+    /// Limits how fast data is written to the destination.
+    Output(u64),
+}
+
+// Wraps a reader or a writer, sleeping as needed to stay under `rate`.
+struct Throttled<T> {
+    inner: T,
+    // Bytes per second.
+    rate: u64,
+    start: Instant,
+    transferred: u64,
+}
+
+impl<T> Throttled<T> {
+    // Each call moves at most this fraction of a second worth of data, so
+    // the transfer is smooth rather than bursty.
+    const CHUNKS_PER_SECOND: u64 = 10;
+
+    fn new(inner: T, rate: u64) -> io::Result<Self> {
+        if rate == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "rate limit must be non-zero",
+            ));
+        }
+        Ok(Throttled {
+            inner,
+            rate,
+            start: Instant::now(),
+            transferred: 0,
+        })
+    }
+
+    fn max_chunk(&self, len: usize) -> usize {
+        let chunk = u64::max(1, self.rate / Self::CHUNKS_PER_SECOND);
+        usize::min(len, chunk as usize)
+    }
+
+    // Sleeps until `transferred` bytes are allowed by the rate.
+    fn record(&mut self, len: usize) {
+        self.transferred += len as u64;
+        let due = Duration::from_secs_f64(
+            self.transferred as f64 / self.rate as f64,
+        );
+        if let Some(ahead) = due.checked_sub(self.start.elapsed()) {
+            thread::sleep(ahead);
+        }
+    }
+}
+
+impl<R: io::Read> io::Read for Throttled<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.max_chunk(buf.len());
+        let read = self.inner.read(&mut buf[..len])?;
+        self.record(read);
+        Ok(read)
+    }
+}
+
+impl<W: io::Write> io::Write for Throttled<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.max_chunk(buf.len());
+        let written = self.inner.write(&buf[..len])?;
+        self.record(written);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// Calls `pause` every `interval` bytes read.
+struct Cooperative<R, F> {
+    inner: R,
+    interval: u64,
+    pause: F,
+    total: u64,
+    next_pause: u64,
+}
+
+impl<R, F> io::Read for Cooperative<R, F>
+where
+    R: io::Read,
+    F: FnMut(u64) -> io::Result<()>,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // The data read so far was compressed by now.
+        if self.total >= self.next_pause {
+            (self.pause)(self.total)?;
+            self.next_pause = self.total + self.interval;
+        }
+
+        let left = self.next_pause - self.total;
+        let len = u64::min(buf.len() as u64, left) as usize;
+        let read = self.inner.read(&mut buf[..len])?;
+        self.total += read as u64;
+        Ok(read)
+    }
+}
+
+/// Size of some data before and after compression.
+///
+/// This is returned by [`compressed_size`].
+///
+/// [`compressed_size`]: fn.compressed_size.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressedSize {
+    /// Number of bytes read from the source.
+    pub original: u64,
+
+    /// Number of bytes the compressed frame would take.
+    pub compressed: u64,
+}
+
+impl CompressedSize {
+    /// Returns the compression ratio: `original / compressed`.
+    ///
+    /// Values below `1.0` mean compression makes the data larger.
+    pub fn ratio(&self) -> f64 {
+        self.original as f64 / self.compressed as f64
+    }
+}
+
+/// Compresses all data from the given source and only returns the
+/// resulting size.
+///
+/// The compressed data is counted and discarded as it is produced, so this
+/// doesn't allocate an output buffer. This can tell whether compressing
+/// some data is worth it before storing it.
+///
+/// A level of `0` uses zstd's default (currently `3`).
+pub fn compressed_size<R: io::Read>(
+    mut source: R,
+    level: i32,
+) -> io::Result<CompressedSize> {
+    let mut encoder = Encoder::new(Counter(0), level)?;
+    let original = io::copy(&mut source, &mut encoder)?;
+    let Counter(compressed) = encoder.finish()?;
+    Ok(CompressedSize {
+        original,
+        compressed,
+    })
+}
+
+// A sink that only counts the bytes written to it.
+struct Counter(u64);
+
+impl io::Write for Counter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
 #[cfg(tests)]
 mod tests {}
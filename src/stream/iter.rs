@@ -0,0 +1,239 @@
+//! Compress and decompress iterators of chunks.
+//!
+//! Channel or pipeline based code often moves data around as a sequence of
+//! buffers rather than through `Read` or `Write`. The [`ZstdIteratorExt`]
+//! trait adapts any iterator of byte chunks into an iterator of compressed
+//! (or decompressed) chunks.
+//!
+//! ```
+//! use zstd::stream::iter::ZstdIteratorExt;
+//!
+//! let messages = vec!["first message", "second message"];
+//!
+//! let compressed = messages.iter().zstd_compress(3)?;
+//! let decompressed = compressed
+//!     .collect::<std::io::Result<Vec<_>>>()?
+//!     .into_iter()
+//!     .zstd_decompress()?;
+//! let content = decompressed.collect::<std::io::Result<Vec<_>>>()?.concat();
+//! assert_eq!(content, b"first messagesecond message");
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! [`ZstdIteratorExt`]: trait.ZstdIteratorExt.html
+use std::io::{self, Write};
+use std::mem;
+
+use crate::stream::raw::{self, Operation};
+use crate::stream::zio;
+
+/// Adds zstd adapters to all iterators.
+pub trait ZstdIteratorExt: Iterator + Sized {
+    /// Compresses the chunks of this iterator as a single zstd stream.
+    ///
+    /// The compressed chunks, concatenated, form one frame. They don't
+    /// match the input chunks: zstd buffers data until it has enough to
+    /// compress, so a small input chunk may not produce any output. They are
+    /// at most `zstd_safe::CCtx::out_size()` bytes long.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`).
+    fn zstd_compress(
+        self,
+        level: i32,
+    ) -> io::Result<ZstdChunks<Self, raw::Encoder<'static>>>
+    where
+        Self::Item: AsRef<[u8]>,
+    {
+        Ok(ZstdChunks::new(
+            self,
+            raw::Encoder::new(level)?,
+            zstd_safe::CCtx::out_size(),
+        ))
+    }
+
+    /// Decompresses the chunks of this iterator, as a single zstd stream.
+    ///
+    /// The compressed stream can be split anywhere: frames don't need to
+    /// match the chunks. Ending in the middle of a frame is an error.
+    ///
+    /// The output chunks are at most `zstd_safe::DCtx::out_size()` bytes
+    /// long: a small input chunk decompressing to a lot of data gives many
+    /// output chunks, decompressed as they are consumed.
+    fn zstd_decompress(
+        self,
+    ) -> io::Result<ZstdChunks<Self, raw::Decoder<'static>>>
+    where
+        Self::Item: AsRef<[u8]>,
+    {
+        Ok(ZstdChunks::new(
+            self,
+            raw::Decoder::new()?,
+            zstd_safe::DCtx::out_size(),
+        ))
+    }
+}
+
+impl<I: Iterator> ZstdIteratorExt for I {}
+
+/// Iterator over the result of a zstd operation on chunks.
+///
+/// This is returned by the methods of [`ZstdIteratorExt`]. Empty output
+/// chunks are skipped. The iteration stops after the first error.
+///
+/// [`ZstdIteratorExt`]: trait.ZstdIteratorExt.html
+pub struct ZstdChunks<I: Iterator, D: Operation> {
+    iter: I,
+    // Input chunk being processed, and how much of it was consumed.
+    current: Option<(I::Item, usize)>,
+    // Output not returned yet is kept in the inner `Vec`.
+    writer: zio::Writer<Vec<u8>, D>,
+    max_chunk_size: usize,
+    done: bool,
+}
+
+impl<I: Iterator, D: Operation> ZstdChunks<I, D> {
+    fn new(iter: I, operation: D, max_chunk_size: usize) -> Self {
+        ZstdChunks {
+            iter,
+            current: None,
+            writer: zio::Writer::new(Vec::new(), operation),
+            max_chunk_size,
+            done: false,
+        }
+    }
+
+    /// Returns a mutable reference to the underlying operation.
+    ///
+    /// This can be used to set parameters before the first chunk.
+    pub fn operation_mut(&mut self) -> &mut D {
+        self.writer.operation_mut()
+    }
+}
+
+impl<I, D> Iterator for ZstdChunks<I, D>
+where
+    I: Iterator,
+    I::Item: AsRef<[u8]>,
+    D: Operation,
+{
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let output = self.writer.writer_mut();
+            if !output.is_empty() {
+                let rest = if output.len() > self.max_chunk_size {
+                    output.split_off(self.max_chunk_size)
+                } else {
+                    Vec::new()
+                };
+                return Some(Ok(mem::replace(output, rest)));
+            }
+            if self.done {
+                return None;
+            }
+
+            // Each `write` only produces a few buffers of output, even when
+            // the input decompresses to a lot of data.
+            let result = match &mut self.current {
+                Some((chunk, consumed))
+                    if *consumed < chunk.as_ref().len() =>
+                {
+                    match self.writer.write(&chunk.as_ref()[*consumed..]) {
+                        Ok(0) => Err(io::Error::new(
+                            io::ErrorKind::WriteZero,
+                            "operation will not accept any more data",
+                        )),
+                        Ok(n) => {
+                            *consumed += n;
+                            Ok(())
+                        }
+                        Err(ref e)
+                            if e.kind() == io::ErrorKind::Interrupted =>
+                        {
+                            Ok(())
+                        }
+                        Err(e) => Err(e),
+                    }
+                }
+                _ => {
+                    self.current = self.iter.next().map(|chunk| (chunk, 0));
+                    if self.current.is_some() {
+                        continue;
+                    }
+                    self.done = true;
+                    self.writer.finish()
+                }
+            };
+            if let Err(e) = result {
+                self.done = true;
+                self.writer.writer_mut().clear();
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ZstdIteratorExt;
+    use std::io;
+
+    #[test]
+    fn test_chunks() {
+        let data = crate::test_util::text(500_000, 1);
+
+        let compressed: Vec<Vec<u8>> = data
+            .chunks(1000)
+            .zstd_compress(1)
+            .unwrap()
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert!(compressed.iter().all(|chunk| !chunk.is_empty()));
+        let stream = compressed.concat();
+        assert_eq!(crate::decode_all(&stream[..]).unwrap(), data);
+
+        // The compressed chunks can be split differently.
+        let decompressed: Vec<Vec<u8>> = stream
+            .chunks(7)
+            .zstd_decompress()
+            .unwrap()
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(decompressed.concat(), data);
+
+        // A small input chunk can decompress to a lot of data.
+        let zeros = vec![0u8; 10_000_000];
+        let stream = crate::encode_all(&zeros[..], 1).unwrap();
+        let decompressed: Vec<Vec<u8>> = std::iter::once(&stream[..])
+            .zstd_decompress()
+            .unwrap()
+            .collect::<io::Result<_>>()
+            .unwrap();
+        let max = zstd_safe::DCtx::out_size();
+        assert!(decompressed.iter().all(|chunk| chunk.len() <= max));
+        assert_eq!(decompressed.concat(), zeros);
+
+        // An empty input still gives a frame.
+        let empty = std::iter::empty::<&[u8]>().zstd_compress(1).unwrap();
+        let empty: Vec<_> = empty.collect::<io::Result<_>>().unwrap();
+        assert_eq!(crate::decode_all(&empty.concat()[..]).unwrap(), b"");
+    }
+
+    #[test]
+    fn test_errors() {
+        let stream = crate::encode_all(&b"some data"[..], 1).unwrap();
+
+        // Truncated stream.
+        let mut chunks = std::iter::once(&stream[..stream.len() - 1])
+            .zstd_decompress()
+            .unwrap();
+        assert!(chunks.any(|chunk| chunk.is_err()));
+        assert!(chunks.next().is_none());
+
+        let mut chunks =
+            std::iter::once(&b"not zstd"[..]).zstd_decompress().unwrap();
+        assert!(chunks.next().unwrap().is_err());
+        assert!(chunks.next().is_none());
+    }
+}
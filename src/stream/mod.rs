@@ -9,7 +9,10 @@
 pub mod read;
 pub mod write;
 
+mod error;
+mod ext;
 mod functions;
+pub mod iter;
 pub mod zio;
 
 #[cfg(test)]
@@ -17,9 +20,21 @@ mod tests;
 
 pub mod raw;
 
-pub use self::functions::{copy_decode, copy_encode, decode_all, encode_all};
+pub use self::error::{
+    DecodeError, DecodeStage, FrameLimitReached, LimitExceeded,
+};
+pub use self::ext::{ZstdReadExt, ZstdWriteExt};
+pub use self::functions::{
+    compressed_size, copy_decode, copy_decode_throttled,
+    copy_decode_with_limit, copy_encode, copy_encode_buffered,
+    copy_encode_cooperative, copy_encode_throttled, copy_encode_with_size,
+    decode_all, decode_all_into, encode_all, encode_all_into, recompress,
+    verify, CompressedSize, RateLimit,
+};
+#[cfg(feature = "experimental")]
+pub use self::raw::AppliedParameters;
 pub use self::read::Decoder;
-pub use self::write::{AutoFinishEncoder, Encoder};
+pub use self::write::{AutoFinishDecoder, AutoFinishEncoder, Encoder};
 
 #[doc(hidden)]
 #[macro_export]
@@ -36,6 +51,69 @@ macro_rules! readwritecommon {
             )
         }
 
+        /// Declares the total size of the data to be compressed.
+        ///
+        /// The size is written in the frame header, and the compression
+        /// fails if a different amount of data is written. This is a `u64`,
+        /// so frames larger than 4GB can be described on 32-bit targets too.
+        ///
+        /// This must be called before any data is compressed.
+        pub fn set_pledged_src_size(
+            &mut self,
+            pledged_src_size: u64,
+        ) -> io::Result<()> {
+            self.$readwrite
+                .operation_mut()
+                .set_pledged_src_size(pledged_src_size)
+        }
+
+        /// Returns the parameters used to compress the current frame.
+        ///
+        /// This includes the actual window size, which decoders need to
+        /// allocate: it can be smaller than the compression level's default
+        /// when a small size was pledged.
+        #[cfg(feature = "experimental")]
+        pub fn applied_parameters(
+            &self,
+        ) -> io::Result<$crate::stream::AppliedParameters> {
+            self.$readwrite.operation().applied_parameters()
+        }
+
+        /// Applies all the parameters of the given profile.
+        ///
+        /// Individual parameters can still be changed afterwards.
+        pub fn apply_profile(
+            &mut self,
+            profile: $crate::Profile,
+        ) -> io::Result<()> {
+            for parameter in profile.parameters() {
+                self.$readwrite.operation_mut().set_parameter(parameter)?;
+            }
+            Ok(())
+        }
+
+        /// Applies all the given parameters.
+        ///
+        /// Parameters not included keep their current value.
+        pub fn apply_parameters(
+            &mut self,
+            parameters: &$crate::CompressionParameters,
+        ) -> io::Result<()> {
+            for parameter in parameters.parameters() {
+                self.$readwrite.operation_mut().set_parameter(parameter)?;
+            }
+            Ok(())
+        }
+
+        /// Controls whether an empty input produces a frame at all.
+        ///
+        /// By default, an empty input still gives a valid (empty) frame.
+        /// When skipped, nothing is written instead. Decoders then need
+        /// `allow_empty` to accept the resulting zero-byte stream.
+        pub fn skip_empty_frame(&mut self, skip: bool) {
+            self.$readwrite.operation_mut().skip_empty_frame(skip);
+        }
+
         /// Enables multithreaded compression
         ///
         /// * If `n_workers == 0` (default), then multithreaded will be
@@ -51,6 +129,18 @@ macro_rules! readwritecommon {
                 .set_parameter(zstd_safe::CParameter::NbWorkers(n_workers))
         }
 
+        /// Draws the compression workers from a shared pool.
+        ///
+        /// See `zstd::ThreadPool`. This has no effect unless
+        /// `multithread` is used, and must be called before writing data.
+        #[cfg(all(feature = "experimental", feature = "zstdmt"))]
+        pub fn thread_pool(
+            &mut self,
+            pool: &'a $crate::ThreadPool,
+        ) -> io::Result<()> {
+            self.$readwrite.operation_mut().set_thread_pool(pool)
+        }
+
         /// Enables or disables storing of the dict id.
         ///
         /// Defaults to true. If false, the behaviour of decoding with a wrong
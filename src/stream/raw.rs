@@ -4,6 +4,10 @@
 //! of data using buffers.
 //!
 //! They are mostly thin wrappers around `zstd_safe::{DCtx, CCtx}`.
+//!
+//! The `InBuffer` and `OutBuffer` types used by `Operation` are re-exported
+//! here: use them from this module rather than from `zstd_safe` directly, so
+//! they always match the version used by this crate.
 use std::io;
 
 pub use zstd_safe::{CParameter, DParameter, InBuffer, OutBuffer};
@@ -79,6 +83,15 @@ pub trait Operation {
         let _ = finished_frame;
         Ok(0)
     }
+
+    /// Returns `true` once this operation will not take any more input.
+    ///
+    /// This is the case for a decoder limited to a number of frames, after
+    /// the last one. `run` should not be called anymore, and callers stop
+    /// right there, leaving the rest of the input untouched.
+    fn is_done(&self) -> bool {
+        false
+    }
 }
 
 /// Dummy operation that just copies its input to the output.
@@ -122,6 +135,16 @@ pub struct Status {
 /// An in-memory decoder for streams of data.
 pub struct Decoder<'a> {
     context: zstd_safe::DCtx<'a>,
+
+    // Set by `allow_empty`.
+    allow_empty: bool,
+    // Whether any input was given to this decoder.
+    started: bool,
+
+    // Set by `set_max_frames`.
+    max_frames: Option<u64>,
+    // Number of frames finished so far.
+    frames: u64,
 }
 
 impl Decoder<'static> {
@@ -137,7 +160,7 @@ impl Decoder<'static> {
         context
             .load_dictionary(dictionary)
             .map_err(map_error_code)?;
-        Ok(Decoder { context })
+        Ok(Decoder::from_context(context))
     }
 }
 
@@ -153,7 +176,37 @@ impl<'a> Decoder<'a> {
         context
             .ref_ddict(dictionary.as_ddict())
             .map_err(map_error_code)?;
-        Ok(Decoder { context })
+        Ok(Decoder::from_context(context))
+    }
+
+    /// Creates a new decoder, referencing the given dictionary.
+    ///
+    /// Unlike `with_dictionary`, the dictionary content is not copied,
+    /// which saves memory when many decoders share the same dictionary.
+    pub fn with_dictionary_by_reference<'b>(
+        dictionary: &'b [u8],
+    ) -> io::Result<Self>
+    where
+        'b: 'a,
+    {
+        let mut context = zstd_safe::DCtx::create();
+        context
+            .load_dictionary_by_reference(dictionary)
+            .map_err(map_error_code)?;
+        Ok(Decoder::from_context(context))
+    }
+
+    /// Creates a new decoder initialized with the given ref prefix.
+    ///
+    /// The prefix must be the same as the one used during compression, and
+    /// is only used for the first frame.
+    pub fn with_ref_prefix<'b>(ref_prefix: &'b [u8]) -> io::Result<Self>
+    where
+        'b: 'a,
+    {
+        let mut context = zstd_safe::DCtx::create();
+        context.ref_prefix(ref_prefix).map_err(map_error_code)?;
+        Ok(Decoder::from_context(context))
     }
 
     /// Sets a decompression parameter for this decoder.
@@ -163,6 +216,83 @@ impl<'a> Decoder<'a> {
             .map_err(map_error_code)?;
         Ok(())
     }
+
+    /// Creates a new decoder around an existing context.
+    pub fn from_context(context: zstd_safe::DCtx<'a>) -> Self {
+        Decoder {
+            context,
+            allow_empty: false,
+            started: false,
+            max_frames: None,
+            frames: 0,
+        }
+    }
+
+    /// Sets this decoder to stop after the first frame.
+    ///
+    /// This is the same as `set_max_frames(Some(1))`.
+    pub fn set_single_frame(&mut self) {
+        self.set_max_frames(Some(1));
+    }
+
+    /// Sets this decoder to stop after the given number of frames.
+    ///
+    /// Once the last frame is finished, `is_done()` returns `true` and
+    /// `run` doesn't consume any more input. Frames already decoded count
+    /// towards this limit. `None` (the default) never stops.
+    ///
+    /// This is the only place where frame limits are implemented: all the
+    /// stream decoders rely on it.
+    pub fn set_max_frames(&mut self, max_frames: Option<u64>) {
+        self.max_frames = max_frames;
+    }
+
+    /// Returns the number of frames finished so far.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Counts a frame which was skipped without going through this decoder.
+    pub fn skip_frame(&mut self) {
+        self.frames += 1;
+    }
+
+    /// Prepares this decoder for a new stream.
+    ///
+    /// Any frame in progress is dropped, and the frame count goes back to
+    /// zero. The dictionary and parameters are kept.
+    pub fn reset(&mut self) -> io::Result<()> {
+        self.context.reset().map_err(map_error_code)?;
+        self.started = false;
+        self.frames = 0;
+        Ok(())
+    }
+
+    /// Controls whether an entirely empty input is accepted.
+    ///
+    /// By default, finishing before a complete frame is an error, even
+    /// if no input was given at all. When allowed, an empty input instead
+    /// decompresses to empty output, like an empty frame would.
+    pub fn allow_empty(&mut self, allow: bool) {
+        self.allow_empty = allow;
+    }
+
+    /// Returns a reference to the underlying context.
+    pub fn context(&self) -> &zstd_safe::DCtx<'a> {
+        &self.context
+    }
+
+    /// Returns a mutable reference to the underlying context.
+    ///
+    /// This gives access to zstd functions not covered by this crate.
+    pub fn context_mut(&mut self) -> &mut zstd_safe::DCtx<'a> {
+        &mut self.context
+    }
+
+    /// Returns the underlying context.
+    pub fn into_context(self) -> zstd_safe::DCtx<'a> {
+        self.context
+    }
 }
 
 impl Operation for Decoder<'_> {
@@ -171,9 +301,29 @@ impl Operation for Decoder<'_> {
         input: &mut InBuffer<'_>,
         output: &mut OutBuffer<'_>,
     ) -> io::Result<usize> {
-        self.context
-            .decompress_stream(output, input)
-            .map_err(map_error_code)
+        if self.is_done() {
+            return Ok(0);
+        }
+        if input.pos < input.src.len() {
+            self.started = true;
+        }
+        let hint =
+            self.context
+                .decompress_stream(output, input)
+                .map_err(|code| {
+                    io::Error::new(
+                        io::ErrorKind::Other,
+                        crate::stream::DecodeError::from_code(code),
+                    )
+                })?;
+        if hint == 0 {
+            self.frames += 1;
+        }
+        Ok(hint)
+    }
+
+    fn is_done(&self) -> bool {
+        self.max_frames.is_some_and(|max| self.frames >= max)
     }
 
     fn reinit(&mut self) -> io::Result<()> {
@@ -185,7 +335,10 @@ impl Operation for Decoder<'_> {
         _output: &mut OutBuffer<'_>,
         finished_frame: bool,
     ) -> io::Result<usize> {
-        if finished_frame {
+        if finished_frame
+            || self.is_done()
+            || (self.allow_empty && !self.started)
+        {
             Ok(0)
         } else {
             Err(io::Error::new(
@@ -199,6 +352,47 @@ impl Operation for Decoder<'_> {
 /// An in-memory encoder for streams of data.
 pub struct Encoder<'a> {
     context: zstd_safe::CCtx<'a>,
+
+    // Size pledged for the current frame, or `CONTENTSIZE_UNKNOWN`.
+    pledged_src_size: u64,
+    // Size of the dictionary, for `applied_parameters`.
+    dict_size: usize,
+    // Whether the dictionary is a ref prefix, only used for one frame.
+    prefix: bool,
+
+    // Set by `skip_empty_frame`.
+    skip_empty_frame: bool,
+    // Whether the current frame has no input, and wasn't flushed.
+    empty: bool,
+}
+
+/// Compression parameters used by an encoder, once the compression level,
+/// source size and explicit parameters are taken into account.
+#[cfg(feature = "experimental")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AppliedParameters {
+    /// Maximum back-reference distance, as a power of two.
+    ///
+    /// Decoders need a buffer of this size.
+    pub window_log: u32,
+
+    /// Size of the multi-probe search table, as a power of two.
+    pub chain_log: u32,
+
+    /// Size of the initial probe table, as a power of two.
+    pub hash_log: u32,
+
+    /// Number of search attempts, as a power of two.
+    pub search_log: u32,
+
+    /// Minimum size of searched matches.
+    pub min_match: u32,
+
+    /// Strategy-dependent match length target.
+    pub target_length: u32,
+
+    /// Match finder used.
+    pub strategy: zstd_safe::Strategy,
 }
 
 impl Encoder<'static> {
@@ -219,7 +413,9 @@ impl Encoder<'static> {
             .load_dictionary(dictionary)
             .map_err(map_error_code)?;
 
-        Ok(Encoder { context })
+        let mut encoder = Encoder::from_context(context);
+        encoder.dict_size = dictionary.len();
+        Ok(encoder)
     }
 }
 
@@ -235,7 +431,83 @@ impl<'a> Encoder<'a> {
         context
             .ref_cdict(dictionary.as_cdict())
             .map_err(map_error_code)?;
-        Ok(Encoder { context })
+        let mut encoder = Encoder::from_context(context);
+        encoder.dict_size = dictionary.size();
+        Ok(encoder)
+    }
+
+    /// Creates a new encoder initialized with the given ref prefix.
+    ///
+    /// The prefix is only used for the first frame: it must be provided
+    /// again (as a prefix or dictionary) during decompression.
+    pub fn with_ref_prefix<'b>(
+        level: i32,
+        ref_prefix: &'b [u8],
+    ) -> io::Result<Self>
+    where
+        'b: 'a,
+    {
+        let mut context = zstd_safe::CCtx::create();
+
+        context
+            .set_parameter(CParameter::CompressionLevel(level))
+            .map_err(map_error_code)?;
+
+        context.ref_prefix(ref_prefix).map_err(map_error_code)?;
+
+        let mut encoder = Encoder::from_context(context);
+        encoder.dict_size = ref_prefix.len();
+        encoder.prefix = true;
+        Ok(encoder)
+    }
+
+    /// Replaces the dictionary used for the next frames.
+    ///
+    /// An empty dictionary removes the current one, as well as any prepared
+    /// dictionary or ref prefix. The other parameters are kept.
+    ///
+    /// This must be called between frames: zstd rejects it while a frame
+    /// is in progress.
+    pub fn set_dictionary(&mut self, dictionary: &[u8]) -> io::Result<()> {
+        self.context
+            .load_dictionary(dictionary)
+            .map_err(map_error_code)?;
+        self.dict_size = dictionary.len();
+        self.prefix = false;
+        Ok(())
+    }
+
+    /// Replaces the dictionary used for the next frames with a prepared one.
+    ///
+    /// This must be called between frames, like `set_dictionary`.
+    pub fn set_prepared_dictionary<'b>(
+        &mut self,
+        dictionary: &EncoderDictionary<'b>,
+    ) -> io::Result<()>
+    where
+        'b: 'a,
+    {
+        self.context
+            .ref_cdict(dictionary.as_cdict())
+            .map_err(map_error_code)?;
+        self.dict_size = dictionary.size();
+        self.prefix = false;
+        Ok(())
+    }
+
+    /// Makes this encoder draw its workers from a shared pool.
+    ///
+    /// This only has an effect with `NbWorkers` set to at least `1`, and
+    /// must be called before starting a frame.
+    #[cfg(all(feature = "experimental", feature = "zstdmt"))]
+    pub fn set_thread_pool(
+        &mut self,
+        pool: &'a crate::ThreadPool,
+    ) -> io::Result<()> {
+        self.context
+            .ref_thread_pool(pool.as_pool())
+            .map_err(map_error_code)?;
+        Ok(())
     }
 
     /// Sets a compression parameter for this encoder.
@@ -245,6 +517,152 @@ impl<'a> Encoder<'a> {
             .map_err(map_error_code)?;
         Ok(())
     }
+
+    /// Declares the total size of the next frame.
+    ///
+    /// The size is written in the frame header, and the compression fails
+    /// if a different amount of data is given.
+    pub fn set_pledged_src_size(
+        &mut self,
+        pledged_src_size: u64,
+    ) -> io::Result<()> {
+        self.context
+            .set_pledged_src_size(pledged_src_size)
+            .map_err(map_error_code)?;
+        self.pledged_src_size = pledged_src_size;
+        Ok(())
+    }
+
+    /// Returns the parameters used to compress the current frame.
+    ///
+    /// These are the parameters of the compression level, adjusted to the
+    /// pledged size (if any) and to the dictionary (or ref prefix) size, and
+    /// overridden by explicitly set parameters,
+    /// like zstd does when a frame starts.
+    #[cfg(feature = "experimental")]
+    pub fn applied_parameters(&self) -> io::Result<AppliedParameters> {
+        use zstd_safe::Strategy;
+
+        let get = |parameter| {
+            self.context
+                .get_parameter(parameter)
+                .map_err(map_error_code)
+        };
+        // zstd's public functions read a size of 0 as unknown, but a
+        // pledged 0 is a real size: 1 gives the same parameters.
+        let src_size = match self.pledged_src_size {
+            0 => 1,
+            size => size,
+        };
+        let dict_size = self.dict_size;
+
+        let level = get(CParameter::CompressionLevel(0))?;
+        let mut params = zstd_safe::get_cparams(level, src_size, dict_size);
+        if get(CParameter::EnableLongDistanceMatching(false))? == 1 {
+            // Default window for long distance matching.
+            params.windowLog = 27;
+        }
+        for (parameter, value) in &mut [
+            (CParameter::WindowLog(0), &mut params.windowLog),
+            (CParameter::ChainLog(0), &mut params.chainLog),
+            (CParameter::HashLog(0), &mut params.hashLog),
+            (CParameter::SearchLog(0), &mut params.searchLog),
+            (CParameter::MinMatch(0), &mut params.minMatch),
+            (CParameter::TargetLength(0), &mut params.targetLength),
+        ] {
+            match get(*parameter)? {
+                0 => (),
+                explicit => **value = explicit as u32,
+            }
+        }
+        let strategy = get(CParameter::Strategy(Strategy::ZSTD_fast))?;
+        if let Some(strategy) = crate::params::strategy(strategy as u32) {
+            params.strategy = strategy;
+        }
+        let params = zstd_safe::adjust_cparams(params, src_size, dict_size);
+
+        Ok(AppliedParameters {
+            window_log: params.windowLog,
+            chain_log: params.chainLog,
+            hash_log: params.hashLog,
+            search_log: params.searchLog,
+            min_match: params.minMatch,
+            target_length: params.targetLength,
+            strategy: params.strategy,
+        })
+    }
+
+    /// Creates a new encoder around an existing context.
+    pub fn from_context(context: zstd_safe::CCtx<'a>) -> Self {
+        Encoder {
+            context,
+            pledged_src_size: zstd_safe::CONTENTSIZE_UNKNOWN,
+            dict_size: 0,
+            prefix: false,
+            skip_empty_frame: false,
+            empty: true,
+        }
+    }
+
+    /// Controls whether a frame without any input is written at all.
+    ///
+    /// By default, finishing an empty input writes a valid frame with no
+    /// content. When skipped, nothing is written instead: zero bytes of
+    /// output for zero bytes of input. Decoders then need
+    /// [`Decoder::allow_empty`] to accept such an empty stream.
+    ///
+    /// Flushing before any input was given still writes nothing, so the
+    /// frame can be skipped entirely.
+    pub fn skip_empty_frame(&mut self, skip: bool) {
+        self.skip_empty_frame = skip;
+    }
+
+    /// Returns `true` if the current frame was started.
+    ///
+    /// This is the case once any input was given, or after a flush, until
+    /// the next `reinit`.
+    pub fn is_frame_started(&self) -> bool {
+        !self.empty
+    }
+
+    /// Returns a reference to the underlying context.
+    pub fn context(&self) -> &zstd_safe::CCtx<'a> {
+        &self.context
+    }
+
+    /// Returns a mutable reference to the underlying context.
+    ///
+    /// This gives access to zstd functions not covered by this crate.
+    pub fn context_mut(&mut self) -> &mut zstd_safe::CCtx<'a> {
+        &mut self.context
+    }
+
+    /// Returns the underlying context.
+    pub fn into_context(self) -> zstd_safe::CCtx<'a> {
+        self.context
+    }
+
+    /// Compresses the entire `input` and ends the frame.
+    ///
+    /// Returns the number of bytes still to write: keep calling this method
+    /// with the same buffers until it returns `Ok(0)`.
+    ///
+    /// This is the only way to compress data with
+    /// `CParameter::StableInBuffer`, which requires the complete input to
+    /// be given to each call.
+    pub fn run_to_end(
+        &mut self,
+        input: &mut InBuffer<'_>,
+        output: &mut OutBuffer<'_>,
+    ) -> io::Result<usize> {
+        self.context
+            .compress_stream2(
+                output,
+                input,
+                zstd_safe::EndDirective::ZSTD_e_end,
+            )
+            .map_err(map_error_code)
+    }
 }
 
 impl<'a> Operation for Encoder<'a> {
@@ -253,12 +671,19 @@ impl<'a> Operation for Encoder<'a> {
         input: &mut InBuffer<'_>,
         output: &mut OutBuffer<'_>,
     ) -> io::Result<usize> {
+        if input.pos < input.src.len() {
+            self.empty = false;
+        }
         self.context
             .compress_stream(output, input)
             .map_err(map_error_code)
     }
 
     fn flush(&mut self, output: &mut OutBuffer<'_>) -> io::Result<usize> {
+        if self.skip_empty_frame && self.empty {
+            return Ok(0);
+        }
+        self.empty = false;
         self.context.flush_stream(output).map_err(map_error_code)
     }
 
@@ -267,6 +692,9 @@ impl<'a> Operation for Encoder<'a> {
         output: &mut OutBuffer<'_>,
         _finished_frame: bool,
     ) -> io::Result<usize> {
+        if self.skip_empty_frame && self.empty {
+            return Ok(0);
+        }
         self.context.end_stream(output).map_err(map_error_code)
     }
 
@@ -274,10 +702,25 @@ impl<'a> Operation for Encoder<'a> {
         self.context
             .reset(zstd_safe::ResetDirective::ZSTD_reset_session_only)
             .map_err(map_error_code)?;
+        self.pledged_src_size = zstd_safe::CONTENTSIZE_UNKNOWN;
+        if self.prefix {
+            self.dict_size = 0;
+            self.prefix = false;
+        }
+        self.empty = true;
         Ok(())
     }
 }
 
+fn _assert_traits() {
+    fn _assert_send<T: Send>(_: T) {}
+
+    _assert_send(Decoder::new());
+    _assert_send(Encoder::new(1));
+    _assert_send(Decoder::with_ref_prefix(&[]));
+    _assert_send(Encoder::with_ref_prefix(1, &[]));
+}
+
 #[cfg(test)]
 mod tests {
     use super::{Decoder, Encoder, InBuffer, Operation, OutBuffer};
@@ -320,4 +763,102 @@ mod tests {
 
         assert_eq!(initial_data, output.as_slice());
     }
+
+    #[test]
+    fn test_buffer_helpers() {
+        let mut encoder = Encoder::new(1).unwrap();
+        let mut compressed = Vec::new();
+
+        let mut input = InBuffer::around(b"AbcdefAbcdefabcdef");
+        let mut output = [0u8; 8];
+        let mut output = OutBuffer::around(&mut output);
+        // Drain the output a few bytes at a time.
+        while !input.remaining().is_empty() {
+            encoder.run(&mut input, &mut output).unwrap();
+            compressed.extend_from_slice(output.written());
+            output.reset();
+        }
+        loop {
+            let hint = encoder.finish(&mut output, true).unwrap();
+            compressed.extend_from_slice(output.written());
+            assert_eq!(output.remaining(), 8 - output.written().len());
+            output.reset();
+            if hint == 0 {
+                break;
+            }
+        }
+
+        input.reset();
+        assert_eq!(input.remaining(), b"AbcdefAbcdefabcdef");
+        assert_eq!(crate::decode_all(&compressed[..]).unwrap(), input.src);
+    }
+
+    #[test]
+    #[cfg(feature = "experimental")]
+    fn test_stable_buffers() {
+        use super::{CParameter, DParameter};
+
+        let initial_data: Vec<u8> =
+            (0..100_000u32).map(|i| (i % 1000) as u8).collect();
+
+        let mut encoder = Encoder::new(1).unwrap();
+        encoder
+            .set_parameter(CParameter::StableInBuffer(true))
+            .unwrap();
+        encoder
+            .set_parameter(CParameter::StableOutBuffer(true))
+            .unwrap();
+
+        let mut input = InBuffer::around(&initial_data);
+        let mut compressed = vec![0u8; zstd_safe::compress_bound(100_000)];
+        let mut output = OutBuffer::around(&mut compressed);
+        while encoder.run_to_end(&mut input, &mut output).unwrap() != 0 {}
+        let compressed = output.as_slice();
+
+        let mut decoder = Decoder::new().unwrap();
+        decoder
+            .set_parameter(DParameter::StableOutBuffer(true))
+            .unwrap();
+
+        let mut input = InBuffer::around(compressed);
+        let mut decompressed = vec![0u8; initial_data.len()];
+        let mut output = OutBuffer::around(&mut decompressed);
+        while decoder.run(&mut input, &mut output).unwrap() != 0 {}
+
+        assert_eq!(&initial_data[..], output.as_slice());
+    }
+
+    #[test]
+    fn test_context_round_trip() {
+        use super::CParameter;
+
+        let mut encoder = Encoder::new(1).unwrap();
+        encoder
+            .context_mut()
+            .set_parameter(CParameter::ChecksumFlag(true))
+            .unwrap();
+
+        // Leave and re-enter the safe wrappers.
+        let ptr = encoder.into_context().into_ptr();
+        let context = unsafe { zstd_safe::CCtx::from_ptr(ptr) };
+        let mut encoder = Encoder::from_context(context);
+        assert_eq!(encoder.context().as_ptr(), ptr);
+
+        let mut output = [0u8; 128];
+        let mut output = OutBuffer::around(&mut output);
+        encoder
+            .run(&mut InBuffer::around(b"AbcdefAbcdef"), &mut output)
+            .unwrap();
+        while encoder.finish(&mut output, true).unwrap() != 0 {}
+
+        // The frame header has the checksum flag.
+        assert_eq!(output.as_slice()[4] & 0x04, 0x04);
+
+        let mut decoder = Decoder::from_context(zstd_safe::DCtx::create());
+        let mut input = InBuffer::around(output.as_slice());
+        let mut decoded = [0u8; 128];
+        let mut decoded = OutBuffer::around(&mut decoded);
+        decoder.run(&mut input, &mut decoded).unwrap();
+        assert_eq!(decoded.as_slice(), b"AbcdefAbcdef");
+    }
 }
@@ -47,3 +47,23 @@ fn test_async_read_worker<R: AsyncRead, W: AsyncWrite>(
     let (_, _, w) = tokio_io::io::copy(decoder, w).wait()?;
     Ok(w)
 }
+
+#[test]
+fn test_decoder_stream() {
+    quickcheck(test as fn(_) -> _);
+
+    fn test(encode_ops: PartialWithErrors<GenWouldBlock>) {
+        use super::{Decoder, DecoderStream};
+        use crate::stream::encode_all;
+        use futures::Stream;
+
+        let source = "abc".repeat(1024 * 10).into_bytes();
+        let encoded = encode_all(&source[..], 1).unwrap();
+        let reader = PartialAsyncRead::new(&encoded[..], encode_ops);
+        let stream = DecoderStream::new(Decoder::new(reader).unwrap(), 1000);
+
+        let chunks = stream.collect().wait().unwrap();
+        assert!(chunks.iter().all(|chunk| chunk.len() <= 1000));
+        assert_eq!(chunks.concat(), source);
+    }
+}
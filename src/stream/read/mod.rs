@@ -1,6 +1,10 @@
 //! Implement pull-based [`Read`] trait for both compressing and decompressing.
 use std::io::{self, BufRead, BufReader, Read};
 
+#[cfg(feature = "tokio")]
+use bytes::Bytes;
+#[cfg(feature = "tokio")]
+use futures::{Async, Poll, Stream};
 #[cfg(feature = "tokio")]
 use tokio_io::AsyncRead;
 
@@ -8,6 +12,8 @@ use crate::dict::{DecoderDictionary, EncoderDictionary};
 use crate::stream::{raw, zio};
 use zstd_safe;
 
+pub use crate::stream::zio::{FrameBoundary, TrailingData};
+
 #[cfg(test)]
 #[cfg(feature = "tokio")]
 mod async_tests;
@@ -15,12 +21,116 @@ mod async_tests;
 #[cfg(test)]
 mod tests;
 
+// Largest preallocation trusted from a frame header, without a `max_size`.
+const MAX_PREALLOCATION: usize = 1 << 26;
+
 /// A decoder that decompress input data from another `Read`.
 ///
 /// This allows to read a stream of compressed data
 /// (good for files or heavy network stream).
+///
+/// It also implements `BufRead`, so it can be given directly to line-based
+/// parsers without an extra `BufReader`.
+///
+/// Frames declaring their content size are checked against it: a frame
+/// decoding to more or less data is an error, and so is input ending in the
+/// middle of a frame (`UnexpectedEof`).
 pub struct Decoder<'a, R: BufRead> {
     reader: zio::Reader<R, raw::Decoder<'a>>,
+
+    // Decompressed data for the `BufRead` implementation.
+    // Only allocated the first time `fill_buf` is called.
+    buffer: Vec<u8>,
+    // Range of `buffer` not consumed yet.
+    pos: usize,
+    filled: usize,
+
+    // Decompressed size declared by the current frame, if any.
+    content_size: Option<u64>,
+
+    // Set by `passthrough`.
+    passthrough: bool,
+    // `true` until we checked whether the input starts with a frame.
+    detect_passthrough: bool,
+
+    // Decompressed bytes returned to the caller so far.
+    delivered: u64,
+    // Last frame boundary entirely returned to the caller.
+    checkpoint: Option<FrameBoundary>,
+}
+
+/// Splits a multi-frame stream into one reader per frame.
+///
+/// This is returned by [`Decoder::frames`].
+///
+/// Since each frame borrows the decoder, this is not an `Iterator`: use
+/// [`Frames::next_frame`] instead.
+///
+/// [`Decoder::frames`]: struct.Decoder.html#method.frames
+/// [`Frames::next_frame`]: struct.Frames.html#method.next_frame
+pub struct Frames<'d, 'a, R: BufRead> {
+    decoder: &'d mut Decoder<'a, R>,
+}
+
+/// Reads the decompressed content of a single frame.
+///
+/// When dropped, the rest of the frame is skipped, so the next frame can be
+/// read from the same decoder.
+pub struct Frame<'f, 'a, R: BufRead> {
+    decoder: &'f mut Decoder<'a, R>,
+    started: bool,
+    done: bool,
+}
+
+/// Iterator over the decompressed content of a stream, in chunks.
+///
+/// This is returned by [`Decoder::into_chunks`].
+///
+/// [`Decoder::into_chunks`]: struct.Decoder.html#method.into_chunks
+pub struct Chunks<'a, R: BufRead> {
+    decoder: Decoder<'a, R>,
+    chunk_size: usize,
+    done: bool,
+}
+
+/// A `Stream` of decompressed chunks read from an async reader.
+///
+/// Each chunk is at most `chunk_size` bytes long, and contains whatever
+/// could be decompressed without blocking.
+#[cfg(feature = "tokio")]
+pub struct DecoderStream<'a, R: AsyncRead + BufRead> {
+    decoder: Decoder<'a, R>,
+    chunk_size: usize,
+    buffer: Vec<u8>,
+}
+
+/// Amount of data written by [`Decoder::read_into_ring`].
+///
+/// [`Decoder::read_into_ring`]: struct.Decoder.html#method.read_into_ring
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RingRead {
+    /// Number of bytes written at the start of the first slice.
+    pub first: usize,
+
+    /// Number of bytes written at the start of the second slice.
+    pub second: usize,
+}
+
+impl RingRead {
+    /// Returns the total number of bytes written.
+    pub fn len(&self) -> usize {
+        self.first + self.second
+    }
+
+    /// Returns `true` if nothing was written, at the end of the stream.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if the data wrapped around to the second slice.
+    pub fn wrapped(&self) -> bool {
+        self.second > 0
+    }
 }
 
 /// An encoder that compress input data from another `Read`.
@@ -30,6 +140,11 @@ pub struct Encoder<'a, R: BufRead> {
 
 impl<R: Read> Decoder<'static, BufReader<R>> {
     /// Creates a new decoder.
+    ///
+    /// The input is read through a `BufReader`. For data already in memory,
+    /// [`from_slice`] avoids this extra copy.
+    ///
+    /// [`from_slice`]: #method.from_slice
     pub fn new(reader: R) -> io::Result<Self> {
         let buffer_size = zstd_safe::DCtx::in_size();
 
@@ -37,6 +152,71 @@ impl<R: Read> Decoder<'static, BufReader<R>> {
     }
 }
 
+impl<'b> Decoder<'static, &'b [u8]> {
+    /// Creates a new decoder reading directly from an in-memory buffer.
+    ///
+    /// The compressed data is given straight to zstd, without going through
+    /// an intermediate buffer. Once done, [`finish`] returns the input left
+    /// after the decompressed data.
+    ///
+    /// [`finish`]: #method.finish
+    pub fn from_slice(data: &'b [u8]) -> io::Result<Self> {
+        Self::with_buffer(data)
+    }
+}
+
+impl<'a, R: Read> Decoder<'a, BufReader<R>> {
+    /// Returns the input read from the underlying reader, but not
+    /// decompressed yet.
+    ///
+    /// After the end of a [`single_frame`] decoder, this is the data
+    /// following the frame which was already pulled into the internal
+    /// buffer. It is missing from the reader returned by [`finish`]: use
+    /// [`into_remainder`] to continue reading right after the frame.
+    ///
+    /// [`single_frame`]: #method.single_frame
+    /// [`finish`]: #method.finish
+    /// [`into_remainder`]: #method.into_remainder
+    pub fn unconsumed_input(&self) -> &[u8] {
+        self.reader.reader().buffer()
+    }
+
+    /// Returns a reader over the input not decompressed yet.
+    ///
+    /// This is the [`unconsumed_input`], followed by the rest of the
+    /// underlying reader.
+    ///
+    /// [`unconsumed_input`]: #method.unconsumed_input
+    pub fn into_remainder(self) -> io::Chain<io::Cursor<Vec<u8>>, R> {
+        let reader = self.finish();
+        let unconsumed = reader.buffer().to_vec();
+        io::Cursor::new(unconsumed).chain(reader.into_inner())
+    }
+
+    /// Starts decoding a new stream from `reader`, and returns the previous
+    /// reader.
+    ///
+    /// The zstd context, dictionary, settings and buffers are all reused,
+    /// which is much cheaper than creating a new decoder: a connection pool
+    /// can keep one decoder per connection. Any data not decompressed yet
+    /// from the previous stream is dropped, including the
+    /// [`unconsumed_input`].
+    ///
+    /// To reuse a decoder created with [`with_buffer`], use
+    /// [`reset_with_buffer`] instead.
+    ///
+    /// [`unconsumed_input`]: #method.unconsumed_input
+    /// [`with_buffer`]: #method.with_buffer
+    /// [`reset_with_buffer`]: #method.reset_with_buffer
+    pub fn reset(&mut self, reader: R) -> io::Result<R> {
+        self.reset_state()?;
+        let buffered = self.reader.reader_mut();
+        let len = buffered.buffer().len();
+        buffered.consume(len);
+        Ok(std::mem::replace(buffered.get_mut(), reader))
+    }
+}
+
 impl<R: BufRead> Decoder<'static, R> {
     /// Creates a new decoder around a `BufRead`.
     pub fn with_buffer(reader: R) -> io::Result<Self> {
@@ -49,18 +229,133 @@ impl<R: BufRead> Decoder<'static, R> {
         let decoder = raw::Decoder::with_dictionary(dictionary)?;
         let reader = zio::Reader::new(reader, decoder);
 
-        Ok(Decoder { reader })
+        Ok(Decoder::from_zio(reader))
     }
 }
 impl<'a, R: BufRead> Decoder<'a, R> {
+    fn from_zio(reader: zio::Reader<R, raw::Decoder<'a>>) -> Self {
+        Decoder {
+            reader,
+            buffer: Vec::new(),
+            pos: 0,
+            filled: 0,
+            content_size: None,
+            passthrough: false,
+            detect_passthrough: false,
+            delivered: 0,
+            checkpoint: None,
+        }
+    }
+
+    /// Starts decoding a new stream from `reader`, and returns the previous
+    /// reader.
+    ///
+    /// This is the same as [`reset`], for any `BufRead`: the zstd context,
+    /// dictionary, settings and buffers are reused for the new stream.
+    ///
+    /// [`reset`]: #method.reset
+    pub fn reset_with_buffer(&mut self, reader: R) -> io::Result<R> {
+        self.reset_state()?;
+        Ok(std::mem::replace(self.reader.reader_mut(), reader))
+    }
+
+    // Clears everything about the current stream, keeping the settings.
+    fn reset_state(&mut self) -> io::Result<()> {
+        self.reader.operation_mut().reset()?;
+        self.reader.reset();
+        self.pos = 0;
+        self.filled = 0;
+        self.content_size = None;
+        self.detect_passthrough = self.passthrough;
+        self.delivered = 0;
+        self.checkpoint = None;
+        Ok(())
+    }
+
     /// Sets this `Decoder` to stop after the first frame.
     ///
     /// By default, it keeps concatenating frames until EOF is reached.
+    /// This is the same as `max_frames(1)`.
     pub fn single_frame(mut self) -> Self {
         self.reader.set_single_frame();
         self
     }
 
+    /// Sets this `Decoder` to stop after `max_frames` frames.
+    ///
+    /// Reading then ends cleanly, leaving the underlying reader right after
+    /// the last frame (see [`finish`] and [`into_remainder`]). Skippable
+    /// frames count towards this limit.
+    ///
+    /// If the input ends before that, reading ends there as usual.
+    ///
+    /// [`finish`]: #method.finish
+    /// [`into_remainder`]: #method.into_remainder
+    pub fn max_frames(mut self, max_frames: u64) -> Self {
+        self.reader.set_max_frames(Some(max_frames));
+        self
+    }
+
+    /// Sets how to handle data following the last frame.
+    ///
+    /// By default (`TrailingData::Strict`), anything after a frame must be
+    /// another frame, and garbage is reported as a decoding error. With
+    /// `TrailingData::Lenient`, the stream ends cleanly at the first data
+    /// which is not a frame: this data is consumed, and its size is given
+    /// by [`trailing_bytes`].
+    ///
+    /// This only applies after a complete frame: input which doesn't start
+    /// with a frame is always an error (see [`passthrough`]). With
+    /// [`single_frame`], nothing after the first frame is read at all.
+    ///
+    /// [`trailing_bytes`]: #method.trailing_bytes
+    /// [`passthrough`]: #method.passthrough
+    /// [`single_frame`]: #method.single_frame
+    pub fn trailing_data(mut self, trailing_data: TrailingData) -> Self {
+        self.reader.set_trailing_data(trailing_data);
+        self
+    }
+
+    /// Returns the size of the data ignored after the last frame.
+    ///
+    /// This is only set with `TrailingData::Lenient`, once the end of the
+    /// stream was reached and some trailing data was found.
+    pub fn trailing_bytes(&self) -> Option<u64> {
+        self.reader.trailing_bytes()
+    }
+
+    /// Sets this `Decoder` to pass the input through unchanged if it does
+    /// not start with a zstd frame.
+    ///
+    /// By default, such input is an error. This allows reading both
+    /// compressed and uncompressed files with the same code.
+    ///
+    /// Only the start of the input is checked: an invalid frame later in
+    /// the stream is still an error.
+    pub fn passthrough(mut self) -> Self {
+        self.passthrough = true;
+        self.detect_passthrough = true;
+        self
+    }
+
+    /// Sets this `Decoder` to accept an entirely empty input.
+    ///
+    /// By default, an empty input is an error, like any incomplete frame.
+    /// This instead decodes it to nothing, for producers which skip the
+    /// frame for empty content (see `Encoder::skip_empty_frame`).
+    pub fn allow_empty(mut self) -> Self {
+        self.reader.operation_mut().allow_empty(true);
+        self
+    }
+
+    /// Returns `true` if the input was not compressed, and is passed
+    /// through unchanged.
+    ///
+    /// This is only known after the first read.
+    pub fn is_passthrough(&self) -> bool {
+        self.reader.is_passthrough()
+    }
+
     /// Creates a new decoder, using an existing `DecoderDictionary`.
     ///
     /// The dictionary must be the same as the one used during compression.
@@ -74,7 +369,42 @@ impl<'a, R: BufRead> Decoder<'a, R> {
         let decoder = raw::Decoder::with_prepared_dictionary(dictionary)?;
         let reader = zio::Reader::new(reader, decoder);
 
-        Ok(Decoder { reader })
+        Ok(Decoder::from_zio(reader))
+    }
+
+    /// Creates a new decoder, referencing an existing dictionary.
+    ///
+    /// The dictionary must be the same as the one used during compression.
+    ///
+    /// The dictionary content is not copied, so it can be shared between
+    /// many decoders.
+    pub fn with_dictionary_by_reference<'b>(
+        reader: R,
+        dictionary: &'b [u8],
+    ) -> io::Result<Self>
+    where
+        'b: 'a,
+    {
+        let decoder = raw::Decoder::with_dictionary_by_reference(dictionary)?;
+        let reader = zio::Reader::new(reader, decoder);
+
+        Ok(Decoder::from_zio(reader))
+    }
+
+    /// Creates a new decoder, using a ref prefix.
+    ///
+    /// The prefix must be the same as the one used during compression.
+    pub fn with_ref_prefix<'b>(
+        reader: R,
+        ref_prefix: &'b [u8],
+    ) -> io::Result<Self>
+    where
+        'b: 'a,
+    {
+        let decoder = raw::Decoder::with_ref_prefix(ref_prefix)?;
+        let reader = zio::Reader::new(reader, decoder);
+
+        Ok(Decoder::from_zio(reader))
     }
 
     /// Recommendation for the size of the output buffer.
@@ -82,6 +412,20 @@ impl<'a, R: BufRead> Decoder<'a, R> {
         zstd_safe::DCtx::out_size()
     }
 
+    /// Accepts frames using windows up to `2^max_window_log` bytes.
+    ///
+    /// By default, frames requiring more than 128MB (`2^27`) are rejected,
+    /// to protect against excessive memory use. Frames compressed with long
+    /// distance matching and a larger window (like `zstd --long=31`) need
+    /// this, similar to `zstd --long=31 -d`.
+    ///
+    /// The decoder only allocates as much memory as the frame requires.
+    pub fn long_mode(&mut self, max_window_log: u32) -> io::Result<()> {
+        self.reader
+            .operation_mut()
+            .set_parameter(zstd_safe::DParameter::WindowLogMax(max_window_log))
+    }
+
     /// Enables or disabled expecting the 4-byte magic header
     pub fn include_magicbytes(
         &mut self,
@@ -111,18 +455,575 @@ impl<'a, R: BufRead> Decoder<'a, R> {
         self.reader.reader_mut()
     }
 
+    /// Returns a mutable reference to the underlying zstd context.
+    ///
+    /// This gives access to zstd functions not covered by this crate. Note
+    /// that mutation of the context may result in surprising results if
+    /// this decoder is continued to be used.
+    pub fn context_mut(&mut self) -> &mut zstd_safe::DCtx<'a> {
+        self.reader.operation_mut().context_mut()
+    }
+
+    /// Returns up to `n` bytes of decompressed data, without consuming them.
+    ///
+    /// The returned data will still be returned by the next reads. Less than
+    /// `n` bytes are only returned if the end of the stream was reached.
+    ///
+    /// This can be used to sniff the content before handing the decoder to
+    /// another consumer.
+    pub fn peek(&mut self, n: usize) -> io::Result<&[u8]> {
+        // Move the data not consumed yet to the front of the buffer.
+        self.buffer.copy_within(self.pos..self.filled, 0);
+        self.filled -= self.pos;
+        self.pos = 0;
+
+        let size = usize::max(n, zstd_safe::DCtx::out_size());
+        if self.buffer.len() < size {
+            self.buffer.resize(size, 0);
+        }
+
+        while self.filled < n {
+            self.update_content_size()?;
+            match self.reader.read(&mut self.buffer[self.filled..]) {
+                Ok(0) => break,
+                Ok(written) => self.filled += written,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(&self.buffer[..usize::min(n, self.filled)])
+    }
+
+    /// Skips the rest of the current frame.
+    ///
+    /// If no frame is in progress (before the first frame, or right after
+    /// the end of a frame), the entire next frame is skipped instead.
+    ///
+    /// Complete frames already available in the underlying reader are
+    /// skipped without being decompressed. Any decompressed data buffered
+    /// (by `peek` or `fill_buf`) is discarded.
+    pub fn skip_frame(&mut self) -> io::Result<()> {
+        let result = self.discard_frame();
+        // Skipped content counts as returned, for `checkpoint`.
+        self.delivered = self.reader.total_out();
+        self.update_checkpoint();
+        result
+    }
+
+    fn discard_frame(&mut self) -> io::Result<()> {
+        self.pos = 0;
+        self.filled = 0;
+
+        if self.reader.is_finished() {
+            return Ok(());
+        }
+
+        if self.reader.is_at_frame_boundary() {
+            let input = self.reader.reader_mut().fill_buf()?;
+            // This fails if the frame is not entirely in `input`.
+            if let Ok(size) = zstd_safe::find_frame_compressed_size(input) {
+                self.reader.skip_frame_input(size);
+                return Ok(());
+            }
+        }
+
+        if self.buffer.is_empty() {
+            self.buffer = vec![0; zstd_safe::DCtx::out_size()];
+        }
+
+        loop {
+            self.update_content_size()?;
+            match self.reader.read(&mut self.buffer) {
+                Ok(0) => return Ok(()),
+                Ok(_) if self.reader.is_at_frame_boundary() => return Ok(()),
+                Ok(_) => {}
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Returns the decompressed size declared by the current frame.
+    ///
+    /// This is only known once the frame header has been read, and only if
+    /// the frame includes its content size (for example frames produced by
+    /// `zstd::block::compress`). It is also unknown if the header is split
+    /// between two fills of the reader's buffer.
+    ///
+    /// This can be used to preallocate the output buffer.
+    pub fn content_size_hint(&self) -> Option<u64> {
+        self.content_size
+    }
+
+    /// Reads until `buf` is full, or the end of the stream is reached.
+    ///
+    /// `read` returns as soon as some data was decompressed, which can be
+    /// much less than requested. This keeps decompressing instead, which
+    /// saves many calls when reading fixed-size records.
+    ///
+    /// Returns the number of bytes read: this is only less than `buf.len()`
+    /// at the end of the stream (unlike `read_exact`, which fails then).
+    /// If an error occurs, the data read so far is lost.
+    pub fn read_max(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.read(&mut buf[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(filled)
+    }
+
+    /// Reads the rest of the stream into a new `Vec`.
+    ///
+    /// When the frame declares its content size, the output is allocated in
+    /// one go. Otherwise, or for the following frames, it grows as needed.
+    ///
+    /// If `max_size` is given, the preallocation never exceeds it, and
+    /// decompressing more than `max_size` bytes fails with an `InvalidData`
+    /// error. Without it, the preallocation is capped at 64MB, and the
+    /// output can grow without limit: give one when decompressing untrusted
+    /// data.
+    pub fn read_to_vec(
+        &mut self,
+        max_size: Option<usize>,
+    ) -> io::Result<Vec<u8>> {
+        let limit = max_size.unwrap_or(usize::MAX);
+        let mut output = Vec::new();
+
+        if self.pos == self.filled {
+            self.update_content_size()?;
+        }
+        if let Some(size) = self.content_size {
+            let cap = max_size.unwrap_or(MAX_PREALLOCATION);
+            output.reserve_exact(u64::min(size, cap as u64) as usize);
+        }
+
+        loop {
+            if output.len() == output.capacity() {
+                // Don't grow a full buffer just to find out we're done.
+                let mut probe = [0u8; 32];
+                let n = match self.read(&mut probe) {
+                    Ok(n) => n,
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {
+                        continue
+                    }
+                    Err(e) => return Err(e),
+                };
+                if n == 0 {
+                    return Ok(output);
+                }
+                output.extend_from_slice(&probe[..n]);
+            } else {
+                let len = output.len();
+                let end =
+                    usize::min(output.capacity(), limit.saturating_add(1));
+                output.resize(end, 0);
+                match self.read(&mut output[len..]) {
+                    Ok(0) => {
+                        output.truncate(len);
+                        return Ok(output);
+                    }
+                    Ok(n) => output.truncate(len + n),
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {
+                        output.truncate(len)
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+
+            if output.len() > limit {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "decompressed data exceeds the size limit",
+                ));
+            }
+        }
+    }
+
+    /// Decompresses data into the free space of a ring buffer.
+    ///
+    /// The free space is given as two slices: `first` runs from the write
+    /// position to the end of the ring, and `second` from the start of the
+    /// ring to the read position. `first` is filled before any data goes to
+    /// `second`, and data is decompressed directly into them, without an
+    /// intermediate buffer.
+    ///
+    /// Like `read`, this may write less than the available space. Nothing is
+    /// written only at the end of the stream.
+    pub fn read_into_ring(
+        &mut self,
+        first: &mut [u8],
+        second: &mut [u8],
+    ) -> io::Result<RingRead> {
+        let mut result = RingRead::default();
+        if !first.is_empty() {
+            result.first = self.read(first)?;
+        }
+        if result.first == first.len() && !second.is_empty() {
+            match self.read(second) {
+                Ok(n) => result.second = n,
+                // The error will come up again on the next call.
+                Err(_) if result.first > 0 => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(result)
+    }
+
+    // Reads the content size from the next frame header, if a new frame is
+    // about to start.
+    //
+    // This runs before every read from `self.reader`, so it also checks the
+    // first input for passthrough.
+    fn update_content_size(&mut self) -> io::Result<()> {
+        if self.reader.is_finished() || !self.reader.is_at_frame_boundary() {
+            return Ok(());
+        }
+
+        let input = self.reader.reader_mut().fill_buf()?;
+        if self.detect_passthrough {
+            self.detect_passthrough = false;
+            // The reader may hold only the first bytes of a frame for now:
+            // only pass through input which can't be the start of one.
+            if input.is_empty() || !zio::may_start_frame(input) {
+                self.reader.set_passthrough();
+                self.content_size = None;
+                return Ok(());
+            }
+        }
+        self.content_size = match zstd_safe::get_frame_content_size(input) {
+            zstd_safe::CONTENTSIZE_UNKNOWN | zstd_safe::CONTENTSIZE_ERROR => {
+                None
+            }
+            size => Some(size),
+        };
+        Ok(())
+    }
+
+    /// Returns where the last frame ended, if a frame ended since the last
+    /// call.
+    ///
+    /// Offsets are counted from the creation of this decoder. Decompressed
+    /// data may still be buffered in the decoder when the boundary is
+    /// reported: compare `decompressed_offset` with the amount of data read
+    /// so far to find the boundary in the output.
+    ///
+    /// Frames skipped with `skip_frame` without being decompressed do not
+    /// count towards `decompressed_offset`.
+    pub fn take_frame_boundary(&mut self) -> Option<FrameBoundary> {
+        self.reader.take_frame_boundary()
+    }
+
+    /// Returns the number of compressed bytes consumed from the underlying
+    /// reader so far.
+    ///
+    /// This includes frames skipped by `skip_frame`, but not input only
+    /// buffered by the reader (see `unconsumed_input`). It can be used to
+    /// report progress, or to locate the end of the compressed data in a
+    /// larger container.
+    pub fn total_in(&self) -> u64 {
+        self.reader.total_in()
+    }
+
+    /// Returns a position from which decoding can be resumed.
+    ///
+    /// This is the end of the last frame whose content was entirely returned
+    /// by this decoder, or `None` if no frame was completed yet.
+    ///
+    /// If decoding is interrupted (for example a download failing halfway),
+    /// it can be resumed later with a fresh decoder, reading the compressed
+    /// input from `compressed_offset`, and appending to the output truncated
+    /// to `decompressed_offset`. Both offsets are counted from the creation of
+    /// this decoder.
+    pub fn checkpoint(&self) -> Option<FrameBoundary> {
+        self.checkpoint
+    }
+
+    // Records the latest frame boundary once its content was delivered.
+    fn update_checkpoint(&mut self) {
+        if let Some(boundary) = self.reader.latest_frame_boundary() {
+            if boundary.decompressed_offset <= self.delivered {
+                self.checkpoint = Some(boundary);
+            }
+        }
+    }
+
+    /// Returns a reader for each frame of the stream.
+    ///
+    /// Each frame can be read independently; frames that are dropped before
+    /// the end are skipped.
+    ///
+    /// ```rust
+    /// # fn main() -> std::io::Result<()> {
+    /// use std::io::Read;
+    ///
+    /// let mut compressed = zstd::encode_all(&b"first"[..], 1)?;
+    /// compressed.extend(zstd::encode_all(&b"second"[..], 1)?);
+    ///
+    /// let mut decoder = zstd::Decoder::new(&compressed[..])?;
+    /// let mut frames = decoder.frames();
+    /// while let Some(mut frame) = frames.next_frame()? {
+    ///     let mut record = String::new();
+    ///     frame.read_to_string(&mut record)?;
+    ///     println!("{}", record);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn frames(&mut self) -> Frames<'_, 'a, R> {
+        self.reader.set_pause_at_frame_end(true);
+        Frames { decoder: self }
+    }
+
+    /// Turns this decoder into an iterator over chunks of decompressed data.
+    ///
+    /// Each chunk is `chunk_size` bytes long, except for the last one which
+    /// may be shorter. The iteration stops after the first error.
+    ///
+    /// # Panics
+    ///
+    /// If `chunk_size` is 0.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # fn main() -> std::io::Result<()> {
+    /// let compressed = zstd::encode_all(&b"Some data"[..], 0)?;
+    ///
+    /// let decoder = zstd::Decoder::new(&compressed[..])?;
+    /// for chunk in decoder.into_chunks(4) {
+    ///     println!("{:?}", chunk?);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn into_chunks(self, chunk_size: usize) -> Chunks<'a, R> {
+        assert!(chunk_size != 0, "chunk size must be non-zero");
+        Chunks {
+            decoder: self,
+            chunk_size,
+            done: false,
+        }
+    }
+
     /// Return the inner `Read`.
     ///
     /// Calling `finish()` is not *required* after reading a stream -
     /// just use it if you need to get the `Read` back.
+    ///
+    /// Only the input actually decompressed is consumed: after a
+    /// [`single_frame`] decoder, the returned reader is positioned right
+    /// after the frame.
+    ///
+    /// [`single_frame`]: #method.single_frame
     pub fn finish(self) -> R {
         self.reader.into_inner()
     }
 }
 
+impl<'d, 'a, R: BufRead> Frames<'d, 'a, R> {
+    /// Returns a reader for the next frame.
+    ///
+    /// Returns `None` when the end of the stream was reached.
+    pub fn next_frame(&mut self) -> io::Result<Option<Frame<'_, 'a, R>>> {
+        let decoder = &mut *self.decoder;
+        decoder.reader.resume();
+        if decoder.pos == decoder.filled
+            && (decoder.reader.is_finished()
+                || decoder.reader.reader_mut().fill_buf()?.is_empty())
+        {
+            return Ok(None);
+        }
+
+        Ok(Some(Frame {
+            decoder,
+            started: false,
+            done: false,
+        }))
+    }
+}
+
+impl<'a, R: BufRead> Chunks<'a, R> {
+    /// Returns the underlying decoder.
+    pub fn into_inner(self) -> Decoder<'a, R> {
+        self.decoder
+    }
+
+    fn read_chunk(&mut self) -> io::Result<Vec<u8>> {
+        let mut chunk = vec![0u8; self.chunk_size];
+        let mut filled = 0;
+        while filled < chunk.len() {
+            match self.decoder.read(&mut chunk[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        chunk.truncate(filled);
+        Ok(chunk)
+    }
+}
+
+impl<R: BufRead> Iterator for Chunks<'_, R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        match self.read_chunk() {
+            Ok(chunk) if chunk.is_empty() => {
+                self.done = true;
+                None
+            }
+            Ok(chunk) => {
+                if chunk.len() < self.chunk_size {
+                    self.done = true;
+                }
+                Some(Ok(chunk))
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl<R: BufRead> std::iter::FusedIterator for Chunks<'_, R> {}
+
+#[cfg(feature = "tokio")]
+impl<'a, R: AsyncRead + BufRead> DecoderStream<'a, R> {
+    /// Creates a new stream around the given decoder.
+    ///
+    /// # Panics
+    ///
+    /// If `chunk_size` is 0.
+    pub fn new(decoder: Decoder<'a, R>, chunk_size: usize) -> Self {
+        assert!(chunk_size != 0, "chunk size must be non-zero");
+        DecoderStream {
+            decoder,
+            chunk_size,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Acquires a reference to the underlying decoder.
+    pub fn get_ref(&self) -> &Decoder<'a, R> {
+        &self.decoder
+    }
+
+    /// Acquires a mutable reference to the underlying decoder.
+    pub fn get_mut(&mut self) -> &mut Decoder<'a, R> {
+        &mut self.decoder
+    }
+
+    /// Returns the underlying decoder.
+    pub fn into_inner(self) -> Decoder<'a, R> {
+        self.decoder
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<R: AsyncRead + BufRead> Stream for DecoderStream<'_, R> {
+    type Item = Bytes;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Bytes>, io::Error> {
+        self.buffer.resize(self.chunk_size, 0);
+        loop {
+            match self.decoder.read(&mut self.buffer) {
+                Ok(0) => return Ok(Async::Ready(None)),
+                Ok(n) => {
+                    return Ok(Async::Ready(Some(Bytes::from(
+                        &self.buffer[..n],
+                    ))))
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    return Ok(Async::NotReady)
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Read for Frame<'_, '_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.done {
+            return Ok(0);
+        }
+
+        self.started = true;
+        let n = self.decoder.read(buf)?;
+        if n == 0 {
+            self.done = true;
+        }
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> Drop for Frames<'_, '_, R> {
+    fn drop(&mut self) {
+        self.decoder.reader.set_pause_at_frame_end(false);
+    }
+}
+
+impl<R: BufRead> Drop for Frame<'_, '_, R> {
+    fn drop(&mut self) {
+        // If the frame was never started, it is still ahead of us.
+        if !self.done
+            && (!self.started || !self.decoder.reader.is_at_frame_boundary())
+        {
+            // Errors will surface when reading the next frame.
+            let _ = self.decoder.skip_frame();
+        }
+    }
+}
+
 impl<R: BufRead> Read for Decoder<'_, R> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.reader.read(buf)
+        // Bypass our own buffer when it's empty, like `BufReader` does.
+        if self.pos == self.filled {
+            self.update_content_size()?;
+            let n = self.reader.read(buf)?;
+            self.delivered += n as u64;
+            self.update_checkpoint();
+            return Ok(n);
+        }
+
+        let n = (&self.buffer[self.pos..self.filled]).read(buf)?;
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for Decoder<'_, R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.filled {
+            if self.buffer.is_empty() {
+                self.buffer = vec![0; zstd_safe::DCtx::out_size()];
+            }
+            self.update_content_size()?;
+            self.filled = self.reader.read(&mut self.buffer)?;
+            self.pos = 0;
+        }
+        Ok(&self.buffer[self.pos..self.filled])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        let amt = usize::min(amt, self.filled - self.pos);
+        self.pos += amt;
+        self.delivered += amt as u64;
+        self.update_checkpoint();
     }
 }
 
@@ -180,6 +1081,23 @@ impl<'a, R: BufRead> Encoder<'a, R> {
         Ok(Encoder { reader })
     }
 
+    /// Creates a new encoder, using a ref prefix.
+    ///
+    /// The same prefix must be given to the decoder.
+    pub fn with_ref_prefix<'b>(
+        reader: R,
+        level: i32,
+        ref_prefix: &'b [u8],
+    ) -> io::Result<Self>
+    where
+        'b: 'a,
+    {
+        let encoder = raw::Encoder::with_ref_prefix(level, ref_prefix)?;
+        let reader = zio::Reader::new(reader, encoder);
+
+        Ok(Encoder { reader })
+    }
+
     /// Recommendation for the size of the output buffer.
     pub fn recommended_output_size() -> usize {
         zstd_safe::CCtx::out_size()
@@ -198,6 +1116,15 @@ impl<'a, R: BufRead> Encoder<'a, R> {
         self.reader.reader_mut()
     }
 
+    /// Returns a mutable reference to the underlying zstd context.
+    ///
+    /// This gives access to zstd functions not covered by this crate. Note
+    /// that mutation of the context may result in surprising results if
+    /// this encoder is continued to be used.
+    pub fn context_mut(&mut self) -> &mut zstd_safe::CCtx<'a> {
+        self.reader.operation_mut().context_mut()
+    }
+
     /// Return the inner `Read`.
     ///
     /// Calling `finish()` is not *required* after reading a stream -
@@ -1,4 +1,4 @@
-use crate::stream::read::{Decoder, Encoder};
+use crate::stream::read::{Decoder, Encoder, FrameBoundary};
 use std::io::Read;
 
 #[test]
@@ -11,6 +11,81 @@ fn test_error_handling() {
     assert_eq!(output.is_err(), true);
 }
 
+#[test]
+fn test_error_context() {
+    use crate::stream::{DecodeError, DecodeStage};
+
+    let first = crate::encode_all(&b"first frame"[..], 1).unwrap();
+    let mut compressed = first.clone();
+    compressed.extend_from_slice(b"Abcdefghabcdefgh");
+
+    let mut decoder = Decoder::new(&compressed[..]).unwrap();
+    let error = decoder.read_to_end(&mut Vec::new()).unwrap_err();
+    let details = DecodeError::from_io_error(&error).unwrap();
+    assert_eq!(details.stage(), DecodeStage::Header);
+    assert_eq!(details.frame_index(), Some(1));
+    assert_eq!(details.frame_offset(), Some(first.len() as u64));
+    assert!(error.to_string().starts_with("Unknown frame descriptor"));
+
+    // Corrupt the checksum of a single frame.
+    let mut encoder =
+        crate::stream::write::Encoder::new(Vec::new(), 1).unwrap();
+    encoder.include_checksum(true).unwrap();
+    std::io::Write::write_all(&mut encoder, b"some content").unwrap();
+    let mut compressed = encoder.finish().unwrap();
+    *compressed.last_mut().unwrap() ^= 1;
+
+    let mut decoder = Decoder::new(&compressed[..]).unwrap();
+    let error = decoder.read_to_end(&mut Vec::new()).unwrap_err();
+    let details = DecodeError::from_io_error(&error).unwrap();
+    assert_eq!(details.stage(), DecodeStage::Checksum);
+    assert_eq!(details.frame_index(), Some(0));
+}
+
+#[test]
+fn test_bufread() {
+    use std::io::BufRead;
+
+    let input = "first line\nsecond line\n".repeat(1000);
+    let compressed = crate::encode_all(input.as_bytes(), 1).unwrap();
+
+    let mut decoder = Decoder::new(&compressed[..]).unwrap();
+
+    // Mix buffered and unbuffered reads.
+    let mut first = String::new();
+    decoder.read_line(&mut first).unwrap();
+    assert_eq!(first, "first line\n");
+
+    let mut rest = String::new();
+    decoder.read_to_string(&mut rest).unwrap();
+    assert_eq!(first + &rest, input);
+
+    let decoder = Decoder::new(&compressed[..]).unwrap();
+    assert_eq!(decoder.lines().count(), 2000);
+}
+
+#[test]
+fn test_peek() {
+    let input = b"%PDF-1.4 and then some more content";
+    let compressed = crate::encode_all(&input[..], 1).unwrap();
+
+    let mut decoder = Decoder::new(&compressed[..]).unwrap();
+    assert_eq!(decoder.peek(4).unwrap(), b"%PDF");
+    assert_eq!(decoder.peek(8).unwrap(), b"%PDF-1.4");
+
+    let mut buffer = [0u8; 5];
+    decoder.read_exact(&mut buffer).unwrap();
+    assert_eq!(&buffer, b"%PDF-");
+    assert_eq!(decoder.peek(3).unwrap(), b"1.4");
+
+    // Peeking past the end only returns what's available.
+    assert_eq!(decoder.peek(1000).unwrap(), &input[5..]);
+
+    let mut output = Vec::new();
+    decoder.read_to_end(&mut output).unwrap();
+    assert_eq!(&output[..], &input[5..]);
+}
+
 #[test]
 fn test_cycle() {
     let input = b"Abcdefghabcdefgh";
@@ -25,3 +100,546 @@ fn test_cycle() {
 
     assert_eq!(input, &buffer[..]);
 }
+
+#[test]
+fn test_skip_frame() {
+    let mut compressed = Vec::new();
+    for frame in &[&b"foo"[..], b"bar", b"baz"] {
+        compressed.extend(crate::encode_all(&frame[..], 1).unwrap());
+    }
+    let large: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+    compressed.extend(crate::encode_all(&large[..], 1).unwrap());
+    compressed.extend(crate::encode_all(&b"end"[..], 1).unwrap());
+
+    let mut decoder = Decoder::new(&compressed[..]).unwrap();
+
+    // Skip an entire frame.
+    decoder.skip_frame().unwrap();
+
+    // Skip the rest of a partially read frame.
+    let mut buffer = [0u8; 1];
+    decoder.read_exact(&mut buffer).unwrap();
+    assert_eq!(&buffer, b"b");
+    decoder.skip_frame().unwrap();
+
+    let mut buffer = [0u8; 3];
+    decoder.read_exact(&mut buffer).unwrap();
+    assert_eq!(&buffer, b"baz");
+
+    // This frame is larger than the input buffer.
+    assert_eq!(decoder.peek(2).unwrap(), &large[..2]);
+    decoder.skip_frame().unwrap();
+
+    let mut output = Vec::new();
+    decoder.read_to_end(&mut output).unwrap();
+    assert_eq!(&output, b"end");
+
+    // Nothing left to skip.
+    decoder.skip_frame().unwrap();
+}
+
+#[test]
+fn test_frames() {
+    let records: Vec<Vec<u8>> = (0..6u8)
+        .map(|i| {
+            (0..1000 * u32::from(i))
+                .map(|j| (j % 7) as u8 + i)
+                .collect()
+        })
+        .collect();
+    let mut compressed = Vec::new();
+    for record in &records {
+        compressed.extend(crate::encode_all(&record[..], 1).unwrap());
+    }
+
+    let mut decoder = Decoder::new(&compressed[..]).unwrap();
+    let mut frames = decoder.frames();
+    let mut i = 0;
+    while let Some(mut frame) = frames.next_frame().unwrap() {
+        if i == 2 {
+            // Only read the beginning of this record.
+            let mut buffer = [0u8; 10];
+            frame.read_exact(&mut buffer).unwrap();
+            assert_eq!(&buffer[..], &records[i][..10]);
+        } else if i == 4 {
+            // Read the exact content, without reaching the end.
+            let mut buffer = vec![0u8; records[i].len()];
+            frame.read_exact(&mut buffer).unwrap();
+            assert_eq!(buffer, records[i]);
+        } else if i != 3 {
+            let mut output = Vec::new();
+            frame.read_to_end(&mut output).unwrap();
+            assert_eq!(output, records[i]);
+        }
+        // Record 3 is skipped entirely.
+        i += 1;
+    }
+    assert_eq!(i, records.len());
+}
+
+#[test]
+fn test_content_size_hint() {
+    let input = vec![b'a'; 5000];
+    // Block compression always includes the content size.
+    let compressed = crate::block::compress(&input, 1).unwrap();
+
+    let mut decoder = Decoder::new(&compressed[..]).unwrap();
+    assert_eq!(decoder.content_size_hint(), None);
+
+    let mut buffer = [0u8; 10];
+    decoder.read_exact(&mut buffer).unwrap();
+    assert_eq!(decoder.content_size_hint(), Some(5000));
+
+    // Streaming compression doesn't know the size in advance.
+    let compressed = crate::encode_all(&input[..], 1).unwrap();
+    let mut decoder = Decoder::new(&compressed[..]).unwrap();
+    decoder.read_exact(&mut buffer).unwrap();
+    assert_eq!(decoder.content_size_hint(), None);
+}
+
+#[test]
+fn test_frame_boundary() {
+    let first = crate::encode_all(&b"first frame"[..], 1).unwrap();
+    let second = crate::encode_all(&b"second"[..], 1).unwrap();
+    let mut compressed = first.clone();
+    compressed.extend_from_slice(&second);
+
+    let mut decoder = Decoder::new(&compressed[..]).unwrap();
+    assert_eq!(decoder.take_frame_boundary(), None);
+
+    let mut buffer = [0u8; 11];
+    decoder.read_exact(&mut buffer).unwrap();
+    assert_eq!(
+        decoder.take_frame_boundary(),
+        Some(FrameBoundary {
+            compressed_offset: first.len() as u64,
+            decompressed_offset: 11,
+        })
+    );
+    // The boundary is only reported once.
+    assert_eq!(decoder.take_frame_boundary(), None);
+
+    let mut output = Vec::new();
+    decoder.read_to_end(&mut output).unwrap();
+    assert_eq!(
+        decoder.take_frame_boundary(),
+        Some(FrameBoundary {
+            compressed_offset: compressed.len() as u64,
+            decompressed_offset: 17,
+        })
+    );
+}
+
+#[test]
+fn test_passthrough() {
+    let read_all = |input: &[u8]| {
+        let mut decoder = Decoder::new(input).unwrap().passthrough();
+        let mut output = Vec::new();
+        decoder.read_to_end(&mut output).unwrap();
+        (output, decoder.is_passthrough())
+    };
+
+    let plain = b"not compressed at all".repeat(10000);
+    assert_eq!(read_all(&plain), (plain.clone(), true));
+    assert_eq!(read_all(b"ab"), (b"ab".to_vec(), true));
+    assert_eq!(read_all(b""), (Vec::new(), true));
+
+    let compressed = crate::encode_all(&plain[..], 1).unwrap();
+    assert_eq!(read_all(&compressed), (plain.clone(), false));
+
+    // The first fill may be shorter than a magic number.
+    for capacity in 1..4 {
+        let reader =
+            std::io::BufReader::with_capacity(capacity, &compressed[..]);
+        let mut decoder = Decoder::with_buffer(reader).unwrap().passthrough();
+        let mut output = Vec::new();
+        decoder.read_to_end(&mut output).unwrap();
+        assert_eq!(output, plain);
+        assert!(!decoder.is_passthrough());
+    }
+
+    // Without passthrough, plain input is still an error.
+    let mut decoder = Decoder::new(&plain[..]).unwrap();
+    assert!(decoder.read_to_end(&mut Vec::new()).is_err());
+}
+
+#[test]
+fn test_read_to_vec() {
+    let input = b"Hello world! ".repeat(1000);
+
+    // The content size is known: the output is allocated exactly.
+    let compressed = crate::block::compress(&input, 1).unwrap();
+    let output = Decoder::new(&compressed[..])
+        .unwrap()
+        .read_to_vec(None)
+        .unwrap();
+    assert_eq!(output, input);
+    assert_eq!(output.capacity(), input.len());
+
+    // Without it, the output grows as needed.
+    let mut encoder =
+        crate::stream::write::Encoder::new(Vec::new(), 1).unwrap();
+    std::io::Write::write_all(&mut encoder, &input).unwrap();
+    let compressed = encoder.finish().unwrap();
+    let output = Decoder::new(&compressed[..])
+        .unwrap()
+        .read_to_vec(Some(input.len()))
+        .unwrap();
+    assert_eq!(output, input);
+
+    // Going over the limit is an error.
+    let error = Decoder::new(&compressed[..])
+        .unwrap()
+        .read_to_vec(Some(input.len() - 1))
+        .unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+    // A forged header doesn't cause a huge allocation.
+    let mut forged = vec![0x28, 0xB5, 0x2F, 0xFD, 0xC0, 0x00];
+    forged.extend_from_slice(&(1u64 << 50).to_le_bytes());
+    forged.extend_from_slice(&[1, 0, 0]);
+    if let Ok(output) = Decoder::new(&forged[..]).unwrap().read_to_vec(None) {
+        assert!(output.is_empty());
+    }
+}
+
+#[test]
+fn test_read_into_ring() {
+    let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+    let compressed = crate::encode_all(&input[..], 1).unwrap();
+    let mut decoder = Decoder::new(&compressed[..]).unwrap();
+
+    // A small ring, drained by a consumer taking 100 bytes at a time.
+    let mut ring = [0u8; 256];
+    let (mut head, mut len) = (0, 0);
+    let mut output = Vec::new();
+    let mut wrapped = false;
+    loop {
+        let tail = (head + len) % ring.len();
+        let read = if tail >= head && len < ring.len() {
+            let (start, end) = ring.split_at_mut(tail);
+            decoder.read_into_ring(end, &mut start[..head]).unwrap()
+        } else {
+            let free = ring.len() - len;
+            decoder
+                .read_into_ring(&mut ring[tail..tail + free], &mut [])
+                .unwrap()
+        };
+        wrapped |= read.wrapped();
+        len += read.len();
+
+        if read.is_empty() && len == 0 {
+            break;
+        }
+        for _ in 0..usize::min(100, len) {
+            output.push(ring[head]);
+            head = (head + 1) % ring.len();
+            len -= 1;
+        }
+    }
+    assert!(wrapped);
+    assert_eq!(output, input);
+}
+
+#[test]
+fn test_into_chunks() {
+    let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+    let compressed = crate::encode_all(&input[..], 1).unwrap();
+
+    let chunks: Vec<Vec<u8>> = Decoder::new(&compressed[..])
+        .unwrap()
+        .into_chunks(3000)
+        .map(Result::unwrap)
+        .collect();
+    let lens: Vec<usize> = chunks.iter().map(Vec::len).collect();
+    assert_eq!(lens, [3000, 3000, 3000, 1000]);
+    assert_eq!(chunks.concat(), input);
+
+    // Corrupted data ends the iteration with an error.
+    let mut chunks = Decoder::new(&compressed[..compressed.len() / 2])
+        .unwrap()
+        .into_chunks(100_000);
+    assert!(chunks.next().unwrap().is_err());
+    assert!(chunks.next().is_none());
+}
+
+#[test]
+fn test_checkpoint() {
+    let chunks: Vec<Vec<u8>> = (0..4u8).map(|i| vec![i; 50_000]).collect();
+    let mut compressed = Vec::new();
+    for chunk in &chunks {
+        compressed.extend(crate::encode_all(&chunk[..], 1).unwrap());
+    }
+    let content = chunks.concat();
+
+    // Read part of the content, then "lose" the connection.
+    let mut decoder = Decoder::new(&compressed[..]).unwrap();
+    assert_eq!(decoder.checkpoint(), None);
+    let mut output = vec![0; 120_000];
+    decoder.read_exact(&mut output).unwrap();
+    let checkpoint = decoder.checkpoint().unwrap();
+    assert_eq!(checkpoint.decompressed_offset, 100_000);
+
+    // Resume from the checkpoint.
+    output.truncate(checkpoint.decompressed_offset as usize);
+    let start = checkpoint.compressed_offset as usize;
+    Decoder::new(&compressed[start..])
+        .unwrap()
+        .read_to_end(&mut output)
+        .unwrap();
+    assert_eq!(output, content);
+}
+
+#[test]
+fn test_long_mode() {
+    use zstd_safe::CParameter;
+
+    let input = b"long distance ".repeat(1000);
+    let mut encoder =
+        crate::stream::write::Encoder::new(Vec::new(), 1).unwrap();
+    let context = encoder.context_mut();
+    context
+        .set_parameter(CParameter::EnableLongDistanceMatching(true))
+        .unwrap();
+    context.set_parameter(CParameter::WindowLog(28)).unwrap();
+    std::io::Write::write_all(&mut encoder, &input).unwrap();
+    let compressed = encoder.finish().unwrap();
+
+    // The window is too large by default.
+    let mut decoder = Decoder::new(&compressed[..]).unwrap();
+    assert!(decoder.read_to_end(&mut Vec::new()).is_err());
+
+    let mut decoder = Decoder::new(&compressed[..]).unwrap();
+    decoder.long_mode(28).unwrap();
+    let mut output = Vec::new();
+    decoder.read_to_end(&mut output).unwrap();
+    assert_eq!(output, input);
+}
+
+#[test]
+fn test_content_size_mismatch() {
+    let data = vec![7u8; 300_000];
+    let frame = crate::frame::store(&data, false);
+
+    // Stored frames with multiple blocks have a 4-bytes content size after
+    // the magic number, header and window descriptors.
+    for &declared in &[1000u32, 299_999, 300_001] {
+        let mut frame = frame.clone();
+        frame[6..10].copy_from_slice(&declared.to_le_bytes());
+
+        let mut output = Vec::new();
+        let mut decoder = Decoder::new(&frame[..]).unwrap();
+        assert!(decoder.read_to_end(&mut output).is_err());
+        assert!(output.len() <= declared as usize);
+    }
+
+    // A frame cut at a block boundary looks valid, but is incomplete.
+    let truncated = &frame[..frame.len() - 3 - (300_000 - 2 * (128 << 10))];
+    let error = Decoder::new(truncated)
+        .unwrap()
+        .read_to_end(&mut Vec::new())
+        .unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn test_unconsumed_input() {
+    let mut input = crate::encode_all(&b"first frame"[..], 1).unwrap();
+    input.extend_from_slice(b"trailing data");
+
+    let mut decoder = Decoder::new(&input[..]).unwrap().single_frame();
+    let mut output = Vec::new();
+    decoder.read_to_end(&mut output).unwrap();
+    assert_eq!(output, b"first frame");
+    assert_eq!(decoder.unconsumed_input(), b"trailing data");
+
+    let mut rest = Vec::new();
+    decoder.into_remainder().read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, b"trailing data");
+
+    // A `BufRead` is left right after the frame.
+    let mut reader = std::io::Cursor::new(&input);
+    let mut decoder =
+        Decoder::with_buffer(&mut reader).unwrap().single_frame();
+    decoder.read_to_end(&mut Vec::new()).unwrap();
+    decoder.finish();
+    let mut rest = Vec::new();
+    reader.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, b"trailing data");
+}
+
+#[test]
+fn test_total_in() {
+    let data = b"progress ".repeat(10_000);
+    let mut input = crate::encode_all(&data[..], 1).unwrap();
+    let frame_len = input.len() as u64;
+    input.extend_from_slice(b"trailing data");
+
+    let mut decoder = Decoder::new(&input[..]).unwrap().single_frame();
+    assert_eq!(decoder.total_in(), 0);
+    let mut buffer = [0u8; 100];
+    decoder.read_exact(&mut buffer).unwrap();
+    assert!(decoder.total_in() > 0 && decoder.total_in() <= frame_len);
+
+    decoder.read_to_end(&mut Vec::new()).unwrap();
+    assert_eq!(decoder.total_in(), frame_len);
+}
+
+#[test]
+fn test_read_max() {
+    let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    let compressed = crate::encode_all(&data[..], 1).unwrap();
+
+    let mut decoder = Decoder::new(&compressed[..]).unwrap();
+    let mut record = vec![0u8; 30_000];
+    let mut output = Vec::new();
+    loop {
+        let len = decoder.read_max(&mut record).unwrap();
+        output.extend_from_slice(&record[..len]);
+        if len < record.len() {
+            break;
+        }
+    }
+    assert_eq!(output, data);
+    assert_eq!(decoder.read_max(&mut record).unwrap(), 0);
+}
+
+#[test]
+fn test_from_slice() {
+    let mut input = crate::encode_all(&b"in memory"[..], 1).unwrap();
+    input.extend_from_slice(b"rest");
+
+    let mut decoder = Decoder::from_slice(&input).unwrap().single_frame();
+    let mut output = Vec::new();
+    decoder.read_to_end(&mut output).unwrap();
+    assert_eq!(output, b"in memory");
+    assert_eq!(decoder.finish(), b"rest");
+}
+
+#[test]
+fn test_trailing_data() {
+    use crate::stream::read::TrailingData;
+
+    let mut input = crate::encode_all(&b"first"[..], 1).unwrap();
+    input.extend(crate::encode_all(&b"second"[..], 1).unwrap());
+    let frames = input.len();
+    input.extend_from_slice(b"\ngarbage");
+
+    let mut output = Vec::new();
+    let error = Decoder::new(&input[..])
+        .unwrap()
+        .read_to_end(&mut output)
+        .unwrap_err();
+    let details = crate::stream::DecodeError::from_io_error(&error).unwrap();
+    assert_eq!(details.frame_index(), Some(2));
+
+    let mut decoder = Decoder::new(&input[..])
+        .unwrap()
+        .trailing_data(TrailingData::Lenient);
+    let mut output = Vec::new();
+    decoder.read_to_end(&mut output).unwrap();
+    assert_eq!(output, b"firstsecond");
+    assert_eq!(decoder.trailing_bytes(), Some(8));
+    assert_eq!(decoder.total_in(), frames as u64);
+
+    // Without trailing data, nothing is reported.
+    let mut decoder = Decoder::new(&input[..frames])
+        .unwrap()
+        .trailing_data(TrailingData::Lenient);
+    decoder.read_to_end(&mut output).unwrap();
+    assert_eq!(decoder.trailing_bytes(), None);
+
+    // Short inputs are checked too.
+    for garbage in &[&b"\n"[..], b"\x28\xB5", b"\x28\xB6"] {
+        let mut input = input[..frames].to_vec();
+        input.extend_from_slice(garbage);
+        let mut decoder = Decoder::new(&input[..])
+            .unwrap()
+            .trailing_data(TrailingData::Lenient);
+        let result = decoder.read_to_end(&mut Vec::new());
+        // A truncated magic number is an incomplete frame.
+        assert_eq!(result.is_ok(), garbage != b"\x28\xB5");
+    }
+}
+
+#[test]
+fn test_max_frames() {
+    let mut input = Vec::new();
+    for frame in &[&b"one"[..], b"two", b"three"] {
+        input.extend(crate::encode_all(*frame, 1).unwrap());
+    }
+    let two_frames = crate::encode_all(&b"one"[..], 1).unwrap().len()
+        + crate::encode_all(&b"two"[..], 1).unwrap().len();
+
+    let mut decoder = Decoder::from_slice(&input).unwrap().max_frames(2);
+    let mut output = Vec::new();
+    decoder.read_to_end(&mut output).unwrap();
+    assert_eq!(output, b"onetwo");
+    assert_eq!(decoder.finish(), &input[two_frames..]);
+
+    // Skippable frames count too.
+    let mut skippable = vec![0x50, 0x2A, 0x4D, 0x18, 0, 0, 0, 0];
+    skippable.extend_from_slice(&input);
+    let mut decoder = Decoder::from_slice(&skippable).unwrap().max_frames(2);
+    let mut output = Vec::new();
+    decoder.read_to_end(&mut output).unwrap();
+    assert_eq!(output, b"one");
+
+    // Fewer frames than the limit is fine.
+    let mut decoder = Decoder::from_slice(&input).unwrap().max_frames(10);
+    let mut output = Vec::new();
+    decoder.read_to_end(&mut output).unwrap();
+    assert_eq!(output, b"onetwothree");
+
+    let mut decoder = Decoder::from_slice(&input).unwrap().max_frames(0);
+    assert_eq!(decoder.read(&mut [0u8; 16]).unwrap(), 0);
+    assert_eq!(decoder.finish(), &input[..]);
+}
+
+#[test]
+fn test_reset() {
+    let dictionary = crate::test_util::text(2_000, 1);
+    let first = crate::test_util::text(50_000, 2);
+    let second = crate::test_util::text(1_000, 3);
+    let encode = |data: &[u8]| {
+        let mut encoder = crate::stream::write::Encoder::with_dictionary(
+            Vec::new(),
+            3,
+            &dictionary,
+        )
+        .unwrap();
+        std::io::Write::write_all(&mut encoder, data).unwrap();
+        encoder.finish().unwrap()
+    };
+    let (first_frame, second_frame) = (encode(&first), encode(&second));
+
+    let reader = std::io::BufReader::new(&first_frame[..]);
+    let mut decoder = Decoder::with_dictionary(reader, &dictionary).unwrap();
+
+    // Stop in the middle of the first stream.
+    let mut buffer = [0u8; 100];
+    decoder.read_exact(&mut buffer).unwrap();
+    assert_eq!(&buffer[..], &first[..100]);
+
+    // The dictionary is kept for the next stream.
+    decoder.reset(&second_frame[..]).unwrap();
+    assert_eq!(decoder.total_in(), 0);
+    let mut output = Vec::new();
+    decoder.read_to_end(&mut output).unwrap();
+    assert_eq!(output, second);
+
+    // Same thing with any `BufRead`, keeping the settings.
+    let compressed = crate::encode_all(&second[..], 1).unwrap();
+    let mut decoder = Decoder::from_slice(&compressed).unwrap().passthrough();
+    decoder.read_exact(&mut buffer).unwrap();
+    let previous = decoder.reset_with_buffer(b"not compressed").unwrap();
+    assert!(previous.len() < compressed.len());
+    let mut output = Vec::new();
+    decoder.read_to_end(&mut output).unwrap();
+    assert_eq!(output, b"not compressed");
+
+    decoder.reset_with_buffer(&compressed).unwrap();
+    let mut output = Vec::new();
+    decoder.read_to_end(&mut output).unwrap();
+    assert_eq!(output, second);
+}
@@ -1,4 +1,7 @@
-use super::{copy_encode, decode_all, encode_all};
+use super::{
+    compressed_size, copy_encode, copy_encode_buffered, copy_encode_with_size,
+    decode_all, decode_all_into, encode_all, encode_all_into, verify,
+};
 use super::{Decoder, Encoder};
 
 use partial_io::{PartialOp, PartialWrite};
@@ -38,6 +41,52 @@ fn test_concatenated_frames() {
     );
 }
 
+#[test]
+fn test_ref_prefix() {
+    use std::io::Write;
+
+    let old = "Some document, version 1.\n".repeat(100);
+    let new = old.replace("1.", "2.");
+
+    let mut enc =
+        Encoder::with_ref_prefix(Vec::new(), 3, old.as_bytes()).unwrap();
+    enc.write_all(new.as_bytes()).unwrap();
+    let delta = enc.finish().unwrap();
+
+    assert!(delta.len() < encode_all(new.as_bytes(), 3).unwrap().len());
+
+    let mut dec =
+        Decoder::with_ref_prefix(&delta[..], old.as_bytes()).unwrap();
+    let mut decoded = Vec::new();
+    io::copy(&mut dec, &mut decoded).unwrap();
+    assert_eq!(decoded, new.as_bytes());
+}
+
+#[test]
+fn test_dictionary_by_reference() {
+    use std::io::{Read, Write};
+
+    let dictionary = "Some shared dictionary content. ".repeat(10);
+    let input = b"Some shared dictionary content, and more.";
+
+    let mut compressed = Vec::new();
+    Encoder::with_dictionary(&mut compressed, 3, dictionary.as_bytes())
+        .unwrap()
+        .auto_finish()
+        .write_all(input)
+        .unwrap();
+
+    let mut decoded = Vec::new();
+    Decoder::with_dictionary_by_reference(
+        &compressed[..],
+        dictionary.as_bytes(),
+    )
+    .unwrap()
+    .read_to_end(&mut decoded)
+    .unwrap();
+    assert_eq!(&decoded[..], &input[..]);
+}
+
 #[test]
 fn test_flush() {
     use std::io::Write;
@@ -217,7 +266,7 @@ fn test_legacy() {
 
 // Check that compressing+decompressing some data gives back the original
 fn test_full_cycle(input: &[u8], level: i32) {
-    crate::test_cycle_unwrap(
+    crate::test_util::cycle_unwrap(
         input,
         |data| encode_all(data, level),
         |data| decode_all(data),
@@ -243,3 +292,428 @@ fn test_ll_source() {
         test_full_cycle(data, level);
     }
 }
+
+#[cfg(feature = "legacy")]
+#[test]
+fn test_legacy_frames() {
+    use std::fs;
+    use std::io::{Read, Write};
+
+    let expected = include_bytes!("../../assets/example.txt");
+
+    for version in &[5, 6, 7] {
+        let filename = format!("assets/example.txt.v{}.zst", version);
+        let input = fs::read(filename).unwrap();
+        let double = [&input[..], &input[..]].concat();
+
+        // Legacy frames are still split and skipped one at a time.
+        let mut decoder = Decoder::new(&double[..]).unwrap();
+        decoder.skip_frame().unwrap();
+        let mut output = Vec::new();
+        decoder.read_to_end(&mut output).unwrap();
+        assert_eq!(&output[..], &expected[..], "v{} skip_frame", version);
+
+        let mut decoder = Decoder::new(&double[..]).unwrap();
+        let mut frames = decoder.frames();
+        let mut count = 0;
+        while let Some(mut frame) = frames.next_frame().unwrap() {
+            let mut output = Vec::new();
+            frame.read_to_end(&mut output).unwrap();
+            assert_eq!(&output[..], &expected[..], "v{} frames", version);
+            count += 1;
+        }
+        assert_eq!(count, 2);
+
+        // They are not mistaken for uncompressed data.
+        let mut decoder = Decoder::new(&input[..]).unwrap().passthrough();
+        let mut output = Vec::new();
+        decoder.read_to_end(&mut output).unwrap();
+        assert!(!decoder.is_passthrough());
+        assert_eq!(&output[..], &expected[..], "v{} passthrough", version);
+
+        let mut decoder =
+            crate::stream::write::Decoder::new(Vec::new()).unwrap();
+        decoder.write_all(&input).unwrap();
+        decoder.flush().unwrap();
+        assert_eq!(
+            &decoder.get_ref()[..],
+            &expected[..],
+            "v{} write",
+            version
+        );
+
+        let output = crate::block::decompress(&input, expected.len()).unwrap();
+        assert_eq!(&output[..], &expected[..], "v{} block", version);
+    }
+}
+
+#[test]
+fn test_copy_encode_with_size() {
+    let data = vec![7u8; 100_000];
+    let mut compressed = Vec::new();
+    copy_encode_with_size(&data[..], &mut compressed, 1, data.len() as u64)
+        .unwrap();
+    assert_eq!(
+        zstd_safe::get_frame_content_size(&compressed),
+        data.len() as u64
+    );
+    assert_eq!(decode_all(&compressed[..]).unwrap(), data);
+
+    // Extra input is left in the source.
+    let mut source = &data[..];
+    copy_encode_with_size(&mut source, io::sink(), 1, 10).unwrap();
+    assert_eq!(source.len(), data.len() - 10);
+
+    // Missing input is an error.
+    let error =
+        copy_encode_with_size(&data[..], io::sink(), 1, 200_000).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn test_verify() {
+    let mut compressed = Vec::new();
+    for _ in 0..3 {
+        let mut encoder = Encoder::new(Vec::new(), 1).unwrap();
+        encoder.include_checksum(true).unwrap();
+        io::Write::write_all(&mut encoder, &[42u8; 10_000]).unwrap();
+        compressed.extend(encoder.finish().unwrap());
+    }
+    verify(&compressed[..]).unwrap();
+
+    // A wrong checksum is detected.
+    let mut corrupted = compressed.clone();
+    *corrupted.last_mut().unwrap() ^= 1;
+    assert!(verify(&corrupted[..]).is_err());
+
+    // As well as a truncated frame or trailing garbage.
+    assert!(verify(&compressed[..compressed.len() - 1]).is_err());
+    compressed.extend_from_slice(b"garbage");
+    assert!(verify(&compressed[..]).is_err());
+}
+
+#[test]
+fn test_compressed_size() {
+    let data = b"Compressible data. ".repeat(1000);
+    let size = compressed_size(&data[..], 3).unwrap();
+    assert_eq!(size.original, data.len() as u64);
+    assert_eq!(
+        size.compressed,
+        encode_all(&data[..], 3).unwrap().len() as u64
+    );
+    assert!(size.ratio() > 10.0);
+
+    // Random-looking data does not compress.
+    let noise = crate::test_util::random_bytes(1000, 1);
+    assert!(compressed_size(&noise[..], 3).unwrap().ratio() < 1.0);
+}
+
+#[test]
+fn test_copy_encode_buffered() {
+    let data = b"Buffered data. ".repeat(10_000);
+
+    // Small buffers exercise the refill loop.
+    let source = io::BufReader::with_capacity(100, &data[..]);
+    let mut compressed = Vec::new();
+    copy_encode_buffered(source, &mut compressed, 1).unwrap();
+    assert_eq!(decode_all(&compressed[..]).unwrap(), data);
+
+    // The output is the same as going through `copy_encode`.
+    let mut expected = Vec::new();
+    copy_encode(&data[..], &mut expected, 1).unwrap();
+    let mut compressed = Vec::new();
+    copy_encode_buffered(&data[..], &mut compressed, 1).unwrap();
+    assert_eq!(compressed, expected);
+}
+
+#[test]
+fn test_all_into() {
+    let mut compressed = Vec::new();
+    let mut decompressed = Vec::new();
+    for i in 0..10 {
+        let message = format!("payload {}", i);
+        compressed.clear();
+        encode_all_into(message.as_bytes(), 1, &mut compressed).unwrap();
+        decompressed.clear();
+        decode_all_into(&compressed[..], &mut decompressed).unwrap();
+        assert_eq!(decompressed, message.as_bytes());
+    }
+
+    // Data is appended, and left untouched on error.
+    let mut output = b"prefix".to_vec();
+    decode_all_into(&compressed[..], &mut output).unwrap();
+    assert_eq!(output, b"prefixpayload 9");
+    let truncated = &compressed[..compressed.len() - 1];
+    assert!(decode_all_into(truncated, &mut output).is_err());
+    assert_eq!(output, b"prefixpayload 9");
+}
+
+#[test]
+fn test_throttled() {
+    use super::{copy_decode_throttled, copy_encode_throttled, RateLimit};
+    use std::time::{Duration, Instant};
+
+    let data = b"throttled data ".repeat(2000);
+
+    // 30KB at 100KB/s takes about 300ms.
+    let start = Instant::now();
+    let mut compressed = Vec::new();
+    copy_encode_throttled(
+        &data[..],
+        &mut compressed,
+        1,
+        RateLimit::Input(100_000),
+    )
+    .unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(250));
+
+    let start = Instant::now();
+    let mut decompressed = Vec::new();
+    copy_decode_throttled(
+        &compressed[..],
+        &mut decompressed,
+        RateLimit::Output(100_000),
+    )
+    .unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(250));
+    assert_eq!(decompressed, data);
+
+    // A zero rate is rejected.
+    let result =
+        copy_encode_throttled(&data[..], Vec::new(), 1, RateLimit::Output(0));
+    assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+    let result = copy_decode_throttled(
+        &compressed[..],
+        Vec::new(),
+        RateLimit::Input(0),
+    );
+    assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_copy_encode_cooperative() {
+    use super::copy_encode_cooperative;
+
+    let data = b"background job ".repeat(10_000);
+
+    let mut pauses = Vec::new();
+    let mut compressed = Vec::new();
+    copy_encode_cooperative(&data[..], &mut compressed, 1, 40_000, |done| {
+        pauses.push(done);
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(pauses, [40_000, 80_000, 120_000]);
+    assert_eq!(decode_all(&compressed[..]).unwrap(), data);
+
+    // The callback can abort the job.
+    let error =
+        copy_encode_cooperative(&data[..], io::sink(), 1, 1000, |_| {
+            Err(io::Error::new(io::ErrorKind::Other, "shutting down"))
+        })
+        .unwrap_err();
+    assert_eq!(error.to_string(), "shutting down");
+
+    let error =
+        copy_encode_cooperative(&data[..], io::sink(), 1, 0, |_| Ok(()))
+            .unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_empty_input() {
+    use std::io::{Read, Write};
+
+    // By default, empty input still gives a frame.
+    let frame = encode_all(&b""[..], 1).unwrap();
+    assert!(!frame.is_empty());
+    assert!(decode_all(&b""[..]).is_err());
+
+    let mut encoder = Encoder::new(Vec::new(), 1).unwrap();
+    encoder.skip_empty_frame(true);
+    encoder.write_all(b"").unwrap();
+    encoder.flush().unwrap();
+    assert!(encoder.finish().unwrap().is_empty());
+
+    let mut encoder = super::read::Encoder::new(&b""[..], 1).unwrap();
+    encoder.skip_empty_frame(true);
+    let mut output = Vec::new();
+    encoder.read_to_end(&mut output).unwrap();
+    assert!(output.is_empty());
+
+    // Non-empty input is not affected.
+    let mut encoder = Encoder::new(Vec::new(), 1).unwrap();
+    encoder.skip_empty_frame(true);
+    encoder.write_all(b"data").unwrap();
+    assert_eq!(decode_all(&encoder.finish().unwrap()[..]).unwrap(), b"data");
+
+    // Both forms decode to nothing.
+    for input in &[&b""[..], &frame[..]] {
+        let mut decoder = Decoder::new(*input).unwrap().allow_empty();
+        let mut output = Vec::new();
+        decoder.read_to_end(&mut output).unwrap();
+        assert!(output.is_empty());
+
+        let mut decoder = super::write::Decoder::new(Vec::new()).unwrap();
+        decoder.allow_empty(true);
+        decoder.write_all(input).unwrap();
+        assert!(decoder.finish().unwrap().is_empty());
+    }
+}
+
+#[test]
+fn test_copy_decode_with_limit() {
+    use super::{copy_decode_with_limit, LimitExceeded};
+
+    let data = b"limited ".repeat(10_000);
+    let compressed = encode_all(&data[..], 1).unwrap();
+
+    let mut output = Vec::new();
+    copy_decode_with_limit(&compressed[..], &mut output, data.len() as u64)
+        .unwrap();
+    assert_eq!(output, data);
+
+    let mut output = Vec::new();
+    let error = copy_decode_with_limit(&compressed[..], &mut output, 1000)
+        .unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(LimitExceeded::from_io_error(&error).unwrap().limit(), 1000);
+    assert_eq!(output.len(), 1000);
+
+    // Corrupted data is still reported as such.
+    let error =
+        copy_decode_with_limit(&b"garbage"[..], io::sink(), 1000).unwrap_err();
+    assert!(LimitExceeded::from_io_error(&error).is_none());
+}
+
+#[test]
+fn test_recompress() {
+    use super::recompress;
+
+    let first = crate::test_util::text(200_000, 1);
+    let second = crate::test_util::text(200_000, 2);
+    let mut input = encode_all(&first[..], 1).unwrap();
+    input.extend(encode_all(&second[..], 1).unwrap());
+    let data = [&first[..], &second[..]].concat();
+
+    let params = "level=19,checksum=1".parse().unwrap();
+    let mut output = Vec::new();
+    recompress(&input[..], &mut output, &params, true).unwrap();
+    assert_eq!(decode_all(&output[..]).unwrap(), data);
+    assert_eq!(crate::frame::checksums(&output).unwrap().len(), 2);
+    assert!(output.len() < input.len());
+
+    let mut output = Vec::new();
+    recompress(&input[..], &mut output, &params, false).unwrap();
+    assert_eq!(decode_all(&output[..]).unwrap(), data);
+    assert_eq!(crate::frame::checksums(&output).unwrap().len(), 1);
+
+    // Skippable frames and declared content sizes are kept.
+    let mut input = Vec::new();
+    crate::frame::write_skippable_frame(&mut input, 3, b"metadata").unwrap();
+    super::copy_encode_with_size(&first[..], &mut input, 1, 200_000).unwrap();
+    input.extend(encode_all(&second[..], 1).unwrap());
+    let mut output = Vec::new();
+    recompress(&input[..], &mut output, &params, true).unwrap();
+    assert_eq!(decode_all(&output[..]).unwrap(), data);
+    assert_eq!(
+        crate::frame::skippable_frames(&output).unwrap(),
+        vec![(3, &b"metadata"[..])]
+    );
+    let frame = &output[16..];
+    assert_eq!(zstd_safe::get_frame_content_size(frame), 200_000);
+    let size = zstd_safe::find_frame_compressed_size(frame).unwrap();
+    assert_eq!(
+        zstd_safe::get_frame_content_size(&frame[size..]),
+        zstd_safe::CONTENTSIZE_UNKNOWN
+    );
+
+    // Truncated input is reported.
+    let mut output = Vec::new();
+    let truncated = &input[..input.len() - 1];
+    assert!(recompress(truncated, &mut output, &params, true).is_err());
+    let mut output = Vec::new();
+    assert!(recompress(&input[..12], &mut output, &params, true).is_err());
+
+    // Invalid input is reported.
+    let mut output = Vec::new();
+    assert!(recompress(&b"garbage"[..], &mut output, &params, true).is_err());
+}
+
+#[test]
+fn test_frame_limit_consistency() {
+    use super::raw::{self, Operation};
+    use std::io::{Read, Write};
+
+    let mut input = Vec::new();
+    for frame in &[&b"one"[..], b"two", b"three"] {
+        input.extend(encode_all(*frame, 1).unwrap());
+    }
+    input.extend_from_slice(b"trailing data");
+
+    for max_frames in 0..4u64 {
+        let expected = &b"onetwothree"[..[0, 3, 6, 11][max_frames as usize]];
+
+        // Raw decoder, fed one byte at a time.
+        let mut decoder = raw::Decoder::new().unwrap();
+        decoder.set_max_frames(Some(max_frames));
+        let mut raw_output = Vec::new();
+        let mut raw_consumed = 0;
+        while !decoder.is_done() && raw_consumed < input.len() {
+            let mut buffer = [0u8; 16];
+            let status = decoder
+                .run_on_buffers(
+                    &input[raw_consumed..=raw_consumed],
+                    &mut buffer,
+                )
+                .unwrap();
+            raw_output.extend_from_slice(&buffer[..status.bytes_written]);
+            raw_consumed += status.bytes_read;
+        }
+        assert_eq!(raw_output, expected);
+        assert_eq!(decoder.frames(), max_frames.min(3));
+
+        let mut decoder = Decoder::with_buffer(&input[..])
+            .unwrap()
+            .max_frames(max_frames);
+        let mut output = Vec::new();
+        decoder.read_to_end(&mut output).unwrap();
+        assert_eq!(output, expected);
+        assert_eq!(decoder.finish(), &input[raw_consumed..]);
+
+        let mut decoder = super::write::Decoder::new(Vec::new()).unwrap();
+        decoder.max_frames(Some(max_frames));
+        let mut written = 0;
+        let error = loop {
+            match decoder.write(&input[written..]) {
+                Ok(n) => written += n,
+                Err(e) => break e,
+            }
+        };
+        assert!(super::FrameLimitReached::from_io_error(&error).is_some());
+        assert_eq!(written, raw_consumed);
+        assert_eq!(decoder.finish().unwrap(), expected);
+    }
+}
+
+#[test]
+fn test_extension_traits() {
+    use super::{ZstdReadExt, ZstdWriteExt};
+    use std::io::{Read, Write};
+
+    let data = crate::test_util::text(100_000, 3);
+
+    let mut encoder = Vec::new().zstd_encode(1).unwrap();
+    encoder.write_all(&data).unwrap();
+    let compressed = encoder.finish().unwrap();
+    assert_eq!(decode_all(&compressed[..]).unwrap(), data);
+
+    let mut output = Vec::new();
+    compressed
+        .as_slice()
+        .zstd_decode()
+        .unwrap()
+        .read_to_end(&mut output)
+        .unwrap();
+    assert_eq!(output, data);
+}
@@ -83,3 +83,95 @@ fn test_async_write_worker<
         .map(f);
     executor::spawn(copy_future).wait_future().unwrap()
 }
+
+#[test]
+fn test_encoder_sink() {
+    quickcheck(test as fn(_) -> _);
+
+    fn test(encode_ops: PartialWithErrors<GenInterruptedWouldBlock>) {
+        use super::{Encoder, EncoderSink};
+        use bytes::Bytes;
+        use futures::{stream, Stream};
+
+        let messages: Vec<Bytes> = (0..100)
+            .map(|i| Bytes::from(format!("message {}\n", i).repeat(i)))
+            .collect();
+        let writer =
+            PartialAsyncWrite::new(Cursor::new(Vec::new()), encode_ops);
+        let sink = EncoderSink::new(Encoder::new(writer, 1).unwrap());
+
+        let (_, sink) = stream::iter_ok::<_, io::Error>(messages.clone())
+            .forward(sink)
+            .wait()
+            .unwrap();
+        let output = sink.into_inner().finish().unwrap();
+        let output = output.into_inner().into_inner();
+
+        let decoded = crate::stream::decode_all(&output[..]).unwrap();
+        assert_eq!(decoded, messages.concat());
+    }
+}
+
+#[test]
+fn test_encoder_sink_idle_flush() {
+    use super::{Encoder, EncoderSink};
+    use bytes::Bytes;
+    use futures::sync::mpsc;
+    use futures::Stream;
+    use std::io::{Read, Write};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    #[derive(Clone)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl AsyncWrite for Shared {
+        fn shutdown(&mut self) -> Poll<(), io::Error> {
+            Ok(().into())
+        }
+    }
+
+    let output = Shared(Arc::new(Mutex::new(Vec::new())));
+    let mut encoder = Encoder::new(output.clone(), 1).unwrap();
+    encoder.idle_flush(Some(Duration::from_millis(20)));
+    let sink = EncoderSink::new(encoder);
+
+    let (sender, receiver) = mpsc::unbounded();
+    let forward = std::thread::spawn(move || {
+        receiver
+            .map_err(|()| io::Error::new(io::ErrorKind::Other, "closed"))
+            .forward(sink)
+            .wait()
+            .map(|_| ())
+    });
+
+    // The message goes out while the stream stays open.
+    sender.unbounded_send(Bytes::from("ping")).unwrap();
+    let start = Instant::now();
+    let mut message = [0u8; 4];
+    loop {
+        let data = output.0.lock().unwrap().clone();
+        let decoder = crate::stream::read::Decoder::new(&data[..]).unwrap();
+        if decoder.take(4).read_exact(&mut message).is_ok() {
+            break;
+        }
+        assert!(start.elapsed() < Duration::from_secs(5), "never flushed");
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    assert_eq!(&message, b"ping");
+
+    drop(sender);
+    forward.join().unwrap().unwrap();
+    let data = output.0.lock().unwrap().clone();
+    assert_eq!(crate::stream::decode_all(&data[..]).unwrap(), b"ping");
+}
@@ -0,0 +1,271 @@
+use std::io::{self, Write};
+use std::mem;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread::{self, JoinHandle};
+
+use super::Encoder;
+
+// Input is handed to the worker in chunks of about this size.
+const CHUNK_SIZE: usize = 128 * 1024;
+
+// Number of chunks waiting for the worker before `write` blocks, and
+// default number of compressed chunks waiting in the output channel.
+const QUEUE_LENGTH: usize = 16;
+
+enum Message {
+    Data(Vec<u8>),
+    Flush,
+}
+
+/// An encoder compressing data on a dedicated worker thread.
+///
+/// Writing only copies the data and hands it to the worker, so the calling
+/// thread doesn't spend time compressing. It only blocks when the worker
+/// falls behind by more than a few megabytes.
+///
+/// The compressed output is either sent as chunks on a channel (see
+/// [`new`]), or written to a writer owned by the worker (see
+/// [`with_writer`]).
+///
+/// Errors from the worker (for example when writing the output) are
+/// returned by the next `write` or `flush`, or by [`finish`]. If dropped
+/// without calling `finish`, the worker finishes the stream in the
+/// background, and errors are ignored.
+///
+/// [`new`]: #method.new
+/// [`with_writer`]: #method.with_writer
+/// [`finish`]: #method.finish
+pub struct ChannelEncoder {
+    // Data not sent to the worker yet.
+    buffer: Vec<u8>,
+
+    // Both are only `None` once the worker is stopped.
+    sender: Option<SyncSender<Message>>,
+    worker: Option<JoinHandle<io::Result<()>>>,
+}
+
+// Sends each write as a chunk on a channel.
+struct ChunkWriter(SyncSender<Vec<u8>>);
+
+impl Write for ChunkWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.send(buf.to_vec()).map_err(|_| {
+            io::Error::new(io::ErrorKind::BrokenPipe, "receiver was dropped")
+        })?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl ChannelEncoder {
+    /// Creates a new encoder, sending the compressed chunks to the returned
+    /// receiver.
+    ///
+    /// The receiver is disconnected once the stream is finished. Dropping it
+    /// makes the encoder fail.
+    ///
+    /// Up to 16 chunks wait in the channel: after that, the worker (and
+    /// eventually `write` and `finish`) blocks until the receiver catches
+    /// up. The receiver should run on another thread. See [`with_bound`] to
+    /// change this.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`).
+    ///
+    /// [`with_bound`]: #method.with_bound
+    pub fn new(level: i32) -> io::Result<(Self, Receiver<Vec<u8>>)> {
+        Self::with_bound(level, QUEUE_LENGTH)
+    }
+
+    /// Creates a new encoder like [`new`], with up to `bound` compressed
+    /// chunks waiting in the channel.
+    ///
+    /// [`new`]: #method.new
+    pub fn with_bound(
+        level: i32,
+        bound: usize,
+    ) -> io::Result<(Self, Receiver<Vec<u8>>)> {
+        let (sender, receiver) = mpsc::sync_channel(bound);
+        let encoder = Self::with_writer(ChunkWriter(sender), level)?;
+        Ok((encoder, receiver))
+    }
+
+    /// Creates a new encoder, writing the compressed data to `writer` from
+    /// the worker thread.
+    ///
+    /// A level of `0` uses zstd's default (currently `3`).
+    pub fn with_writer<W>(writer: W, level: i32) -> io::Result<Self>
+    where
+        W: Write + Send + 'static,
+    {
+        let encoder = Encoder::new(writer, level)?;
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_LENGTH);
+        let worker = thread::spawn(move || run(encoder, receiver));
+
+        Ok(ChannelEncoder {
+            buffer: Vec::with_capacity(CHUNK_SIZE),
+            sender: Some(sender),
+            worker: Some(worker),
+        })
+    }
+
+    /// Finishes the stream, and waits for the worker to write it entirely.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.send_buffer()?;
+        self.stop()
+    }
+
+    // Stops the worker, returning its result.
+    fn stop(&mut self) -> io::Result<()> {
+        // Disconnecting the channel tells the worker to finish.
+        self.sender = None;
+        match self.worker.take() {
+            Some(worker) => worker.join().unwrap_or_else(|_| {
+                Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "compression thread panicked",
+                ))
+            }),
+            None => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "compression thread already stopped",
+            )),
+        }
+    }
+
+    fn send(&mut self, message: Message) -> io::Result<()> {
+        let sent = match &self.sender {
+            Some(sender) => sender.send(message).is_ok(),
+            None => false,
+        };
+        if sent {
+            return Ok(());
+        }
+        // The worker stopped early: report why.
+        match self.stop() {
+            Err(e) => Err(e),
+            Ok(()) => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "compression thread stopped",
+            )),
+        }
+    }
+
+    fn send_buffer(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let chunk =
+            mem::replace(&mut self.buffer, Vec::with_capacity(CHUNK_SIZE));
+        self.send(Message::Data(chunk))
+    }
+}
+
+fn run<W: Write>(
+    mut encoder: Encoder<'static, W>,
+    receiver: Receiver<Message>,
+) -> io::Result<()> {
+    for message in receiver {
+        match message {
+            Message::Data(chunk) => encoder.write_all(&chunk)?,
+            Message::Flush => encoder.flush()?,
+        }
+    }
+    encoder.finish()?.flush()
+}
+
+impl Write for ChannelEncoder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.buffer.len() >= CHUNK_SIZE {
+            self.send_buffer()?;
+        }
+        let len = usize::min(buf.len(), CHUNK_SIZE - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+
+    /// Sends the buffered data to the worker, and asks it to flush.
+    ///
+    /// This does not wait for the worker: the flushed output is available
+    /// once the worker reaches this point.
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_buffer()?;
+        self.send(Message::Flush)
+    }
+}
+
+impl Drop for ChannelEncoder {
+    // Lets the worker finish the stream in the background.
+    fn drop(&mut self) {
+        if self.worker.is_some() {
+            let _ = self.send_buffer();
+        }
+    }
+}
+
+fn _assert_traits() {
+    fn _assert_send<T: Send>(_: T) {}
+
+    _assert_send(ChannelEncoder::new(1));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChannelEncoder;
+    use std::io::{self, Write};
+
+    #[test]
+    fn test_channel_encoder() {
+        let data = b"channel encoder ".repeat(50_000);
+
+        let (mut encoder, receiver) = ChannelEncoder::new(1).unwrap();
+        for chunk in data.chunks(1000) {
+            encoder.write_all(chunk).unwrap();
+        }
+        encoder.finish().unwrap();
+
+        let compressed: Vec<u8> = receiver.iter().flatten().collect();
+        assert_eq!(crate::decode_all(&compressed[..]).unwrap(), data);
+    }
+
+    #[test]
+    fn test_bound() {
+        let data = crate::test_util::text(2_000_000, 3);
+
+        // The output is bigger than the channel: it must be received while
+        // writing.
+        let (mut encoder, receiver) =
+            ChannelEncoder::with_bound(1, 1).unwrap();
+        let consumer = std::thread::spawn(move || {
+            receiver.iter().flatten().collect::<Vec<u8>>()
+        });
+        encoder.write_all(&data).unwrap();
+        encoder.finish().unwrap();
+
+        let compressed = consumer.join().unwrap();
+        assert_eq!(crate::decode_all(&compressed[..]).unwrap(), data);
+    }
+
+    #[test]
+    fn test_worker_error() {
+        struct Failing;
+
+        impl Write for Failing {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::Other, "disk full"))
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut encoder = ChannelEncoder::with_writer(Failing, 1).unwrap();
+        encoder.write_all(b"data").unwrap();
+        encoder.flush().unwrap();
+        let error = encoder.finish().unwrap_err();
+        assert_eq!(error.to_string(), "disk full");
+    }
+}
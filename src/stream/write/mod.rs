@@ -1,8 +1,11 @@
 //! Implement push-based [`Write`] trait for both compressing and decompressing.
-use std::io::{self, Write};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::time::{Duration, Instant};
 
 #[cfg(feature = "tokio")]
-use futures::Poll;
+use bytes::Bytes;
+#[cfg(feature = "tokio")]
+use futures::{Async, AsyncSink, Poll, Sink, StartSend};
 #[cfg(feature = "tokio")]
 use tokio_io::AsyncWrite;
 
@@ -11,13 +14,96 @@ use zstd_safe;
 use crate::dict::{DecoderDictionary, EncoderDictionary};
 use crate::stream::{raw, zio};
 
+mod channel;
+mod tee;
+
 #[cfg(test)]
 #[cfg(feature = "tokio")]
 mod async_tests;
 
+pub use self::channel::ChannelEncoder;
+pub use self::tee::TeeEncoder;
+pub use crate::stream::zio::State;
+
 #[cfg(test)]
 mod tests;
 
+/// A `Sink` compressing each received buffer into an async writer.
+///
+/// All buffers are written to the same compressed stream. This is useful to
+/// plug an `Encoder` at the end of a message-oriented async pipeline.
+///
+/// Backpressure comes from the underlying writer: a new buffer is only
+/// accepted once the previous one was entirely given to the encoder.
+///
+/// Closing the sink finishes the stream and shuts the writer down.
+///
+/// By default, `poll_complete` flushes the encoder right away. If the
+/// encoder has an [`idle_flush`] delay, the flush instead waits until no
+/// buffer was received for that long: messages arriving in a burst are
+/// compressed together, and a quiet connection still gets them in time.
+///
+/// [`idle_flush`]: struct.Encoder.html#method.idle_flush
+#[cfg(feature = "tokio")]
+pub struct EncoderSink<'a, W: AsyncWrite> {
+    encoder: Encoder<'a, W>,
+
+    // Part of the last buffer not given to the encoder yet.
+    pending: Option<Bytes>,
+
+    // When the last wake-up for an idle flush is due.
+    wake_up: Option<Instant>,
+
+    // Created on the first idle flush wake-up.
+    timer: Option<Timer>,
+}
+
+// Wakes tasks up at a given time, from a single helper thread.
+//
+// futures 0.1 has no timer of its own. Only the last requested wake-up is
+// kept, and the thread stops once the timer is dropped.
+#[cfg(feature = "tokio")]
+struct Timer {
+    sender: std::sync::mpsc::Sender<(Instant, futures::task::Task)>,
+}
+
+#[cfg(feature = "tokio")]
+impl Timer {
+    fn new() -> Self {
+        use std::sync::mpsc::{self, RecvTimeoutError};
+
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut next: Option<(Instant, futures::task::Task)> = None;
+            loop {
+                let received = match &next {
+                    Some((deadline, _)) => receiver.recv_timeout(
+                        deadline.saturating_duration_since(Instant::now()),
+                    ),
+                    None => receiver
+                        .recv()
+                        .map_err(|_| RecvTimeoutError::Disconnected),
+                };
+                match received {
+                    Ok(wake_up) => next = Some(wake_up),
+                    Err(RecvTimeoutError::Timeout) => {
+                        if let Some((_, task)) = next.take() {
+                            task.notify();
+                        }
+                    }
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+        });
+        Timer { sender }
+    }
+
+    fn wake_up_at(&self, deadline: Instant, task: futures::task::Task) {
+        // The thread only stops once we are dropped.
+        let _ = self.sender.send((deadline, task));
+    }
+}
+
 /// An encoder that compress and forward data to another writer.
 ///
 /// This allows to compress a stream of data
@@ -31,6 +117,33 @@ mod tests;
 pub struct Encoder<'a, W: Write> {
     // output writer (compressed data)
     writer: zio::Writer<W, raw::Encoder<'a>>,
+
+    // Set by `reserve_content_size`.
+    // Boxed to keep `try_finish`'s error small.
+    header_patch: Option<Box<HeaderPatch>>,
+
+    // Set by `idle_flush`.
+    // Boxed to keep `try_finish`'s error small.
+    idle_flush: Option<Box<IdleFlush>>,
+
+    // Set by `auto_flush_every`.
+    auto_flush_every: Option<usize>,
+    // Input consumed since the last flush.
+    unflushed: usize,
+}
+
+struct IdleFlush {
+    delay: Duration,
+    // Time of the last write not flushed yet.
+    last_write: Option<Instant>,
+}
+
+// Where and how to write the content size once it is known.
+struct HeaderPatch {
+    // Position of the reserved space, right before the frame header.
+    start: u64,
+    // Number of uncompressed bytes written so far.
+    content_size: u64,
 }
 
 /// A decoder that decompress and forward data to another writer.
@@ -39,7 +152,29 @@ pub struct Decoder<'a, W: Write> {
     writer: zio::Writer<W, raw::Decoder<'a>>,
 }
 
+/// A wrapper around a `Decoder<W>` that finishes the stream on drop.
+///
+/// Finishing flushes any decompressed data, and reports an error if the
+/// last frame is incomplete. It never panics: errors are given to the
+/// `on_finish` callback, or can be handled directly by calling [`finish`]
+/// instead of dropping. With `auto_finish()`, they are discarded, leaving a
+/// truncated output.
+///
+/// [`finish`]: #method.finish
+pub struct AutoFinishDecoder<'a, W: Write> {
+    writer: zio::AutoFinishWriter<W, raw::Decoder<'a>>,
+}
+
 /// A wrapper around an `Encoder<W>` that finishes the stream on drop.
+///
+/// Finishing never panics: errors are given to the `on_finish` callback,
+/// or can be handled directly by calling [`finish`] instead of dropping.
+/// With `auto_finish()`, they are discarded, leaving a truncated output.
+///
+/// Unlike `Encoder`, this is not `Send`, since the `on_finish` callback is
+/// not required to be.
+///
+/// [`finish`]: #method.finish
 pub struct AutoFinishEncoder<'a, W: Write> {
     // We wrap this in an option to take it during drop.
     encoder: Option<Encoder<'a, W>>,
@@ -71,13 +206,23 @@ impl<'a, W: Write> AutoFinishEncoder<'a, W> {
     pub fn get_mut(&mut self) -> &mut W {
         self.encoder.as_mut().unwrap().get_mut()
     }
+
+    /// Finishes the stream now, and returns the inner writer.
+    ///
+    /// Errors are returned directly: the `on_finish` callback is not called.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.on_finish = None;
+        self.encoder.take().unwrap().finish()
+    }
 }
 
 impl<W: Write> Drop for AutoFinishEncoder<'_, W> {
     fn drop(&mut self) {
-        let result = self.encoder.take().unwrap().finish();
-        if let Some(mut on_finish) = self.on_finish.take() {
-            on_finish(result);
+        if let Some(encoder) = self.encoder.take() {
+            let result = encoder.finish();
+            if let Some(mut on_finish) = self.on_finish.take() {
+                on_finish(result);
+            }
         }
     }
 }
@@ -102,6 +247,21 @@ impl<W: Write> Encoder<'static, W> {
         Self::with_dictionary(writer, level, &[])
     }
 
+    /// Creates a new encoder, letting environment variables override the
+    /// settings.
+    ///
+    /// Like the zstd CLI, `ZSTD_CLEVEL` replaces `level`, and
+    /// `ZSTD_NBTHREADS` enables multithreaded compression (only with the
+    /// `zstdmt` feature: it is ignored otherwise). See
+    /// [`CompressionParameters::from_env`] for details.
+    ///
+    /// [`CompressionParameters::from_env`]: ../../struct.CompressionParameters.html#method.from_env
+    pub fn with_env_overrides(writer: W, level: i32) -> io::Result<Self> {
+        let mut encoder = Self::new(writer, level)?;
+        encoder.apply_parameters(&crate::CompressionParameters::from_env()?)?;
+        Ok(encoder)
+    }
+
     /// Creates a new encoder, using an existing dictionary.
     ///
     /// (Provides better compression ratio for small files,
@@ -115,11 +275,21 @@ impl<W: Write> Encoder<'static, W> {
     ) -> io::Result<Self> {
         let encoder = raw::Encoder::with_dictionary(level, dictionary)?;
         let writer = zio::Writer::new(writer, encoder);
-        Ok(Encoder { writer })
+        Ok(Encoder::from_zio(writer))
     }
 }
 
 impl<'a, W: Write> Encoder<'a, W> {
+    fn from_zio(writer: zio::Writer<W, raw::Encoder<'a>>) -> Self {
+        Encoder {
+            writer,
+            header_patch: None,
+            idle_flush: None,
+            auto_flush_every: None,
+            unflushed: 0,
+        }
+    }
+
     /// Creates a new encoder, using an existing prepared `EncoderDictionary`.
     ///
     /// (Provides better compression ratio for small files,
@@ -133,18 +303,38 @@ impl<'a, W: Write> Encoder<'a, W> {
     {
         let encoder = raw::Encoder::with_prepared_dictionary(dictionary)?;
         let writer = zio::Writer::new(writer, encoder);
-        Ok(Encoder { writer })
+        Ok(Encoder::from_zio(writer))
     }
 
-    /// Returns a wrapper around `self` that will finish the stream on drop.
+    /// Creates a new encoder, using a ref prefix.
+    ///
+    /// The prefix acts as a single-use raw-content dictionary: this is useful
+    /// to compress a new version of some data against the previous version.
+    /// The same prefix must be given to the decoder.
     ///
-    /// # Panic
+    /// A level of `0` uses zstd's default (currently `3`).
+    pub fn with_ref_prefix<'b>(
+        writer: W,
+        level: i32,
+        ref_prefix: &'b [u8],
+    ) -> io::Result<Self>
+    where
+        'b: 'a,
+    {
+        let encoder = raw::Encoder::with_ref_prefix(level, ref_prefix)?;
+        let writer = zio::Writer::new(writer, encoder);
+        Ok(Encoder::from_zio(writer))
+    }
+
+    /// Returns a wrapper around `self` that will finish the stream on drop.
     ///
-    /// Panics on drop if an error happens when finishing the stream.
+    /// Errors happening when finishing the stream on drop are ignored, and
+    /// the output is silently left truncated, without the end of the frame.
+    /// This is only a convenience for cases where that is acceptable: use
+    /// `on_finish` to handle these errors, or call
+    /// `AutoFinishEncoder::finish` (or `finish()` directly) to get them back.
     pub fn auto_finish(self) -> AutoFinishEncoder<'a, W> {
-        self.on_finish(|result| {
-            result.unwrap();
-        })
+        self.on_finish(|_| ())
     }
 
     /// Returns an encoder that will finish the stream on drop.
@@ -170,6 +360,15 @@ impl<'a, W: Write> Encoder<'a, W> {
         self.writer.writer_mut()
     }
 
+    /// Returns a mutable reference to the underlying zstd context.
+    ///
+    /// This gives access to zstd functions not covered by this crate. Note
+    /// that mutation of the context may result in surprising results if
+    /// this encoder is continued to be used.
+    pub fn context_mut(&mut self) -> &mut zstd_safe::CCtx<'a> {
+        self.writer.operation_mut().context_mut()
+    }
+
     /// **Required**: Finishes the stream.
     ///
     /// You *need* to finish the stream when you're done writing, either with
@@ -204,6 +403,55 @@ impl<'a, W: Write> Encoder<'a, W> {
         }
     }
 
+    /// Returns the amount of data buffered by this encoder, which did not
+    /// reach the inner writer yet.
+    ///
+    /// This counts both the input not compressed yet (zstd waits for a full
+    /// block before compressing), and the compressed output not written
+    /// yet. Calling `flush` writes all of it out, at the cost of a worse
+    /// compression ratio when done too often.
+    ///
+    /// This is an estimate: compressed data still in zstd's own output
+    /// buffer is not included.
+    #[cfg(feature = "experimental")]
+    pub fn pending_bytes(&self) -> u64 {
+        let progression =
+            self.writer.operation().context().get_frame_progression();
+        let input = progression.ingested.saturating_sub(progression.consumed);
+        let output = progression.produced.saturating_sub(progression.flushed);
+        input + output + self.writer.pending_output().len() as u64
+    }
+
+    /// Returns the inner writer, if all data written so far reached it.
+    ///
+    /// This is the case after a successful [`flush`] or [`do_finish`], or
+    /// if nothing was written at all. Otherwise, this encoder is returned
+    /// with an error, instead of silently dropping the buffered data.
+    ///
+    /// Note that the output is only a complete zstd frame once finished.
+    ///
+    /// [`flush`]: Write::flush
+    /// [`do_finish`]: #method.do_finish
+    pub fn try_into_inner(self) -> Result<W, (Self, io::Error)> {
+        if self.writer.has_pending_output() {
+            let error = io::Error::new(
+                io::ErrorKind::Other,
+                "unflushed compressed data, call flush or finish first",
+            );
+            return Err((self, error));
+        }
+        Ok(self.writer.into_inner().0)
+    }
+
+    /// Returns the current state of this encoder.
+    ///
+    /// After an error, this tells whether the failed call can be retried
+    /// (for example a `WouldBlock` while flushing), or if the stream is
+    /// broken for good.
+    pub fn state(&self) -> State {
+        self.writer.state()
+    }
+
     /// Attemps to finish the stream.
     ///
     /// You *need* to finish the stream when you're done writing, either with
@@ -212,21 +460,285 @@ impl<'a, W: Write> Encoder<'a, W> {
         self.writer.finish()
     }
 
+    /// Ends the current frame, so more data can go to a new one.
+    ///
+    /// The context, parameters and dictionary are kept for the next frame,
+    /// which is cheaper than creating a new encoder. This is also the place
+    /// to change the dictionary with `set_dictionary`.
+    ///
+    /// This can't be used with `reserve_content_size`, which only covers a
+    /// single frame.
+    pub fn finish_frame(&mut self) -> io::Result<()> {
+        if self.header_patch.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "cannot start a new frame with a reserved content size",
+            ));
+        }
+        self.writer.finish_frame()
+    }
+
+    /// Replaces the underlying writer, and returns the previous one.
+    ///
+    /// The next frames go to `writer`, with the same context, parameters
+    /// and dictionary: log rotation can roll to a new file this way, without
+    /// warming up a new encoder. The previous writer is not flushed.
+    ///
+    /// This must be called at a frame boundary, once all the output reached
+    /// the previous writer: either before writing any data, or after a
+    /// successful `finish_frame`. Otherwise, an error is returned and
+    /// nothing changes.
+    pub fn replace_writer(&mut self, writer: W) -> io::Result<W> {
+        if self.writer.operation().is_frame_started()
+            || self.writer.has_pending_output()
+            || self.header_patch.is_some()
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "the writer can only be replaced between frames",
+            ));
+        }
+        Ok(std::mem::replace(self.writer.writer_mut(), writer))
+    }
+
+    /// Replaces the dictionary used for the next frames.
+    ///
+    /// This lets a long-lived encoder follow dictionary updates without
+    /// recreating its context. An empty dictionary removes the current one
+    /// (see also `clear_dictionary`). The decoder must use the same
+    /// dictionary for each frame.
+    ///
+    /// This must be called at a frame boundary: either before writing any
+    /// data, or right after `finish_frame`. Otherwise, an error is returned
+    /// and the current frame is not affected.
+    pub fn set_dictionary(&mut self, dictionary: &[u8]) -> io::Result<()> {
+        self.writer.operation_mut().set_dictionary(dictionary)
+    }
+
+    /// Replaces the dictionary used for the next frames with a prepared one.
+    ///
+    /// This follows the same rules as `set_dictionary`.
+    pub fn set_prepared_dictionary<'b>(
+        &mut self,
+        dictionary: &EncoderDictionary<'b>,
+    ) -> io::Result<()>
+    where
+        'b: 'a,
+    {
+        self.writer
+            .operation_mut()
+            .set_prepared_dictionary(dictionary)
+    }
+
+    /// Removes the dictionary for the next frames.
+    ///
+    /// This must be called at a frame boundary, like `set_dictionary`.
+    pub fn clear_dictionary(&mut self) -> io::Result<()> {
+        self.set_dictionary(&[])
+    }
+
     /// Return a recommendation for the size of data to write at once.
     pub fn recommended_input_size() -> usize {
         zstd_safe::CCtx::in_size()
     }
 
+    /// Flushes the stream every time `n_bytes` of input were consumed.
+    ///
+    /// This bounds how much data a decoder on the other end can be waiting
+    /// for, without calling `flush` manually. Each flush slightly degrades
+    /// the compression ratio.
+    ///
+    /// `None` (the default) disables this.
+    pub fn auto_flush_every(&mut self, n_bytes: Option<usize>) {
+        self.auto_flush_every = n_bytes.filter(|&n| n > 0);
+    }
+
+    /// Flushes the stream once no data was written for `delay`.
+    ///
+    /// zstd buffers data internally until enough is available: on an
+    /// interactive connection, messages could stay there while the
+    /// connection is quiet. With this enabled, `flush_if_idle` flushes them
+    /// once the writer has been idle for `delay`.
+    ///
+    /// An [`EncoderSink`] does this automatically.
+    ///
+    /// [`EncoderSink`]: struct.EncoderSink.html
+    ///
+    /// `None` (the default) disables this.
+    pub fn idle_flush(&mut self, delay: Option<Duration>) {
+        self.idle_flush = delay.map(|delay| {
+            Box::new(IdleFlush {
+                delay,
+                last_write: None,
+            })
+        });
+    }
+
+    /// Flushes the stream if no data was written for the `idle_flush` delay.
+    ///
+    /// Returns how long to wait before calling this again, or `None` if
+    /// everything written so far was flushed. An async writer will typically
+    /// arm a timer with this value after each write, and call this method
+    /// when it fires.
+    ///
+    /// As with `flush`, an async inner writer may return `WouldBlock`: the
+    /// flush is then still due, and this can be called again later.
+    pub fn flush_if_idle(&mut self) -> io::Result<Option<Duration>> {
+        let (delay, last_write) = match self.idle_flush.as_deref() {
+            Some(IdleFlush {
+                delay,
+                last_write: Some(last_write),
+            }) => (*delay, *last_write),
+            _ => return Ok(None),
+        };
+
+        let idle = last_write.elapsed();
+        if idle < delay {
+            return Ok(Some(delay - idle));
+        }
+        self.flush()?;
+        Ok(None)
+    }
+
     crate::readwritecommon!(writer);
 }
 
+impl<'a, W: Write + Seek> Encoder<'a, W> {
+    /// Reserves room to store the content size in the frame header.
+    ///
+    /// This must be called before writing any data, at most once: otherwise
+    /// an error is returned. The stream must then be
+    /// finished with [`finish_with_content_size`], which goes back to write
+    /// the final content size in the header: until then, the output is not
+    /// a valid zstd stream.
+    ///
+    /// This is useful when the size is not known in advance, but consumers
+    /// rely on the header to preallocate their output.
+    ///
+    /// [`finish_with_content_size`]: #method.finish_with_content_size
+    pub fn reserve_content_size(&mut self) -> io::Result<()> {
+        if self.writer.operation().is_frame_started()
+            || self.writer.has_pending_output()
+            || self.header_patch.is_some()
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "the content size must be reserved before writing any data",
+            ));
+        }
+
+        let writer = self.writer.writer_mut();
+        let start = writer.stream_position()?;
+
+        // The header will grow by 8 bytes, for the content size field.
+        writer.write_all(&[0u8; 8])?;
+
+        // The frame header is the start of the operation's output.
+        self.writer.capture_output(HEADER_CAPTURE_SIZE);
+        self.header_patch = Some(Box::new(HeaderPatch {
+            start,
+            content_size: 0,
+        }));
+        Ok(())
+    }
+
+    /// Finishes the stream, and writes the content size in the frame header.
+    ///
+    /// If `reserve_content_size` was not called, this is the same as
+    /// `finish`.
+    ///
+    /// The writer is returned positioned at the end of the stream.
+    pub fn finish_with_content_size(mut self) -> io::Result<W> {
+        let patch = match self.header_patch.take() {
+            Some(patch) => patch,
+            None => return self.finish(),
+        };
+
+        if self.writer.captured_output().is_empty() {
+            // Nothing was output yet. Finishing right away would let zstd
+            // use the input size as the content size, in a shorter header:
+            // start the frame with a flush instead.
+            self.writer.flush()?;
+        }
+        self.writer.finish()?;
+
+        let header =
+            patched_header(self.writer.captured_output(), patch.content_size)?;
+        let mut writer = self.writer.into_inner().0;
+        let end = writer.stream_position()?;
+        writer.seek(SeekFrom::Start(patch.start))?;
+        writer.write_all(&header)?;
+        writer.seek(SeekFrom::Start(end))?;
+
+        Ok(writer)
+    }
+}
+
+// Maximum size of a frame header (`ZSTD_FRAMEHEADERSIZE_MAX`).
+const HEADER_CAPTURE_SIZE: usize = 18;
+
+// Returns a frame header including the given content size, as an 8-byte
+// field. `data` must start with a frame header without content size.
+fn patched_header(data: &[u8], content_size: u64) -> io::Result<Vec<u8>> {
+    // Magic number, descriptor, window descriptor and dictionary ID.
+    let header_len = match data.get(4) {
+        Some(descriptor)
+            if data[..4] == zstd_safe::MAGICNUMBER.to_le_bytes()
+                && descriptor & 0xE0 == 0 =>
+        {
+            6 + [0, 1, 2, 4][usize::from(descriptor & 3)]
+        }
+        _ => 0,
+    };
+    if header_len == 0 || data.len() < header_len {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            "unexpected frame header",
+        ));
+    }
+
+    let mut header = data[..header_len].to_vec();
+    header[4] |= 0xC0;
+    header.extend_from_slice(&content_size.to_le_bytes());
+    Ok(header)
+}
+
 impl<'a, W: Write> Write for Encoder<'a, W> {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.writer.write(buf)
+    fn write(&mut self, mut buf: &[u8]) -> io::Result<usize> {
+        if let Some(every) = self.auto_flush_every {
+            if self.unflushed >= every {
+                // The last automatic flush did not complete.
+                self.flush()?;
+            }
+            // Stop right where the next flush is due.
+            buf = &buf[..usize::min(buf.len(), every - self.unflushed)];
+        }
+
+        let written = self.writer.write(buf)?;
+        if let Some(patch) = &mut self.header_patch {
+            patch.content_size += written as u64;
+        }
+        if let Some(idle_flush) = &mut self.idle_flush {
+            idle_flush.last_write = Some(Instant::now());
+        }
+        if let Some(every) = self.auto_flush_every {
+            self.unflushed += written;
+            if self.unflushed >= every {
+                // The data is already consumed: if this fails, the flush is
+                // attempted again by the next call.
+                let _ = self.flush();
+            }
+        }
+        Ok(written)
     }
 
     fn flush(&mut self) -> io::Result<()> {
-        self.writer.flush()
+        self.writer.flush()?;
+        if let Some(idle_flush) = &mut self.idle_flush {
+            idle_flush.last_write = None;
+        }
+        self.unflushed = 0;
+        Ok(())
     }
 }
 
@@ -240,6 +752,114 @@ impl<'a, W: AsyncWrite> AsyncWrite for Encoder<'a, W> {
     }
 }
 
+#[cfg(feature = "tokio")]
+impl<'a, W: AsyncWrite> EncoderSink<'a, W> {
+    /// Creates a new sink around the given encoder.
+    pub fn new(encoder: Encoder<'a, W>) -> Self {
+        EncoderSink {
+            encoder,
+            pending: None,
+            wake_up: None,
+            timer: None,
+        }
+    }
+
+    /// Acquires a reference to the underlying encoder.
+    pub fn get_ref(&self) -> &Encoder<'a, W> {
+        &self.encoder
+    }
+
+    /// Acquires a mutable reference to the underlying encoder.
+    pub fn get_mut(&mut self) -> &mut Encoder<'a, W> {
+        &mut self.encoder
+    }
+
+    /// Returns the underlying encoder.
+    ///
+    /// Any buffer not entirely written yet is lost.
+    pub fn into_inner(self) -> Encoder<'a, W> {
+        self.encoder
+    }
+
+    // Gives the pending buffer to the encoder.
+    fn poll_pending(&mut self) -> Poll<(), io::Error> {
+        while let Some(pending) = &mut self.pending {
+            match self.encoder.write(pending) {
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "writer will not accept any more data",
+                    ))
+                }
+                Ok(n) => pending.advance(n),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    return Ok(Async::NotReady)
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+            if pending.is_empty() {
+                self.pending = None;
+            }
+        }
+        Ok(Async::Ready(()))
+    }
+
+    // Makes sure the current task is polled again after `delay`.
+    //
+    // All the wake-ups of this sink go through the same timer thread. A
+    // wake-up that is still due is reused, and simply arms a new one if it
+    // comes too early.
+    fn wake_up_after(&mut self, delay: Duration) {
+        let now = Instant::now();
+        if let Some(wake_up) = self.wake_up {
+            if wake_up > now {
+                return;
+            }
+        }
+        self.wake_up = Some(now + delay);
+
+        self.timer
+            .get_or_insert_with(Timer::new)
+            .wake_up_at(now + delay, futures::task::current());
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<W: AsyncWrite> Sink for EncoderSink<'_, W> {
+    type SinkItem = Bytes;
+    type SinkError = io::Error;
+
+    fn start_send(&mut self, item: Bytes) -> StartSend<Bytes, io::Error> {
+        if self.poll_pending()?.is_not_ready() {
+            return Ok(AsyncSink::NotReady(item));
+        }
+        if !item.is_empty() {
+            self.pending = Some(item);
+        }
+        Ok(AsyncSink::Ready)
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), io::Error> {
+        use tokio_io::try_nb;
+
+        futures::try_ready!(self.poll_pending());
+        if self.encoder.idle_flush.is_some() {
+            if let Some(delay) = try_nb!(self.encoder.flush_if_idle()) {
+                self.wake_up_after(delay);
+                return Ok(Async::NotReady);
+            }
+        }
+        try_nb!(self.encoder.flush());
+        Ok(Async::Ready(()))
+    }
+
+    fn close(&mut self) -> Poll<(), io::Error> {
+        futures::try_ready!(self.poll_pending());
+        self.encoder.shutdown()
+    }
+}
+
 impl<W: Write> Decoder<'static, W> {
     /// Creates a new decoder.
     pub fn new(writer: W) -> io::Result<Self> {
@@ -273,6 +893,83 @@ impl<'a, W: Write> Decoder<'a, W> {
         Ok(Decoder { writer })
     }
 
+    /// Creates a new decoder, referencing an existing dictionary.
+    ///
+    /// The dictionary content is not copied, so it can be shared between
+    /// many decoders.
+    pub fn with_dictionary_by_reference<'b>(
+        writer: W,
+        dictionary: &'b [u8],
+    ) -> io::Result<Self>
+    where
+        'b: 'a,
+    {
+        let decoder = raw::Decoder::with_dictionary_by_reference(dictionary)?;
+        let writer = zio::Writer::new(writer, decoder);
+        Ok(Decoder { writer })
+    }
+
+    /// Creates a new decoder, using a ref prefix.
+    ///
+    /// The prefix must be the same as the one used during compression.
+    pub fn with_ref_prefix<'b>(
+        writer: W,
+        ref_prefix: &'b [u8],
+    ) -> io::Result<Self>
+    where
+        'b: 'a,
+    {
+        let decoder = raw::Decoder::with_ref_prefix(ref_prefix)?;
+        let writer = zio::Writer::new(writer, decoder);
+        Ok(Decoder { writer })
+    }
+
+    /// Accepts frames using windows up to `2^max_window_log` bytes.
+    ///
+    /// By default, frames requiring more than 128MB (`2^27`) are rejected,
+    /// to protect against excessive memory use. Frames compressed with long
+    /// distance matching and a larger window (like `zstd --long=31`) need
+    /// this, similar to `zstd --long=31 -d`.
+    ///
+    /// The decoder only allocates as much memory as the frame requires.
+    pub fn long_mode(&mut self, max_window_log: u32) -> io::Result<()> {
+        self.writer
+            .operation_mut()
+            .set_parameter(zstd_safe::DParameter::WindowLogMax(max_window_log))
+    }
+
+    /// Controls whether an entirely empty input is accepted.
+    ///
+    /// By default, finishing without any input is an error, like any
+    /// incomplete frame. When allowed, it gives an empty output instead,
+    /// for producers which skip the frame for empty content (see
+    /// `Encoder::skip_empty_frame`).
+    pub fn allow_empty(&mut self, allow: bool) {
+        self.writer.operation_mut().allow_empty(allow);
+    }
+
+    /// Sets this `Decoder` to stop after the first frame.
+    ///
+    /// This is the same as `max_frames(Some(1))`.
+    pub fn single_frame(&mut self) {
+        self.writer.operation_mut().set_single_frame();
+    }
+
+    /// Sets this `Decoder` to stop after `max_frames` frames.
+    ///
+    /// This follows the same rules as the read `Decoder`: writes only
+    /// consume input up to the end of the last frame, so the caller knows
+    /// exactly where the frames ended. Once the last frame is finished,
+    /// `write` doesn't take any more input and fails with a
+    /// [`FrameLimitReached`] error. `finish` then succeeds.
+    ///
+    /// `None` (the default) keeps decoding frames until the end.
+    ///
+    /// [`FrameLimitReached`]: ../struct.FrameLimitReached.html
+    pub fn max_frames(&mut self, max_frames: Option<u64>) {
+        self.writer.operation_mut().set_max_frames(max_frames);
+    }
+
     /// Enables or disabled expecting the 4-byte magic header
     pub fn include_magicbytes(
         &mut self,
@@ -302,17 +999,99 @@ impl<'a, W: Write> Decoder<'a, W> {
         self.writer.writer_mut()
     }
 
+    /// Returns a mutable reference to the underlying zstd context.
+    ///
+    /// This gives access to zstd functions not covered by this crate. Note
+    /// that mutation of the context may result in surprising results if
+    /// this decoder is continued to be used.
+    pub fn context_mut(&mut self) -> &mut zstd_safe::DCtx<'a> {
+        self.writer.operation_mut().context_mut()
+    }
+
     /// Returns the inner `Write`.
+    ///
+    /// Decompressed data may still be buffered: use `finish` to write it
+    /// all first.
     pub fn into_inner(self) -> W {
         self.writer.into_inner().0
     }
 
+    /// Returns the current state of this decoder.
+    ///
+    /// Corrupted input moves the decoder to [`State::Errored`].
+    pub fn state(&self) -> State {
+        self.writer.state()
+    }
+
+    /// Writes any buffered decompressed data, and returns the inner `Write`.
+    ///
+    /// Returns an error if the last frame is incomplete.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.finish()?;
+        Ok(self.into_inner())
+    }
+
+    /// Returns a wrapper around `self` that will finish the stream on drop.
+    ///
+    /// Errors happening when finishing the stream on drop are ignored, and
+    /// the output is silently left truncated. This is only a convenience for
+    /// cases where that is acceptable: use `on_finish` to handle these
+    /// errors, or call `AutoFinishDecoder::finish` (or `finish()` directly)
+    /// to get them back.
+    pub fn auto_finish(self) -> AutoFinishDecoder<'a, W> {
+        self.on_finish(|_| ())
+    }
+
+    /// Returns a decoder that will finish the stream on drop.
+    ///
+    /// Calls the given callback with the result from `finish()`.
+    pub fn on_finish<F: 'static + FnMut(io::Result<W>)>(
+        self,
+        f: F,
+    ) -> AutoFinishDecoder<'a, W> {
+        AutoFinishDecoder {
+            writer: self.writer.on_finish(f),
+        }
+    }
+
     /// Return a recommendation for the size of data to write at once.
     pub fn recommended_input_size() -> usize {
         zstd_safe::DCtx::in_size()
     }
 }
 
+impl<'a, W: Write> AutoFinishDecoder<'a, W> {
+    /// Acquires a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        self.writer.writer()
+    }
+
+    /// Acquires a mutable reference to the underlying writer.
+    ///
+    /// Note that mutation of the writer may result in surprising results if
+    /// this decoder is continued to be used.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.writer.writer_mut()
+    }
+
+    /// Finishes the stream now, and returns the inner writer.
+    ///
+    /// Errors are returned directly: the `on_finish` callback is not called.
+    pub fn finish(self) -> io::Result<W> {
+        self.writer.finish()
+    }
+}
+
+impl<W: Write> Write for AutoFinishDecoder<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
 impl<W: Write> Write for Decoder<'_, W> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         self.writer.write(buf)
@@ -0,0 +1,181 @@
+use std::io::{self, Write};
+
+use super::Encoder;
+
+/// An encoder also forwarding the uncompressed data to a second writer.
+///
+/// This produces both a compressed and a raw copy of the same data in a
+/// single pass, for example to write a compressed archive while filling an
+/// uncompressed cache.
+///
+/// Both outputs always receive the same data. When the raw writer fails
+/// after the encoder accepted some data, this data is kept and written
+/// again before anything else: the error is returned by the next `write`,
+/// `flush` or [`finish`], which can be retried after a `WouldBlock` or an
+/// `Interrupted` error.
+///
+/// [`finish`]: #method.finish
+pub struct TeeEncoder<'a, W: Write, C: Write> {
+    encoder: Encoder<'a, W>,
+    copy: C,
+
+    // Data given to the encoder, but not to `copy` yet.
+    pending: Vec<u8>,
+    // Error from `copy` not reported yet.
+    error: Option<io::Error>,
+}
+
+impl<'a, W: Write, C: Write> TeeEncoder<'a, W, C> {
+    /// Creates a new tee around the given encoder, copying the uncompressed
+    /// data to `copy`.
+    pub fn new(encoder: Encoder<'a, W>, copy: C) -> Self {
+        TeeEncoder {
+            encoder,
+            copy,
+            pending: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Acquires a reference to the encoder.
+    pub fn encoder(&self) -> &Encoder<'a, W> {
+        &self.encoder
+    }
+
+    /// Acquires a mutable reference to the encoder.
+    pub fn encoder_mut(&mut self) -> &mut Encoder<'a, W> {
+        &mut self.encoder
+    }
+
+    /// Acquires a reference to the writer receiving uncompressed data.
+    pub fn copy(&self) -> &C {
+        &self.copy
+    }
+
+    /// Acquires a mutable reference to the writer receiving uncompressed
+    /// data.
+    pub fn copy_mut(&mut self) -> &mut C {
+        &mut self.copy
+    }
+
+    /// Finishes the compressed stream, and returns both writers.
+    ///
+    /// On error, both outputs may be incomplete.
+    pub fn finish(mut self) -> io::Result<(W, C)> {
+        self.write_pending()?;
+        let writer = self.encoder.finish()?;
+        self.copy.flush()?;
+        Ok((writer, self.copy))
+    }
+
+    // Reports any delayed error, then gives `pending` to `copy`.
+    fn write_pending(&mut self) -> io::Result<()> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        let mut offset = 0;
+        let result = write_copy(&mut self.copy, &self.pending, &mut offset);
+        self.pending.drain(..offset);
+        result
+    }
+}
+
+// Writes `data[*offset..]`, keeping track of what was written on error.
+fn write_copy<C: Write>(
+    copy: &mut C,
+    data: &[u8],
+    offset: &mut usize,
+) -> io::Result<()> {
+    while *offset < data.len() {
+        match copy.write(&data[*offset..]) {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "copy will not accept any more data",
+                ))
+            }
+            Ok(n) => *offset += n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+impl<W: Write, C: Write> Write for TeeEncoder<'_, W, C> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_pending()?;
+
+        let written = self.encoder.write(buf)?;
+        let mut offset = 0;
+        if let Err(error) =
+            write_copy(&mut self.copy, &buf[..written], &mut offset)
+        {
+            // The encoder already has this data: report the error on the
+            // next call, and write the rest to `copy` after that.
+            self.pending.extend_from_slice(&buf[offset..written]);
+            self.error = Some(error);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_pending()?;
+        self.encoder.flush()?;
+        self.copy.flush()
+    }
+}
+
+fn _assert_traits() {
+    fn _assert_send<T: Send>(_: T) {}
+
+    _assert_send(TeeEncoder::new(
+        Encoder::new(Vec::new(), 1).unwrap(),
+        Vec::new(),
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TeeEncoder;
+    use crate::stream::write::Encoder;
+    use partial_io::{PartialOp, PartialWrite};
+    use std::io::{ErrorKind, Write};
+
+    #[test]
+    fn test_tee() {
+        let data = b"tee encoder ".repeat(10_000);
+
+        let encoder = Encoder::new(Vec::new(), 1).unwrap();
+        let mut tee = TeeEncoder::new(encoder, Vec::new());
+        tee.write_all(&data).unwrap();
+        let (compressed, copy) = tee.finish().unwrap();
+
+        assert_eq!(crate::decode_all(&compressed[..]).unwrap(), data);
+        assert_eq!(copy, data);
+    }
+
+    #[test]
+    fn test_copy_errors() {
+        let data = b"tee encoder ".repeat(10_000);
+
+        // The copy only takes a few bytes, then fails once.
+        let ops =
+            vec![PartialOp::Limited(5), PartialOp::Err(ErrorKind::Other)];
+        let copy = PartialWrite::new(Vec::new(), ops);
+        let encoder = Encoder::new(Vec::new(), 1).unwrap();
+        let mut tee = TeeEncoder::new(encoder, copy);
+
+        // The first write reaches the encoder, the error comes next.
+        let written = tee.write(&data).unwrap();
+        assert!(written > 5);
+        let error = tee.write(&data[written..]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Other);
+
+        // Retrying catches up: both outputs are complete.
+        tee.write_all(&data[written..]).unwrap();
+        let (compressed, copy) = tee.finish().unwrap();
+        assert_eq!(crate::decode_all(&compressed[..]).unwrap(), data);
+        assert_eq!(copy.into_inner(), data);
+    }
+}
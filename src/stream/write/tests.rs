@@ -70,3 +70,389 @@ fn setup_partial_write(input_data: &[u8]) -> Encoder<PartialWrite<Vec<u8>>> {
 
     z
 }
+
+#[test]
+fn test_reserve_content_size() {
+    let input: Vec<u8> = (0..300_000u32).map(|i| (i % 97) as u8).collect();
+
+    // Start after some unrelated data.
+    let mut buffer = Cursor::new(b"header".to_vec());
+    buffer.set_position(6);
+
+    let mut encoder = Encoder::new(buffer, 1).unwrap();
+    encoder.include_checksum(true).unwrap();
+    encoder.reserve_content_size().unwrap();
+    for chunk in input.chunks(1000) {
+        encoder.write_all(chunk).unwrap();
+    }
+    let buffer = encoder.finish_with_content_size().unwrap();
+    assert_eq!(buffer.position(), buffer.get_ref().len() as u64);
+
+    let encoded = &buffer.get_ref()[6..];
+    assert_eq!(
+        zstd_safe::get_frame_content_size(encoded),
+        input.len() as u64
+    );
+    assert_eq!(decode_all(encoded).unwrap(), input);
+}
+
+#[test]
+fn test_reserve_content_size_small() {
+    for input in &[&b""[..], b"small"] {
+        let mut encoder = Encoder::new(Cursor::new(Vec::new()), 1).unwrap();
+        encoder.reserve_content_size().unwrap();
+        encoder.write_all(input).unwrap();
+        let encoded = encoder.finish_with_content_size().unwrap().into_inner();
+
+        assert_eq!(
+            zstd_safe::get_frame_content_size(&encoded),
+            input.len() as u64
+        );
+        assert_eq!(&decode_all(&encoded[..]).unwrap(), input);
+    }
+}
+
+#[test]
+fn test_reserve_content_size_flushes() {
+    let input: Vec<u8> = (0..100_000u32).map(|i| (i % 89) as u8).collect();
+
+    // The header goes out with a flush, before any write produces output.
+    let mut encoder = Encoder::new(Cursor::new(Vec::new()), 1).unwrap();
+    encoder.reserve_content_size().unwrap();
+    encoder.write_all(&input[..10]).unwrap();
+    encoder.flush().unwrap();
+    encoder.write_all(&input[10..]).unwrap();
+    encoder.flush().unwrap();
+    let encoded = encoder.finish_with_content_size().unwrap().into_inner();
+
+    assert_eq!(
+        zstd_safe::get_frame_content_size(&encoded),
+        input.len() as u64
+    );
+    assert_eq!(decode_all(&encoded[..]).unwrap(), input);
+
+    // Too late once data was written, or if already reserved.
+    let mut encoder = Encoder::new(Cursor::new(Vec::new()), 1).unwrap();
+    encoder.write_all(b"data").unwrap();
+    assert!(encoder.reserve_content_size().is_err());
+
+    let mut encoder = Encoder::new(Cursor::new(Vec::new()), 1).unwrap();
+    encoder.reserve_content_size().unwrap();
+    assert!(encoder.reserve_content_size().is_err());
+}
+
+#[test]
+fn test_idle_flush() {
+    use std::io::Read;
+    use std::time::Duration;
+
+    let delay = Duration::from_millis(20);
+    let mut encoder = Encoder::new(Vec::new(), 1).unwrap();
+    assert_eq!(encoder.flush_if_idle().unwrap(), None);
+
+    encoder.idle_flush(Some(delay));
+    encoder.write_all(b"ping").unwrap();
+
+    // Not idle for long enough yet: the message is still in zstd.
+    let wait = encoder.flush_if_idle().unwrap().unwrap();
+    assert!(wait <= delay);
+    assert!(encoder.get_ref().len() < 10);
+
+    std::thread::sleep(wait);
+    assert_eq!(encoder.flush_if_idle().unwrap(), None);
+
+    // The message can be decoded, even though the frame isn't finished.
+    let mut message = [0u8; 4];
+    crate::stream::read::Decoder::new(&encoder.get_ref()[..])
+        .unwrap()
+        .read_exact(&mut message)
+        .unwrap();
+    assert_eq!(&message, b"ping");
+}
+
+#[test]
+fn test_auto_flush_every() {
+    use std::io::Read;
+
+    let mut encoder = Encoder::new(Vec::new(), 1).unwrap();
+    encoder.auto_flush_every(Some(1000));
+
+    // Less than the threshold stays buffered.
+    encoder.write_all(&[b'a'; 999]).unwrap();
+    let buffered = encoder.get_ref().len();
+    assert!(buffered < 10);
+
+    // A single large write is flushed every 1000 bytes.
+    encoder.write_all(&[b'b'; 2501]).unwrap();
+    let mut decoder =
+        crate::stream::read::Decoder::new(&encoder.get_ref()[..]).unwrap();
+    let mut output = vec![0u8; 3000];
+    decoder.read_exact(&mut output).unwrap();
+    assert_eq!(&output[..999], &[b'a'; 999][..]);
+    assert_eq!(&output[999..], &[b'b'; 2001][..]);
+
+    // The last 500 bytes wait for the next flush.
+    assert_eq!(
+        decoder.read(&mut [0u8; 1]).unwrap_err().kind(),
+        std::io::ErrorKind::UnexpectedEof
+    );
+
+    let compressed = encoder.finish().unwrap();
+    assert_eq!(decode_all(&compressed[..]).unwrap().len(), 3500);
+}
+
+#[test]
+#[cfg(feature = "experimental")]
+fn test_applied_parameters() {
+    use zstd_safe::CParameter;
+
+    let mut encoder = Encoder::new(Vec::new(), 19).unwrap();
+    assert_eq!(encoder.applied_parameters().unwrap().window_log, 23);
+
+    // A small pledged size shrinks the window.
+    encoder.set_pledged_src_size(1000).unwrap();
+    assert_eq!(encoder.applied_parameters().unwrap().window_log, 10);
+
+    // An empty frame is not an unknown size.
+    let mut encoder = Encoder::new(Vec::new(), 19).unwrap();
+    encoder.set_pledged_src_size(0).unwrap();
+    assert_eq!(encoder.applied_parameters().unwrap().window_log, 10);
+
+    // The window covers the dictionary too.
+    let dictionary = crate::test_util::text(100_000, 3);
+    let mut encoder =
+        Encoder::with_dictionary(Vec::new(), 19, &dictionary).unwrap();
+    encoder.set_pledged_src_size(1000).unwrap();
+    assert_eq!(encoder.applied_parameters().unwrap().window_log, 17);
+
+    let mut encoder = Encoder::new(Vec::new(), 1).unwrap();
+    let context = encoder.context_mut();
+    context
+        .set_parameter(CParameter::EnableLongDistanceMatching(true))
+        .unwrap();
+    assert_eq!(encoder.applied_parameters().unwrap().window_log, 27);
+
+    let context = encoder.context_mut();
+    context.set_parameter(CParameter::WindowLog(20)).unwrap();
+    context
+        .set_parameter(CParameter::Strategy(zstd_safe::Strategy::ZSTD_lazy))
+        .unwrap();
+    let params = encoder.applied_parameters().unwrap();
+    assert_eq!(params.window_log, 20);
+    assert_eq!(params.strategy, zstd_safe::Strategy::ZSTD_lazy);
+}
+
+#[test]
+fn test_auto_finish_errors() {
+    use std::cell::RefCell;
+    use std::io;
+    use std::rc::Rc;
+
+    struct Failing;
+
+    impl Write for Failing {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::Other, "disk full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // Dropping never panics.
+    let mut encoder = Encoder::new(Failing, 1).unwrap().auto_finish();
+    encoder.write_all(b"some data").unwrap();
+    drop(encoder);
+
+    // Errors are reported to the callback.
+    let error = Rc::new(RefCell::new(None));
+    let reported = error.clone();
+    let encoder = Encoder::new(Failing, 1).unwrap().on_finish(move |result| {
+        *reported.borrow_mut() = result.err();
+    });
+    drop(encoder);
+    assert_eq!(error.borrow().as_ref().unwrap().to_string(), "disk full");
+
+    // Or returned directly.
+    let encoder = Encoder::new(Failing, 1).unwrap().auto_finish();
+    assert!(encoder.finish().is_err());
+}
+
+#[test]
+fn test_decoder_finish() {
+    let input = b"Hello world! ".repeat(1000);
+    let compressed = crate::encode_all(&input[..], 1).unwrap();
+
+    let mut decoder = Decoder::new(Vec::new()).unwrap();
+    decoder.write_all(&compressed).unwrap();
+    assert_eq!(decoder.finish().unwrap(), input);
+
+    // An incomplete frame is an error.
+    let mut decoder = Decoder::new(Vec::new()).unwrap().auto_finish();
+    decoder
+        .write_all(&compressed[..compressed.len() - 4])
+        .unwrap();
+    assert!(decoder.finish().is_err());
+
+    // Dropping the decoder writes everything out.
+    let mut output = Vec::new();
+    let mut decoder = Decoder::new(&mut output).unwrap().auto_finish();
+    decoder.write_all(&compressed).unwrap();
+    drop(decoder);
+    assert_eq!(output, input);
+}
+
+#[test]
+fn test_try_into_inner() {
+    let input = b"Hello world! ".repeat(1000);
+
+    // Nothing written yet.
+    let encoder = Encoder::new(Vec::new(), 1).unwrap();
+    assert!(encoder.try_into_inner().ok().unwrap().is_empty());
+
+    let mut encoder = Encoder::new(Vec::new(), 1).unwrap();
+    encoder.write_all(&input).unwrap();
+    let (mut encoder, error) = match encoder.try_into_inner() {
+        Ok(_) => panic!("writer taken out of an unflushed encoder"),
+        Err(err) => err,
+    };
+    assert_eq!(error.kind(), std::io::ErrorKind::Other);
+
+    encoder.do_finish().unwrap();
+    let output = encoder.try_into_inner().ok().unwrap();
+    assert_eq!(decode_all(&output[..]).unwrap(), input);
+
+    // A flushed encoder already wrote all the blocks so far.
+    let mut encoder = Encoder::new(Vec::new(), 1).unwrap();
+    encoder.write_all(&input).unwrap();
+    encoder.flush().unwrap();
+    let flushed = encoder.get_ref().len();
+    assert_eq!(encoder.try_into_inner().ok().unwrap().len(), flushed);
+}
+
+#[test]
+#[cfg(feature = "experimental")]
+fn test_pending_bytes() {
+    let mut encoder = Encoder::new(Vec::new(), 1).unwrap();
+    assert_eq!(encoder.pending_bytes(), 0);
+
+    // Less than a block: zstd keeps it until a flush.
+    encoder.write_all(&[42u8; 1000]).unwrap();
+    assert_eq!(encoder.pending_bytes(), 1000);
+    assert!(encoder.get_ref().is_empty());
+
+    encoder.flush().unwrap();
+    assert_eq!(encoder.pending_bytes(), 0);
+    assert!(!encoder.get_ref().is_empty());
+}
+
+#[test]
+fn test_state() {
+    use crate::stream::write::State;
+    use std::io::ErrorKind;
+
+    // Blocks once, then accepts everything.
+    let ops = vec![PartialOp::Err(ErrorKind::WouldBlock)];
+    let mut encoder =
+        Encoder::new(PartialWrite::new(Vec::new(), ops), 1).unwrap();
+    assert_eq!(encoder.state(), State::Streaming);
+
+    encoder.write_all(&[42u8; 1000]).unwrap();
+    let error = encoder.flush().unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::WouldBlock);
+    assert_eq!(encoder.state(), State::Flushing);
+    encoder.flush().unwrap();
+    assert_eq!(encoder.state(), State::Streaming);
+
+    encoder.do_finish().unwrap();
+    assert_eq!(encoder.state(), State::Finished);
+
+    let mut decoder = Decoder::new(Vec::new()).unwrap();
+    assert_eq!(decoder.state(), State::Streaming);
+    assert!(decoder.write_all(b"not a zstd frame").is_err());
+    assert_eq!(decoder.state(), State::Errored);
+    assert!(decoder.finish().is_err());
+}
+
+#[test]
+fn test_switch_dictionary() {
+    use crate::stream::read;
+
+    let first = crate::test_util::text(2_000, 1);
+    let second = crate::test_util::text(2_000, 2);
+    let data = crate::test_util::text(1_000, 3);
+
+    let mut encoder = Encoder::with_dictionary(Vec::new(), 3, &first).unwrap();
+    encoder.write_all(&data).unwrap();
+
+    // The dictionary can't change in the middle of a frame.
+    assert!(encoder.set_dictionary(&second).is_err());
+
+    encoder.finish_frame().unwrap();
+    encoder.set_dictionary(&second).unwrap();
+    encoder.write_all(&data).unwrap();
+    encoder.finish_frame().unwrap();
+    encoder.clear_dictionary().unwrap();
+    encoder.write_all(&data).unwrap();
+    let compressed = encoder.finish().unwrap();
+
+    // Each frame needs its own dictionary.
+    let mut input = &compressed[..];
+    for dictionary in &[&first[..], &second[..], &[]] {
+        let mut decoder = read::Decoder::with_dictionary(input, dictionary)
+            .unwrap()
+            .single_frame();
+        let mut output = Vec::new();
+        std::io::Read::read_to_end(&mut decoder, &mut output).unwrap();
+        assert_eq!(output, data);
+        input = decoder.finish();
+    }
+    assert!(input.is_empty());
+
+    // The first frame can't be decoded with the wrong dictionary.
+    let mut wrong = read::Decoder::with_dictionary(&compressed[..], &second)
+        .unwrap()
+        .single_frame();
+    let mut output = Vec::new();
+    let result = std::io::Read::read_to_end(&mut wrong, &mut output);
+    assert!(result.is_err() || output != data);
+}
+
+#[test]
+fn test_replace_writer() {
+    let mut encoder = Encoder::new(Vec::new(), 1).unwrap();
+    assert_eq!(encoder.replace_writer(Vec::new()).unwrap(), b"");
+
+    encoder.write_all(b"first file").unwrap();
+    // Not at a frame boundary.
+    assert!(encoder.replace_writer(Vec::new()).is_err());
+    encoder.finish_frame().unwrap();
+
+    let first = encoder.replace_writer(Vec::new()).unwrap();
+    encoder.write_all(b"second file").unwrap();
+    let second = encoder.finish().unwrap();
+
+    assert_eq!(decode_all(&first[..]).unwrap(), b"first file");
+    assert_eq!(decode_all(&second[..]).unwrap(), b"second file");
+}
+
+#[test]
+#[cfg(all(feature = "experimental", feature = "zstdmt"))]
+fn test_thread_pool() {
+    let pool = crate::ThreadPool::new(2).unwrap();
+    let input = crate::test_util::text(1_000_000, 5);
+
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                let mut encoder = Encoder::new(Vec::new(), 1).unwrap();
+                encoder.multithread(2).unwrap();
+                encoder.thread_pool(&pool).unwrap();
+                encoder.write_all(&input).unwrap();
+                let compressed = encoder.finish().unwrap();
+                assert_eq!(decode_all(&compressed[..]).unwrap(), input);
+            });
+        }
+    });
+}
@@ -3,5 +3,6 @@
 mod reader;
 mod writer;
 
-pub use self::reader::Reader;
-pub use self::writer::Writer;
+pub(crate) use self::reader::may_start_frame;
+pub use self::reader::{FrameBoundary, Reader, TrailingData};
+pub use self::writer::{AutoFinishWriter, State, Writer};
@@ -1,6 +1,30 @@
 use std::io::{self, BufRead, Read};
 
-use crate::stream::raw::{InBuffer, Operation, OutBuffer};
+use crate::stream::raw::{self, InBuffer, Operation, OutBuffer};
+
+/// Position in the stream where a frame ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameBoundary {
+    /// Number of bytes consumed from the underlying reader at this point.
+    pub compressed_offset: u64,
+
+    /// Number of bytes produced by the operation at this point.
+    pub decompressed_offset: u64,
+}
+
+/// How to handle data following the last frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrailingData {
+    /// Data which is not a frame after the last one is an error.
+    ///
+    /// This data is given to the operation, which reports the error.
+    Strict,
+
+    /// Reading stops cleanly at the first data which is not a frame.
+    ///
+    /// The rest of the input is consumed and counted.
+    Lenient,
+}
 
 // [ reader -> zstd ] -> output
 /// Implements the [`Read`] API around an [`Operation`].
@@ -15,8 +39,31 @@ pub struct Reader<R, D> {
 
     finished: bool,
 
-    single_frame: bool,
     finished_frame: bool,
+
+    // `true` while a frame was started but not finished yet.
+    in_frame: bool,
+
+    // When set, reads stop at the end of each frame until `resume()`.
+    pause_at_frame_end: bool,
+    paused: bool,
+
+    total_in: u64,
+    total_out: u64,
+    last_frame_end: Option<FrameBoundary>,
+    // Same as `last_frame_end`, but never taken.
+    latest_frame_end: Option<FrameBoundary>,
+    // Number of frames finished so far.
+    frame_count: u64,
+
+    // When set, input is copied unchanged instead of going through the
+    // operation.
+    passthrough: bool,
+
+    // What to do when more input after a frame doesn't start a new frame.
+    trailing_data: TrailingData,
+    // Size of the data ignored after the last frame.
+    trailing_bytes: Option<u64>,
 }
 
 impl<R, D> Reader<R, D> {
@@ -28,14 +75,115 @@ impl<R, D> Reader<R, D> {
             reader,
             operation,
             finished: false,
-            single_frame: false,
             finished_frame: false,
+            in_frame: false,
+            pause_at_frame_end: false,
+            paused: false,
+            total_in: 0,
+            total_out: 0,
+            last_frame_end: None,
+            latest_frame_end: None,
+            frame_count: 0,
+            passthrough: false,
+            trailing_data: TrailingData::Strict,
+            trailing_bytes: None,
         }
     }
 
-    /// Sets `self` to stop after the first decoded frame.
-    pub fn set_single_frame(&mut self) {
-        self.single_frame = true;
+    /// Sets `self` to copy the rest of the input unchanged, bypassing the
+    /// operation.
+    ///
+    /// The remaining input is treated as a single frame.
+    pub fn set_passthrough(&mut self) {
+        self.passthrough = true;
+    }
+
+    /// Returns `true` if `self` copies its input unchanged.
+    pub fn is_passthrough(&self) -> bool {
+        self.passthrough
+    }
+
+    /// Sets how to handle input which doesn't start a new frame after the
+    /// end of a frame.
+    ///
+    /// The default, `TrailingData::Strict`, gives such input to the
+    /// operation like any other. This only makes sense for decompression.
+    pub fn set_trailing_data(&mut self, trailing_data: TrailingData) {
+        self.trailing_data = trailing_data;
+    }
+
+    /// Returns the number of bytes ignored after the last frame, with
+    /// `TrailingData::Lenient`.
+    ///
+    /// This is `None` until some trailing data is found.
+    pub fn trailing_bytes(&self) -> Option<u64> {
+        self.trailing_bytes
+    }
+
+    /// Sets whether `self` should pause at the end of each frame.
+    ///
+    /// While paused, reads return `Ok(0)`, even for empty frames. Call
+    /// `resume()` to start reading the next frame.
+    pub fn set_pause_at_frame_end(&mut self, pause: bool) {
+        self.pause_at_frame_end = pause;
+        if !pause {
+            self.paused = false;
+        }
+    }
+
+    /// Resumes reading after pausing at the end of a frame.
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    /// Returns `true` if no frame is currently in progress.
+    ///
+    /// This is the case before the first frame, and right after a frame
+    /// was finished.
+    pub fn is_at_frame_boundary(&self) -> bool {
+        !self.in_frame
+    }
+
+    /// Returns `true` if this reader reached the end of its output.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Returns the number of bytes consumed from the underlying reader.
+    pub fn total_in(&self) -> u64 {
+        self.total_in
+    }
+
+    /// Returns the number of bytes produced by the operation.
+    pub fn total_out(&self) -> u64 {
+        self.total_out
+    }
+
+    /// Returns where the last frame ended, if a frame ended since the last
+    /// call.
+    pub fn take_frame_boundary(&mut self) -> Option<FrameBoundary> {
+        self.last_frame_end.take()
+    }
+
+    /// Returns where the last frame ended, if any frame ended yet.
+    pub fn latest_frame_boundary(&self) -> Option<FrameBoundary> {
+        self.latest_frame_end
+    }
+
+    fn finish_frame(&mut self, boundary: FrameBoundary) {
+        self.in_frame = false;
+        self.finished_frame = true;
+        self.last_frame_end = Some(boundary);
+        self.latest_frame_end = Some(boundary);
+        self.frame_count += 1;
+        if self.pause_at_frame_end {
+            self.paused = true;
+        }
+    }
+
+    /// Returns a reference to the underlying operation.
+    pub fn operation(&self) -> &D {
+        &self.operation
     }
 
     /// Returns a mutable reference to the underlying operation.
@@ -57,40 +205,165 @@ impl<R, D> Reader<R, D> {
     pub fn into_inner(self) -> R {
         self.reader
     }
+
+    /// Clears all the progress, to start over with a new input.
+    ///
+    /// The settings are kept. The operation and the underlying reader are
+    /// left untouched: callers need to reset or replace them themselves.
+    pub fn reset(&mut self) {
+        self.finished = false;
+        self.finished_frame = false;
+        self.in_frame = false;
+        self.paused = false;
+        self.total_in = 0;
+        self.total_out = 0;
+        self.last_frame_end = None;
+        self.latest_frame_end = None;
+        self.frame_count = 0;
+        self.passthrough = false;
+        self.trailing_bytes = None;
+    }
 }
+
+impl<R> Reader<R, raw::Decoder<'_>> {
+    /// Sets `self` to stop after the first decoded frame.
+    ///
+    /// See `raw::Decoder::set_single_frame`.
+    pub fn set_single_frame(&mut self) {
+        self.operation.set_single_frame();
+    }
+
+    /// Sets `self` to stop after the given number of frames.
+    ///
+    /// See `raw::Decoder::set_max_frames`.
+    pub fn set_max_frames(&mut self, max_frames: Option<u64>) {
+        self.operation.set_max_frames(max_frames);
+    }
+}
+
+impl<R: BufRead> Reader<R, raw::Decoder<'_>> {
+    /// Skips an entire frame directly in the underlying reader.
+    ///
+    /// `size` is the compressed size of the frame, which must start at the
+    /// current position. It is not processed by the operation, and the next
+    /// read will start a new frame.
+    pub fn skip_frame_input(&mut self, size: usize) {
+        self.reader.consume(size);
+        self.total_in += size as u64;
+        self.operation.skip_frame();
+        self.finish_frame(FrameBoundary {
+            compressed_offset: self.total_in,
+            decompressed_offset: self.total_out,
+        });
+        if self.operation.is_done() {
+            self.finished = true;
+        }
+    }
+}
+
 // Read and retry on Interrupted errors.
 fn fill_buf<R>(reader: &mut R) -> io::Result<&[u8]>
 where
     R: BufRead,
 {
-    // This doesn't work right now because of the borrow-checker.
-    // When it can be made to compile, it would allow Reader to automatically
-    // retry on `Interrupted` error.
-    /*
     loop {
         match reader.fill_buf() {
             Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
-            otherwise => return otherwise,
+            Err(e) => return Err(e),
+            // Don't try reading again after the end of the input.
+            Ok([]) => return Ok(&[]),
+            Ok(_) => break,
         }
     }
-    */
 
-    // Workaround for now
+    // Returning the buffer from inside the loop doesn't pass the
+    // borrow-checker yet. Once filled, getting it again is cheap.
     reader.fill_buf()
 }
 
+// Returns `false` if `input` cannot be the start of a frame.
+//
+// Input shorter than a magic number only needs to be the start of one.
+pub(crate) fn may_start_frame(input: &[u8]) -> bool {
+    if input.len() >= 4 {
+        return zstd_safe::is_frame(input) != 0;
+    }
+    let rest = match input.first() {
+        None => return true,
+        // Skippable frames use 16 magic numbers.
+        Some(0x50..=0x5F) => &[0x2A, 0x4D, 0x18],
+        // Legacy frames, and current ones.
+        Some(0x22..=0x28) => &[0xB5, 0x2F, 0xFD],
+        Some(_) => return false,
+    };
+    input[1..] == rest[..input.len() - 1]
+}
+
+impl<R: BufRead, D> Reader<R, D> {
+    // With `TrailingData::Lenient`, skips the input after a frame if it
+    // doesn't start a new one.
+    //
+    // Returns `true` if reading should stop here.
+    fn skip_trailing_data(&mut self) -> io::Result<bool> {
+        if self.trailing_data != TrailingData::Lenient || !self.finished_frame
+        {
+            return Ok(false);
+        }
+        if may_start_frame(fill_buf(&mut self.reader)?) {
+            return Ok(false);
+        }
+
+        let mut trailing = 0;
+        loop {
+            let len = fill_buf(&mut self.reader)?.len();
+            if len == 0 {
+                break;
+            }
+            self.reader.consume(len);
+            trailing += len as u64;
+        }
+        self.trailing_bytes = Some(trailing);
+        self.finished = true;
+        Ok(true)
+    }
+}
+
 impl<R, D> Read for Reader<R, D>
 where
     R: BufRead,
     D: Operation,
 {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        if self.finished {
+        if self.finished || self.paused {
             return Ok(0);
         }
 
+        if self.passthrough {
+            let input = fill_buf(&mut self.reader)?;
+            let len = usize::min(input.len(), buf.len());
+            buf[..len].copy_from_slice(&input[..len]);
+            self.reader.consume(len);
+            self.total_in += len as u64;
+            self.total_out += len as u64;
+
+            self.in_frame = len != 0;
+            if len == 0 {
+                self.finished = true;
+            }
+            return Ok(len);
+        }
+
         // Keep trying until _something_ has been written.
         loop {
+            if self.operation.is_done() {
+                // The rest of the input is left untouched.
+                self.finished = true;
+                return Ok(0);
+            }
+            if self.skip_trailing_data()? {
+                return Ok(0);
+            }
+
             let (bytes_read, bytes_written) = {
                 // Start with a fresh pool of un-processed data.
                 // This is the only line that can return an interuption error.
@@ -115,14 +388,40 @@ where
                     }
 
                     // Phase 1: feed input to the operation
-                    let hint = self.operation.run(&mut src, &mut dst)?;
+                    let (frame_index, input_offset) =
+                        (self.frame_count, self.total_in);
+                    let frame_offset = self
+                        .latest_frame_end
+                        .map_or(0, |end| end.compressed_offset);
+                    let hint = self
+                        .operation
+                        .run(&mut src, &mut dst)
+                        .map_err(|e| {
+                            crate::stream::error::locate(
+                                e,
+                                frame_index,
+                                frame_offset,
+                                input_offset,
+                            )
+                        })?;
 
+                    self.in_frame = hint != 0;
                     if hint == 0 {
                         // We just finished a frame.
                         self.finished_frame = true;
-                        if self.single_frame {
+                        self.last_frame_end = Some(FrameBoundary {
+                            compressed_offset: self.total_in + src.pos as u64,
+                            decompressed_offset: self.total_out
+                                + dst.pos as u64,
+                        });
+                        self.latest_frame_end = self.last_frame_end;
+                        self.frame_count += 1;
+                        if self.operation.is_done() {
                             self.finished = true;
                         }
+                        if self.pause_at_frame_end {
+                            self.paused = true;
+                        }
                     }
                 } else {
                     // TODO: Make it Work!
@@ -148,8 +447,10 @@ where
                 (src.pos, dst.pos)
             };
             self.reader.consume(bytes_read);
+            self.total_in += bytes_read as u64;
+            self.total_out += bytes_written as u64;
 
-            if bytes_written > 0 {
+            if bytes_written > 0 || self.finished || self.paused {
                 return Ok(bytes_written);
             }
             // We need more data! Try again!
@@ -194,4 +495,35 @@ mod tests {
         let decoded = crate::decode_all(&output[..]).unwrap();
         assert_eq!(&decoded, input);
     }
+
+    #[test]
+    fn test_interrupted() {
+        use crate::stream::raw::Decoder;
+        use partial_io::{PartialOp, PartialRead};
+        use std::io::{BufReader, ErrorKind};
+        use std::iter;
+
+        let input = b"AbcdefghAbcdefgh.".repeat(1000);
+        let compressed = crate::encode_all(&input[..], 1).unwrap();
+
+        let ops = iter::repeat(PartialOp::Err(ErrorKind::Interrupted))
+            .zip(iter::repeat(PartialOp::Limited(100)))
+            .flat_map(|(a, b)| vec![a, b]);
+        let source = BufReader::with_capacity(
+            100,
+            PartialRead::new(&compressed[..], ops),
+        );
+        let mut reader = Reader::new(source, Decoder::new().unwrap());
+
+        // Unlike `read_to_end`, `read` alone doesn't retry.
+        let mut output = Vec::new();
+        let mut buffer = [0u8; 1000];
+        loop {
+            match reader.read(&mut buffer).unwrap() {
+                0 => break,
+                n => output.extend_from_slice(&buffer[..n]),
+            }
+        }
+        assert_eq!(output, input);
+    }
 }
@@ -1,9 +1,38 @@
 use std::io::{self, Write};
 
 use crate::stream::raw::{InBuffer, Operation, OutBuffer};
+use crate::stream::FrameLimitReached;
 
 // input -> [ zstd -> buffer -> writer ]
 
+/// Current state of a [`Writer`], as seen from the outside.
+///
+/// This tells supervisory code whether a stream can still be used, or if
+/// it only needs to be drained or dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum State {
+    /// Accepting data, no flush or finish in progress.
+    Streaming,
+
+    /// A flush was interrupted (for example by a `WouldBlock` error from
+    /// the inner writer) and should be retried.
+    Flushing,
+
+    /// Finishing started but is not complete yet, and should be retried.
+    Finishing,
+
+    /// The stream was finished, and all of it reached the inner writer.
+    Finished,
+
+    /// The operation failed (for example on corrupted data), and the
+    /// stream cannot be used anymore.
+    ///
+    /// Errors from the inner writer do not lead to this state: the failed
+    /// call can be retried.
+    Errored,
+}
+
 /// Implements the [`Write`] API around an [`Operation`].
 ///
 /// This can be used to wrap a raw in-memory operation in a write-focused API.
@@ -14,14 +43,51 @@ pub struct Writer<W, D> {
     writer: W,
     operation: D,
 
+    // Output of the operation: only `buffer[..len]` is valid, and
+    // `buffer[offset..len]` still has to be written out.
+    //
+    // The buffer is zero-initialized once, so it can be handed to zstd
+    // without ever exposing uninitialized memory.
     offset: usize,
-    buffer: Vec<u8>,
+    len: usize,
+    buffer: Box<[u8]>,
 
     // When `true`, indicates that nothing should be added to the buffer.
     // All that's left if to empty the buffer.
     finished: bool,
 
     finished_frame: bool,
+
+    // Whether some input was consumed since the last complete flush.
+    unflushed: bool,
+
+    // Set by `capture_output`.
+    // Boxed to keep `Writer` small.
+    capture: Option<Box<Capture>>,
+
+    state: State,
+}
+
+// Output recorded by `Writer::capture_output`.
+struct Capture {
+    data: Vec<u8>,
+    limit: usize,
+}
+
+/// A wrapper around a `Writer` that finishes the operation on drop.
+///
+/// This avoids silently truncated output when a writer is dropped early,
+/// for example in error-handling paths.
+///
+/// Finishing never panics: errors are given to the `on_finish` callback,
+/// or can be handled directly by calling [`finish`] instead of dropping.
+///
+/// [`finish`]: #method.finish
+pub struct AutoFinishWriter<W: Write, D: Operation> {
+    // We wrap this in an option to take it during drop.
+    writer: Option<Writer<W, D>>,
+
+    on_finish: Option<Box<dyn FnMut(io::Result<W>)>>,
 }
 
 impl<W, D> Writer<W, D>
@@ -38,11 +104,15 @@ where
             operation,
 
             offset: 0,
+            len: 0,
             // 32KB buffer? That's what flate2 uses
-            buffer: Vec::with_capacity(32 * 1024),
+            buffer: vec![0; 32 * 1024].into_boxed_slice(),
 
             finished: false,
             finished_frame: false,
+            unflushed: false,
+            capture: None,
+            state: State::Streaming,
         }
     }
 
@@ -56,6 +126,7 @@ where
     ///
     /// Keep calling it until it returns `Ok(())`, then don't call it again.
     pub fn finish(&mut self) -> io::Result<()> {
+        self.set_state(State::Finishing);
         loop {
             // Keep trying until we're really done.
             self.write_from_offset()?;
@@ -63,15 +134,17 @@ where
             // At this point the buffer has been fully written out.
 
             if self.finished {
+                self.unflushed = false;
+                self.set_state(State::Finished);
                 return Ok(());
             }
 
             // Let's fill this buffer again!
 
             let finished_frame = self.finished_frame;
-            let hint = unsafe {
-                self.with_full_buffer(|dst, op| op.finish(dst, finished_frame))
-            };
+            let hint = self
+                .with_full_buffer(|dst, op| op.finish(dst, finished_frame));
+            self.check_error(&hint);
             self.offset = 0;
             // println!("Hint: {:?}\nOut:{:?}", hint, &self.buffer);
 
@@ -79,8 +152,9 @@ where
             // Could happen with invalid data, ...
             let hint = hint?;
 
-            if hint != 0 && self.buffer.is_empty() {
+            if hint != 0 && self.len == 0 {
                 // This happens if we are decoding an incomplete frame.
+                self.state = State::Errored;
                 return Err(io::Error::new(
                     io::ErrorKind::UnexpectedEof,
                     "incomplete frame",
@@ -93,25 +167,57 @@ where
         }
     }
 
-    /// Run the given closure on `self.buffer`.
+    /// Ends the current frame, and prepares the operation for a new one.
     ///
-    /// Before running it, the buffer will look as big as its capacity,
-    /// but the memory there may be uninitialized.
+    /// Unlike `finish`, this lets more data be written afterwards, in a new
+    /// frame. Keep calling it until it returns `Ok(())`.
+    pub fn finish_frame(&mut self) -> io::Result<()> {
+        self.finish()?;
+
+        let result = self.operation.reinit();
+        self.check_error(&result);
+        result?;
+        self.finished = false;
+        self.finished_frame = false;
+        self.set_state(State::Streaming);
+        Ok(())
+    }
+
+    /// Returns the current state of this writer.
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    // Moves to the given state, unless the operation already failed.
+    fn set_state(&mut self, state: State) {
+        if self.state != State::Errored {
+            self.state = state;
+        }
+    }
+
+    // Marks the operation as failed if `result` is an error.
+    fn check_error<T>(&mut self, result: &io::Result<T>) {
+        if result.is_err() {
+            self.state = State::Errored;
+        }
+    }
+
+    /// Run the given closure on the entire `self.buffer`.
     ///
-    /// It is only safe to write in this buffer, not to read from there.
-    unsafe fn with_full_buffer<F, T>(&mut self, f: F) -> T
+    /// The previous content is discarded: only what the closure writes is
+    /// kept as the new output.
+    fn with_full_buffer<F, T>(&mut self, f: F) -> T
     where
         F: FnOnce(&mut OutBuffer<'_>, &mut D) -> T,
     {
-        let capacity = self.buffer.capacity();
-        self.buffer.set_len(capacity);
-
-        let (bytes_written, result) = {
-            let mut output = OutBuffer::around(&mut self.buffer);
-            let result = f(&mut output, &mut self.operation);
-            (output.pos, result)
-        };
-        self.buffer.set_len(bytes_written);
+        let mut output = OutBuffer::around(&mut self.buffer);
+        let result = f(&mut output, &mut self.operation);
+        self.len = output.pos;
+
+        if let Some(capture) = &mut self.capture {
+            let n = usize::min(self.len, capture.limit - capture.data.len());
+            capture.data.extend_from_slice(&self.buffer[..n]);
+        }
         result
     }
 
@@ -121,8 +227,14 @@ where
     fn write_from_offset(&mut self) -> io::Result<()> {
         // The code looks a lot like `write_all`, but keeps track of what has
         // been written in case we're interrupted.
-        while self.offset < self.buffer.len() {
-            match self.writer.write(&self.buffer[self.offset..]) {
+        while self.offset < self.len {
+            match self.writer.write(&self.buffer[self.offset..self.len]) {
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "writer will not accept any more data",
+                    ))
+                }
                 Ok(n) => self.offset += n,
                 Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
                 Err(e) => return Err(e),
@@ -131,6 +243,20 @@ where
         Ok(())
     }
 
+    /// Returns a wrapper around `self` that will finish the operation on
+    /// drop.
+    ///
+    /// Calls the given callback with the result, and the inner writer.
+    pub fn on_finish<F: 'static + FnMut(io::Result<W>)>(
+        self,
+        f: F,
+    ) -> AutoFinishWriter<W, D> {
+        AutoFinishWriter {
+            writer: Some(self),
+            on_finish: Some(Box::new(f)),
+        }
+    }
+
     /// Return the wrapped `Writer` and `Operation`.
     ///
     /// Careful: if you call this before calling [`Writer::finish()`], the
@@ -159,16 +285,99 @@ where
         &mut self.operation
     }
 
+    /// Returns `true` if some output has not reached the inner writer yet.
+    ///
+    /// This is the case after any input is consumed, until the next
+    /// complete [`flush`] or [`finish`].
+    ///
+    /// [`flush`]: Write::flush
+    /// [`finish`]: Writer::finish
+    pub fn has_pending_output(&self) -> bool {
+        self.unflushed || !self.pending_output().is_empty()
+    }
+
+    /// Returns the output of the operation not written to the inner writer
+    /// yet.
+    pub fn pending_output(&self) -> &[u8] {
+        &self.buffer[self.offset..self.len]
+    }
+
     /// Returns the offset in the current buffer. Only useful for debugging.
     #[cfg(test)]
     pub fn offset(&self) -> usize {
         self.offset
     }
 
-    /// Returns the current buffer. Only useful for debugging.
-    #[cfg(test)]
+    /// Starts recording the next `limit` bytes of output.
+    ///
+    /// Unlike `buffer`, the recorded bytes don't depend on how the output
+    /// was split between calls. Any previous recording is discarded.
+    pub fn capture_output(&mut self, limit: usize) {
+        self.capture = Some(Box::new(Capture {
+            data: Vec::new(),
+            limit,
+        }));
+    }
+
+    /// Returns the output recorded since `capture_output`.
+    pub fn captured_output(&self) -> &[u8] {
+        self.capture.as_ref().map_or(&[], |capture| &capture.data)
+    }
+
+    /// Returns the current buffer.
+    ///
+    /// This is the last output of the operation, which may not have been
+    /// fully written to the inner writer yet.
     pub fn buffer(&self) -> &[u8] {
-        &self.buffer
+        &self.buffer[..self.len]
+    }
+}
+
+impl<W: Write, D: Operation> AutoFinishWriter<W, D> {
+    /// Gives a reference to the inner writer.
+    pub fn writer(&self) -> &W {
+        self.writer.as_ref().unwrap().writer()
+    }
+
+    /// Gives a mutable reference to the inner writer.
+    pub fn writer_mut(&mut self) -> &mut W {
+        self.writer.as_mut().unwrap().writer_mut()
+    }
+
+    /// Gives a mutable reference to the inner operation.
+    pub fn operation_mut(&mut self) -> &mut D {
+        self.writer.as_mut().unwrap().operation_mut()
+    }
+
+    /// Finishes the operation now, and returns the inner writer.
+    ///
+    /// Errors are returned directly: the `on_finish` callback is not called.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.on_finish = None;
+        let mut writer = self.writer.take().unwrap();
+        writer.finish()?;
+        Ok(writer.into_inner().0)
+    }
+}
+
+impl<W: Write, D: Operation> Drop for AutoFinishWriter<W, D> {
+    fn drop(&mut self) {
+        if let Some(mut writer) = self.writer.take() {
+            let result = writer.finish().map(|()| writer.into_inner().0);
+            if let Some(mut on_finish) = self.on_finish.take() {
+                on_finish(result);
+            }
+        }
+    }
+}
+
+impl<W: Write, D: Operation> Write for AutoFinishWriter<W, D> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.as_mut().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.as_mut().unwrap().flush()
     }
 }
 
@@ -187,18 +396,27 @@ where
             self.write_from_offset()?;
             // At this point `self.buffer` can safely be discarded.
 
+            // The operation won't take anything after its last frame.
+            if self.operation.is_done() {
+                if buf.is_empty() {
+                    return Ok(0);
+                }
+                return Err(FrameLimitReached.into());
+            }
+
             // Support writing concatenated frames by re-initializing the
             // context.
             if self.finished_frame {
-                self.operation.reinit()?;
+                let result = self.operation.reinit();
+                self.check_error(&result);
+                result?;
                 self.finished_frame = false;
             }
 
             let mut src = InBuffer::around(buf);
-            let hint = unsafe {
-                self.with_full_buffer(|dst, op| op.run(&mut src, dst))
-            };
+            let hint = self.with_full_buffer(|dst, op| op.run(&mut src, dst));
             let bytes_read = src.pos;
+            self.check_error(&hint);
 
             // println!("Hint: {:?}\nRead {}", hint, bytes_read);
 
@@ -208,6 +426,12 @@ where
             if hint == 0 {
                 self.finished_frame = true;
             }
+            if bytes_read > 0 {
+                self.unflushed = true;
+                if self.state == State::Flushing {
+                    self.state = State::Streaming;
+                }
+            }
 
             // As we said, as soon as we've consumed something, return.
             if bytes_read > 0 || buf.is_empty() {
@@ -218,17 +442,24 @@ where
     }
 
     fn flush(&mut self) -> io::Result<()> {
+        if self.state == State::Streaming {
+            self.state = State::Flushing;
+        }
         let mut finished = self.finished;
         loop {
             // If the output is blocked or has an error, return now.
             self.write_from_offset()?;
 
             if finished {
+                self.unflushed = false;
+                if self.state == State::Flushing {
+                    self.state = State::Streaming;
+                }
                 return Ok(());
             }
 
-            let hint =
-                unsafe { self.with_full_buffer(|dst, op| op.flush(dst)) };
+            let hint = self.with_full_buffer(|dst, op| op.flush(dst));
+            self.check_error(&hint);
 
             self.offset = 0;
             let hint = hint?;
@@ -243,6 +474,26 @@ mod tests {
     use super::Writer;
     use std::io::Write;
 
+    #[test]
+    fn test_auto_finish() {
+        use crate::stream::raw::Encoder;
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let output = Rc::new(RefCell::new(None));
+        let result = output.clone();
+        let mut writer = Writer::new(Vec::new(), Encoder::new(1).unwrap())
+            .on_finish(move |finished| {
+                *result.borrow_mut() = Some(finished.unwrap());
+            });
+        writer.write_all(b"dropped early").unwrap();
+        drop(writer);
+
+        let output = output.borrow_mut().take().unwrap();
+        let decoded = crate::decode_all(&output[..]).unwrap();
+        assert_eq!(decoded, b"dropped early");
+    }
+
     #[test]
     fn test_noop() {
         use crate::stream::raw::NoOp;
@@ -295,4 +546,87 @@ mod tests {
         // println!("Output: {:?}", output);
         assert_eq!(&output, input);
     }
+
+    #[test]
+    fn test_interrupted() {
+        use crate::stream::raw::Encoder;
+        use partial_io::{PartialOp, PartialWrite};
+        use std::io::ErrorKind;
+        use std::iter;
+
+        let input = b"AbcdefghAbcdefgh.".repeat(1000);
+
+        let ops = iter::repeat(PartialOp::Err(ErrorKind::Interrupted))
+            .zip(iter::repeat(PartialOp::Limited(10)))
+            .flat_map(|(a, b)| vec![a, b]);
+        let mut writer = Writer::new(
+            PartialWrite::new(Vec::new(), ops),
+            Encoder::new(1).unwrap(),
+        );
+        // Unlike `write_all`, `write` alone doesn't retry.
+        let mut remaining = &input[..];
+        while !remaining.is_empty() {
+            let written = writer.write(remaining).unwrap();
+            remaining = &remaining[written..];
+        }
+        writer.flush().unwrap();
+        writer.finish().unwrap();
+
+        let output = writer.into_inner().0.into_inner();
+        assert_eq!(crate::decode_all(&output[..]).unwrap(), input);
+    }
+
+    #[test]
+    fn test_write_zero() {
+        use crate::stream::raw::NoOp;
+
+        let mut buffer = [0u8; 4];
+        let mut writer = Writer::new(&mut buffer[..], NoOp);
+        writer.write_all(b"too long").unwrap();
+        let error = writer.finish().unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::WriteZero);
+    }
+
+    #[test]
+    fn test_partial_writes() {
+        use crate::stream::raw::Decoder;
+        use partial_io::{PartialOp, PartialWrite};
+        use std::io::ErrorKind;
+        use std::iter;
+
+        let input = b"AbcdefghAbcdefgh.".repeat(5000);
+        let compressed = crate::encode_all(&input[..], 1).unwrap();
+
+        // The inner writer takes at most 7 bytes at a time, and blocks
+        // every other call: pending output must survive across calls.
+        let ops = iter::repeat(PartialOp::Err(ErrorKind::WouldBlock))
+            .zip(iter::repeat(PartialOp::Limited(7)))
+            .flat_map(|(a, b)| vec![a, b]);
+        let mut writer = Writer::new(
+            PartialWrite::new(Vec::new(), ops),
+            Decoder::new().unwrap(),
+        );
+
+        let mut remaining = &compressed[..];
+        while !remaining.is_empty() {
+            match writer.write(remaining) {
+                Ok(written) => remaining = &remaining[written..],
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
+                    assert!(writer.has_pending_output());
+                }
+                Err(e) => panic!("{}", e),
+            }
+        }
+        loop {
+            match writer.finish() {
+                Ok(()) => break,
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => (),
+                Err(e) => panic!("{}", e),
+            }
+        }
+        assert!(writer.pending_output().is_empty());
+
+        let output = writer.into_inner().0.into_inner();
+        assert_eq!(output, input);
+    }
 }
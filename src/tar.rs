@@ -0,0 +1,139 @@
+//! Create and extract `.tar.zst` archives.
+//!
+//! This module wires the stream `Encoder` and `Decoder` to the [`tar`]
+//! crate's `Builder` and `Archive`.
+//!
+//! *This module requires the `tar` feature.*
+//!
+//! [`tar`]: https://docs.rs/tar
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! # fn main() -> std::io::Result<()> {
+//! use std::fs::File;
+//!
+//! let mut builder = zstd::tar::builder(File::create("logs.tar.zst")?, 3)?;
+//! builder.append_dir_all("logs", "/var/log/app")?;
+//! zstd::tar::finish(builder)?;
+//!
+//! zstd::tar::archive(File::open("logs.tar.zst")?)?.unpack("restored")?;
+//! # Ok(())
+//! # }
+//! ```
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use crate::stream::{read, write};
+
+/// Creates a tar builder compressing the archive to `writer`.
+///
+/// Once all entries are appended, use [`finish`] to complete both the
+/// archive and the zstd frame.
+///
+/// [`finish`]: fn.finish.html
+pub fn builder<W: Write>(
+    writer: W,
+    level: i32,
+) -> io::Result<::tar::Builder<write::Encoder<'static, W>>> {
+    Ok(::tar::Builder::new(write::Encoder::new(writer, level)?))
+}
+
+/// Completes the archive and the compressed stream.
+///
+/// Returns the inner writer.
+pub fn finish<W: Write>(
+    builder: ::tar::Builder<write::Encoder<'_, W>>,
+) -> io::Result<W> {
+    builder.into_inner()?.finish()
+}
+
+/// Opens a compressed tar archive from `reader`.
+///
+/// The archive may span several zstd frames, as produced by concatenating
+/// `.tar.zst` files or by a multi-threaded or seekable compressor.
+///
+/// Concatenated archives each end with an end-of-archive marker: call
+/// `set_ignore_zeros(true)` on the result to read entries past the first
+/// one.
+pub fn archive<R: Read>(
+    reader: R,
+) -> io::Result<::tar::Archive<read::Decoder<'static, BufReader<R>>>> {
+    Ok(::tar::Archive::new(read::Decoder::new(reader)?))
+}
+
+/// Opens a compressed tar archive from an already buffered `reader`.
+///
+/// See [`archive`] for details.
+///
+/// [`archive`]: fn.archive.html
+pub fn archive_with_buffer<R: BufRead>(
+    reader: R,
+) -> io::Result<::tar::Archive<read::Decoder<'static, R>>> {
+    Ok(::tar::Archive::new(read::Decoder::with_buffer(reader)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{archive, archive_with_buffer, builder, finish};
+    use std::io::Read;
+
+    fn build(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = builder(Vec::new(), 1).unwrap();
+        for &(path, data) in entries {
+            let mut header = ::tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, data).unwrap();
+        }
+        finish(builder).unwrap()
+    }
+
+    fn entries<R: Read>(
+        mut archive: ::tar::Archive<R>,
+    ) -> Vec<(String, Vec<u8>)> {
+        archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let path = entry.path().unwrap().display().to_string();
+                let mut data = Vec::new();
+                entry.read_to_end(&mut data).unwrap();
+                (path, data)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_cycle() {
+        let data = b"hello, tar".repeat(1000);
+        let compressed = build(&[("a.txt", &data), ("dir/b.txt", b"")]);
+
+        let entries = entries(archive(&compressed[..]).unwrap());
+        assert_eq!(
+            entries,
+            vec![
+                ("a.txt".to_string(), data),
+                ("dir/b.txt".to_string(), Vec::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_concatenated() {
+        let mut compressed = build(&[("a.txt", b"first")]);
+        compressed.extend(build(&[("b.txt", b"second")]));
+
+        let mut archive = archive_with_buffer(&compressed[..]).unwrap();
+        archive.set_ignore_zeros(true);
+        let entries = entries(archive);
+        assert_eq!(
+            entries,
+            vec![
+                ("a.txt".to_string(), b"first".to_vec()),
+                ("b.txt".to_string(), b"second".to_vec()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,227 @@
+//! Helpers to test code built on this crate.
+//!
+//! This module is only available with the `test-util` feature. It exposes
+//! the tools used by this crate's own tests:
+//!
+//! * [`cycle`] and [`cycle_unwrap`] check that data survives a round-trip.
+//! * [`corpus`] generates inputs covering the usual edge cases (empty,
+//!   incompressible, highly repetitive, ...).
+//! * [`decode_arbitrary`] feeds any bytes to every decoder, for fuzz
+//!   targets: it must never panic, whatever the input.
+//!
+//! ```
+//! use zstd::test_util;
+//!
+//! for input in test_util::corpus(42) {
+//!     test_util::cycle_unwrap(
+//!         &input,
+//!         |data| zstd::encode_all(data, 1),
+//!         |data| zstd::decode_all(data),
+//!     );
+//! }
+//! ```
+//!
+//! [`cycle`]: fn.cycle.html
+//! [`cycle_unwrap`]: fn.cycle_unwrap.html
+//! [`corpus`]: fn.corpus.html
+//! [`decode_arbitrary`]: fn.decode_arbitrary.html
+use std::io::{self, Read, Write};
+
+/// Checks that `g(f(data))` gives back `data`.
+///
+/// Panics otherwise.
+pub fn cycle<F, G>(data: &[u8], f: F, g: G)
+where
+    F: Fn(&[u8]) -> Vec<u8>,
+    G: Fn(&[u8]) -> Vec<u8>,
+{
+    let mid = f(data);
+    let end = g(&mid);
+    assert_eq!(data, &end[..]);
+}
+
+/// Checks that `g(f(data))` gives back `data`, with fallible functions.
+///
+/// Panics if either function fails, or if the result differs.
+pub fn cycle_unwrap<F, G>(data: &[u8], f: F, g: G)
+where
+    F: Fn(&[u8]) -> io::Result<Vec<u8>>,
+    G: Fn(&[u8]) -> io::Result<Vec<u8>>,
+{
+    cycle(data, |data| f(data).unwrap(), |data| g(data).unwrap())
+}
+
+/// Returns a set of test inputs, covering the usual edge cases.
+///
+/// This includes empty and single-byte inputs, incompressible data, long
+/// runs, text-like data, and inputs spanning several zstd blocks. The
+/// `seed` changes the generated content, but not the shape of the set.
+pub fn corpus(seed: u64) -> Vec<Vec<u8>> {
+    let mut rng = Rng::new(seed);
+    let mut corpus = vec![Vec::new(), vec![rng.next() as u8]];
+    for &len in &[100, 10_000, 300_000] {
+        corpus.push(vec![0; len]);
+        corpus.push(random_bytes(len, rng.next()));
+        corpus.push(text(len, rng.next()));
+    }
+    corpus
+}
+
+/// Returns `len` pseudo-random bytes, which do not compress.
+pub fn random_bytes(len: usize, seed: u64) -> Vec<u8> {
+    let mut rng = Rng::new(seed);
+    (0..len).map(|_| rng.next() as u8).collect()
+}
+
+/// Returns `len` bytes of text-like data, which compresses reasonably well.
+pub fn text(len: usize, seed: u64) -> Vec<u8> {
+    const WORDS: &[&str] = &[
+        "the",
+        "frame",
+        "block",
+        "window",
+        "level",
+        "of",
+        "and",
+        "zstd",
+        "stream",
+        "dictionary",
+        "a",
+        "checksum",
+        "match",
+        "literal",
+        "to",
+    ];
+
+    let mut rng = Rng::new(seed);
+    let mut text = Vec::with_capacity(len + 16);
+    while text.len() < len {
+        let word = WORDS[rng.next() as usize % WORDS.len()];
+        text.extend_from_slice(word.as_bytes());
+        // About one line break every dozen words.
+        let separator = match rng.next() % 12 {
+            0 => b'\n',
+            _ => b' ',
+        };
+        text.push(separator);
+    }
+    text.truncate(len);
+    text
+}
+
+/// Decodes `data` with every decoder, ignoring the results.
+///
+/// Errors are expected for arbitrary input, but panics (or crashes) are
+/// bugs. This is meant to be called from fuzz targets.
+///
+/// Decompressed output is capped, so decompression bombs don't exhaust
+/// memory.
+pub fn decode_arbitrary(data: &[u8]) {
+    const LIMIT: u64 = 1 << 20;
+
+    let _ = crate::stream::copy_decode_with_limit(data, io::sink(), LIMIT);
+    let _ = crate::block::decompress(data, LIMIT as usize);
+    let _ = crate::frame::checksums(data);
+
+    // Small reads and writes take different paths than whole buffers.
+    if let Ok(decoder) = crate::stream::read::Decoder::from_slice(data) {
+        let mut decoder = decoder.take(LIMIT);
+        let mut buffer = [0u8; 7];
+        while let Ok(1..=7) = decoder.read(&mut buffer) {}
+    }
+    let writer = LimitedWriter {
+        inner: io::sink(),
+        remaining: LIMIT,
+    };
+    if let Ok(mut decoder) = crate::stream::write::Decoder::new(writer) {
+        if data.chunks(7).all(|chunk| decoder.write_all(chunk).is_ok()) {
+            let _ = decoder.finish();
+        }
+    }
+}
+
+// Fails writes once `remaining` bytes were written.
+struct LimitedWriter<W> {
+    inner: W,
+    remaining: u64,
+}
+
+impl<W: Write> Write for LimitedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.len() as u64 > self.remaining {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "output limit reached",
+            ));
+        }
+        let written = self.inner.write(buf)?;
+        self.remaining -= written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// Small xorshift generator: reproducible, and good enough for test data.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // The state must never be zero: the one seed giving a zero state
+        // uses the seed `0` state instead.
+        const MIX: u64 = 0x9E37_79B9_7F4A_7C15;
+        match seed ^ MIX {
+            0 => Rng(MIX),
+            state => Rng(state),
+        }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{corpus, cycle_unwrap, decode_arbitrary, Rng};
+
+    #[test]
+    fn test_rng_seeds() {
+        // No seed gives a stuck generator.
+        let mut rng = Rng::new(0x9E37_79B9_7F4A_7C15);
+        assert_ne!(rng.next(), 0);
+        assert_ne!(rng.next(), rng.next());
+    }
+
+    #[test]
+    fn test_corpus_cycle() {
+        for input in corpus(1) {
+            cycle_unwrap(
+                &input,
+                |data| crate::encode_all(data, 1),
+                |data| crate::decode_all(data),
+            );
+        }
+    }
+
+    #[test]
+    fn test_decode_arbitrary() {
+        for input in corpus(2) {
+            decode_arbitrary(&input);
+
+            // Damaged frames are the interesting case.
+            let mut frame = crate::encode_all(&input[..], 1).unwrap();
+            decode_arbitrary(&frame);
+            for i in (0..frame.len()).step_by(frame.len() / 20 + 1) {
+                frame[i] ^= 0x55;
+                decode_arbitrary(&frame);
+                decode_arbitrary(&frame[..i]);
+            }
+        }
+    }
+}
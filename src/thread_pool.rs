@@ -0,0 +1,52 @@
+use std::io;
+
+/// A bounded pool of compression threads, shared by many encoders.
+///
+/// By default, each multithreaded encoder spawns its own `n_workers`
+/// threads. Encoders using a shared pool draw their workers from it
+/// instead, which keeps the total number of threads predictable when many
+/// encoders run at once.
+///
+/// Encoders still need `multithread(n_workers)` with `n_workers >= 1` to
+/// use the pool. The pool must outlive them: a `static` pool, or one
+/// created at startup and borrowed by request handlers, works well.
+///
+/// This is only available with the `zstdmt` and `experimental` features.
+///
+/// ```
+/// use std::io::Write;
+///
+/// let pool = zstd::ThreadPool::new(4)?;
+///
+/// let mut encoder = zstd::stream::write::Encoder::new(Vec::new(), 3)?;
+/// encoder.multithread(2)?;
+/// encoder.thread_pool(&pool)?;
+/// encoder.write_all(b"some data")?;
+/// let compressed = encoder.finish()?;
+/// assert_eq!(zstd::decode_all(&compressed[..])?, b"some data");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct ThreadPool {
+    pool: zstd_safe::ThreadPool,
+}
+
+impl ThreadPool {
+    /// Creates a pool with the given number of threads.
+    pub fn new(num_threads: usize) -> io::Result<Self> {
+        let pool =
+            zstd_safe::ThreadPool::create(num_threads).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::Other, "cannot create pool")
+            })?;
+        Ok(ThreadPool { pool })
+    }
+
+    pub(crate) fn as_pool(&self) -> &zstd_safe::ThreadPool {
+        &self.pool
+    }
+}
+
+fn _assert_traits() {
+    fn _assert_send_sync<T: Send + Sync>(_: T) {}
+
+    _assert_send_sync(ThreadPool::new(1));
+}
@@ -0,0 +1,62 @@
+//! One-shot functions for JavaScript bindings.
+//!
+//! These only use byte buffers and report errors as strings, so they can be
+//! exported directly with `wasm-bindgen`:
+//!
+//! ```rust,ignore
+//! #[wasm_bindgen]
+//! pub fn decompress(data: &[u8]) -> Result<Vec<u8>, JsValue> {
+//!     zstd::wasm::decompress(data).map_err(JsValue::from)
+//! }
+//! ```
+//!
+//! This module is available on `wasm32` targets, or with the `wasm` feature.
+use std::io;
+
+use crate::stream;
+
+// Larger outputs still work, with a growing buffer.
+const MAX_PREALLOCATION: usize = 1 << 26;
+
+/// Compresses `data` into a single frame.
+///
+/// A level of `0` uses zstd's default (currently `3`).
+pub fn compress(data: &[u8], level: i32) -> Result<Vec<u8>, String> {
+    crate::block::compress(data, level).map_err(to_string)
+}
+
+/// Decompresses all the frames in `data`.
+///
+/// The output is allocated in one go when the first frame declares a
+/// reasonable content size.
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, String> {
+    let capacity = match zstd_safe::get_frame_content_size(data) {
+        zstd_safe::CONTENTSIZE_UNKNOWN | zstd_safe::CONTENTSIZE_ERROR => 0,
+        // Don't trust the header with a huge allocation.
+        size => u64::min(size, MAX_PREALLOCATION as u64) as usize,
+    };
+    let mut result = Vec::with_capacity(capacity);
+    stream::copy_decode(data, &mut result).map_err(to_string)?;
+    Ok(result)
+}
+
+fn to_string(error: io::Error) -> String {
+    error.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compress, decompress};
+
+    #[test]
+    fn test_cycle() {
+        let data = b"hello from the browser".repeat(100);
+        let compressed = compress(&data, 1).unwrap();
+        assert_eq!(decompress(&compressed).unwrap(), data);
+
+        let streamed = crate::encode_all(&data[..], 1).unwrap();
+        assert_eq!(decompress(&streamed).unwrap(), data);
+
+        assert!(decompress(b"garbage").is_err());
+    }
+}
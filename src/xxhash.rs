@@ -0,0 +1,95 @@
+use std::hash::Hasher;
+use std::io;
+
+/// Computes the XXH64 hash of `data`, with the given seed.
+///
+/// This uses the xxhash implementation bundled with zstd. Frame checksums
+/// are the lowest 32 bits of this hash, with a seed of `0`.
+pub fn xxhash64(data: &[u8], seed: u64) -> u64 {
+    zstd_safe::xxh64(data, seed)
+}
+
+/// Computes the XXH64 hash of data given in several pieces.
+///
+/// Data can be given with `update`, or through the `Hasher` or `io::Write`
+/// implementations.
+pub struct XxHash64 {
+    state: zstd_safe::XXH64State,
+}
+
+impl XxHash64 {
+    /// Creates a new hasher with the given seed.
+    pub fn new(seed: u64) -> Self {
+        XxHash64 {
+            state: zstd_safe::XXH64State::create(seed),
+        }
+    }
+
+    /// Feeds more data to the hasher.
+    pub fn update(&mut self, data: &[u8]) {
+        self.state.update(data);
+    }
+
+    /// Returns the hash of all the data given so far.
+    ///
+    /// More data can still be added afterwards.
+    pub fn digest(&self) -> u64 {
+        self.state.digest()
+    }
+
+    /// Forgets all the data given so far, and starts again with `seed`.
+    pub fn reset(&mut self, seed: u64) {
+        self.state.reset(seed);
+    }
+}
+
+impl Default for XxHash64 {
+    fn default() -> Self {
+        XxHash64::new(0)
+    }
+}
+
+impl Hasher for XxHash64 {
+    fn write(&mut self, bytes: &[u8]) {
+        self.update(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        self.digest()
+    }
+}
+
+impl io::Write for XxHash64 {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{xxhash64, XxHash64};
+
+    #[test]
+    fn test_frame_checksum() {
+        let data = b"Some data to hash".repeat(100);
+        let mut encoder =
+            crate::stream::write::Encoder::new(Vec::new(), 1).unwrap();
+        encoder.include_checksum(true).unwrap();
+        std::io::Write::write_all(&mut encoder, &data).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let stored = crate::frame::checksum(&compressed).unwrap().unwrap();
+        assert_eq!(stored, xxhash64(&data, 0) as u32);
+
+        let mut hasher = XxHash64::default();
+        for chunk in data.chunks(7) {
+            hasher.update(chunk);
+        }
+        assert_eq!(hasher.digest(), xxhash64(&data, 0));
+    }
+}
@@ -33,6 +33,36 @@ pub use zstd_sys::ZSTD_strategy as Strategy;
 /// Reset directive.
 pub use zstd_sys::ZSTD_ResetDirective as ResetDirective;
 
+/// Values returned by `get_error_code`.
+pub use zstd_sys::ZSTD_ErrorCode as error_code;
+
+/// How far `CCtx::compress_stream2` should go (continue, flush or end).
+pub use zstd_sys::ZSTD_EndDirective as EndDirective;
+
+/// A literals/match sequence, as produced by the match finder.
+#[cfg(feature = "experimental")]
+pub use zstd_sys::ZSTD_Sequence as Sequence;
+
+/// Whether a list of sequences contains explicit block delimiters.
+#[cfg(feature = "experimental")]
+pub use zstd_sys::ZSTD_sequenceFormat_e as SequenceFormat;
+
+/// Low-level compression parameters (window size, strategy, ...).
+#[cfg(feature = "experimental")]
+pub use zstd_sys::ZSTD_compressionParameters as CompressionParameters;
+
+/// Progress of the current frame in a compression context.
+#[cfg(feature = "experimental")]
+pub use zstd_sys::ZSTD_frameProgression as FrameProgression;
+
+/// Parameters for the fastCover dictionary training algorithm.
+#[cfg(feature = "experimental")]
+pub use zstd_sys::ZDICT_fastCover_params_t as FastCoverParams;
+
+/// Parameters for the dictionary header, common to all training algorithms.
+#[cfg(feature = "experimental")]
+pub use zstd_sys::ZDICT_params_t as DictParams;
+
 #[cfg(feature = "std")]
 use std::os::raw::{c_char, c_int, c_ulonglong, c_void};
 
@@ -42,6 +72,7 @@ use libc::{c_char, c_int, c_ulonglong, c_void};
 use core::marker::PhantomData;
 use core::ops::Deref;
 use core::ops::DerefMut;
+use core::ops::RangeInclusive;
 use core::str;
 
 // Re-define constants from zstd_sys
@@ -129,6 +160,34 @@ pub fn version_number() -> u32 {
     unsafe { zstd_sys::ZSTD_versionNumber() as u32 }
 }
 
+/// Returns `true` if the experimental API can be used with the linked
+/// library.
+///
+/// The experimental API is only stable within a single zstd version: when
+/// linking a system library (with the `pkg-config` feature), it must be the
+/// same version as the bundled headers (`VERSION_NUMBER`). Otherwise:
+///
+/// * experimental parameters are rejected as unsupported,
+/// * sequence functions and `ThreadPool` fail with `version_unsupported`,
+/// * dictionaries loaded by reference or with a dedicated search structure
+///   are created as regular dictionaries instead.
+#[cfg(feature = "experimental")]
+pub fn experimental_api_supported() -> bool {
+    version_number() == VERSION_NUMBER
+}
+
+// `ZSTD_error_parameter_unsupported`, from `zstd_errors.h`.
+#[cfg(feature = "experimental")]
+const PARAMETER_UNSUPPORTED: ErrorCode = 0usize.wrapping_sub(40);
+
+// `ZSTD_error_version_unsupported`, from `zstd_errors.h`.
+#[cfg(feature = "experimental")]
+const VERSION_UNSUPPORTED: ErrorCode = 0usize.wrapping_sub(12);
+
+// `ZSTD_error_dstSize_tooSmall`, from `zstd_errors.h`.
+#[cfg(feature = "experimental")]
+const DST_SIZE_TOO_SMALL: ErrorCode = 0usize.wrapping_sub(70);
+
 pub fn version_string() -> &'static str {
     unsafe { c_char_to_str(zstd_sys::ZSTD_versionString()) }
 }
@@ -203,6 +262,34 @@ impl CCtx<'static> {
 }
 
 impl<'a> CCtx<'a> {
+    /// Wraps a raw `ZSTD_CCtx` pointer.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must come from `ZSTD_createCCtx`, and must not be used or freed
+    /// elsewhere: it will be freed when the result is dropped. Any data it
+    /// references (dictionary, prefix) must outlive `'a`.
+    pub unsafe fn from_ptr(ptr: *mut zstd_sys::ZSTD_CCtx) -> Self {
+        CCtx(ptr, PhantomData)
+    }
+
+    /// Returns the raw `ZSTD_CCtx` pointer, to call functions not wrapped
+    /// here.
+    ///
+    /// The pointer stays owned by `self`.
+    pub fn as_ptr(&self) -> *mut zstd_sys::ZSTD_CCtx {
+        self.0
+    }
+
+    /// Returns the raw `ZSTD_CCtx` pointer, without freeing the context.
+    ///
+    /// The caller is responsible for freeing it with `ZSTD_freeCCtx`.
+    pub fn into_ptr(self) -> *mut zstd_sys::ZSTD_CCtx {
+        let ptr = self.0;
+        core::mem::forget(self);
+        ptr
+    }
+
     /// Wraps the `ZSTD_compressCCtx()` function
     pub fn compress(
         &mut self,
@@ -386,7 +473,7 @@ impl<'a> CCtx<'a> {
         &mut self,
         output: &mut OutBuffer,
         input: &mut InBuffer,
-        end_op: zstd_sys::ZSTD_EndDirective,
+        end_op: EndDirective,
     ) -> SafeResult {
         let mut output = output.wrap();
         let mut input = input.wrap();
@@ -438,58 +525,50 @@ impl<'a> CCtx<'a> {
     }
 
     pub fn set_parameter(&mut self, param: CParameter) -> SafeResult {
-        // TODO: Until bindgen properly generates a binding for this, we'll need to do it here.
-        #[cfg(feature = "experimental")]
-        use zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam2 as ZSTD_c_format;
-        #[cfg(feature = "experimental")]
-        use zstd_sys::ZSTD_format_e;
-
-        use zstd_sys::ZSTD_cParameter::*;
-        use CParameter::*;
-
-        let (param, value) = match param {
-            #[cfg(feature = "experimental")]
-            Format(FrameFormat::One) => {
-                (ZSTD_c_format, ZSTD_format_e::ZSTD_f_zstd1 as c_int)
-            }
-            #[cfg(feature = "experimental")]
-            Format(FrameFormat::Magicless) => (
-                ZSTD_c_format,
-                ZSTD_format_e::ZSTD_f_zstd1_magicless as c_int,
-            ),
-            CompressionLevel(level) => (ZSTD_c_compressionLevel, level),
-            WindowLog(value) => (ZSTD_c_windowLog, value as c_int),
-            HashLog(value) => (ZSTD_c_hashLog, value as c_int),
-            ChainLog(value) => (ZSTD_c_chainLog, value as c_int),
-            SearchLog(value) => (ZSTD_c_searchLog, value as c_int),
-            MinMatch(value) => (ZSTD_c_minMatch, value as c_int),
-            TargetLength(value) => (ZSTD_c_targetLength, value as c_int),
-            Strategy(strategy) => (ZSTD_c_strategy, strategy as c_int),
-            EnableLongDistanceMatching(flag) => {
-                (ZSTD_c_enableLongDistanceMatching, flag as c_int)
-            }
-            LdmHashLog(value) => (ZSTD_c_ldmHashLog, value as c_int),
-            LdmMinMatch(value) => (ZSTD_c_ldmMinMatch, value as c_int),
-            LdmBucketSizeLog(value) => {
-                (ZSTD_c_ldmBucketSizeLog, value as c_int)
-            }
-            LdmHashRateLog(value) => (ZSTD_c_ldmHashRateLog, value as c_int),
-            ContentSizeFlag(flag) => (ZSTD_c_contentSizeFlag, flag as c_int),
-            ChecksumFlag(flag) => (ZSTD_c_checksumFlag, flag as c_int),
-            DictIdFlag(flag) => (ZSTD_c_dictIDFlag, flag as c_int),
-
-            NbWorkers(value) => (ZSTD_c_nbWorkers, value as c_int),
-
-            JobSize(value) => (ZSTD_c_jobSize, value as c_int),
-
-            OverlapSizeLog(value) => (ZSTD_c_overlapLog, value as c_int),
-        };
+        let (param, value) = cparameter(param)?;
 
         parse_code(unsafe {
             zstd_sys::ZSTD_CCtx_setParameter(self.0, param, value)
         })
     }
 
+    /// Wraps the `ZSTD_CCtx_getParameter()` function.
+    ///
+    /// Returns the value currently set for this parameter, where `0` usually
+    /// means "automatic". The value carried by `param` is ignored.
+    #[cfg(feature = "experimental")]
+    pub fn get_parameter(&self, param: CParameter) -> Result<i32, ErrorCode> {
+        let (param, _) = cparameter(param)?;
+        let mut value: c_int = 0;
+        parse_code(unsafe {
+            zstd_sys::ZSTD_CCtx_getParameter(self.0, param, &mut value)
+        })?;
+        Ok(value as i32)
+    }
+
+    /// Wraps the `ZSTD_getFrameProgression()` function.
+    #[cfg(feature = "experimental")]
+    pub fn get_frame_progression(&self) -> FrameProgression {
+        unsafe { zstd_sys::ZSTD_getFrameProgression(self.0) }
+    }
+
+    /// Wraps the `ZSTD_CCtx_refThreadPool()` function.
+    ///
+    /// The pool must outlive the context. It must be set before starting a
+    /// frame.
+    #[cfg(all(feature = "experimental", feature = "zstdmt"))]
+    pub fn ref_thread_pool<'b>(&mut self, pool: &'b ThreadPool) -> SafeResult
+    where
+        'b: 'a,
+    {
+        if !experimental_api_supported() {
+            return Err(VERSION_UNSUPPORTED);
+        }
+        parse_code(unsafe {
+            zstd_sys::ZSTD_CCtx_refThreadPool(self.0, pool.0)
+        })
+    }
+
     pub fn set_pledged_src_size(
         &mut self,
         pledged_src_size: u64,
@@ -502,6 +581,83 @@ impl<'a> CCtx<'a> {
         })
     }
 
+    /// Wraps the `ZSTD_compressBegin_usingDict()` function.
+    ///
+    /// The dictionary is referenced by the context, and must outlive it.
+    #[cfg(feature = "experimental")]
+    pub fn compress_begin_using_dict<'b>(
+        &mut self,
+        dict: &'b [u8],
+        compression_level: CompressionLevel,
+    ) -> SafeResult
+    where
+        'b: 'a,
+    {
+        let code = unsafe {
+            zstd_sys::ZSTD_compressBegin_usingDict(
+                self.0,
+                ptr_void(dict),
+                dict.len(),
+                compression_level,
+            )
+        };
+        parse_code(code)
+    }
+
+    /// Wraps the `ZSTD_copyCCtx()` function.
+    ///
+    /// `prepared` must have been started with `compress_begin_using_dict`,
+    /// and not used to compress anything yet. A `pledged_src_size` of `0`
+    /// means unknown.
+    #[cfg(feature = "experimental")]
+    pub fn copy_cctx(
+        &mut self,
+        prepared: &CCtx<'a>,
+        pledged_src_size: u64,
+    ) -> SafeResult {
+        parse_code(unsafe {
+            zstd_sys::ZSTD_copyCCtx(
+                self.0,
+                prepared.0,
+                pledged_src_size as c_ulonglong,
+            )
+        })
+    }
+
+    /// Wraps the `ZSTD_compressContinue()` function.
+    #[cfg(feature = "experimental")]
+    pub fn compress_continue(
+        &mut self,
+        dst: &mut [u8],
+        src: &[u8],
+    ) -> SafeResult {
+        let code = unsafe {
+            zstd_sys::ZSTD_compressContinue(
+                self.0,
+                ptr_mut_void(dst),
+                dst.len(),
+                ptr_void(src),
+                src.len(),
+            )
+        };
+        parse_code(code)
+    }
+
+    /// Wraps the `ZSTD_compressEnd()` function.
+    #[cfg(feature = "experimental")]
+    pub fn compress_end(&mut self, dst: &mut [u8], src: &[u8]) -> SafeResult {
+        let code = unsafe {
+            zstd_sys::ZSTD_compressEnd(
+                self.0,
+                ptr_mut_void(dst),
+                dst.len(),
+                ptr_void(src),
+                src.len(),
+            )
+        };
+        parse_code(code)
+    }
+
     /// Wraps the `ZSTD_getBlockSize()` function.
     #[cfg(feature = "experimental")]
     pub fn get_block_size(&self) -> usize {
@@ -526,6 +682,65 @@ impl<'a> CCtx<'a> {
         };
         parse_code(code)
     }
+
+    /// Wraps the `ZSTD_generateSequences()` function.
+    ///
+    /// Returns the number of sequences written to `out_seqs`.
+    ///
+    /// zstd doesn't check the size of `out_seqs`: this fails with
+    /// `dstSize_tooSmall` if it is shorter than `sequence_bound(src.len())`.
+    #[cfg(feature = "experimental")]
+    pub fn generate_sequences(
+        &mut self,
+        out_seqs: &mut [Sequence],
+        src: &[u8],
+    ) -> SafeResult {
+        if !experimental_api_supported() {
+            return Err(VERSION_UNSUPPORTED);
+        }
+        if out_seqs.len() < sequence_bound(src.len()) {
+            return Err(DST_SIZE_TOO_SMALL);
+        }
+        parse_code(unsafe {
+            zstd_sys::ZSTD_generateSequences(
+                self.0,
+                out_seqs.as_mut_ptr(),
+                out_seqs.len(),
+                ptr_void(src),
+                src.len(),
+            )
+        })
+    }
+
+    /// Wraps the `ZSTD_compressSequences()` function.
+    ///
+    /// # Safety
+    ///
+    /// The literal and match lengths of `in_seqs` must not add up to more
+    /// than `src.len()`: zstd reads the literals from `src` without checking.
+    /// Unless `CParameter::ValidateSequences` is enabled, the offsets must
+    /// also be valid (not reaching before the start of the data).
+    #[cfg(feature = "experimental")]
+    pub unsafe fn compress_sequences(
+        &mut self,
+        dst: &mut [u8],
+        in_seqs: &[Sequence],
+        src: &[u8],
+    ) -> SafeResult {
+        if !experimental_api_supported() {
+            return Err(VERSION_UNSUPPORTED);
+        }
+        parse_code(zstd_sys::ZSTD_compressSequences(
+            self.0,
+            ptr_mut_void(dst),
+            dst.len(),
+            in_seqs.as_ptr(),
+            in_seqs.len(),
+            ptr_void(src),
+            src.len(),
+        ))
+    }
+
     pub fn in_size() -> usize {
         unsafe { zstd_sys::ZSTD_CStreamInSize() }
     }
@@ -550,6 +765,46 @@ impl<'a> Drop for CCtx<'a> {
 unsafe impl<'a> Send for CCtx<'a> {}
 // CCtx can't be shared across threads, so it does not implement Sync.
 
+/// A pool of worker threads, which compression contexts can share.
+///
+/// Wraps `ZSTD_threadPool`.
+#[cfg(all(feature = "experimental", feature = "zstdmt"))]
+pub struct ThreadPool(*mut zstd_sys::ZSTD_threadPool);
+
+#[cfg(all(feature = "experimental", feature = "zstdmt"))]
+impl ThreadPool {
+    /// Wraps the `ZSTD_createThreadPool()` function.
+    ///
+    /// Returns `None` if the pool could not be created, or if the linked
+    /// library doesn't match the experimental API.
+    pub fn create(num_threads: usize) -> Option<Self> {
+        if !experimental_api_supported() {
+            return None;
+        }
+        let pool = unsafe { zstd_sys::ZSTD_createThreadPool(num_threads) };
+        if pool.is_null() {
+            None
+        } else {
+            Some(ThreadPool(pool))
+        }
+    }
+}
+
+#[cfg(all(feature = "experimental", feature = "zstdmt"))]
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        unsafe {
+            zstd_sys::ZSTD_freeThreadPool(self.0);
+        }
+    }
+}
+
+// The pool has its own locking, and can be used by many contexts at once.
+#[cfg(all(feature = "experimental", feature = "zstdmt"))]
+unsafe impl Send for ThreadPool {}
+#[cfg(all(feature = "experimental", feature = "zstdmt"))]
+unsafe impl Sync for ThreadPool {}
+
 unsafe fn c_char_to_str(text: *const c_char) -> &'static str {
     #[cfg(not(feature = "std"))]
     {
@@ -559,14 +814,14 @@ unsafe fn c_char_to_str(text: *const c_char) -> &'static str {
         // Cast it to a slice
         let slice = core::slice::from_raw_parts(text as *mut u8, len);
         // And hope it's still text.
-        str::from_utf8(slice).expect("bad error message from zstd")
+        str::from_utf8(slice).unwrap_or("Unknown error")
     }
 
     #[cfg(feature = "std")]
     {
         std::ffi::CStr::from_ptr(text)
             .to_str()
-            .expect("bad error message from zstd")
+            .unwrap_or("Unknown error")
     }
 }
 
@@ -577,6 +832,14 @@ pub fn get_error_name(code: usize) -> &'static str {
     }
 }
 
+/// Wraps the `ZSTD_getErrorCode()` function.
+///
+/// Returns `error_code::ZSTD_error_no_error` if `code` is not an error. A
+/// newer library may return values missing from `error_code`.
+pub fn get_error_code(code: usize) -> error_code::Type {
+    unsafe { zstd_sys::ZSTD_getErrorCode(code) }
+}
+
 /// Wraps the `ZSTD_compressCCtx()` function
 pub fn compress_cctx(
     ctx: &mut CCtx,
@@ -614,6 +877,34 @@ impl DCtx<'static> {
 }
 
 impl<'a> DCtx<'a> {
+    /// Wraps a raw `ZSTD_DCtx` pointer.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must come from `ZSTD_createDCtx`, and must not be used or freed
+    /// elsewhere: it will be freed when the result is dropped. Any data it
+    /// references (dictionary, prefix) must outlive `'a`.
+    pub unsafe fn from_ptr(ptr: *mut zstd_sys::ZSTD_DCtx) -> Self {
+        DCtx(ptr, PhantomData)
+    }
+
+    /// Returns the raw `ZSTD_DCtx` pointer, to call functions not wrapped
+    /// here.
+    ///
+    /// The pointer stays owned by `self`.
+    pub fn as_ptr(&self) -> *mut zstd_sys::ZSTD_DCtx {
+        self.0
+    }
+
+    /// Returns the raw `ZSTD_DCtx` pointer, without freeing the context.
+    ///
+    /// The caller is responsible for freeing it with `ZSTD_freeDCtx`.
+    pub fn into_ptr(self) -> *mut zstd_sys::ZSTD_DCtx {
+        let ptr = self.0;
+        core::mem::forget(self);
+        ptr
+    }
+
     /// Wraps the `ZSTD_decompressDCtx()` function.
     pub fn decompress(&mut self, dst: &mut [u8], src: &[u8]) -> SafeResult {
         parse_code(unsafe {
@@ -717,6 +1008,30 @@ impl<'a> DCtx<'a> {
         })
     }
 
+    /// Wraps the `ZSTD_DCtx_loadDictionary_byReference()` function.
+    ///
+    /// The dictionary content is not copied, so it must outlive the context.
+    /// It is copied anyway if the experimental API is not supported.
+    #[cfg(feature = "experimental")]
+    pub fn load_dictionary_by_reference<'b>(
+        &mut self,
+        dict: &'b [u8],
+    ) -> SafeResult
+    where
+        'b: 'a,
+    {
+        if !experimental_api_supported() {
+            return self.load_dictionary(dict);
+        }
+        parse_code(unsafe {
+            zstd_sys::ZSTD_DCtx_loadDictionary_byReference(
+                self.0,
+                ptr_void(dict),
+                dict.len(),
+            )
+        })
+    }
+
     pub fn ref_ddict<'b>(&mut self, ddict: &DDict<'b>) -> SafeResult
     where
         'b: 'a,
@@ -738,27 +1053,7 @@ impl<'a> DCtx<'a> {
     }
 
     pub fn set_parameter(&mut self, param: DParameter) -> SafeResult {
-        #[cfg(feature = "experimental")]
-        use zstd_sys::ZSTD_dParameter::ZSTD_d_experimentalParam1 as ZSTD_d_format;
-        #[cfg(feature = "experimental")]
-        use zstd_sys::ZSTD_format_e;
-
-        use zstd_sys::ZSTD_dParameter::*;
-        use DParameter::*;
-
-        let (param, value) = match param {
-            #[cfg(feature = "experimental")]
-            Format(FrameFormat::One) => {
-                (ZSTD_d_format, ZSTD_format_e::ZSTD_f_zstd1 as c_int)
-            }
-            #[cfg(feature = "experimental")]
-            Format(FrameFormat::Magicless) => (
-                ZSTD_d_format,
-                ZSTD_format_e::ZSTD_f_zstd1_magicless as c_int,
-            ),
-
-            WindowLogMax(value) => (ZSTD_d_windowLogMax, value as c_int),
-        };
+        let (param, value) = dparameter(param)?;
 
         parse_code(unsafe {
             zstd_sys::ZSTD_DCtx_setParameter(self.0, param, value)
@@ -890,6 +1185,82 @@ impl CDict<'static> {
             PhantomData,
         )
     }
+
+    /// Creates a dictionary optimized for many small inputs.
+    ///
+    /// Wraps the `ZSTD_createCDict_advanced2()` function, with
+    /// `ZSTD_c_enableDedicatedDictSearch` enabled. This only has an effect
+    /// on some compression levels (currently 5 to 12); other levels work as
+    /// with a regular dictionary.
+    #[cfg(feature = "experimental")]
+    pub fn create_with_dedicated_search(
+        dict_buffer: &[u8],
+        compression_level: CompressionLevel,
+    ) -> Self {
+        CDict(
+            unsafe {
+                create_cdict_with_dedicated_search(
+                    dict_buffer,
+                    compression_level,
+                    zstd_sys::ZSTD_dictLoadMethod_e::ZSTD_dlm_byCopy,
+                )
+            },
+            PhantomData,
+        )
+    }
+}
+
+// With `ZSTD_dlm_byRef`, the result must not outlive `dict_buffer`.
+#[cfg(feature = "experimental")]
+unsafe fn create_cdict_with_dedicated_search(
+    dict_buffer: &[u8],
+    compression_level: CompressionLevel,
+    load_method: zstd_sys::ZSTD_dictLoadMethod_e,
+) -> *mut zstd_sys::ZSTD_CDict {
+    use zstd_sys::ZSTD_cParameter::ZSTD_c_experimentalParam8 as ZSTD_c_enableDedicatedDictSearch;
+    use zstd_sys::ZSTD_dictLoadMethod_e::ZSTD_dlm_byCopy;
+
+    // This is only a speed optimization: without it, we can't be sure of
+    // the parameter's number, or of `ZSTD_createCDict_advanced2`'s
+    // signature. Create a regular dictionary instead.
+    if !experimental_api_supported() {
+        return if load_method == ZSTD_dlm_byCopy {
+            zstd_sys::ZSTD_createCDict(
+                ptr_void(dict_buffer),
+                dict_buffer.len(),
+                compression_level,
+            )
+        } else {
+            zstd_sys::ZSTD_createCDict_byReference(
+                ptr_void(dict_buffer),
+                dict_buffer.len(),
+                compression_level,
+            )
+        };
+    }
+
+    let params = zstd_sys::ZSTD_createCCtxParams();
+    zstd_sys::ZSTD_CCtxParams_init(params, compression_level);
+    zstd_sys::ZSTD_CCtxParams_setParameter(
+        params,
+        ZSTD_c_enableDedicatedDictSearch,
+        1,
+    );
+    let cdict = zstd_sys::ZSTD_createCDict_advanced2(
+        ptr_void(dict_buffer),
+        dict_buffer.len(),
+        load_method,
+        zstd_sys::ZSTD_dictContentType_e::ZSTD_dct_auto,
+        params,
+        // Same as the (not exported) `ZSTD_defaultCMem`.
+        zstd_sys::ZSTD_customMem {
+            customAlloc: None,
+            customFree: None,
+            opaque: core::ptr::null_mut(),
+        },
+    );
+    zstd_sys::ZSTD_freeCCtxParams(params);
+    cdict
 }
 
 impl<'a> CDict<'a> {
@@ -909,6 +1280,28 @@ impl<'a> CDict<'a> {
             PhantomData,
         )
     }
+
+    /// Creates a dictionary optimized for many small inputs, referencing
+    /// `dict_buffer`.
+    ///
+    /// See `CDict::create_with_dedicated_search`.
+    #[cfg(feature = "experimental")]
+    pub fn create_by_reference_with_dedicated_search(
+        dict_buffer: &'a [u8],
+        compression_level: CompressionLevel,
+    ) -> Self {
+        CDict(
+            unsafe {
+                create_cdict_with_dedicated_search(
+                    dict_buffer,
+                    compression_level,
+                    zstd_sys::ZSTD_dictLoadMethod_e::ZSTD_dlm_byRef,
+                )
+            },
+            PhantomData,
+        )
+    }
+
     pub fn sizeof(&self) -> usize {
         unsafe { zstd_sys::ZSTD_sizeof_CDict(self.0) }
     }
@@ -1109,6 +1502,23 @@ impl<'a> OutBuffer<'a> {
         let pos = self.pos;
         &self.dst[..pos]
     }
+
+    /// Returns the data written so far.
+    pub fn written(&self) -> &[u8] {
+        &self.dst[..self.pos]
+    }
+
+    /// Returns how many bytes can still be written.
+    pub fn remaining(&self) -> usize {
+        self.dst.len() - self.pos
+    }
+
+    /// Starts writing again from the beginning of the buffer.
+    ///
+    /// The data written so far is overwritten by the next operations.
+    pub fn reset(&mut self) {
+        self.pos = 0;
+    }
 }
 
 impl<'a, 'b> Drop for OutBufferWrapper<'a, 'b> {
@@ -1144,6 +1554,16 @@ impl<'a> InBuffer<'a> {
         InBuffer { src, pos: 0 }
     }
 
+    /// Returns the data not read yet.
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.src[self.pos..]
+    }
+
+    /// Starts reading again from the beginning of the buffer.
+    pub fn reset(&mut self) {
+        self.pos = 0;
+    }
+
     fn wrap<'b>(&'b mut self) -> InBufferWrapper<'b, 'a> {
         InBufferWrapper {
             buf: zstd_sys::ZSTD_inBuffer {
@@ -1175,7 +1595,7 @@ pub fn compress_stream2(
     cctx: &mut CCtx,
     output: &mut OutBuffer,
     input: &mut InBuffer,
-    end_op: zstd_sys::ZSTD_EndDirective,
+    end_op: EndDirective,
 ) -> SafeResult {
     cctx.compress_stream2(output, input, end_op)
 }
@@ -1249,6 +1669,45 @@ pub fn find_frame_compressed_size(src: &[u8]) -> SafeResult {
     parse_code(code)
 }
 
+/// Wraps the `ZSTD_getCParams()` function.
+///
+/// Returns the parameters used for this level, adjusted to the source and
+/// dictionary sizes. Use `CONTENTSIZE_UNKNOWN` if the source size is not
+/// known.
+#[cfg(feature = "experimental")]
+pub fn get_cparams(
+    compression_level: CompressionLevel,
+    estimated_src_size: u64,
+    dict_size: usize,
+) -> CompressionParameters {
+    unsafe {
+        zstd_sys::ZSTD_getCParams(
+            compression_level,
+            estimated_src_size as c_ulonglong,
+            dict_size,
+        )
+    }
+}
+
+/// Wraps the `ZSTD_adjustCParams()` function.
+///
+/// Reduces the given parameters to what is useful for the source and
+/// dictionary sizes.
+#[cfg(feature = "experimental")]
+pub fn adjust_cparams(
+    cparams: CompressionParameters,
+    src_size: u64,
+    dict_size: usize,
+) -> CompressionParameters {
+    unsafe {
+        zstd_sys::ZSTD_adjustCParams(
+            cparams,
+            src_size as c_ulonglong,
+            dict_size,
+        )
+    }
+}
+
 /// Wraps the `ZSTD_getFrameContentSize()` function.
 ///
 /// `src` should contain at least a frame header.
@@ -1412,6 +1871,20 @@ pub fn dctx_load_dictionary(dctx: &mut DCtx<'_>, dict: &[u8]) -> SafeResult {
     dctx.load_dictionary(dict)
 }
 
+/// Wraps the `ZSTD_DCtx_loadDictionary_byReference()` function.
+///
+/// Dictionary must outlive the context.
+#[cfg(feature = "experimental")]
+pub fn dctx_load_dictionary_by_reference<'a, 'b>(
+    dctx: &mut DCtx<'a>,
+    dict: &'b [u8],
+) -> SafeResult
+where
+    'b: 'a,
+{
+    dctx.load_dictionary_by_reference(dict)
+}
+
 /// Wraps the `ZSTD_DCtx_refDDict()` function.
 pub fn dctx_ref_ddict<'a, 'b>(
     dctx: &mut DCtx<'a>,
@@ -1495,6 +1968,27 @@ pub enum CParameter {
     #[cfg(feature = "experimental")]
     Format(FrameFormat),
 
+    /// Format of the sequences given to `CCtx::compress_sequences`.
+    #[cfg(feature = "experimental")]
+    BlockDelimiters(SequenceFormat),
+
+    /// Makes `CCtx::compress_sequences` check the given sequences.
+    #[cfg(feature = "experimental")]
+    ValidateSequences(bool),
+
+    /// Promises that the input buffer will stay the same between calls.
+    ///
+    /// This avoids copying the input to an internal buffer, but requires
+    /// using `ZSTD_e_end` for every call to `CCtx::compress_stream2`.
+    #[cfg(feature = "experimental")]
+    StableInBuffer(bool),
+
+    /// Promises that the output buffer will stay the same between calls.
+    ///
+    /// This lets zstd compress directly into the output buffer.
+    #[cfg(feature = "experimental")]
+    StableOutBuffer(bool),
+
     CompressionLevel(CompressionLevel),
 
     WindowLog(u32),
@@ -1541,6 +2035,163 @@ pub enum DParameter {
     /// See `FrameFormat`.
     #[cfg(feature = "experimental")]
     Format(FrameFormat),
+
+    /// Promises that the output buffer will stay the same between calls.
+    ///
+    /// This lets zstd decompress directly into the output buffer, without
+    /// an internal window buffer.
+    #[cfg(feature = "experimental")]
+    StableOutBuffer(bool),
+}
+
+// Maps a parameter to its zstd counterpart and value.
+fn cparameter(
+    param: CParameter,
+) -> Result<(zstd_sys::ZSTD_cParameter, c_int), ErrorCode> {
+    // TODO: Until bindgen properly generates a binding for this, we'll need to do it here.
+    #[cfg(feature = "experimental")]
+    use zstd_sys::ZSTD_cParameter::{
+        ZSTD_c_experimentalParam10 as ZSTD_c_stableOutBuffer,
+        ZSTD_c_experimentalParam11 as ZSTD_c_blockDelimiters,
+        ZSTD_c_experimentalParam12 as ZSTD_c_validateSequences,
+        ZSTD_c_experimentalParam2 as ZSTD_c_format,
+        ZSTD_c_experimentalParam9 as ZSTD_c_stableInBuffer,
+    };
+    #[cfg(feature = "experimental")]
+    use zstd_sys::ZSTD_format_e;
+
+    use zstd_sys::ZSTD_cParameter::*;
+    use CParameter::*;
+
+    // Experimental parameters may be numbered differently in another
+    // version of the library.
+    #[cfg(feature = "experimental")]
+    {
+        let experimental = matches!(
+            param,
+            Format(_)
+                | BlockDelimiters(_)
+                | ValidateSequences(_)
+                | StableInBuffer(_)
+                | StableOutBuffer(_)
+        );
+        if experimental && !experimental_api_supported() {
+            return Err(PARAMETER_UNSUPPORTED);
+        }
+    }
+
+    let (param, value) = match param {
+        #[cfg(feature = "experimental")]
+        Format(FrameFormat::One) => {
+            (ZSTD_c_format, ZSTD_format_e::ZSTD_f_zstd1 as c_int)
+        }
+        #[cfg(feature = "experimental")]
+        Format(FrameFormat::Magicless) => (
+            ZSTD_c_format,
+            ZSTD_format_e::ZSTD_f_zstd1_magicless as c_int,
+        ),
+        #[cfg(feature = "experimental")]
+        BlockDelimiters(format) => (ZSTD_c_blockDelimiters, format as c_int),
+        #[cfg(feature = "experimental")]
+        ValidateSequences(flag) => (ZSTD_c_validateSequences, flag as c_int),
+        #[cfg(feature = "experimental")]
+        StableInBuffer(flag) => (ZSTD_c_stableInBuffer, flag as c_int),
+        #[cfg(feature = "experimental")]
+        StableOutBuffer(flag) => (ZSTD_c_stableOutBuffer, flag as c_int),
+        CompressionLevel(level) => (ZSTD_c_compressionLevel, level),
+        WindowLog(value) => (ZSTD_c_windowLog, value as c_int),
+        HashLog(value) => (ZSTD_c_hashLog, value as c_int),
+        ChainLog(value) => (ZSTD_c_chainLog, value as c_int),
+        SearchLog(value) => (ZSTD_c_searchLog, value as c_int),
+        MinMatch(value) => (ZSTD_c_minMatch, value as c_int),
+        TargetLength(value) => (ZSTD_c_targetLength, value as c_int),
+        Strategy(strategy) => (ZSTD_c_strategy, strategy as c_int),
+        EnableLongDistanceMatching(flag) => {
+            (ZSTD_c_enableLongDistanceMatching, flag as c_int)
+        }
+        LdmHashLog(value) => (ZSTD_c_ldmHashLog, value as c_int),
+        LdmMinMatch(value) => (ZSTD_c_ldmMinMatch, value as c_int),
+        LdmBucketSizeLog(value) => (ZSTD_c_ldmBucketSizeLog, value as c_int),
+        LdmHashRateLog(value) => (ZSTD_c_ldmHashRateLog, value as c_int),
+        ContentSizeFlag(flag) => (ZSTD_c_contentSizeFlag, flag as c_int),
+        ChecksumFlag(flag) => (ZSTD_c_checksumFlag, flag as c_int),
+        DictIdFlag(flag) => (ZSTD_c_dictIDFlag, flag as c_int),
+
+        NbWorkers(value) => (ZSTD_c_nbWorkers, value as c_int),
+
+        JobSize(value) => (ZSTD_c_jobSize, value as c_int),
+
+        OverlapSizeLog(value) => (ZSTD_c_overlapLog, value as c_int),
+    };
+
+    Ok((param, value))
+}
+
+fn dparameter(
+    param: DParameter,
+) -> Result<(zstd_sys::ZSTD_dParameter, c_int), ErrorCode> {
+    #[cfg(feature = "experimental")]
+    use zstd_sys::ZSTD_dParameter::{
+        ZSTD_d_experimentalParam1 as ZSTD_d_format,
+        ZSTD_d_experimentalParam2 as ZSTD_d_stableOutBuffer,
+    };
+    #[cfg(feature = "experimental")]
+    use zstd_sys::ZSTD_format_e;
+
+    use zstd_sys::ZSTD_dParameter::*;
+    use DParameter::*;
+
+    #[cfg(feature = "experimental")]
+    {
+        let experimental = matches!(param, Format(_) | StableOutBuffer(_));
+        if experimental && !experimental_api_supported() {
+            return Err(PARAMETER_UNSUPPORTED);
+        }
+    }
+
+    let (param, value) = match param {
+        #[cfg(feature = "experimental")]
+        Format(FrameFormat::One) => {
+            (ZSTD_d_format, ZSTD_format_e::ZSTD_f_zstd1 as c_int)
+        }
+        #[cfg(feature = "experimental")]
+        Format(FrameFormat::Magicless) => (
+            ZSTD_d_format,
+            ZSTD_format_e::ZSTD_f_zstd1_magicless as c_int,
+        ),
+        #[cfg(feature = "experimental")]
+        StableOutBuffer(flag) => (ZSTD_d_stableOutBuffer, flag as c_int),
+
+        WindowLogMax(value) => (ZSTD_d_windowLogMax, value as c_int),
+    };
+
+    Ok((param, value))
+}
+
+/// Wraps the `ZSTD_cParam_getBounds()` function.
+///
+/// Returns the range of values accepted for this parameter. The value
+/// carried by `param` is ignored.
+pub fn cparam_get_bounds(
+    param: CParameter,
+) -> Result<RangeInclusive<i32>, ErrorCode> {
+    let (param, _) = cparameter(param)?;
+    let bounds = unsafe { zstd_sys::ZSTD_cParam_getBounds(param) };
+    parse_code(bounds.error)?;
+    Ok(bounds.lowerBound as i32..=bounds.upperBound as i32)
+}
+
+/// Wraps the `ZSTD_dParam_getBounds()` function.
+///
+/// Returns the range of values accepted for this parameter. The value
+/// carried by `param` is ignored.
+pub fn dparam_get_bounds(
+    param: DParameter,
+) -> Result<RangeInclusive<i32>, ErrorCode> {
+    let (param, _) = dparameter(param)?;
+    let bounds = unsafe { zstd_sys::ZSTD_dParam_getBounds(param) };
+    parse_code(bounds.error)?;
+    Ok(bounds.lowerBound as i32..=bounds.upperBound as i32)
 }
 
 /// Wraps the `ZSTD_DCtx_setParameter()` function.
@@ -1579,6 +2230,30 @@ pub fn train_from_buffer(
     })
 }
 
+/// Wraps the `ZDICT_optimizeTrainFromBuffer_fastCover()` function.
+///
+/// Zero fields in `parameters` are searched for, and `parameters` is filled
+/// with the values actually used.
+#[cfg(feature = "experimental")]
+pub fn optimize_train_from_buffer_fast_cover(
+    dict_buffer: &mut [u8],
+    samples_buffer: &[u8],
+    samples_sizes: &[usize],
+    parameters: &mut FastCoverParams,
+) -> SafeResult {
+    assert_eq!(samples_buffer.len(), samples_sizes.iter().sum());
+    parse_code(unsafe {
+        zstd_sys::ZDICT_optimizeTrainFromBuffer_fastCover(
+            ptr_mut_void(dict_buffer),
+            dict_buffer.len(),
+            ptr_void(samples_buffer),
+            samples_sizes.as_ptr(),
+            samples_sizes.len() as u32,
+            parameters,
+        )
+    })
+}
+
 /// Wraps the `ZSTD_getDictID_fromDict()` function.
 pub fn get_dict_id(dict_buffer: &[u8]) -> Option<u32> {
     let id = unsafe {
@@ -1607,6 +2282,56 @@ pub fn compress_block(
     cctx.compress_block(dst, src)
 }
 
+/// Returns the number of sequences `generate_sequences` may need for
+/// `src_size` bytes of input.
+#[cfg(feature = "experimental")]
+pub fn sequence_bound(src_size: usize) -> usize {
+    // At most one sequence per minimal match (3 bytes), plus one delimiter
+    // per block (at least 1KB each).
+    src_size / 3 + src_size / (1 << 10) + 2
+}
+
+/// Wraps the `ZSTD_generateSequences()` function.
+///
+/// See `CCtx::generate_sequences`. This fails with `version_unsupported` if
+/// the experimental API is not supported.
+#[cfg(feature = "experimental")]
+pub fn generate_sequences(
+    cctx: &mut CCtx,
+    out_seqs: &mut [Sequence],
+    src: &[u8],
+) -> SafeResult {
+    cctx.generate_sequences(out_seqs, src)
+}
+
+/// Wraps the `ZSTD_compressSequences()` function.
+///
+/// # Safety
+///
+/// See `CCtx::compress_sequences`.
+#[cfg(feature = "experimental")]
+pub unsafe fn compress_sequences(
+    cctx: &mut CCtx,
+    dst: &mut [u8],
+    in_seqs: &[Sequence],
+    src: &[u8],
+) -> SafeResult {
+    cctx.compress_sequences(dst, in_seqs, src)
+}
+
+/// Wraps the `ZSTD_mergeBlockDelimiters()` function.
+///
+/// Returns the number of sequences left at the start of `sequences`.
+#[cfg(feature = "experimental")]
+pub fn merge_block_delimiters(sequences: &mut [Sequence]) -> usize {
+    unsafe {
+        zstd_sys::ZSTD_mergeBlockDelimiters(
+            sequences.as_mut_ptr(),
+            sequences.len(),
+        )
+    }
+}
+
 /// Wraps the `ZSTD_decompressBlock()` function.
 #[cfg(feature = "experimental")]
 pub fn decompress_block(dctx: &mut DCtx, dst: &mut [u8], src: &[u8]) -> usize {
@@ -1618,3 +2343,62 @@ pub fn decompress_block(dctx: &mut DCtx, dst: &mut [u8], src: &[u8]) -> usize {
 pub fn insert_block(dctx: &mut DCtx, block: &[u8]) -> usize {
     dctx.insert_block(block)
 }
+
+/// Wraps the `XXH64()` function.
+///
+/// This is the hash used for frame checksums (zstd stores its lowest 32
+/// bits), using a seed of `0`.
+pub fn xxh64(input: &[u8], seed: u64) -> u64 {
+    unsafe { zstd_sys::XXH64(input.as_ptr() as *const _, input.len(), seed) }
+}
+
+/// Streaming XXH64 state.
+///
+/// Wraps `XXH64_state_t`.
+pub struct XXH64State(*mut zstd_sys::XXH64_state_t);
+
+impl XXH64State {
+    /// Creates a new state, ready to hash with the given seed.
+    ///
+    /// Wraps the `XXH64_createState()` and `XXH64_reset()` functions.
+    pub fn create(seed: u64) -> Self {
+        let mut state = XXH64State(unsafe { zstd_sys::XXH64_createState() });
+        assert!(!state.0.is_null(), "could not allocate XXH64 state");
+        state.reset(seed);
+        state
+    }
+
+    /// Wraps the `XXH64_reset()` function.
+    pub fn reset(&mut self, seed: u64) {
+        unsafe {
+            zstd_sys::XXH64_reset(self.0, seed);
+        }
+    }
+
+    /// Wraps the `XXH64_update()` function.
+    pub fn update(&mut self, input: &[u8]) {
+        unsafe {
+            zstd_sys::XXH64_update(
+                self.0,
+                input.as_ptr() as *const _,
+                input.len(),
+            );
+        }
+    }
+
+    /// Wraps the `XXH64_digest()` function.
+    pub fn digest(&self) -> u64 {
+        unsafe { zstd_sys::XXH64_digest(self.0) }
+    }
+}
+
+impl Drop for XXH64State {
+    fn drop(&mut self) {
+        unsafe {
+            zstd_sys::XXH64_freeState(self.0);
+        }
+    }
+}
+
+unsafe impl Send for XXH64State {}
+unsafe impl Sync for XXH64State {}
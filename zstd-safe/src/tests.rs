@@ -123,3 +123,67 @@ fn test_checksum() {
     // The error message will complain about the checksum.
     assert!(err.contains("checksum"));
 }
+
+#[cfg(feature = "experimental")]
+#[test]
+fn test_experimental_api_supported() {
+    // The bundled library always matches its headers.
+    if !cfg!(feature = "pkg-config") {
+        assert!(crate::experimental_api_supported());
+    }
+
+    let mut cctx = crate::CCtx::create();
+    let result = cctx.set_parameter(crate::CParameter::StableOutBuffer(true));
+    assert_eq!(result.is_ok(), crate::experimental_api_supported());
+}
+
+#[cfg(feature = "experimental")]
+#[test]
+fn test_generate_sequences_bound() {
+    let src = [b'a'; 10_000];
+    let empty = crate::Sequence {
+        offset: 0,
+        litLength: 0,
+        matchLength: 0,
+        rep: 0,
+    };
+    let mut cctx = crate::CCtx::create();
+
+    // Too short for the worst case: rejected before calling zstd.
+    let mut sequences = [empty; 4];
+    let err = cctx.generate_sequences(&mut sequences, &src).unwrap_err();
+    assert_eq!(
+        crate::get_error_name(err),
+        "Destination buffer is too small"
+    );
+
+    let mut sequences = [empty; 3344];
+    assert_eq!(sequences.len(), crate::sequence_bound(src.len()));
+    assert!(cctx.generate_sequences(&mut sequences, &src).unwrap() > 0);
+}
+
+#[test]
+fn test_xxh64() {
+    // Reference values from the xxhash test suite.
+    assert_eq!(zstd_safe::xxh64(b"", 0), 0xEF46DB3751D8E999);
+    assert_eq!(zstd_safe::xxh64(b"a", 0), 0xD24EC4F1A98C6E5B);
+
+    let mut state = zstd_safe::XXH64State::create(0);
+    state.update(b"hello ");
+    state.update(b"world");
+    assert_eq!(state.digest(), zstd_safe::xxh64(b"hello world", 0));
+
+    state.reset(42);
+    state.update(b"hello world");
+    assert_eq!(state.digest(), zstd_safe::xxh64(b"hello world", 42));
+}
+
+#[test]
+fn test_get_error_code() {
+    use crate::error_code::*;
+
+    let mut buffer = [0u8; 8];
+    let code = crate::decompress(&mut buffer, b"definitely not zstd").unwrap_err();
+    assert_eq!(crate::get_error_code(code), ZSTD_error_prefix_unknown);
+    assert_eq!(crate::get_error_code(0), ZSTD_error_no_error);
+}
@@ -18,6 +18,8 @@ fn generate_bindings(defs: Vec<&str>, headerpaths: Vec<PathBuf>) {
         .size_t_is_usize(true)
         .use_core()
         .rustified_enum(".*")
+        // Newer libraries may return codes missing from this list.
+        .constified_enum_module("ZSTD_ErrorCode")
         .clang_args(
             headerpaths
                 .into_iter()
@@ -46,7 +48,9 @@ fn generate_bindings(_: Vec<&str>, _: Vec<PathBuf>) {}
 
 #[cfg(feature = "pkg-config")]
 fn pkg_config() -> (Vec<&'static str>, Vec<PathBuf>) {
+    // Older versions lack the advanced API (`ZSTD_compressStream2`, ...).
     let library = pkg_config::Config::new()
+        .atleast_version("1.4.0")
         .statik(true)
         .cargo_metadata(!cfg!(feature = "non-cargo"))
         .probe("libzstd")
@@ -141,6 +145,15 @@ fn compile_zstd() {
     #[cfg(feature = "debug")]
     config.define("DEBUGLEVEL", Some("5"));
 
+    // wasm32-unknown-unknown has no libc: use our own headers instead,
+    // backed by the functions in `src/wasm_shim.rs`.
+    let target_arch =
+        std::env::var("CARGO_CFG_TARGET_ARCH").unwrap_or_default();
+    let target_os = std::env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    if target_arch == "wasm32" && target_os == "unknown" {
+        config.include("wasm-shim/");
+    }
+
     set_pthread(&mut config);
     set_legacy(&mut config);
     enable_threading(&mut config);
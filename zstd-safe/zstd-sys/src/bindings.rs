@@ -1234,3 +1234,44 @@ extern "C" {
 extern "C" {
     pub fn ZDICT_getErrorName(errorCode: usize) -> *const libc::c_char;
 }
+pub mod ZSTD_ErrorCode {
+    pub type Type = u32;
+    pub const ZSTD_error_no_error: Type = 0;
+    pub const ZSTD_error_GENERIC: Type = 1;
+    pub const ZSTD_error_prefix_unknown: Type = 10;
+    pub const ZSTD_error_version_unsupported: Type = 12;
+    pub const ZSTD_error_frameParameter_unsupported: Type = 14;
+    pub const ZSTD_error_frameParameter_windowTooLarge: Type = 16;
+    pub const ZSTD_error_corruption_detected: Type = 20;
+    pub const ZSTD_error_checksum_wrong: Type = 22;
+    pub const ZSTD_error_dictionary_corrupted: Type = 30;
+    pub const ZSTD_error_dictionary_wrong: Type = 32;
+    pub const ZSTD_error_dictionaryCreation_failed: Type = 34;
+    pub const ZSTD_error_parameter_unsupported: Type = 40;
+    pub const ZSTD_error_parameter_outOfBound: Type = 42;
+    pub const ZSTD_error_tableLog_tooLarge: Type = 44;
+    pub const ZSTD_error_maxSymbolValue_tooLarge: Type = 46;
+    pub const ZSTD_error_maxSymbolValue_tooSmall: Type = 48;
+    pub const ZSTD_error_stage_wrong: Type = 60;
+    pub const ZSTD_error_init_missing: Type = 62;
+    pub const ZSTD_error_memory_allocation: Type = 64;
+    pub const ZSTD_error_workSpace_tooSmall: Type = 66;
+    pub const ZSTD_error_dstSize_tooSmall: Type = 70;
+    pub const ZSTD_error_srcSize_wrong: Type = 72;
+    pub const ZSTD_error_dstBuffer_null: Type = 74;
+    pub const ZSTD_error_frameIndex_tooLarge: Type = 100;
+    pub const ZSTD_error_seekableIO: Type = 102;
+    pub const ZSTD_error_dstBuffer_wrong: Type = 104;
+    pub const ZSTD_error_srcBuffer_wrong: Type = 105;
+    pub const ZSTD_error_maxCode: Type = 120;
+}
+extern "C" {
+    #[doc = " ZSTD_getErrorCode() :"]
+    #[doc = "    convert a `size_t` function result into a `ZSTD_ErrorCode` enum type,"]
+    #[doc = "    which can be used to compare with enum list published above"]
+    pub fn ZSTD_getErrorCode(functionResult: usize) -> ZSTD_ErrorCode::Type;
+}
+extern "C" {
+    #[doc = "< Same as ZSTD_getErrorName, but using a `ZSTD_ErrorCode` enum argument"]
+    pub fn ZSTD_getErrorString(code: ZSTD_ErrorCode::Type) -> *const libc::c_char;
+}
@@ -11,6 +11,16 @@ extern crate libc;
 #[cfg(feature = "std")]
 extern crate std;
 
+// Without a libc, zstd gets its allocator and `string.h` functions from us.
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+extern crate alloc;
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+mod wasm_shim;
+
+mod xxhash;
+pub use xxhash::*;
+
 // If running bindgen, we'll end up with the correct bindings anyway.
 #[cfg(feature = "bindgen")]
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
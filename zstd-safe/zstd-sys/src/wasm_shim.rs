@@ -0,0 +1,164 @@
+//! Implements the libc functions used by zstd, for targets without a libc.
+//!
+//! The matching declarations are in the `wasm-shim` headers.
+use alloc::alloc::{alloc, alloc_zeroed, dealloc, Layout};
+use core::cmp::Ordering;
+use core::ffi::c_void;
+use core::ptr::{copy, copy_nonoverlapping, swap_nonoverlapping, write_bytes};
+use core::slice;
+
+// Each allocation starts with its total size, needed to free it.
+const HEADER_SIZE: usize = core::mem::size_of::<usize>();
+const ALIGN: usize = 16;
+
+#[no_mangle]
+pub extern "C" fn rust_zstd_wasm_shim_malloc(size: usize) -> *mut c_void {
+    allocate(size, false)
+}
+
+#[no_mangle]
+pub extern "C" fn rust_zstd_wasm_shim_calloc(
+    nmemb: usize,
+    size: usize,
+) -> *mut c_void {
+    match nmemb.checked_mul(size) {
+        Some(size) => allocate(size, true),
+        None => core::ptr::null_mut(),
+    }
+}
+
+fn allocate(size: usize, zeroed: bool) -> *mut c_void {
+    let full_size = match size.checked_add(ALIGN) {
+        Some(full_size) => full_size,
+        None => return core::ptr::null_mut(),
+    };
+    let layout = match Layout::from_size_align(full_size, ALIGN) {
+        Ok(layout) => layout,
+        Err(_) => return core::ptr::null_mut(),
+    };
+    unsafe {
+        let ptr = if zeroed {
+            alloc_zeroed(layout)
+        } else {
+            alloc(layout)
+        };
+        if ptr.is_null() {
+            return core::ptr::null_mut();
+        }
+        // Keep the returned pointer aligned.
+        let ptr = ptr.add(ALIGN);
+        *(ptr.sub(HEADER_SIZE) as *mut usize) = full_size;
+        ptr as *mut c_void
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_zstd_wasm_shim_free(ptr: *mut c_void) {
+    if ptr.is_null() {
+        return;
+    }
+    let ptr = ptr as *mut u8;
+    let full_size = *(ptr.sub(HEADER_SIZE) as *const usize);
+    dealloc(
+        ptr.sub(ALIGN),
+        Layout::from_size_align_unchecked(full_size, ALIGN),
+    );
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_zstd_wasm_shim_memcpy(
+    dest: *mut c_void,
+    src: *const c_void,
+    n: usize,
+) -> *mut c_void {
+    copy_nonoverlapping(src as *const u8, dest as *mut u8, n);
+    dest
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_zstd_wasm_shim_memmove(
+    dest: *mut c_void,
+    src: *const c_void,
+    n: usize,
+) -> *mut c_void {
+    copy(src as *const u8, dest as *mut u8, n);
+    dest
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_zstd_wasm_shim_memset(
+    dest: *mut c_void,
+    c: i32,
+    n: usize,
+) -> *mut c_void {
+    write_bytes(dest as *mut u8, c as u8, n);
+    dest
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_zstd_wasm_shim_memcmp(
+    str1: *const c_void,
+    str2: *const c_void,
+    n: usize,
+) -> i32 {
+    let str1 = slice::from_raw_parts(str1 as *const u8, n);
+    let str2 = slice::from_raw_parts(str2 as *const u8, n);
+    match str1.cmp(str2) {
+        Ordering::Less => -1,
+        Ordering::Equal => 0,
+        Ordering::Greater => 1,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rust_zstd_wasm_shim_qsort(
+    base: *mut c_void,
+    n_items: usize,
+    size: usize,
+    compar: extern "C" fn(*const c_void, *const c_void) -> i32,
+) {
+    // zstd only sorts integers.
+    match size {
+        1 => qsort::<[u8; 1]>(base, n_items, compar),
+        2 => qsort::<[u8; 2]>(base, n_items, compar),
+        4 => qsort::<[u8; 4]>(base, n_items, compar),
+        8 => qsort::<[u8; 8]>(base, n_items, compar),
+        16 => qsort::<[u8; 16]>(base, n_items, compar),
+        // Unwinding out of this C callback would be undefined behaviour:
+        // handle any other size, slowly.
+        _ => insertion_sort(base as *mut u8, n_items, size, compar),
+    }
+}
+
+unsafe fn qsort<T>(
+    base: *mut c_void,
+    n_items: usize,
+    compar: extern "C" fn(*const c_void, *const c_void) -> i32,
+) {
+    let items = slice::from_raw_parts_mut(base as *mut T, n_items);
+    items.sort_unstable_by(|a, b| {
+        compar(a as *const T as *const c_void, b as *const T as *const c_void)
+            .cmp(&0)
+    });
+}
+
+unsafe fn insertion_sort(
+    base: *mut u8,
+    n_items: usize,
+    size: usize,
+    compar: extern "C" fn(*const c_void, *const c_void) -> i32,
+) {
+    for i in 1..n_items {
+        let mut j = i;
+        while j > 0 {
+            let previous = base.add((j - 1) * size);
+            let current = base.add(j * size);
+            if compar(previous as *const c_void, current as *const c_void) <= 0
+            {
+                break;
+            }
+            swap_nonoverlapping(previous, current, size);
+            j -= 1;
+        }
+    }
+}
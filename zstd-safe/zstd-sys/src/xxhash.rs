@@ -0,0 +1,43 @@
+// The xxhash implementation bundled with zstd (`lib/common/xxhash.c`).
+//
+// These are not part of `zstd.h`, so they are not generated by bindgen.
+
+/// Result of the xxhash functions which can fail.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum XXH_errorcode {
+    XXH_OK = 0,
+    XXH_ERROR = 1,
+}
+
+/// Opaque state for streaming XXH64 hashing.
+#[repr(C)]
+pub struct XXH64_state_t {
+    _private: [u8; 0],
+}
+
+extern "C" {
+    /// Hashes `length` bytes from `input` in one go.
+    pub fn XXH64(
+        input: *const ::core::ffi::c_void,
+        length: usize,
+        seed: u64,
+    ) -> u64;
+    /// Allocates a new streaming state.
+    pub fn XXH64_createState() -> *mut XXH64_state_t;
+    /// Frees a state allocated by `XXH64_createState`.
+    pub fn XXH64_freeState(statePtr: *mut XXH64_state_t) -> XXH_errorcode;
+    /// Starts a new hash with the given seed.
+    pub fn XXH64_reset(
+        statePtr: *mut XXH64_state_t,
+        seed: u64,
+    ) -> XXH_errorcode;
+    /// Feeds more data to the hash.
+    pub fn XXH64_update(
+        statePtr: *mut XXH64_state_t,
+        input: *const ::core::ffi::c_void,
+        length: usize,
+    ) -> XXH_errorcode;
+    /// Returns the hash of the data given so far.
+    pub fn XXH64_digest(statePtr: *const XXH64_state_t) -> u64;
+}
@@ -1,5 +1,5 @@
 #!/bin/sh
-bindgen="bindgen --blacklist-type=max_align_t --size_t-is-usize --rustified-enum=.* --use-core"
+bindgen="bindgen --blacklist-type=max_align_t --size_t-is-usize --rustified-enum=.* --constified-enum-module=ZSTD_ErrorCode --use-core"
 no_std="--ctypes-prefix libc"
 experimental="-DZSTD_STATIC_LINKING_ONLY -DZDICT_STATIC_LINKING_ONLY"
 
@@ -0,0 +1,13 @@
+#include <stddef.h>
+
+#ifndef _STDIO_H
+#define _STDIO_H 1
+
+// There is no console to write to: only used for debug and progress output,
+// which is disabled unless requested.
+#define stderr NULL
+#define fprintf(stream, ...) ((void)(stream), 0)
+#define printf(...) 0
+#define fflush(stream) ((void)(stream), 0)
+
+#endif // _STDIO_H
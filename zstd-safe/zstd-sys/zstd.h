@@ -3,12 +3,14 @@
 /* Just use installed headers */
 #include <zstd.h>
 #include <zdict.h>
+#include <zstd_errors.h>
 // Don't use experimental features like zstdmt
 
 #else // #ifdef PKG_CONFIG
 
 #include "zstd/lib/zstd.h"
 #include "zstd/lib/dictBuilder/zdict.h"
+#include "zstd/lib/common/zstd_errors.h"
 #ifdef ZSTD_STATIC_LINKING_ONLY
 #include "zstd/lib/compress/zstdmt_compress.h"
 #endif // #ifdef ZSTD_STATIC_LINKING_ONLY